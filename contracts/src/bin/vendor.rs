@@ -69,6 +69,10 @@ fn run() -> Result<()> {
             "BalancerV2WeightedPool",
             "balancer-labs/balancer-v2-monorepo/a3b570a2aa655d4c4941a67e3db6a06fbd72ef09/pkg/deployments/extra-abis/WeightedPool.json",
         )?
+        .npm(
+            "ChainlinkAggregatorV3",
+            "@chainlink/contracts@0.2.1/abi/v0.8/AggregatorV3Interface.json",
+        )?
         .npm(
             "ERC20",
             "@openzeppelin/contracts@3.3.0/build/contracts/ERC20.json",
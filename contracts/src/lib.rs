@@ -11,6 +11,7 @@ include!(concat!(
     env!("OUT_DIR"),
     "/BalancerV2WeightedPool2TokensFactory.rs"
 ));
+include!(concat!(env!("OUT_DIR"), "/ChainlinkAggregatorV3.rs"));
 include!(concat!(env!("OUT_DIR"), "/ERC20.rs"));
 include!(concat!(env!("OUT_DIR"), "/ERC20Mintable.rs"));
 include!(concat!(env!("OUT_DIR"), "/GPv2AllowListAuthentication.rs"));
@@ -0,0 +1,30 @@
+-- Audit log of the settings changed through the admin api. The most recent change per token and
+-- solver and the most recent market makable token list are applied again when the orderbook
+-- starts.
+CREATE TABLE token_setting_changes (
+    id bigserial PRIMARY KEY,
+    changed_at timestamptz NOT NULL,
+    token bytea NOT NULL,
+    -- 'allowed', 'denied' or 'unlisted'
+    listing text NOT NULL,
+    fee_factor double precision,
+    comment text
+);
+
+CREATE INDEX token_setting_changes_token_id ON token_setting_changes USING BTREE (token, id DESC);
+
+CREATE TABLE solver_setting_changes (
+    id bigserial PRIMARY KEY,
+    changed_at timestamptz NOT NULL,
+    solver text NOT NULL,
+    enabled boolean NOT NULL,
+    comment text
+);
+
+-- The id is the revision of the list that solvers use to notice changes.
+CREATE TABLE market_makable_token_list_changes (
+    id bigserial PRIMARY KEY,
+    changed_at timestamptz NOT NULL,
+    url text NOT NULL,
+    comment text
+);
@@ -1,11 +1,11 @@
 use contracts::{IUniswapLikeRouter, WETH9};
 use ethcontract::prelude::{Account, Address, PrivateKey, U256};
 use model::{
-    order::{OrderBuilder, OrderKind, BUY_ETH_ADDRESS},
+    order::{OrderBuilder, OrderCreationPayload, OrderKind, BUY_ETH_ADDRESS},
     SigningScheme,
 };
+use orderbook_client::error::FeeError;
 use secp256k1::SecretKey;
-use serde_json::json;
 use shared::{
     maintenance::Maintaining,
     sources::uniswap::{pair_provider::UniswapPairProvider, pool_fetching::PoolFetcher},
@@ -13,7 +13,7 @@ use shared::{
 };
 use solver::{
     liquidity::uniswap::UniswapLikeLiquidity, liquidity_collector::LiquidityCollector,
-    metrics::NoopMetrics,
+    metrics::NoopMetrics, settlement_simulation::NodeSettlementSimulator,
 };
 use std::{collections::HashSet, sync::Arc, time::Duration};
 use web3::signing::SecretKeyRef;
@@ -22,16 +22,13 @@ mod ganache;
 #[macro_use]
 mod services;
 use crate::services::{
-    create_orderbook_api, deploy_mintable_token, to_wei, GPv2, OrderbookServices, UniswapContracts,
-    API_HOST,
+    create_orderbook_api, create_orderbook_client, deploy_mintable_token, to_wei, GPv2,
+    OrderbookServices, UniswapContracts,
 };
 
 const TRADER_BUY_ETH_A_PK: [u8; 32] = [1; 32];
 const TRADER_BUY_ETH_B_PK: [u8; 32] = [2; 32];
 
-const ORDER_PLACEMENT_ENDPOINT: &str = "/api/v1/orders/";
-const FEE_ENDPOINT: &str = "/api/v1/fee/";
-
 #[tokio::test]
 async fn ganache_eth_integration() {
     ganache::test(eth_integration).await;
@@ -110,29 +107,16 @@ async fn eth_integration(web3: Web3) {
         ..
     } = OrderbookServices::new(&web3, &gpv2, &uniswap_factory, native_token).await;
 
-    let client = reqwest::Client::new();
+    let client = create_orderbook_client();
 
     // Test fee endpoint
-    let client_ref = &client;
-    let estimate_fee = |sell_token, buy_token| async move {
-        client_ref
-            .get(&format!(
-                "{}{}?sellToken={:?}&buyToken={:?}&amount={}&kind=sell",
-                API_HOST,
-                FEE_ENDPOINT,
-                sell_token,
-                buy_token,
-                to_wei(42)
-            ))
-            .send()
-            .await
-            .unwrap()
-    };
+    let estimate_fee =
+        |sell_token, buy_token| client.get_fee(sell_token, buy_token, to_wei(42), OrderKind::Sell);
     let fee_buy_eth = estimate_fee(token.address(), BUY_ETH_ADDRESS).await;
-    assert_eq!(fee_buy_eth.status(), 200);
+    assert!(fee_buy_eth.is_ok());
     // Eth is only supported as the buy token
     let fee_invalid_token = estimate_fee(BUY_ETH_ADDRESS, token.address()).await;
-    assert_eq!(fee_invalid_token.status(), 404);
+    assert!(matches!(fee_invalid_token, Err(FeeError::NotFound)));
 
     // Place Orders
     assert_ne!(weth.address(), BUY_ETH_ADDRESS);
@@ -150,12 +134,13 @@ async fn eth_integration(web3: Web3) {
         )
         .build()
         .order_creation;
-    let placement = client
-        .post(&format!("{}{}", API_HOST, ORDER_PLACEMENT_ENDPOINT))
-        .body(json!(order_buy_eth_a).to_string())
-        .send()
-        .await;
-    assert_eq!(placement.unwrap().status(), 201);
+    client
+        .create_order(&OrderCreationPayload {
+            order_creation: order_buy_eth_a,
+            from: None,
+        })
+        .await
+        .unwrap();
     let order_buy_eth_b = OrderBuilder::default()
         .with_kind(OrderKind::Sell)
         .with_sell_token(token.address())
@@ -170,12 +155,13 @@ async fn eth_integration(web3: Web3) {
         )
         .build()
         .order_creation;
-    let placement = client
-        .post(&format!("{}{}", API_HOST, ORDER_PLACEMENT_ENDPOINT))
-        .body(json!(order_buy_eth_b).to_string())
-        .send()
-        .await;
-    assert_eq!(placement.unwrap().status(), 201);
+    client
+        .create_order(&OrderCreationPayload {
+            order_creation: order_buy_eth_b,
+            from: None,
+        })
+        .await
+        .unwrap();
 
     // Drive solution
     let uniswap_pair_provider = Arc::new(UniswapPairProvider {
@@ -198,6 +184,7 @@ async fn eth_integration(web3: Web3) {
         uniswap_like_liquidity: vec![uniswap_liquidity],
         orderbook_api: create_orderbook_api(&web3, weth.address()),
         balancer_v2_liquidity: None,
+        order_policy: None,
     };
     let network_id = web3.net().version().await.unwrap();
     let mut driver = solver::driver::Driver::new(
@@ -211,14 +198,21 @@ async fn eth_integration(web3: Web3) {
         native_token,
         Duration::from_secs(0),
         Arc::new(NoopMetrics::default()),
-        web3.clone(),
-        network_id,
+        Arc::new(NodeSettlementSimulator {
+            contract: gpv2.settlement.clone(),
+            web3: web3.clone(),
+            network_id,
+        }),
+        1,
         1,
         Duration::from_secs(30),
         f64::MAX,
         None,
+        1,
         block_stream,
         1.0,
+        Default::default(),
+        None,
     );
     driver.single_run().await.unwrap();
 
@@ -2,18 +2,17 @@ use contracts::IUniswapLikeRouter;
 use ethcontract::prelude::{Account, Address, PrivateKey, U256};
 use hex_literal::hex;
 use model::{
-    order::{OrderBuilder, OrderKind},
+    order::{OrderBuilder, OrderCreationPayload, OrderKind},
     SigningScheme,
 };
 use secp256k1::SecretKey;
-use serde_json::json;
 use shared::{
     sources::uniswap::{pair_provider::UniswapPairProvider, pool_fetching::PoolFetcher},
     Web3,
 };
 use solver::{
     liquidity::uniswap::UniswapLikeLiquidity, liquidity_collector::LiquidityCollector,
-    metrics::NoopMetrics,
+    metrics::NoopMetrics, settlement_simulation::NodeSettlementSimulator,
 };
 use std::{collections::HashSet, sync::Arc, time::Duration};
 use web3::signing::SecretKeyRef;
@@ -22,8 +21,8 @@ mod ganache;
 #[macro_use]
 mod services;
 use crate::services::{
-    create_orderbook_api, deploy_mintable_token, to_wei, GPv2, OrderbookServices, UniswapContracts,
-    API_HOST,
+    create_orderbook_api, create_orderbook_client, deploy_mintable_token, to_wei, GPv2,
+    OrderbookServices, UniswapContracts,
 };
 use shared::maintenance::Maintaining;
 
@@ -32,8 +31,6 @@ const TRADER_A_PK: [u8; 32] =
 const TRADER_B_PK: [u8; 32] =
     hex!("0000000000000000000000000000000000000000000000000000000000000002");
 
-const ORDER_PLACEMENT_ENDPOINT: &str = "/api/v1/orders/";
-
 #[tokio::test]
 async fn ganache_onchain_settlement() {
     ganache::test(onchain_settlement).await;
@@ -107,7 +104,7 @@ async fn onchain_settlement(web3: Web3) {
         block_stream,
     } = OrderbookServices::new(&web3, &gpv2, &uniswap_factory, native_token).await;
 
-    let client = reqwest::Client::new();
+    let client = create_orderbook_client();
 
     let order_a = OrderBuilder::default()
         .with_sell_token(token_a.address())
@@ -123,12 +120,13 @@ async fn onchain_settlement(web3: Web3) {
         )
         .build()
         .order_creation;
-    let placement = client
-        .post(&format!("{}{}", API_HOST, ORDER_PLACEMENT_ENDPOINT))
-        .body(json!(order_a).to_string())
-        .send()
-        .await;
-    assert_eq!(placement.unwrap().status(), 201);
+    client
+        .create_order(&OrderCreationPayload {
+            order_creation: order_a,
+            from: None,
+        })
+        .await
+        .unwrap();
 
     let order_b = OrderBuilder::default()
         .with_sell_token(token_b.address())
@@ -144,12 +142,13 @@ async fn onchain_settlement(web3: Web3) {
         )
         .build()
         .order_creation;
-    let placement = client
-        .post(&format!("{}{}", API_HOST, ORDER_PLACEMENT_ENDPOINT))
-        .body(json!(order_b).to_string())
-        .send()
-        .await;
-    assert_eq!(placement.unwrap().status(), 201);
+    client
+        .create_order(&OrderCreationPayload {
+            order_creation: order_b,
+            from: None,
+        })
+        .await
+        .unwrap();
     let uniswap_pair_provider = Arc::new(UniswapPairProvider {
         factory: uniswap_factory.clone(),
         chain_id,
@@ -171,6 +170,7 @@ async fn onchain_settlement(web3: Web3) {
         uniswap_like_liquidity: vec![uniswap_liquidity],
         orderbook_api: create_orderbook_api(&web3, native_token),
         balancer_v2_liquidity: None,
+        order_policy: None,
     };
     let network_id = web3.net().version().await.unwrap();
     let mut driver = solver::driver::Driver::new(
@@ -184,14 +184,21 @@ async fn onchain_settlement(web3: Web3) {
         native_token,
         Duration::from_secs(0),
         Arc::new(NoopMetrics::default()),
-        web3.clone(),
-        network_id,
+        Arc::new(NodeSettlementSimulator {
+            contract: gpv2.settlement.clone(),
+            web3: web3.clone(),
+            network_id,
+        }),
+        1,
         1,
         Duration::from_secs(30),
         f64::MAX,
         None,
+        1,
         block_stream,
         1.0,
+        Default::default(),
+        None,
     );
     driver.single_run().await.unwrap();
 
@@ -5,9 +5,16 @@ use ethcontract::{
 };
 use model::DomainSeparator;
 use orderbook::{
-    account_balances::Web3BalanceFetcher, database::Postgres, event_updater::EventUpdater,
-    fee::EthAwareMinFeeCalculator, metrics::Metrics, orderbook::Orderbook,
+    account_balances::Web3BalanceFetcher,
+    api::rate_limit::RateLimiter,
+    database::Postgres,
+    event_updater::EventUpdater,
+    fee::{EthAwareMinFeeCalculator, FeeFactorOverrides},
+    metrics::Metrics,
+    orderbook::Orderbook,
+    runtime_settings::RuntimeSettings,
 };
+use orderbook_client::OrderbookClient;
 use prometheus::Registry;
 use shared::{
     bad_token::list_based::ListBasedDetector,
@@ -48,6 +55,13 @@ pub fn to_wei(base: u32) -> U256 {
     U256::from(base) * U256::from(10).pow(18.into())
 }
 
+pub fn create_orderbook_client() -> OrderbookClient {
+    OrderbookClient::new(
+        reqwest::Url::from_str(API_HOST).unwrap(),
+        std::time::Duration::from_secs(10),
+    )
+}
+
 pub fn create_orderbook_api(web3: &Web3, weth_address: H160) -> OrderBookApi {
     let weth = WETH9::at(&web3, weth_address);
     solver::orderbook::OrderBookApi::new(
@@ -172,6 +186,13 @@ impl OrderbookServices {
             bad_token_detector.clone(),
             native_token,
         ));
+        let fee_factors = Arc::new(FeeFactorOverrides::default());
+        let runtime_settings = Arc::new(RuntimeSettings::new(
+            db.clone(),
+            bad_token_detector.clone(),
+            None,
+            fee_factors.clone(),
+        ));
         let fee_calculator = Arc::new(EthAwareMinFeeCalculator::new(
             price_estimator.clone(),
             gas_estimator,
@@ -179,6 +200,7 @@ impl OrderbookServices {
             db.clone(),
             1.0,
             bad_token_detector.clone(),
+            fee_factors,
         ));
         let orderbook = Arc::new(Orderbook::new(
             gpv2.domain_separator,
@@ -195,6 +217,7 @@ impl OrderbookServices {
         ));
         let maintenance = ServiceMaintenance {
             maintainers: vec![orderbook.clone(), db.clone(), event_updater],
+            heartbeat: Default::default(),
         };
         orderbook::serve_task(
             db.clone(),
@@ -204,6 +227,10 @@ impl OrderbookServices {
             API_HOST[7..].parse().expect("Couldn't parse API address"),
             registry,
             metrics,
+            Default::default(),
+            runtime_settings,
+            None,
+            Arc::new(RateLimiter::new(Default::default())),
         );
 
         Self {
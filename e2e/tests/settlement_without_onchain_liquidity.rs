@@ -2,11 +2,10 @@ use contracts::IUniswapLikeRouter;
 use ethcontract::prelude::{Account, Address, PrivateKey, U256};
 use hex_literal::hex;
 use model::{
-    order::{OrderBuilder, OrderKind},
+    order::{OrderBuilder, OrderCreationPayload, OrderKind},
     SigningScheme,
 };
 use secp256k1::SecretKey;
-use serde_json::json;
 use shared::{
     sources::uniswap::{pair_provider::UniswapPairProvider, pool_fetching::PoolFetcher},
     token_list::{Token, TokenList},
@@ -14,7 +13,7 @@ use shared::{
 };
 use solver::{
     liquidity::uniswap::UniswapLikeLiquidity, liquidity_collector::LiquidityCollector,
-    metrics::NoopMetrics,
+    metrics::NoopMetrics, settlement_simulation::NodeSettlementSimulator,
 };
 use std::{collections::HashSet, sync::Arc, time::Duration};
 use web3::signing::SecretKeyRef;
@@ -23,16 +22,14 @@ mod ganache;
 #[macro_use]
 mod services;
 use crate::services::{
-    create_orderbook_api, deploy_mintable_token, to_wei, GPv2, OrderbookServices, UniswapContracts,
-    API_HOST,
+    create_orderbook_api, create_orderbook_client, deploy_mintable_token, to_wei, GPv2,
+    OrderbookServices, UniswapContracts,
 };
 use shared::maintenance::Maintaining;
 
 const TRADER_A_PK: [u8; 32] =
     hex!("0000000000000000000000000000000000000000000000000000000000000001");
 
-const ORDER_PLACEMENT_ENDPOINT: &str = "/api/v1/orders/";
-
 #[tokio::test]
 async fn ganache_onchain_settlement_without_liquidity() {
     ganache::test(onchain_settlement_without_liquidity).await;
@@ -117,7 +114,7 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
         block_stream,
     } = OrderbookServices::new(&web3, &gpv2, &uniswap_factory, native_token).await;
 
-    let client = reqwest::Client::new();
+    let client = create_orderbook_client();
 
     let order = OrderBuilder::default()
         .with_sell_token(token_a.address())
@@ -133,12 +130,13 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
         )
         .build()
         .order_creation;
-    let placement = client
-        .post(&format!("{}{}", API_HOST, ORDER_PLACEMENT_ENDPOINT))
-        .body(json!(order).to_string())
-        .send()
-        .await;
-    assert_eq!(placement.unwrap().status(), 201);
+    client
+        .create_order(&OrderCreationPayload {
+            order_creation: order,
+            from: None,
+        })
+        .await
+        .unwrap();
 
     let uniswap_pair_provider = Arc::new(UniswapPairProvider {
         factory: uniswap_factory.clone(),
@@ -161,6 +159,7 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
         uniswap_like_liquidity: vec![uniswap_liquidity],
         orderbook_api: create_orderbook_api(&web3, native_token),
         balancer_v2_liquidity: None,
+        order_policy: None,
     };
     let network_id = web3.net().version().await.unwrap();
     let market_makable_token_list = TokenList::new(maplit::hashmap! {
@@ -182,14 +181,21 @@ async fn onchain_settlement_without_liquidity(web3: Web3) {
         native_token,
         Duration::from_secs(0),
         Arc::new(NoopMetrics::default()),
-        web3.clone(),
-        network_id,
+        Arc::new(NodeSettlementSimulator {
+            contract: gpv2.settlement.clone(),
+            web3: web3.clone(),
+            network_id,
+        }),
+        1,
         1,
         Duration::from_secs(10),
         f64::MAX,
         Some(market_makable_token_list),
+        1,
         block_stream,
         1.0,
+        Default::default(),
+        None,
     );
     driver.single_run().await.unwrap();
 
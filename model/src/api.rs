@@ -0,0 +1,109 @@
+//! Responses of the orderbook api that are not orders or trades.
+
+use crate::{
+    h160_hexadecimal::HexadecimalH160,
+    u256_decimal::{self, DecimalU256},
+};
+use chrono::{DateTime, Utc};
+use num::BigInt;
+use primitive_types::{H160, U256};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Provides the information to calculate the fees.
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeInformation {
+    /// Expiration date of the offered fee. Order service might not accept the fee after this
+    /// expiration date. Encoded as ISO 8601 UTC.
+    pub expiration_date: DateTime<Utc>,
+    /// Absolute fee in sell token atoms.
+    #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
+    pub amount: U256,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeAndQuoteSellResponse {
+    /// The fee that is deducted from sellAmountBeforeFee. The sell amount that is traded is
+    /// sellAmountBeforeFee - fee.
+    pub fee: FeeInformation,
+    /// The expected buy amount for the traded sell amount.
+    #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
+    pub buy_amount_after_fee: U256,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeAndQuoteBuyResponse {
+    /// The fee that is deducted from sellAmountBeforeFee. The sell amount that is traded is
+    /// sellAmountBeforeFee - fee.
+    pub fee: FeeInformation,
+    /// The sell amount including the fee.
+    #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
+    pub sell_amount_before_fee: U256,
+}
+
+/// Provides the information about an estimated price.
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+pub struct AmountEstimate {
+    /// The estimated amount.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "DecimalU256")]
+    pub amount: BigInt,
+    /// The token in which the amount is given.
+    #[schemars(with = "HexadecimalH160")]
+    pub token: H160,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SolverSettings {
+    /// The solvers that were disabled through the admin api.
+    pub disabled_solvers: BTreeSet<String>,
+    /// The token list that was set through the admin api. It replaces the list the solver was
+    /// started with.
+    #[serde(default)]
+    pub market_makable_token_list: Option<MarketMakableTokenList>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketMakableTokenList {
+    pub url: String,
+    /// Changes whenever the list is set so that solvers fetch it again even if the url stayed the
+    /// same.
+    pub revision: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+
+    #[test]
+    fn fee_and_quote_deserialization() {
+        let value = json!({
+            "fee": {
+                "expirationDate": "1970-01-01T00:00:03Z",
+                "amount": "1",
+            },
+            "buyAmountAfterFee": "2",
+        });
+        let expected = FeeAndQuoteSellResponse {
+            fee: FeeInformation {
+                expiration_date: DateTime::from_utc(NaiveDateTime::from_timestamp(3, 0), Utc),
+                amount: 1.into(),
+            },
+            buy_amount_after_fee: 2.into(),
+        };
+        let deserialized: FeeAndQuoteSellResponse = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(deserialized, expected);
+        assert_eq!(serde_json::to_value(expected).unwrap(), value);
+    }
+}
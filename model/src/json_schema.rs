@@ -0,0 +1,122 @@
+//! JSON schemas for the types whose serialization is implemented by hand, so that the api
+//! documentation can be derived from the models.
+//!
+//! Fields with a custom serde representation refer to one of these types with
+//! `#[schemars(with = "...")]`.
+
+use crate::{h160_hexadecimal::HexadecimalH160, order::OrderUid, u256_decimal::DecimalU256};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation},
+    JsonSchema,
+};
+
+/// A string schema with a description and an optional regex pattern.
+pub fn string_schema(description: &str, pattern: Option<&str>) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.to_string()),
+            ..Default::default()
+        })),
+        string: pattern.map(|pattern| {
+            Box::new(StringValidation {
+                pattern: Some(pattern.to_string()),
+                ..Default::default()
+            })
+        }),
+        ..Default::default()
+    }
+    .into()
+}
+
+macro_rules! string_schema_impl {
+    ($type:ty, $name:literal, $description:literal, $pattern:expr) => {
+        impl JsonSchema for $type {
+            fn schema_name() -> String {
+                $name.to_string()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                string_schema($description, $pattern)
+            }
+        }
+    };
+}
+
+/// 32 bytes of arbitrary application specific data.
+pub struct AppData;
+
+/// An unsigned integer of arbitrary size.
+pub struct BigUint;
+
+/// A 32 byte transaction hash.
+pub struct TransactionHash;
+
+string_schema_impl!(
+    HexadecimalH160,
+    "Address",
+    "20 byte Ethereum address encoded as a hex with `0x` prefix.",
+    Some("^0x[0-9a-fA-F]{40}$")
+);
+string_schema_impl!(
+    DecimalU256,
+    "TokenAmount",
+    "Amount of a token. uint256 encoded in decimal.",
+    Some("^[0-9]+$")
+);
+string_schema_impl!(
+    AppData,
+    "AppData",
+    "32 bytes encoded as hex with `0x` prefix.",
+    Some("^0x[0-9a-fA-F]{64}$")
+);
+string_schema_impl!(
+    BigUint,
+    "BigUint",
+    "A big unsigned integer encoded in decimal.",
+    Some("^[0-9]+$")
+);
+string_schema_impl!(
+    TransactionHash,
+    "TransactionHash",
+    "32 byte digest encoded as a hex with `0x` prefix.",
+    Some("^0x[0-9a-fA-F]{64}$")
+);
+string_schema_impl!(
+    OrderUid,
+    "UID",
+    "Unique identifier for the order: 56 bytes encoded as hex with `0x` prefix. Bytes 0 to 32 \
+     are the order digest, bytes 32 to 52 the owner address and bytes 52 to 56 valid to.",
+    Some("^0x[0-9a-fA-F]{112}$")
+);
+string_schema_impl!(
+    crate::Signature,
+    "Signature",
+    "65 bytes encoded as hex with `0x` prefix. r + s + v from the spec.",
+    Some("^0x[0-9a-fA-F]{130}$")
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::order::Order;
+    use schemars::schema_for;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn order_schema_matches_serialization() {
+        let schema = serde_json::to_value(schema_for!(Order)).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let order = serde_json::to_value(Order::default()).unwrap();
+        let mut fields = order.as_object().unwrap().keys().collect::<Vec<_>>();
+        let mut documented = properties.keys().collect::<Vec<_>>();
+        fields.sort();
+        documented.sort();
+        assert_eq!(fields, documented);
+        assert_eq!(properties["uid"], json!({ "$ref": "#/definitions/UID" }));
+        assert_eq!(
+            schema["definitions"]["Address"]["type"],
+            Value::String("string".to_string())
+        );
+    }
+}
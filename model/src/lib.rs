@@ -1,7 +1,9 @@
 //! Contains models that are shared between the orderbook and the solver.
 
+pub mod api;
 pub mod appdata_hexadecimal;
 pub mod h160_hexadecimal;
+pub mod json_schema;
 pub mod order;
 pub mod ratio_as_decimal;
 pub mod trade;
@@ -11,6 +13,7 @@ use ethabi::{encode, Token};
 use hex::{FromHex, FromHexError};
 use lazy_static::lazy_static;
 use primitive_types::{H160, H256};
+use schemars::JsonSchema;
 use serde::{de, Deserialize, Serialize};
 use std::fmt;
 use web3::{
@@ -18,7 +21,8 @@ use web3::{
     types::Recovery,
 };
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash)]
+/// How was the order signed?
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SigningScheme {
     Eip712,
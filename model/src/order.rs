@@ -3,6 +3,7 @@
 use crate::{
     appdata_hexadecimal,
     h160_hexadecimal::{self, HexadecimalH160},
+    json_schema::{self, AppData},
     u256_decimal::{self, DecimalU256},
     DomainSeparator, Signature, SigningScheme, TokenPair,
 };
@@ -11,6 +12,7 @@ use derivative::Derivative;
 use hex_literal::hex;
 use num_bigint::BigUint;
 use primitive_types::{H160, H256, U256};
+use schemars::JsonSchema;
 use secp256k1::key::ONE_KEY;
 use serde::{de, Deserialize, Serialize};
 use serde::{Deserializer, Serializer};
@@ -27,7 +29,7 @@ pub const BUY_ETH_ADDRESS: H160 = H160([0xee; 20]);
 /// An order that is returned when querying the orderbook.
 ///
 /// Contains extra fields that are populated by the orderbook.
-#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize, Hash, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Order {
     #[serde(flatten)]
@@ -42,7 +44,7 @@ impl Default for Order {
     }
 }
 
-#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize, Hash, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum OrderStatus {
     Open,
@@ -165,37 +167,60 @@ impl OrderBuilder {
 
 /// An order as provided to the orderbook by the frontend.
 #[serde_as]
-#[derive(Eq, PartialEq, Clone, Copy, Derivative, Deserialize, Serialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Copy, Derivative, Deserialize, Serialize, Hash, JsonSchema)]
 #[derivative(Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderCreation {
+    /// ERC20 token to be sold.
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     pub sell_token: H160,
+    /// ERC20 token to be bought.
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     pub buy_token: H160,
+    /// An optional address to receive the proceeds of the trade instead of the owner (i.e. the
+    /// order signer).
     #[serde(default)]
     #[serde_as(as = "Option<HexadecimalH160>")]
+    #[schemars(with = "Option<HexadecimalH160>")]
     pub receiver: Option<H160>,
+    /// Amount of sellToken to be sold in atoms.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     pub sell_amount: U256,
+    /// Amount of buyToken to be bought in atoms.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     pub buy_amount: U256,
+    /// Unix timestamp until the order is valid. uint32.
     pub valid_to: u32,
+    /// Arbitrary application specific data that can be added to an order. This can also be used
+    /// to ensure uniqueness between two orders with otherwise the exact same parameters.
     #[derivative(Debug(format_with = "debug_app_data"))]
     #[serde(with = "appdata_hexadecimal")]
+    #[schemars(with = "AppData")]
     pub app_data: [u8; 32],
+    /// Fees: feeRatio * sellAmount + minimal_fee in atoms.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     pub fee_amount: U256,
     pub kind: OrderKind,
+    /// Is this a fill-or-kill order or a partially fillable order?
     pub partially_fillable: bool,
     pub signature: Signature,
     pub signing_scheme: SigningScheme,
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
 pub struct OrderCreationPayload {
     #[serde(flatten)]
     pub order_creation: OrderCreation,
+    /// If set, the backend enforces that this address matches what is decoded as the signer of
+    /// the signature. This helps catch errors with invalid signature encodings as the backend
+    /// might otherwise silently work with an unexpected address that for example does not have
+    /// any balance.
+    #[schemars(with = "Option<HexadecimalH160>")]
     pub from: Option<H160>,
 }
 
@@ -283,6 +308,16 @@ pub struct OrderCancellation {
     pub signing_scheme: SigningScheme,
 }
 
+/// EIP712 signature of struct OrderCancellation { orderUid: bytes } from the order's owner.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(rename = "OrderCancellation")]
+pub struct OrderCancellationPayload {
+    /// OrderCancellation signed by the owner.
+    pub signature: Signature,
+    pub signing_scheme: SigningScheme,
+}
+
 impl Default for OrderCancellation {
     fn default() -> Self {
         let mut result = Self {
@@ -317,32 +352,53 @@ impl OrderCancellation {
         self.signature
             .validate(self.signing_scheme, domain_separator, &self.hash_struct())
     }
+
+    pub fn payload(&self) -> OrderCancellationPayload {
+        OrderCancellationPayload {
+            signature: self.signature,
+            signing_scheme: self.signing_scheme,
+        }
+    }
 }
 
 /// An order as provided to the orderbook by the frontend.
 #[serde_as]
-#[derive(Eq, PartialEq, Clone, Derivative, Deserialize, Serialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Derivative, Deserialize, Serialize, Hash, JsonSchema)]
 #[derivative(Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderMetaData {
+    /// Creation time of the order. Encoded as ISO 8601 UTC.
     pub creation_date: DateTime<Utc>,
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     pub owner: H160,
     pub uid: OrderUid,
+    /// Amount of sellToken available for the settlement contract to spend on behalf of the owner.
+    /// Null if the api was unable to fetch the balance.
     #[serde_as(as = "Option<DecimalU256>")]
+    #[schemars(with = "Option<DecimalU256>")]
     pub available_balance: Option<U256>,
+    /// The total amount of buyToken that has been executed for this order.
     #[derivative(Debug(format_with = "debug_biguint_to_string"))]
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub executed_buy_amount: BigUint,
+    /// The total amount of sellToken that has been executed for this order including fees.
     #[derivative(Debug(format_with = "debug_biguint_to_string"))]
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub executed_sell_amount: BigUint,
+    /// The total amount of sellToken that has been executed for this order without fees.
     #[derivative(Debug(format_with = "debug_biguint_to_string"))]
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub executed_sell_amount_before_fees: BigUint,
+    /// The total amount of fees that have been executed for this order.
     #[derivative(Debug(format_with = "debug_biguint_to_string"))]
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub executed_fee_amount: BigUint,
+    /// Has this order been invalidated?
     pub invalidated: bool,
     pub status: OrderStatus,
 }
@@ -446,9 +502,13 @@ impl<'de> Deserialize<'de> for OrderUid {
     }
 }
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash, enum_utils::FromStr)]
+/// Is this a buy order or sell order?
+#[derive(
+    Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash, JsonSchema, enum_utils::FromStr,
+)]
 #[enumeration(case_insensitive)]
 #[serde(rename_all = "lowercase")]
+#[schemars(rename = "OrderType")]
 pub enum OrderKind {
     Buy,
     Sell,
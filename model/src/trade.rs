@@ -1,27 +1,42 @@
 //! Contains the Trade type as described by the specification with serialization as described by the openapi documentation.
 
-use crate::order::OrderUid;
+use crate::{
+    h160_hexadecimal::HexadecimalH160,
+    json_schema::{self, TransactionHash},
+    order::OrderUid,
+};
 use num_bigint::BigUint;
 use primitive_types::{H160, H256};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize, Hash)]
+/// Trade data such as executed amounts, fees, order id and block number.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize, Hash, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Trade {
     pub block_number: u64,
     pub log_index: u64,
     pub order_uid: OrderUid,
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub buy_amount: BigUint,
+    /// Amount of sellToken that has been executed for this trade including fees.
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub sell_amount: BigUint,
     #[serde(with = "serde_with::rust::display_fromstr")]
+    #[schemars(with = "json_schema::BigUint")]
     pub sell_amount_before_fees: BigUint,
     // ORDER DATA
+    #[schemars(with = "HexadecimalH160")]
     pub owner: H160,
+    #[schemars(with = "HexadecimalH160")]
     pub buy_token: H160,
+    #[schemars(with = "HexadecimalH160")]
     pub sell_token: H160,
     // Settlement Data
+    /// Hash of the settlement transaction containing the trade, if available.
+    #[schemars(with = "Option<TransactionHash>")]
     pub tx_hash: Option<H256>,
 }
 
@@ -0,0 +1,224 @@
+//! Errors of the routes of the orderbook api.
+//!
+//! Every route has its own error type with a variant for every error type the orderbook responds
+//! with. All routes are rate limited so every error type also has `TooManyRequests`. Everything
+//! else, like transport errors, internal server errors and unknown error types, is `Other`.
+
+use anyhow::anyhow;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+/// The body of error responses.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorBody {
+    error_type: String,
+    description: String,
+}
+
+pub trait RouteError: Sized {
+    fn from_error_type(error_type: &str) -> Option<Self>;
+    fn too_many_requests(retry_after: Option<Duration>) -> Self;
+    fn other(err: anyhow::Error) -> Self;
+}
+
+/// Routes that don't have any specific errors.
+impl RouteError for anyhow::Error {
+    fn from_error_type(_: &str) -> Option<Self> {
+        None
+    }
+
+    fn too_many_requests(retry_after: Option<Duration>) -> Self {
+        anyhow!("too many requests, retry after {:?}", retry_after)
+    }
+
+    fn other(err: anyhow::Error) -> Self {
+        err
+    }
+}
+
+/// Turns the status and body of a response into the expected value or the error of the route.
+/// `retry_after` is the value of the `Retry-After` header.
+pub fn parse_response<T, E>(
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    body: &[u8],
+) -> Result<T, E>
+where
+    T: DeserializeOwned,
+    E: RouteError,
+{
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(E::too_many_requests(retry_after));
+    }
+    if status.is_success() {
+        return serde_json::from_slice(body).map_err(|err| {
+            E::other(anyhow!(
+                "failed to decode response {:?}: {}",
+                String::from_utf8_lossy(body),
+                err
+            ))
+        });
+    }
+    Err(match serde_json::from_slice::<ErrorBody>(body) {
+        Ok(error) => E::from_error_type(&error.error_type).unwrap_or_else(|| {
+            E::other(anyhow!(
+                "{} {}: {}",
+                status,
+                error.error_type,
+                error.description
+            ))
+        }),
+        Err(_) => E::other(anyhow!("{}: {:?}", status, String::from_utf8_lossy(body))),
+    })
+}
+
+macro_rules! route_error {
+    ($(#[$attr:meta])* $name:ident { $($variant:ident),* $(,)? }) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub enum $name {
+            $($variant,)*
+            TooManyRequests { retry_after: Option<Duration> },
+            Other(anyhow::Error),
+        }
+
+        impl RouteError for $name {
+            fn from_error_type(error_type: &str) -> Option<Self> {
+                match error_type {
+                    $(stringify!($variant) => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            fn too_many_requests(retry_after: Option<Duration>) -> Self {
+                Self::TooManyRequests { retry_after }
+            }
+
+            fn other(err: anyhow::Error) -> Self {
+                Self::Other(err)
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                match self {
+                    $(Self::$variant => f.write_str(stringify!($variant)),)*
+                    Self::TooManyRequests { retry_after: Some(retry_after) } => {
+                        write!(f, "TooManyRequests, retry after {:?}", retry_after)
+                    }
+                    Self::TooManyRequests { retry_after: None } => f.write_str("TooManyRequests"),
+                    Self::Other(err) => write!(f, "{:#}", err),
+                }
+            }
+        }
+
+        impl std::error::Error for $name {}
+    };
+}
+
+route_error!(
+    /// Why an order was not added. Mirrors the orderbook's `AddOrderResult`.
+    AddOrderError {
+        UnsupportedToken,
+        WrongOwner,
+        DuplicatedOrder,
+        InvalidSignature,
+        Forbidden,
+        InsufficientValidTo,
+        MissingOrderData,
+        InsufficientFunds,
+        InsufficientFee,
+        TransferEthToContract,
+        SameBuyAndSellToken,
+    }
+);
+
+route_error!(
+    /// Why an order was not cancelled. Mirrors the orderbook's `OrderCancellationResult`.
+    CancelOrderError {
+        InvalidSignature,
+        AlreadyCancelled,
+        OrderFullyExecuted,
+        OrderExpired,
+        OrderNotFound,
+        WrongOwner,
+    }
+);
+
+route_error!(GetOrderError { NotFound });
+
+route_error!(GetOrdersError { InvalidOrderFilter });
+
+route_error!(GetTradesError { InvalidTradeFilter });
+
+route_error!(FeeError {
+    NotFound,
+    UnsupportedToken,
+});
+
+route_error!(FeeAndQuoteError {
+    NoLiquidity,
+    UnsupportedToken,
+    AmountIsZero,
+    SellAmountDoesNotCoverFee,
+});
+
+route_error!(AmountEstimateError {
+    UnsupportedToken,
+    NotFound,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_success() {
+        let result: Result<String, AddOrderError> = parse_response(StatusCode::OK, None, b"\"ok\"");
+        assert_eq!(result.unwrap(), "ok");
+    }
+
+    #[test]
+    fn parses_known_error_types() {
+        let body = br#"{"errorType":"InsufficientFee","description":"fee too low"}"#;
+        let result: Result<String, AddOrderError> =
+            parse_response(StatusCode::BAD_REQUEST, None, body);
+        assert!(matches!(result, Err(AddOrderError::InsufficientFee)));
+    }
+
+    #[test]
+    fn parses_too_many_requests() {
+        let body = br#"{"errorType":"TooManyRequests","description":"slow down"}"#;
+        let result: Result<String, GetOrderError> = parse_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(Duration::from_secs(2)),
+            body,
+        );
+        assert!(matches!(
+            result,
+            Err(GetOrderError::TooManyRequests {
+                retry_after: Some(retry_after)
+            }) if retry_after == Duration::from_secs(2)
+        ));
+    }
+
+    #[test]
+    fn unknown_errors_are_other() {
+        let body = br#"{"errorType":"InternalServerError","description":""}"#;
+        let result: Result<String, FeeError> =
+            parse_response(StatusCode::INTERNAL_SERVER_ERROR, None, body);
+        assert!(matches!(result, Err(FeeError::Other(_))));
+
+        let result: Result<String, FeeError> = parse_response(StatusCode::NOT_FOUND, None, b"");
+        assert!(matches!(result, Err(FeeError::Other(_))));
+
+        let result: Result<u32, FeeError> =
+            parse_response(StatusCode::OK, None, b"\"not a number\"");
+        assert!(matches!(result, Err(FeeError::Other(_))));
+    }
+}
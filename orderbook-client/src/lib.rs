@@ -0,0 +1,224 @@
+//! Client for the orderbook api.
+
+pub mod error;
+
+use crate::error::{
+    parse_response, AddOrderError, AmountEstimateError, CancelOrderError, FeeAndQuoteError,
+    FeeError, GetOrderError, GetOrdersError, GetTradesError, RouteError,
+};
+use model::{
+    api::{
+        AmountEstimate, FeeAndQuoteBuyResponse, FeeAndQuoteSellResponse, FeeInformation,
+        SolverSettings,
+    },
+    order::{Order, OrderCancellation, OrderCreationPayload, OrderKind, OrderUid},
+    trade::Trade,
+};
+use primitive_types::{H160, U256};
+use reqwest::{header::RETRY_AFTER, Client, RequestBuilder, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use std::time::Duration;
+
+/// Which orders the orders route returns. At least one of owner, sell token and buy token has to
+/// be set. By default only currently valid orders are returned.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<H160>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sell_token: Option<H160>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_token: Option<H160>,
+    /// Defaults to the current time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_valid_to: Option<u32>,
+    pub include_fully_executed: bool,
+    pub include_invalidated: bool,
+    pub include_insufficient_balance: bool,
+    pub include_unsupported_tokens: bool,
+}
+
+/// Which trades the trades route returns. Exactly one of owner and order uid has to be set.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TradeQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<H160>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_uid: Option<OrderUid>,
+}
+
+#[derive(Clone)]
+pub struct OrderbookClient {
+    base: Url,
+    client: Client,
+}
+
+impl OrderbookClient {
+    /// base: protocol and host of the url. example: `https://example.com`
+    pub fn new(base: Url, request_timeout: Duration) -> Self {
+        // Unwrap because we cannot handle client creation failing.
+        let client = Client::builder().timeout(request_timeout).build().unwrap();
+        Self { base, client }
+    }
+
+    pub async fn create_order(
+        &self,
+        order: &OrderCreationPayload,
+    ) -> Result<OrderUid, AddOrderError> {
+        self.send(self.client.post(self.url("orders")).json(order))
+            .await
+    }
+
+    pub async fn cancel_order(
+        &self,
+        cancellation: &OrderCancellation,
+    ) -> Result<(), CancelOrderError> {
+        let url = self.url(&format!("orders/{}", cancellation.order_uid));
+        self.send::<String, _>(self.client.delete(url).json(&cancellation.payload()))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn get_order(&self, uid: &OrderUid) -> Result<Order, GetOrderError> {
+        self.send(self.client.get(self.url(&format!("orders/{}", uid))))
+            .await
+    }
+
+    pub async fn get_orders(&self, query: &OrderQuery) -> Result<Vec<Order>, GetOrdersError> {
+        self.send(self.client.get(self.url("orders")).query(query))
+            .await
+    }
+
+    /// The orders that solvers should be solving right now.
+    pub async fn get_solvable_orders(&self) -> anyhow::Result<Vec<Order>> {
+        self.send(self.client.get(self.url("solvable_orders")))
+            .await
+    }
+
+    pub async fn get_trades(&self, query: &TradeQuery) -> Result<Vec<Trade>, GetTradesError> {
+        self.send(self.client.get(self.url("trades")).query(query))
+            .await
+    }
+
+    /// The minimum fee of an order trading the amount of sell token for sell orders or of buy
+    /// token for buy orders.
+    pub async fn get_fee(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        kind: OrderKind,
+    ) -> Result<FeeInformation, FeeError> {
+        let query = [
+            ("sellToken", format!("{:?}", sell_token)),
+            ("buyToken", format!("{:?}", buy_token)),
+            ("amount", amount.to_string()),
+            ("kind", order_kind(kind).to_string()),
+        ];
+        self.send(self.client.get(self.url("fee")).query(&query))
+            .await
+    }
+
+    /// The fee and the resulting buy amount of selling the amount of sell token.
+    pub async fn get_fee_and_quote_sell(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        sell_amount_before_fee: U256,
+    ) -> Result<FeeAndQuoteSellResponse, FeeAndQuoteError> {
+        let query = [
+            ("sellToken", format!("{:?}", sell_token)),
+            ("buyToken", format!("{:?}", buy_token)),
+            ("sellAmountBeforeFee", sell_amount_before_fee.to_string()),
+        ];
+        self.send(self.client.get(self.url("feeAndQuote/sell")).query(&query))
+            .await
+    }
+
+    /// The fee and the needed sell amount of buying the amount of buy token.
+    pub async fn get_fee_and_quote_buy(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        buy_amount_after_fee: U256,
+    ) -> Result<FeeAndQuoteBuyResponse, FeeAndQuoteError> {
+        let query = [
+            ("sellToken", format!("{:?}", sell_token)),
+            ("buyToken", format!("{:?}", buy_token)),
+            ("buyAmountAfterFee", buy_amount_after_fee.to_string()),
+        ];
+        self.send(self.client.get(self.url("feeAndQuote/buy")).query(&query))
+            .await
+    }
+
+    /// The estimated amount of quote token for buying or selling the amount of base token.
+    pub async fn get_amount_estimate(
+        &self,
+        base_token: H160,
+        quote_token: H160,
+        kind: OrderKind,
+        amount: U256,
+    ) -> Result<AmountEstimate, AmountEstimateError> {
+        let path = format!(
+            "markets/{:?}-{:?}/{}/{}",
+            base_token,
+            quote_token,
+            order_kind(kind),
+            amount
+        );
+        self.send(self.client.get(self.url(&path))).await
+    }
+
+    pub async fn get_solver_settings(&self) -> anyhow::Result<SolverSettings> {
+        self.send(self.client.get(self.url("solver_settings")))
+            .await
+    }
+
+    fn url(&self, path: &str) -> Url {
+        let mut url = self.base.clone();
+        url.set_path(&format!("api/v1/{}", path));
+        url
+    }
+
+    async fn send<T, E>(&self, request: RequestBuilder) -> Result<T, E>
+    where
+        T: DeserializeOwned,
+        E: RouteError,
+    {
+        let response = request.send().await.map_err(|err| E::other(err.into()))?;
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.parse().ok())
+            .map(Duration::from_secs);
+        let body = response.bytes().await.map_err(|err| E::other(err.into()))?;
+        parse_response(status, retry_after, &body)
+    }
+}
+
+fn order_kind(kind: OrderKind) -> &'static str {
+    match kind {
+        OrderKind::Buy => "buy",
+        OrderKind::Sell => "sell",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let client = OrderbookClient::new(
+            Url::parse("http://localhost:8080").unwrap(),
+            Duration::from_secs(1),
+        );
+        assert_eq!(
+            client.url("feeAndQuote/sell").as_str(),
+            "http://localhost:8080/api/v1/feeAndQuote/sell"
+        );
+    }
+}
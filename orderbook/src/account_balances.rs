@@ -191,13 +191,9 @@ mod tests {
     use contracts::ERC20Mintable;
     use ethcontract::prelude::Account;
     use hex_literal::hex;
-    use shared::transport::create_env_test_transport;
+    use shared::transport::{create_env_test_transport, create_fixture_test_transport};
 
-    #[tokio::test]
-    #[ignore]
-    async fn mainnet_can_transfer() {
-        let http = create_env_test_transport();
-        let web3 = Web3::new(http);
+    async fn can_transfer(web3: Web3) {
         let settlement = contracts::GPv2Settlement::deployed(&web3).await.unwrap();
         let allowance = settlement.allowance_manager().call().await.unwrap();
         let fetcher = Web3BalanceFetcher::new(web3, allowance, settlement.address());
@@ -211,11 +207,7 @@ mod tests {
         assert!(call_result);
     }
 
-    #[tokio::test]
-    #[ignore]
-    async fn mainnet_cannot_transfer() {
-        let http = create_env_test_transport();
-        let web3 = Web3::new(http);
+    async fn cannot_transfer(web3: Web3) {
         let settlement = contracts::GPv2Settlement::deployed(&web3).await.unwrap();
         let allowance = settlement.allowance_manager().call().await.unwrap();
         let fetcher = Web3BalanceFetcher::new(web3, allowance, settlement.address());
@@ -231,6 +223,38 @@ mod tests {
         assert!(call_result);
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn mainnet_can_transfer() {
+        can_transfer(Web3::new(create_env_test_transport())).await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn mainnet_cannot_transfer() {
+        cannot_transfer(Web3::new(create_env_test_transport())).await;
+    }
+
+    // The synthetic fixtures contain scripted node responses for the mainnet addresses rather than
+    // recorded mainnet state.
+    #[tokio::test]
+    async fn synthetic_can_transfer() {
+        can_transfer(Web3::new(create_fixture_test_transport(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/synthetic_can_transfer.json"
+        ))))
+        .await;
+    }
+
+    #[tokio::test]
+    async fn synthetic_cannot_transfer() {
+        cannot_transfer(Web3::new(create_fixture_test_transport(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/synthetic_cannot_transfer.json"
+        ))))
+        .await;
+    }
+
     #[tokio::test]
     #[ignore]
     async fn watch_testnet_balance() {
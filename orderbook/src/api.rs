@@ -1,3 +1,4 @@
+mod admin;
 mod cancel_order;
 mod create_order;
 mod get_fee_and_quote;
@@ -6,7 +7,10 @@ mod get_markets;
 mod get_order_by_uid;
 mod get_orders;
 mod get_solvable_orders;
+mod get_solver_settings;
 mod get_trades;
+pub mod openapi;
+pub mod rate_limit;
 
 use crate::{
     database::trades::TradeRetrieving,
@@ -14,7 +18,11 @@ use crate::{
     metrics::start_request,
     metrics::{end_request, LabelledReply, Metrics},
     orderbook::Orderbook,
+    runtime_settings::RuntimeSettings,
 };
+pub use admin::AdminToken;
+use rate_limit::{RateLimiter, TooManyRequests};
+
 use anyhow::Error as anyhowError;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -26,12 +34,16 @@ use warp::{
     wrap_fn, Filter, Rejection, Reply,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_all_routes(
     database: Arc<dyn TradeRetrieving>,
     orderbook: Arc<Orderbook>,
     fee_calculator: Arc<EthAwareMinFeeCalculator>,
     price_estimator: Arc<dyn PriceEstimating>,
     metrics: Arc<Metrics>,
+    runtime_settings: Arc<RuntimeSettings>,
+    admin_token: Option<AdminToken>,
+    rate_limiter: Arc<RateLimiter>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let create_order = create_order::create_order(orderbook.clone());
     let get_orders = get_orders::get_orders(orderbook.clone());
@@ -46,11 +58,21 @@ pub fn handle_all_routes(
         get_fee_and_quote::get_fee_and_quote_sell(fee_calculator.clone(), price_estimator.clone());
     let get_fee_and_quote_buy =
         get_fee_and_quote::get_fee_and_quote_buy(fee_calculator, price_estimator.clone());
+    let get_solver_settings = get_solver_settings::get_solver_settings(runtime_settings.clone());
+    let admin = admin::admin(runtime_settings, admin_token);
+    let get_openapi = openapi::get_openapi();
+    let rate_limit = rate_limit::rate_limit(rate_limiter, metrics.clone());
     let cors = warp::cors()
         .allow_any_origin()
         .allow_methods(vec!["GET", "POST", "DELETE", "OPTIONS", "PUT", "PATCH"])
-        .allow_headers(vec!["Origin", "Content-Type", "X-Auth-Token", "X-AppId"]);
-    let routes_with_labels = warp::path!("api" / "v1" / ..).and(
+        .allow_headers(vec![
+            "Origin",
+            "Content-Type",
+            "X-Auth-Token",
+            "X-AppId",
+            "Authorization",
+        ]);
+    let routes_with_labels = warp::path!("api" / "v1" / ..).and(rate_limit).and(
         (create_order.map(|reply| LabelledReply::new(reply, "create_order")))
             .or(get_orders.map(|reply| LabelledReply::new(reply, "get_orders")))
             .unify()
@@ -73,6 +95,12 @@ pub fn handle_all_routes(
             .unify()
             .or(get_fee_and_quote_buy
                 .map(|reply| LabelledReply::new(reply, "get_fee_and_quote_buy")))
+            .unify()
+            .or(get_solver_settings.map(|reply| LabelledReply::new(reply, "get_solver_settings")))
+            .unify()
+            .or(admin.map(|reply| LabelledReply::new(reply, "admin")))
+            .unify()
+            .or(get_openapi.map(|reply| LabelledReply::new(reply, "get_openapi")))
             .unify(),
     );
     routes_with_labels
@@ -83,6 +111,12 @@ pub fn handle_all_routes(
 
 // We turn Rejection into Reply to workaround warp not setting CORS headers on rejections.
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    if err.find::<admin::Unauthorized>().is_some() {
+        return Ok(admin::unauthorized_response().into_response());
+    }
+    if let Some(err) = err.find::<TooManyRequests>() {
+        return Ok(err.response());
+    }
     Ok(err.default_response())
 }
 
@@ -0,0 +1,249 @@
+use crate::api::extract_payload;
+use crate::runtime_settings::{RuntimeSettings, TokenSettings};
+use anyhow::Result;
+use primitive_types::H160;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use shared::bad_token::list_based::TokenListing;
+use std::{
+    convert::Infallible,
+    fmt::{self, Debug, Formatter},
+    str::FromStr,
+    sync::Arc,
+};
+use subtle::ConstantTimeEq;
+use warp::{hyper::StatusCode, reply, Filter, Rejection, Reply};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenSettingsPayload {
+    listing: TokenListing,
+    /// Factor the minimum fee of orders selling the token is multiplied with.
+    #[serde(default)]
+    fee_factor: Option<f64>,
+    /// Why the settings were changed, stored in the audit log.
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SolverSettingsPayload {
+    enabled: bool,
+    /// Why the settings were changed, stored in the audit log.
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketMakableTokenListPayload {
+    /// Url of the list of tokens the settlement contract is willing to buy when settling trades
+    /// without external liquidity.
+    url: String,
+    /// Why the settings were changed, stored in the audit log.
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+/// The secret admin requests are authenticated with. It is not shown in debug output so that it
+/// doesn't end up in the logs.
+#[derive(Clone)]
+pub struct AdminToken(pub String);
+
+impl Debug for AdminToken {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("AdminToken(..)")
+    }
+}
+
+impl FromStr for AdminToken {
+    type Err = Infallible;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        Ok(Self(token.to_string()))
+    }
+}
+
+/// Rejects requests without the admin token as bearer token in the authorization header. All
+/// requests are rejected as not found if no admin token is configured.
+fn authorize(
+    admin_token: Option<AdminToken>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let provided = header
+                .as_deref()
+                .and_then(|header| header.strip_prefix("Bearer "))
+                .map(str::to_string);
+            let result = match (&admin_token, provided) {
+                (None, _) => Err(warp::reject::not_found()),
+                // Compared in constant time so that the token can't be guessed from response times.
+                (Some(token), Some(provided))
+                    if token.0.as_bytes().ct_eq(provided.as_bytes()).into() =>
+                {
+                    Ok(())
+                }
+                (Some(_), _) => Err(warp::reject::custom(Unauthorized)),
+            };
+            async move { result }
+        })
+        .untuple_one()
+}
+
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+pub fn unauthorized_response() -> impl Reply {
+    reply::with_status(
+        super::error("Unauthorized", "missing or wrong admin token"),
+        StatusCode::UNAUTHORIZED,
+    )
+}
+
+fn update_response(result: Result<()>) -> impl Reply {
+    match result {
+        Ok(()) => reply::with_status(reply::json(&"Updated"), StatusCode::OK),
+        Err(err) => {
+            tracing::error!(?err, "admin settings update error");
+            reply::with_status(
+                super::error("InvalidSettings", format!("{:?}", err)),
+                StatusCode::BAD_REQUEST,
+            )
+        }
+    }
+}
+
+fn get_token_settings(
+    settings: Arc<RuntimeSettings>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("tokens")
+        .and(warp::get())
+        .map(move || reply::json(&settings.token_settings()))
+}
+
+fn update_token_settings(
+    settings: Arc<RuntimeSettings>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("tokens" / H160)
+        .and(warp::put())
+        .and(extract_payload())
+        .and_then(move |token, payload: TokenSettingsPayload| {
+            let settings = settings.clone();
+            async move {
+                let token_settings = TokenSettings {
+                    listing: payload.listing,
+                    fee_factor: payload.fee_factor,
+                };
+                let result = settings
+                    .update_token(token, token_settings, payload.comment)
+                    .await;
+                Result::<_, Infallible>::Ok(update_response(result))
+            }
+        })
+}
+
+fn update_solver_settings(
+    settings: Arc<RuntimeSettings>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("solvers" / String)
+        .and(warp::put())
+        .and(extract_payload())
+        .and_then(move |solver, payload: SolverSettingsPayload| {
+            let settings = settings.clone();
+            async move {
+                let result = settings
+                    .update_solver(solver, payload.enabled, payload.comment)
+                    .await;
+                Result::<_, Infallible>::Ok(update_response(result))
+            }
+        })
+}
+
+fn update_market_makable_token_list(
+    settings: Arc<RuntimeSettings>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("market_makable_token_list")
+        .and(warp::put())
+        .and(extract_payload())
+        .and_then(move |payload: MarketMakableTokenListPayload| {
+            let settings = settings.clone();
+            async move {
+                let result = settings
+                    .update_market_makable_token_list(payload.url, payload.comment)
+                    .await;
+                Result::<_, Infallible>::Ok(update_response(result))
+            }
+        })
+}
+
+/// Routes for changing the runtime settings. They require the admin token.
+pub fn admin(
+    settings: Arc<RuntimeSettings>,
+    admin_token: Option<AdminToken>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("admin" / ..).and(authorize(admin_token)).and(
+        get_token_settings(settings.clone())
+            .map(|reply| Box::new(reply) as Box<dyn Reply>)
+            .or(update_token_settings(settings.clone())
+                .map(|reply| Box::new(reply) as Box<dyn Reply>))
+            .unify()
+            .or(update_solver_settings(settings.clone())
+                .map(|reply| Box::new(reply) as Box<dyn Reply>))
+            .unify()
+            .or(update_market_makable_token_list(settings)
+                .map(|reply| Box::new(reply) as Box<dyn Reply>))
+            .unify(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use warp::test::request;
+
+    #[test]
+    fn token_settings_payload_deserialization() {
+        assert_eq!(
+            serde_json::from_value::<TokenSettingsPayload>(json!({
+                "listing": "denied",
+                "comment": "honeypot",
+            }))
+            .unwrap(),
+            TokenSettingsPayload {
+                listing: TokenListing::Denied,
+                fee_factor: None,
+                comment: Some("honeypot".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_admin_token() {
+        let filter = warp::path!("admin").and(authorize(Some(AdminToken("secret".to_string()))));
+        assert!(request()
+            .path("/admin")
+            .header("authorization", "Bearer secret")
+            .filter(&filter)
+            .await
+            .is_ok());
+        assert!(request()
+            .path("/admin")
+            .header("authorization", "Bearer wrong")
+            .filter(&filter)
+            .await
+            .is_err());
+        assert!(request().path("/admin").filter(&filter).await.is_err());
+
+        let disabled = warp::path!("admin").and(authorize(None));
+        assert!(request()
+            .path("/admin")
+            .header("authorization", "Bearer secret")
+            .filter(&disabled)
+            .await
+            .is_err());
+    }
+}
@@ -1,28 +1,16 @@
 use crate::api::extract_payload;
 use crate::orderbook::{OrderCancellationResult, Orderbook};
 use anyhow::Result;
-use model::Signature;
-use model::{
-    order::{OrderCancellation, OrderUid},
-    SigningScheme,
-};
-use serde::{Deserialize, Serialize};
+use model::order::{OrderCancellation, OrderCancellationPayload, OrderUid};
 use std::{convert::Infallible, sync::Arc};
 use warp::{hyper::StatusCode, Filter, Rejection, Reply};
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct CancellationPayload {
-    signature: Signature,
-    signing_scheme: SigningScheme,
-}
-
 pub fn cancel_order_request(
 ) -> impl Filter<Extract = (OrderCancellation,), Error = Rejection> + Clone {
     warp::path!("orders" / OrderUid)
         .and(warp::delete())
         .and(extract_payload())
-        .map(|uid, payload: CancellationPayload| OrderCancellation {
+        .map(|uid, payload: OrderCancellationPayload| OrderCancellation {
             order_uid: uid,
             signature: payload.signature,
             signing_scheme: payload.signing_scheme,
@@ -84,13 +72,15 @@ mod tests {
     use super::*;
     use ethcontract::H256;
     use hex_literal::hex;
+    use model::{Signature, SigningScheme};
+    use serde::Deserialize;
     use serde_json::json;
     use warp::test::request;
 
     #[test]
     fn cancellation_payload_deserialization() {
         assert_eq!(
-            CancellationPayload::deserialize(json!({
+            OrderCancellationPayload::deserialize(json!({
                 "signature": "0x\
                     000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\
                     202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f\
@@ -98,7 +88,7 @@ mod tests {
                 "signingScheme": "eip712"
             }))
             .unwrap(),
-            CancellationPayload {
+            OrderCancellationPayload {
                 signature: Signature {
                     r: H256(hex!(
                         "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
@@ -122,10 +112,7 @@ mod tests {
             .path(&format!("/orders/{:}", cancellation.order_uid))
             .method("DELETE")
             .header("content-type", "application/json")
-            .json(&CancellationPayload {
-                signature: cancellation.signature,
-                signing_scheme: cancellation.signing_scheme,
-            });
+            .json(&cancellation.payload());
         let result = request.filter(&filter).await.unwrap();
         assert_eq!(result, cancellation);
     }
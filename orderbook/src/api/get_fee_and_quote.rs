@@ -1,9 +1,13 @@
 use crate::fee::{MinFeeCalculating, MinFeeCalculationError};
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, Utc};
 use ethcontract::{H160, U256};
-use model::h160_hexadecimal;
-use model::{order::OrderKind, u256_decimal};
+use model::h160_hexadecimal::{self, HexadecimalH160};
+use model::{
+    api::{FeeAndQuoteBuyResponse, FeeAndQuoteSellResponse, FeeInformation},
+    order::OrderKind,
+    u256_decimal::{self, DecimalU256},
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use shared::{
     conversions::{big_int_to_u256, U256Ext},
@@ -13,59 +17,36 @@ use std::convert::Infallible;
 use std::sync::Arc;
 use warp::{hyper::StatusCode, reply, Filter, Rejection, Reply};
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct Fee {
-    #[serde(with = "u256_decimal")]
-    amount: U256,
-    expiration_date: DateTime<Utc>,
-}
-
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-struct SellQuery {
+pub struct SellQuery {
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     sell_token: H160,
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     buy_token: H160,
-    // The total amount to be sold from which the fee will be deducted.
+    /// The total amount to be sold from which the fee will be deducted.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     sell_amount_before_fee: U256,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-struct SellResponse {
-    // The fee that is deducted from sell_amount_before_fee. The sell amount that is traded is
-    // sell_amount_before_fee - fee_in_sell_token.
-    fee: Fee,
-    // The expected buy amount for the traded sell amount.
-    #[serde(with = "u256_decimal")]
-    buy_amount_after_fee: U256,
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct BuyQuery {
+pub struct BuyQuery {
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     sell_token: H160,
     #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
     buy_token: H160,
-    // The total amount to be bought.
+    /// The total amount to be bought.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     buy_amount_after_fee: U256,
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct BuyResponse {
-    // The fee that is deducted from sell_amount_before_fee. The sell amount that is traded is
-    // sell_amount_before_fee - fee_in_sell_token.
-    fee: Fee,
-    #[serde(with = "u256_decimal")]
-    sell_amount_before_fee: U256,
-}
-
 #[derive(Debug)]
 enum Error {
     NoLiquidity,
@@ -98,7 +79,7 @@ async fn calculate_sell(
     fee_calculator: Arc<dyn MinFeeCalculating>,
     price_estimator: Arc<dyn PriceEstimating>,
     query: SellQuery,
-) -> Result<SellResponse, Error> {
+) -> Result<FeeAndQuoteSellResponse, Error> {
     if query.sell_amount_before_fee.is_zero() {
         return Err(Error::AmountIsZero);
     }
@@ -129,8 +110,8 @@ async fn calculate_sell(
         big_int_to_u256(&(sell_amount_after_fee.to_big_rational() / price).to_integer())
             .map_err(Error::Other)?;
 
-    Ok(SellResponse {
-        fee: Fee {
+    Ok(FeeAndQuoteSellResponse {
+        fee: FeeInformation {
             expiration_date,
             amount: fee,
         },
@@ -142,7 +123,7 @@ async fn calculate_buy(
     fee_calculator: Arc<dyn MinFeeCalculating>,
     price_estimator: Arc<dyn PriceEstimating>,
     query: BuyQuery,
-) -> Result<BuyResponse, Error> {
+) -> Result<FeeAndQuoteBuyResponse, Error> {
     if query.buy_amount_after_fee.is_zero() {
         return Err(Error::AmountIsZero);
     }
@@ -171,8 +152,8 @@ async fn calculate_buy(
         .checked_add(fee)
         .ok_or_else(|| Error::Other(anyhow!("overflow in sell_amount_before_fee")))?;
 
-    Ok(BuyResponse {
-        fee: Fee {
+    Ok(FeeAndQuoteBuyResponse {
+        fee: FeeInformation {
             expiration_date,
             amount: fee,
         },
@@ -258,6 +239,7 @@ pub fn get_fee_and_quote_buy(
 mod tests {
     use super::*;
     use crate::fee::MockMinFeeCalculating;
+    use chrono::Utc;
     use futures::FutureExt;
     use hex_literal::hex;
     use num::BigRational;
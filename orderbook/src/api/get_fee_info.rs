@@ -2,28 +2,27 @@ use crate::fee::{EthAwareMinFeeCalculator, MinFeeCalculating, MinFeeCalculationE
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use model::{order::OrderKind, u256_decimal};
+use model::{
+    api::FeeInformation,
+    order::OrderKind,
+    u256_decimal::{self, DecimalU256},
+};
 use primitive_types::{H160, U256};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use shared::H160Wrapper;
 use std::convert::Infallible;
 use std::sync::Arc;
 use warp::{hyper::StatusCode, reply, Filter, Rejection, Reply};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-struct FeeInfo {
-    pub expiration_date: DateTime<Utc>,
-    #[serde(with = "u256_decimal")]
-    pub amount: U256,
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct Query {
+pub struct Query {
     sell_token: H160Wrapper,
     buy_token: H160Wrapper,
+    /// The amount of the sell token for sell orders or of the buy token for buy orders.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     amount: U256,
     kind: OrderKind,
 }
@@ -39,7 +38,7 @@ pub fn get_fee_info_response(
 ) -> impl Reply {
     match result {
         Ok((amount, expiration_date)) => {
-            let fee_info = FeeInfo {
+            let fee_info = FeeInformation {
                 expiration_date,
                 amount,
             };
@@ -85,12 +84,18 @@ pub fn get_fee_info(
 
 // TODO remove legacy fee endpoint once frontend is updated
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Provides the information to calculate the fees.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-struct LegacyFeeInfo {
+#[schemars(rename = "LegacyFeeInformation")]
+pub struct LegacyFeeInfo {
+    /// Expiration date of the offered fee. Encoded as ISO 8601 UTC.
     pub expiration_date: DateTime<Utc>,
+    /// Absolute amount of minimal fee charged per order in specified sellToken.
     #[serde(with = "u256_decimal")]
+    #[schemars(with = "DecimalU256")]
     pub minimal_fee: U256,
+    /// The fee ratio charged on a sellAmount. Denoted in basis points.
     pub fee_ratio: u32,
 }
 
@@ -176,7 +181,7 @@ mod tests {
                 .into_response();
         assert_eq!(response.status(), StatusCode::OK);
         let body = response_body(response).await;
-        let body: FeeInfo = serde_json::from_slice(body.as_slice()).unwrap();
+        let body: FeeInformation = serde_json::from_slice(body.as_slice()).unwrap();
         assert_eq!(body.amount, U256::zero());
         assert!(body.expiration_date.gt(&chrono::offset::Utc::now()))
     }
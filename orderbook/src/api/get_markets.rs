@@ -1,8 +1,7 @@
 use anyhow::{anyhow, Result};
 use ethcontract::{H160, U256};
-use model::order::OrderKind;
-use num::{BigInt, BigRational};
-use serde::{Deserialize, Serialize};
+use model::{api::AmountEstimate, order::OrderKind};
+use num::BigRational;
 use shared::{
     conversions::U256Ext,
     price_estimate::{PriceEstimating, PriceEstimationError},
@@ -18,13 +17,6 @@ struct AmountEstimateQuery {
     kind: OrderKind,
 }
 
-#[derive(Deserialize, Serialize)]
-struct AmountEstimateResult {
-    #[serde(with = "serde_with::rust::display_fromstr")]
-    amount: BigInt,
-    token: H160,
-}
-
 struct TokenAmount(U256);
 impl FromStr for TokenAmount {
     type Err = anyhow::Error;
@@ -81,7 +73,7 @@ fn get_amount_estimate_response(
                 OrderKind::Sell => query_amount / price,
             };
             reply::with_status(
-                reply::json(&AmountEstimateResult {
+                reply::json(&AmountEstimate {
                     amount: response_amount.to_integer(),
                     token: query.market.quote_token,
                 }),
@@ -171,7 +163,7 @@ mod tests {
                 .into_response();
         assert_eq!(response.status(), StatusCode::OK);
 
-        let estimate: AmountEstimateResult =
+        let estimate: AmountEstimate =
             serde_json::from_slice(response_body(response).await.as_slice()).unwrap();
         assert_eq!(estimate.amount, 50.into());
         assert_eq!(estimate.token, query.market.quote_token);
@@ -186,7 +178,7 @@ mod tests {
         )
         .into_response();
 
-        let estimate: AmountEstimateResult =
+        let estimate: AmountEstimate =
             serde_json::from_slice(response_body(response).await.as_slice()).unwrap();
         assert_eq!(estimate.amount, 200.into());
         assert_eq!(estimate.token, query.market.quote_token);
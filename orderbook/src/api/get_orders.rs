@@ -3,6 +3,7 @@ use crate::{
 };
 use anyhow::Result;
 use model::order::Order;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use shared::{time::now_in_epoch_seconds, H160Wrapper};
 use std::{convert::Infallible, sync::Arc};
@@ -13,9 +14,10 @@ use warp::{
 };
 
 // The default values create a filter that only includes valid orders.
-#[derive(Default, Deserialize)]
+#[derive(Default, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-struct Query {
+pub struct Query {
+    /// Minimum validTo of the returned orders. Defaults to the current time.
     #[serde(default = "now_in_epoch_seconds")]
     min_valid_to: u32,
     owner: Option<H160Wrapper>,
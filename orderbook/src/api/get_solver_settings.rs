@@ -0,0 +1,30 @@
+use crate::runtime_settings::RuntimeSettings;
+use std::sync::Arc;
+use warp::{reply, Filter, Rejection, Reply};
+
+fn get_solver_settings_request() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path!("solver_settings").and(warp::get())
+}
+
+pub fn get_solver_settings(
+    settings: Arc<RuntimeSettings>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    get_solver_settings_request().map(move || reply::json(&settings.solver_settings()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::test::request;
+
+    #[tokio::test]
+    async fn get_solver_settings_request_ok() {
+        let filter = get_solver_settings_request();
+        assert!(request()
+            .path("/solver_settings")
+            .method("GET")
+            .filter(&filter)
+            .await
+            .is_ok());
+    }
+}
@@ -5,6 +5,7 @@ use anyhow::Result;
 use futures::TryStreamExt;
 use model::order::OrderUid;
 use model::trade::Trade;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use shared::H160Wrapper;
 use std::convert::Infallible;
@@ -12,9 +13,9 @@ use std::sync::Arc;
 use warp::reply::{Json, WithStatus};
 use warp::{hyper::StatusCode, Filter, Rejection, Reply};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-struct Query {
+pub struct Query {
     pub order_uid: Option<OrderUid>,
     pub owner: Option<H160Wrapper>,
 }
@@ -0,0 +1,653 @@
+//! The OpenAPI document of the api.
+//!
+//! The schemas are derived from the request and response types of the routes so that the document
+//! can't drift from the implementation. The tests make sure that every route and error type is
+//! documented.
+
+use super::{admin, get_fee_and_quote, get_fee_info, get_orders, get_trades};
+use crate::runtime_settings::TokenSettings;
+use model::{
+    api::{
+        AmountEstimate, FeeAndQuoteBuyResponse, FeeAndQuoteSellResponse, FeeInformation,
+        SolverSettings,
+    },
+    h160_hexadecimal::HexadecimalH160,
+    order::{Order, OrderCancellationPayload, OrderCreationPayload, OrderKind, OrderUid},
+    trade::Trade,
+    u256_decimal::DecimalU256,
+};
+use primitive_types::H160;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use warp::{hyper::StatusCode, reply, Filter, Rejection, Reply};
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+fn inline_schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    T::json_schema(generator)
+}
+
+/// A single method on a path of the api.
+struct Operation {
+    method: &'static str,
+    /// Path relative to `/api/v1/`.
+    path: &'static str,
+    summary: &'static str,
+    description: Option<&'static str>,
+    path_parameters: Vec<(&'static str, SchemaFn)>,
+    query: Option<SchemaFn>,
+    body: Option<SchemaFn>,
+    response: (StatusCode, &'static str, SchemaFn),
+    errors: Vec<(StatusCode, &'static [&'static str])>,
+    admin: bool,
+}
+
+impl Operation {
+    fn new(method: &'static str, path: &'static str, summary: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            summary,
+            description: None,
+            path_parameters: Vec::new(),
+            query: None,
+            body: None,
+            response: (StatusCode::OK, "", schema::<Value>),
+            // Every route is rate limited.
+            errors: vec![(StatusCode::TOO_MANY_REQUESTS, &["TooManyRequests"])],
+            admin: false,
+        }
+    }
+
+    fn description(mut self, description: &'static str) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    fn path_parameter<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        self.path_parameters.push((name, schema::<T>));
+        self
+    }
+
+    /// Documents every field of the type as query parameter.
+    fn query<T: JsonSchema>(mut self) -> Self {
+        self.query = Some(inline_schema::<T>);
+        self
+    }
+
+    fn body<T: JsonSchema>(mut self) -> Self {
+        self.body = Some(schema::<T>);
+        self
+    }
+
+    fn response<T: JsonSchema>(mut self, status: StatusCode, description: &'static str) -> Self {
+        self.response = (status, description, schema::<T>);
+        self
+    }
+
+    fn errors(mut self, status: StatusCode, error_types: &'static [&'static str]) -> Self {
+        self.errors.push((status, error_types));
+        self
+    }
+
+    fn internal_errors(self) -> Self {
+        self.errors(StatusCode::INTERNAL_SERVER_ERROR, &["InternalServerError"])
+    }
+
+    /// Requires the admin token.
+    fn admin(mut self) -> Self {
+        self.admin = true;
+        self.errors(StatusCode::UNAUTHORIZED, &["Unauthorized"])
+    }
+
+    fn to_json(&self, generator: &mut SchemaGenerator) -> Value {
+        let mut parameters = self
+            .path_parameters
+            .iter()
+            .map(|(name, schema)| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": schema(generator),
+                })
+            })
+            .collect::<Vec<_>>();
+        if let Some(query) = self.query {
+            parameters.extend(query_parameters(query(generator)));
+        }
+
+        let mut responses = Map::new();
+        let (status, description, schema) = self.response;
+        responses.insert(
+            status.as_u16().to_string(),
+            json!({
+                "description": description,
+                "content": { "application/json": { "schema": schema(generator) } },
+            }),
+        );
+        for (status, error_types) in self.errors_by_status() {
+            let mut response = json!({
+                "description": status.canonical_reason().unwrap_or_default(),
+                "content": { "application/json": { "schema": error_schema(&error_types) } },
+            });
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                response["headers"] = json!({
+                    "Retry-After": {
+                        "description": "Seconds until the request is allowed again.",
+                        "schema": { "type": "integer" },
+                    },
+                });
+            }
+            responses.insert(status.as_u16().to_string(), response);
+        }
+
+        let mut operation = json!({
+            "summary": self.summary,
+            "responses": responses,
+        });
+        if let Some(description) = self.description {
+            operation["description"] = json!(description);
+        }
+        if !parameters.is_empty() {
+            operation["parameters"] = json!(parameters);
+        }
+        if let Some(body) = self.body {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": body(generator) } },
+            });
+        }
+        if self.admin {
+            operation["security"] = json!([{ "AdminToken": [] }]);
+        }
+        operation
+    }
+
+    fn errors_by_status(&self) -> BTreeMap<StatusCode, Vec<&'static str>> {
+        let mut errors = BTreeMap::<_, Vec<_>>::new();
+        for (status, error_types) in &self.errors {
+            errors.entry(*status).or_default().extend(*error_types);
+        }
+        errors
+    }
+}
+
+/// Turns the properties of an object schema into query parameters.
+fn query_parameters(schema: Schema) -> Vec<Value> {
+    let object = schema.into_object().object.unwrap_or_default();
+    let required = object.required;
+    object
+        .properties
+        .into_iter()
+        .map(|(name, schema)| {
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&name),
+            });
+            let mut schema = serde_json::to_value(schema).unwrap();
+            if let Value::Object(schema) = &mut schema {
+                // Defaults can depend on the time the document is created, like the minimum valid
+                // to of orders, so they are only mentioned in the description.
+                schema.remove("default");
+                if let Some(description) = schema.remove("description") {
+                    parameter["description"] = description;
+                }
+            }
+            parameter["schema"] = schema;
+            parameter
+        })
+        .collect()
+}
+
+fn error_schema(error_types: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "errorType": { "type": "string", "enum": error_types },
+            "description": { "type": "string" },
+        },
+        "required": ["errorType", "description"],
+    })
+}
+
+fn operations() -> Vec<Operation> {
+    const FEE_AND_QUOTE_ERRORS: &[&str] = &[
+        "UnsupportedToken",
+        "AmountIsZero",
+        "SellAmountDoesNotCoverFee",
+    ];
+
+    vec![
+        Operation::new("post", "orders", "Create a new order.")
+            .body::<OrderCreationPayload>()
+            .response::<OrderUid>(StatusCode::CREATED, "Order has been accepted.")
+            .errors(
+                StatusCode::BAD_REQUEST,
+                &[
+                    "UnsupportedToken",
+                    "WrongOwner",
+                    "DuplicatedOrder",
+                    "InvalidSignature",
+                    "InsufficientValidTo",
+                    "MissingOrderData",
+                    "InsufficientFunds",
+                    "InsufficientFee",
+                    "TransferEthToContract",
+                    "SameBuyAndSellToken",
+                ],
+            )
+            .errors(StatusCode::FORBIDDEN, &["Forbidden"])
+            .internal_errors(),
+        Operation::new("get", "orders", "Get existing orders.")
+            .description(
+                "By default all currently valid orders are returned. The set of returned orders \
+                 can be reduced by setting owner, sell token, buy token filters. It can be \
+                 increased by disabling different order validity exclusion criteria. At least one \
+                 of owner, sellToken, buyToken has to be set.",
+            )
+            .query::<get_orders::Query>()
+            .response::<Vec<Order>>(StatusCode::OK, "Existing orders.")
+            .errors(StatusCode::BAD_REQUEST, &["InvalidOrderFilter"])
+            .internal_errors(),
+        Operation::new("get", "orders/{UID}", "Get existing order from UID.")
+            .path_parameter::<OrderUid>("UID")
+            .response::<Order>(StatusCode::OK, "Order.")
+            .errors(StatusCode::NOT_FOUND, &["NotFound"])
+            .internal_errors(),
+        Operation::new(
+            "delete",
+            "orders/{UID}",
+            "Cancels order by marking it invalid with a timestamp.",
+        )
+        .description(
+            "The successful deletion might not prevent solvers from settling the order. \
+             Authentication must be provided by signing an OrderCancellation.",
+        )
+        .path_parameter::<OrderUid>("UID")
+        .body::<OrderCancellationPayload>()
+        .response::<String>(StatusCode::OK, "Order cancelled.")
+        .errors(
+            StatusCode::BAD_REQUEST,
+            &[
+                "InvalidSignature",
+                "AlreadyCancelled",
+                "OrderFullyExecuted",
+                "OrderExpired",
+            ],
+        )
+        .errors(StatusCode::NOT_FOUND, &["OrderNotFound"])
+        .errors(StatusCode::UNAUTHORIZED, &["WrongOwner"])
+        .internal_errors(),
+        Operation::new("get", "solvable_orders", "Get solvable orders.")
+            .description(
+                "The set of orders that solvers should be solving right now. These orders are \
+                 determined to be valid at the time of the request.",
+            )
+            .response::<Vec<Order>>(StatusCode::OK, "The orders.")
+            .internal_errors(),
+        Operation::new("get", "trades", "Get existing trades.")
+            .description("Exactly one of owner or orderUid has to be set.")
+            .query::<get_trades::Query>()
+            .response::<Vec<Trade>>(StatusCode::OK, "All trades.")
+            .errors(StatusCode::BAD_REQUEST, &["InvalidTradeFilter"])
+            .internal_errors(),
+        Operation::new(
+            "get",
+            "fee",
+            "The fee that is charged for placing an order.",
+        )
+        .description(
+            "The fee is described by a minimum fee in order to cover the gas costs for \
+                 onchain settling.",
+        )
+        .query::<get_fee_info::Query>()
+        .response::<FeeInformation>(StatusCode::OK, "The fee.")
+        .errors(StatusCode::BAD_REQUEST, &["UnsupportedToken"])
+        .errors(StatusCode::NOT_FOUND, &["NotFound"])
+        .internal_errors(),
+        Operation::new(
+            "get",
+            "tokens/{sellToken}/fee",
+            "The minimum fee of orders selling the token.",
+        )
+        .description("Deprecated in favour of the fee route.")
+        .path_parameter::<HexadecimalH160>("sellToken")
+        .response::<get_fee_info::LegacyFeeInfo>(StatusCode::OK, "The fee.")
+        .errors(StatusCode::BAD_REQUEST, &["UnsupportedToken"])
+        .errors(StatusCode::NOT_FOUND, &["NotFound"])
+        .internal_errors(),
+        Operation::new(
+            "get",
+            "markets/{baseToken}-{quoteToken}/{kind}/{amount}",
+            "Estimate the amount of quote token for buying or selling an amount of base token.",
+        )
+        .path_parameter::<HexadecimalH160>("baseToken")
+        .path_parameter::<HexadecimalH160>("quoteToken")
+        .path_parameter::<OrderKind>("kind")
+        .path_parameter::<DecimalU256>("amount")
+        .response::<AmountEstimate>(StatusCode::OK, "The price denominated in quote token.")
+        .errors(StatusCode::BAD_REQUEST, &["UnsupportedToken"])
+        .errors(StatusCode::NOT_FOUND, &["NotFound"]),
+        Operation::new(
+            "get",
+            "feeAndQuote/sell",
+            "The fee and the buy amount for selling an amount of sell token.",
+        )
+        .description(
+            "sellAmountBeforeFee is the total amount that is available for the order. From it \
+             the fee is deducted and the buy amount is calculated.",
+        )
+        .query::<get_fee_and_quote::SellQuery>()
+        .response::<FeeAndQuoteSellResponse>(StatusCode::OK, "The fee and quote.")
+        .errors(StatusCode::BAD_REQUEST, FEE_AND_QUOTE_ERRORS)
+        .errors(StatusCode::NOT_FOUND, &["NoLiquidity"])
+        .internal_errors(),
+        Operation::new(
+            "get",
+            "feeAndQuote/buy",
+            "The fee and the sell amount for buying an amount of buy token.",
+        )
+        .description(
+            "Returns the total sell amount that is needed for the buy amount and how much of it \
+             is the fee.",
+        )
+        .query::<get_fee_and_quote::BuyQuery>()
+        .response::<FeeAndQuoteBuyResponse>(StatusCode::OK, "The fee and quote.")
+        .errors(StatusCode::BAD_REQUEST, FEE_AND_QUOTE_ERRORS)
+        .errors(StatusCode::NOT_FOUND, &["NoLiquidity"])
+        .internal_errors(),
+        Operation::new("get", "solver_settings", "Get the solver settings.")
+            .response::<SolverSettings>(StatusCode::OK, "The solver settings."),
+        Operation::new(
+            "get",
+            "admin/tokens",
+            "Get the settings of all tokens that are listed or have a fee factor.",
+        )
+        .admin()
+        .response::<BTreeMap<H160, TokenSettings>>(StatusCode::OK, "Settings by token address."),
+        Operation::new(
+            "put",
+            "admin/tokens/{token}",
+            "Change the settings of a token.",
+        )
+        .description(
+            "Moves the token to the allow or deny list and sets the factor its minimum fee is \
+             multiplied with. Takes effect immediately and is recorded in the database together \
+             with the comment.",
+        )
+        .admin()
+        .path_parameter::<HexadecimalH160>("token")
+        .body::<admin::TokenSettingsPayload>()
+        .response::<String>(StatusCode::OK, "Settings updated.")
+        .errors(StatusCode::BAD_REQUEST, &["InvalidSettings"]),
+        Operation::new(
+            "put",
+            "admin/solvers/{solver}",
+            "Enable or disable a solver.",
+        )
+        .description("Takes effect in the next run loop of the solver.")
+        .admin()
+        .path_parameter::<String>("solver")
+        .body::<admin::SolverSettingsPayload>()
+        .response::<String>(StatusCode::OK, "Settings updated.")
+        .errors(StatusCode::BAD_REQUEST, &["InvalidSettings"]),
+        Operation::new(
+            "put",
+            "admin/market_makable_token_list",
+            "Set the list of tokens the settlement contract is willing to buy without external \
+             liquidity.",
+        )
+        .description(
+            "Replaces the list the solvers were started with. Solvers fetch the list again in \
+             their next run loop.",
+        )
+        .admin()
+        .body::<admin::MarketMakableTokenListPayload>()
+        .response::<String>(StatusCode::OK, "Settings updated.")
+        .errors(StatusCode::BAD_REQUEST, &["InvalidSettings"]),
+        Operation::new("get", "openapi.json", "This document.")
+            .response::<Value>(StatusCode::OK, "The OpenAPI document."),
+    ]
+}
+
+/// Creates the OpenAPI document of all routes.
+pub fn document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let mut paths = Map::new();
+    for operation in operations() {
+        let path = paths
+            .entry(format!("/api/v1/{}", operation.path))
+            .or_insert_with(|| json!({}));
+        path[operation.method] = operation.to_json(&mut generator);
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "version": "0.0.1",
+            "title": "Order Book API",
+        },
+        "servers": [
+            { "url": "https://protocol-mainnet.dev.gnosisdev.com", "description": "Mainnet (Staging)" },
+            { "url": "https://protocol-mainnet.gnosis.io", "description": "Mainnet (Prod)" },
+            { "url": "https://protocol-rinkeby.dev.gnosisdev.com", "description": "Rinkeby (Staging)" },
+            { "url": "https://protocol-rinkeby.gnosis.io", "description": "Rinkeby (Prod)" },
+            { "url": "https://protocol-xdai.dev.gnosisdev.com", "description": "xDai (Staging)" },
+            { "url": "https://protocol-xdai.gnosis.io", "description": "xDai (Prod)" },
+            { "url": "http://localhost:8080", "description": "Local" },
+        ],
+        "paths": paths,
+        "components": {
+            "schemas": generator.take_definitions(),
+            "securitySchemes": {
+                "AdminToken": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+fn get_openapi_request() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path!("openapi.json").and(warp::get())
+}
+
+pub fn get_openapi() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let document = document();
+    get_openapi_request().map(move || reply::json(&document))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    /// A route as it is declared with `warp::path!`.
+    #[derive(Debug)]
+    struct Route {
+        method: String,
+        /// The literal segments of the path, `None` for parameters.
+        segments: Vec<Option<String>>,
+    }
+
+    impl Route {
+        fn is_documented_by(&self, operation: &Operation) -> bool {
+            let segments = operation.path.split('/').collect::<Vec<_>>();
+            self.method == operation.method
+                && self.segments.len() == segments.len()
+                && self
+                    .segments
+                    .iter()
+                    .zip(segments)
+                    .all(|(route, documented)| match route {
+                        Some(literal) => literal == documented,
+                        None => documented.contains('{'),
+                    })
+        }
+    }
+
+    /// The names and non test sources of all modules of the api, so that new route modules are
+    /// checked without having to list them here.
+    fn api_modules() -> Vec<(String, String)> {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/api");
+        let mut modules = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+                let source = fs::read_to_string(&path).unwrap();
+                let source = source.split("#[cfg(test)]").next().unwrap().to_string();
+                (name, source)
+            })
+            .collect::<Vec<_>>();
+        modules.sort();
+        assert!(modules.len() > 1);
+        modules
+    }
+
+    /// The routes declared in the source of a module. Routes that are nested in a path ending in
+    /// `..` get its segments as prefix.
+    fn routes(source: &str) -> Vec<Route> {
+        let mut prefix = Vec::new();
+        let mut routes = Vec::new();
+        for declaration in source.split("warp::path!(").skip(1) {
+            let end = declaration.find(')').unwrap();
+            let mut segments = declaration[..end]
+                .split('/')
+                .map(str::trim)
+                .map(|segment| {
+                    segment
+                        .strip_prefix('"')
+                        .and_then(|segment| segment.strip_suffix('"'))
+                        .map(str::to_string)
+                })
+                .collect::<Vec<_>>();
+            if declaration[..end].trim_end().ends_with("..") {
+                segments.pop();
+                prefix = segments;
+                continue;
+            }
+            let method = ["get", "post", "put", "delete"]
+                .iter()
+                .filter_map(|method| {
+                    let position = declaration.find(&format!("warp::{}()", method))?;
+                    Some((position, method.to_string()))
+                })
+                .min()
+                .map(|(_, method)| method)
+                .unwrap_or_else(|| panic!("route {} has no method", &declaration[..end]));
+            routes.push(Route { method, segments });
+        }
+        for route in &mut routes {
+            route.segments.splice(0..0, prefix.iter().cloned());
+        }
+        routes
+    }
+
+    /// The string literals that are passed as first argument to the function.
+    fn string_arguments<'a>(source: &'a str, function: &str) -> Vec<&'a str> {
+        source
+            .split(function)
+            .skip(1)
+            .filter_map(|call| call.trim_start().strip_prefix('"'))
+            .filter_map(|argument| argument.split('"').next())
+            .collect()
+    }
+
+    #[test]
+    fn documents_every_route() {
+        let operations = operations();
+        let routes = api_modules()
+            .iter()
+            .flat_map(|(_, source)| routes(source))
+            .collect::<Vec<_>>();
+        assert!(!routes.is_empty());
+        for route in &routes {
+            assert!(
+                operations
+                    .iter()
+                    .any(|operation| route.is_documented_by(operation)),
+                "route {:?} is not documented",
+                route
+            );
+        }
+        for operation in &operations {
+            assert!(
+                routes.iter().any(|route| route.is_documented_by(operation)),
+                "documented {} {} does not exist",
+                operation.method,
+                operation.path
+            );
+        }
+    }
+
+    #[test]
+    fn documents_every_error_type() {
+        let operations = operations();
+        for (module, source) in api_modules() {
+            let routes = routes(&source);
+            // Errors of modules without routes, like the rate limit, can occur on every route.
+            let module_operations = operations
+                .iter()
+                .filter(|operation| {
+                    routes.is_empty()
+                        || routes.iter().any(|route| route.is_documented_by(operation))
+                })
+                .collect::<Vec<_>>();
+            for error_type in string_arguments(&source, "error(") {
+                let documented = |operation: &&Operation| {
+                    operation
+                        .errors
+                        .iter()
+                        .any(|(_, error_types)| error_types.contains(&error_type))
+                };
+                let is_documented = if routes.is_empty() {
+                    module_operations.iter().all(documented)
+                } else {
+                    module_operations.iter().any(documented)
+                };
+                assert!(
+                    is_documented,
+                    "error {} of module {} is not documented",
+                    error_type, module
+                );
+            }
+        }
+    }
+    #[test]
+    fn document_contains_schemas() {
+        let document = document();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        for name in &[
+            "Address",
+            "OrderCreationPayload",
+            "Order",
+            "Trade",
+            "UID",
+            "FeeAndQuoteSellResponse",
+            "TokenSettings",
+        ] {
+            assert!(schemas.contains_key(*name), "missing schema {}", name);
+        }
+        assert_eq!(
+            document["paths"]["/api/v1/orders"]["post"]["requestBody"]["content"]
+                ["application/json"]["schema"]["$ref"],
+            "#/components/schemas/OrderCreationPayload"
+        );
+        let parameters = document["paths"]["/api/v1/feeAndQuote/sell"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|parameter| parameter["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(parameters, ["buyToken", "sellAmountBeforeFee", "sellToken"]);
+    }
+}
@@ -0,0 +1,422 @@
+//! Per client rate limiting of the api.
+//!
+//! Clients are identified by their IP address and, if they send one of the configured ones, by
+//! their `X-AppId` header. Every client has a token bucket per route cost which allows bursts of
+//! up to the configured number of requests per minute and refills continuously at that rate. A
+//! request has to be allowed by the buckets of both its IP address and its app id.
+//!
+//! Behind a reverse proxy the remote address is the proxy's. If it is configured as trusted the
+//! client's address is taken from the `X-Forwarded-For` header instead.
+
+use anyhow::Result;
+use shared::maintenance::Maintaining;
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use strum_macros::AsStaticStr;
+use warp::{
+    hyper::StatusCode,
+    path::FullPath,
+    reject::Reject,
+    reply::{with_header, with_status, Response},
+    Filter, Rejection, Reply,
+};
+
+/// How expensive a route is to serve.
+#[derive(AsStaticStr, Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Cost {
+    Cheap,
+    /// Routes that estimate fees or prices, which fetches pools and queries the node on every
+    /// call.
+    Expensive,
+}
+
+impl Cost {
+    fn of_path(path: &str) -> Self {
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        match segments.as_slice() {
+            ["api", "v1", "fee"]
+            | ["api", "v1", "tokens", _, "fee"]
+            | ["api", "v1", "feeAndQuote", ..]
+            | ["api", "v1", "markets", ..] => Self::Expensive,
+            _ => Self::Cheap,
+        }
+    }
+}
+
+/// Requests per minute that a single client can make. `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Budget {
+    pub cheap: Option<NonZeroU32>,
+    pub expensive: Option<NonZeroU32>,
+}
+
+impl Budget {
+    fn get(&self, cost: Cost) -> Option<NonZeroU32> {
+        match cost {
+            Cost::Cheap => self.cheap,
+            Cost::Expensive => self.expensive,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RateLimits {
+    pub per_ip: Budget,
+    pub per_app_id: Budget,
+    /// The `X-AppId`s that have their own budget and metrics label. Requests with other app ids
+    /// are only limited by their IP address so that clients can't avoid the limit by sending a
+    /// new app id with every request.
+    pub app_ids: HashSet<String>,
+    /// Proxies whose `X-Forwarded-For` header is used to determine the IP address of the client.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+pub trait Metrics: Send + Sync {
+    /// The app id is one of the configured ones, `other` or `none` if the request has no app id.
+    fn request_checked(&self, app_id: &str, cost: Cost, limited: bool);
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Client {
+    Ip(IpAddr),
+    AppId(String),
+}
+
+struct Bucket {
+    limit: NonZeroU32,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: NonZeroU32, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.get() as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let per_second = self.limit.get() as f64 / 60.;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(self.limit.get() as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.limit.get() as f64
+    }
+
+    /// How long until the bucket has a token.
+    fn wait(&self) -> Duration {
+        if self.tokens >= 1. {
+            return Duration::from_secs(0);
+        }
+        Duration::from_secs_f64((1. - self.tokens) * 60. / self.limit.get() as f64)
+    }
+}
+
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<HashMap<(Client, Cost), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Default::default(),
+        }
+    }
+
+    /// The configured app id of the request.
+    fn known_app_id<'a>(&self, app_id: Option<&'a str>) -> Option<&'a str> {
+        app_id.filter(|app_id| self.limits.app_ids.contains(*app_id))
+    }
+
+    /// The IP address of the client. Follows the `X-Forwarded-For` header from the right for as
+    /// long as the address it was received from is a trusted proxy.
+    fn client_ip(&self, remote: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let mut ip = remote?;
+        for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+            if !self.limits.trusted_proxies.contains(&ip) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(hop) => ip = hop,
+                Err(_) => break,
+            }
+        }
+        Some(ip)
+    }
+
+    /// Takes a token from the buckets of all clients of the request or returns how long to wait
+    /// until the request would be allowed. Nothing is taken if the request is not allowed.
+    fn check(
+        &self,
+        ip: Option<IpAddr>,
+        app_id: Option<&str>,
+        cost: Cost,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let mut clients = Vec::new();
+        if let (Some(ip), Some(limit)) = (ip, self.limits.per_ip.get(cost)) {
+            clients.push(((Client::Ip(ip), cost), limit));
+        }
+        if let (Some(app_id), Some(limit)) = (app_id, self.limits.per_app_id.get(cost)) {
+            clients.push(((Client::AppId(app_id.to_string()), cost), limit));
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut wait = Duration::from_secs(0);
+        for (key, limit) in &clients {
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| Bucket::full(*limit, now));
+            bucket.refill(now);
+            wait = wait.max(bucket.wait());
+        }
+        if wait > Duration::from_secs(0) {
+            return Err(wait);
+        }
+        for (key, _) in &clients {
+            buckets.get_mut(key).unwrap().tokens -= 1.;
+        }
+        Ok(())
+    }
+
+    /// Forgets clients whose buckets have refilled completely so that memory does not grow with
+    /// every client that ever made a request.
+    fn remove_idle_clients(&self, now: Instant) {
+        self.buckets.lock().unwrap().retain(|_, bucket| {
+            bucket.refill(now);
+            !bucket.is_full()
+        });
+    }
+}
+
+#[async_trait::async_trait]
+impl Maintaining for RateLimiter {
+    async fn run_maintenance(&self) -> Result<()> {
+        self.remove_idle_clients(Instant::now());
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct TooManyRequests {
+    retry_after: Duration,
+}
+
+impl Reject for TooManyRequests {}
+
+impl TooManyRequests {
+    pub fn response(&self) -> Response {
+        // Retry-After only supports whole seconds so round up to not invite a retry that is
+        // rejected again.
+        let retry_after = self.retry_after.as_secs_f64().ceil() as u64;
+        with_header(
+            with_status(
+                super::error(
+                    "TooManyRequests",
+                    format!("Rate limit exceeded, retry in {} seconds", retry_after),
+                ),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            "Retry-After",
+            retry_after.to_string(),
+        )
+        .into_response()
+    }
+}
+
+/// Rejects requests of clients that exceeded their budget with `TooManyRequests`.
+pub fn rate_limit(
+    limiter: Arc<RateLimiter>,
+    metrics: Arc<dyn Metrics>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>("X-Forwarded-For"))
+        .and(warp::header::optional::<String>("X-AppId"))
+        .and_then(
+            move |path: FullPath,
+                  remote: Option<SocketAddr>,
+                  forwarded_for: Option<String>,
+                  app_id: Option<String>| {
+                let limiter = limiter.clone();
+                let metrics = metrics.clone();
+                async move {
+                    let cost = Cost::of_path(path.as_str());
+                    let ip = limiter
+                        .client_ip(remote.map(|address| address.ip()), forwarded_for.as_deref());
+                    let known_app_id = limiter.known_app_id(app_id.as_deref());
+                    let result = limiter.check(ip, known_app_id, cost, Instant::now());
+                    let app_id_label = match (known_app_id, &app_id) {
+                        (Some(app_id), _) => app_id,
+                        (None, Some(_)) => "other",
+                        (None, None) => "none",
+                    };
+                    metrics.request_checked(app_id_label, cost, result.is_err());
+                    result.map_err(|retry_after| {
+                        warp::reject::custom(TooManyRequests { retry_after })
+                    })
+                }
+            },
+        )
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(cheap: u32, expensive: u32) -> Budget {
+        Budget {
+            cheap: NonZeroU32::new(cheap),
+            expensive: NonZeroU32::new(expensive),
+        }
+    }
+
+    #[test]
+    fn classifies_routes() {
+        assert_eq!(Cost::of_path("/api/v1/orders"), Cost::Cheap);
+        assert_eq!(Cost::of_path("/api/v1/tokens/0x00"), Cost::Cheap);
+        assert_eq!(Cost::of_path("/api/v1/fee"), Cost::Expensive);
+        assert_eq!(Cost::of_path("/api/v1/fee/"), Cost::Expensive);
+        assert_eq!(Cost::of_path("/api/v1/tokens/0x00/fee"), Cost::Expensive);
+        assert_eq!(Cost::of_path("/api/v1/feeAndQuote/sell"), Cost::Expensive);
+        assert_eq!(
+            Cost::of_path("/api/v1/markets/0x00-0x01/sell/1"),
+            Cost::Expensive
+        );
+    }
+
+    #[test]
+    fn limits_requests_per_ip() {
+        let limiter = RateLimiter::new(RateLimits {
+            per_ip: limits(2, 0),
+            ..Default::default()
+        });
+        let ip = Some([1, 2, 3, 4].into());
+        let now = Instant::now();
+
+        assert!(limiter.check(ip, None, Cost::Cheap, now).is_ok());
+        assert!(limiter.check(ip, None, Cost::Cheap, now).is_ok());
+        assert_eq!(
+            limiter.check(ip, None, Cost::Cheap, now),
+            Err(Duration::from_secs(30))
+        );
+        // Other addresses and unlimited costs are not affected.
+        assert!(limiter
+            .check(Some([4, 3, 2, 1].into()), None, Cost::Cheap, now)
+            .is_ok());
+        assert!(limiter.check(ip, None, Cost::Expensive, now).is_ok());
+        // Half a minute refills one of the two requests per minute.
+        let later = now + Duration::from_secs(30);
+        assert!(limiter.check(ip, None, Cost::Cheap, later).is_ok());
+        assert!(limiter.check(ip, None, Cost::Cheap, later).is_err());
+    }
+
+    #[test]
+    fn requests_need_to_be_allowed_for_ip_and_app_id() {
+        let limiter = RateLimiter::new(RateLimits {
+            per_ip: limits(0, 1),
+            per_app_id: limits(0, 2),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        let first_ip = Some([1, 1, 1, 1].into());
+        let second_ip = Some([2, 2, 2, 2].into());
+        assert!(limiter
+            .check(first_ip, Some("app"), Cost::Expensive, now)
+            .is_ok());
+        // Rejected by the ip so the app id keeps its token.
+        assert!(limiter
+            .check(first_ip, Some("app"), Cost::Expensive, now)
+            .is_err());
+        assert!(limiter
+            .check(second_ip, Some("app"), Cost::Expensive, now)
+            .is_ok());
+        // Rejected by the app id.
+        assert!(limiter
+            .check(Some([3, 3, 3, 3].into()), Some("app"), Cost::Expensive, now)
+            .is_err());
+        assert!(limiter
+            .check(Some([3, 3, 3, 3].into()), None, Cost::Expensive, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn only_configured_app_ids_are_known() {
+        let limiter = RateLimiter::new(RateLimits {
+            app_ids: vec!["CowSwap".to_string()].into_iter().collect(),
+            ..Default::default()
+        });
+        assert_eq!(limiter.known_app_id(Some("CowSwap")), Some("CowSwap"));
+        assert_eq!(limiter.known_app_id(Some("random")), None);
+        assert_eq!(limiter.known_app_id(None), None);
+    }
+
+    #[test]
+    fn client_ip_is_forwarded_by_trusted_proxies() {
+        let proxy = [10, 0, 0, 1].into();
+        let client = [1, 2, 3, 4].into();
+        let limiter = RateLimiter::new(RateLimits {
+            trusted_proxies: vec![proxy],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            limiter.client_ip(Some(proxy), Some("1.2.3.4")),
+            Some(client)
+        );
+        // Only the addresses added by trusted proxies can be relied on, anything before it could
+        // have been sent by the client.
+        assert_eq!(
+            limiter.client_ip(Some(proxy), Some("5.6.7.8, 1.2.3.4, 10.0.0.1")),
+            Some(client)
+        );
+        assert_eq!(
+            limiter.client_ip(Some(client), Some("5.6.7.8")),
+            Some(client)
+        );
+        assert_eq!(limiter.client_ip(Some(proxy), Some("invalid")), Some(proxy));
+        assert_eq!(limiter.client_ip(Some(proxy), None), Some(proxy));
+        assert_eq!(limiter.client_ip(None, Some("1.2.3.4")), None);
+    }
+
+    #[test]
+    fn removes_idle_clients() {
+        let limiter = RateLimiter::new(RateLimits {
+            per_ip: limits(60, 0),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        limiter
+            .check(Some([1, 1, 1, 1].into()), None, Cost::Cheap, now)
+            .unwrap();
+
+        limiter.remove_idle_clients(now);
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+        limiter.remove_idle_clients(now + Duration::from_secs(1));
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn retry_after_is_rounded_up() {
+        let response = TooManyRequests {
+            retry_after: Duration::from_millis(1500),
+        }
+        .response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["Retry-After"], "2");
+    }
+}
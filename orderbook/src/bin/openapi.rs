@@ -0,0 +1,7 @@
+//! Prints the OpenAPI document of the orderbook api so that it can be validated and published
+//! without running the service.
+
+fn main() {
+    let document = orderbook::api::openapi::document();
+    println!("{}", serde_json::to_string_pretty(&document).unwrap());
+}
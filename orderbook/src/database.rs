@@ -2,10 +2,13 @@ pub mod events;
 pub mod fees;
 pub mod instrumented;
 pub mod orders;
+pub mod runtime_settings;
 pub mod trades;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use futures::stream::BoxStream;
+use serde_json::{json, Value};
+use shared::health::HealthChecking;
 use sqlx::{Executor, PgPool, Row};
 use std::collections::HashMap;
 
@@ -14,12 +17,15 @@ use std::collections::HashMap;
 // enough anyway.
 
 // The names of all tables we use in the db.
-const ALL_TABLES: [&str; 5] = [
+const ALL_TABLES: [&str; 8] = [
     "orders",
     "trades",
     "invalidations",
     "min_fee_measurements",
     "settlements",
+    "token_setting_changes",
+    "solver_setting_changes",
+    "market_makable_token_list_changes",
 ];
 
 // The pool uses an Arc internally.
@@ -62,6 +68,17 @@ impl Postgres {
     }
 }
 
+#[async_trait::async_trait]
+impl HealthChecking for Postgres {
+    async fn check_health(&self) -> Result<Value> {
+        self.pool
+            .execute("SELECT 1;")
+            .await
+            .context("database is not reachable")?;
+        Ok(json!({}))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,7 +91,7 @@ mod tests {
         db.clear().await.unwrap();
 
         let counts = db.count_rows_in_tables().await.unwrap();
-        assert_eq!(counts.len(), 5);
+        assert_eq!(counts.len(), 8);
         assert!(counts.iter().all(|(_, count)| *count == 0));
 
         db.insert_order(&Default::default()).await.unwrap();
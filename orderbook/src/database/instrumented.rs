@@ -1,5 +1,5 @@
 use super::{orders::OrderStoring, trades::TradeRetrieving, Postgres};
-use crate::fee::MinFeeStoring;
+use crate::{fee::MinFeeStoring, runtime_settings::SettingsChangeStoring};
 use prometheus::Histogram;
 use shared::{event_handling::EventStoring, maintenance::Maintaining};
 use std::sync::Arc;
@@ -126,6 +126,81 @@ impl OrderStoring for Instrumented {
     }
 }
 
+#[async_trait::async_trait]
+impl SettingsChangeStoring for Instrumented {
+    async fn record_token_settings(
+        &self,
+        token: ethcontract::H160,
+        settings: crate::runtime_settings::TokenSettings,
+        comment: Option<String>,
+    ) -> anyhow::Result<()> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("record_token_settings")
+            .start_timer();
+        self.inner
+            .record_token_settings(token, settings, comment)
+            .await
+    }
+
+    async fn record_solver_enabled(
+        &self,
+        solver: String,
+        enabled: bool,
+        comment: Option<String>,
+    ) -> anyhow::Result<()> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("record_solver_enabled")
+            .start_timer();
+        self.inner
+            .record_solver_enabled(solver, enabled, comment)
+            .await
+    }
+
+    async fn latest_token_settings(
+        &self,
+    ) -> anyhow::Result<Vec<(ethcontract::H160, crate::runtime_settings::TokenSettings)>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("latest_token_settings")
+            .start_timer();
+        self.inner.latest_token_settings().await
+    }
+
+    async fn latest_solver_enabled(&self) -> anyhow::Result<Vec<(String, bool)>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("latest_solver_enabled")
+            .start_timer();
+        self.inner.latest_solver_enabled().await
+    }
+
+    async fn record_market_makable_token_list(
+        &self,
+        url: String,
+        comment: Option<String>,
+    ) -> anyhow::Result<i64> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("record_market_makable_token_list")
+            .start_timer();
+        self.inner
+            .record_market_makable_token_list(url, comment)
+            .await
+    }
+
+    async fn latest_market_makable_token_list(
+        &self,
+    ) -> anyhow::Result<Option<model::api::MarketMakableTokenList>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("latest_market_makable_token_list")
+            .start_timer();
+        self.inner.latest_market_makable_token_list().await
+    }
+}
+
 impl TradeRetrieving for Instrumented {
     fn trades<'a>(
         &'a self,
@@ -0,0 +1,192 @@
+use super::Postgres;
+use crate::conversions::h160_from_vec;
+use crate::runtime_settings::{SettingsChangeStoring, TokenSettings};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use ethcontract::H160;
+use model::api::MarketMakableTokenList;
+use shared::bad_token::list_based::TokenListing;
+
+fn listing_to_str(listing: TokenListing) -> &'static str {
+    match listing {
+        TokenListing::Allowed => "allowed",
+        TokenListing::Denied => "denied",
+        TokenListing::Unlisted => "unlisted",
+    }
+}
+
+fn listing_from_str(listing: &str) -> Result<TokenListing> {
+    match listing {
+        "allowed" => Ok(TokenListing::Allowed),
+        "denied" => Ok(TokenListing::Denied),
+        "unlisted" => Ok(TokenListing::Unlisted),
+        _ => Err(anyhow!("unknown token listing {}", listing)),
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TokenSettingsRow {
+    token: Vec<u8>,
+    listing: String,
+    fee_factor: Option<f64>,
+}
+
+#[async_trait::async_trait]
+impl SettingsChangeStoring for Postgres {
+    async fn record_token_settings(
+        &self,
+        token: H160,
+        settings: TokenSettings,
+        comment: Option<String>,
+    ) -> Result<()> {
+        const QUERY: &str = "\
+            INSERT INTO token_setting_changes (changed_at, token, listing, fee_factor, comment) \
+            VALUES ($1, $2, $3, $4, $5);";
+        sqlx::query(QUERY)
+            .bind(Utc::now())
+            .bind(token.as_bytes())
+            .bind(listing_to_str(settings.listing))
+            .bind(settings.fee_factor)
+            .bind(comment)
+            .execute(&self.pool)
+            .await
+            .context("insert token_setting_changes failed")
+            .map(|_| ())
+    }
+
+    async fn record_solver_enabled(
+        &self,
+        solver: String,
+        enabled: bool,
+        comment: Option<String>,
+    ) -> Result<()> {
+        const QUERY: &str = "\
+            INSERT INTO solver_setting_changes (changed_at, solver, enabled, comment) \
+            VALUES ($1, $2, $3, $4);";
+        sqlx::query(QUERY)
+            .bind(Utc::now())
+            .bind(solver)
+            .bind(enabled)
+            .bind(comment)
+            .execute(&self.pool)
+            .await
+            .context("insert solver_setting_changes failed")
+            .map(|_| ())
+    }
+
+    async fn latest_token_settings(&self) -> Result<Vec<(H160, TokenSettings)>> {
+        const QUERY: &str = "\
+            SELECT DISTINCT ON (token) token, listing, fee_factor \
+            FROM token_setting_changes \
+            ORDER BY token, id DESC;";
+        let rows: Vec<TokenSettingsRow> = sqlx::query_as(QUERY)
+            .fetch_all(&self.pool)
+            .await
+            .context("load token_setting_changes failed")?;
+        rows.into_iter()
+            .map(|row| {
+                let token = h160_from_vec(row.token)?;
+                let settings = TokenSettings {
+                    listing: listing_from_str(&row.listing)?,
+                    fee_factor: row.fee_factor,
+                };
+                Ok((token, settings))
+            })
+            .collect()
+    }
+
+    async fn latest_solver_enabled(&self) -> Result<Vec<(String, bool)>> {
+        const QUERY: &str = "\
+            SELECT DISTINCT ON (solver) solver, enabled \
+            FROM solver_setting_changes \
+            ORDER BY solver, id DESC;";
+        sqlx::query_as(QUERY)
+            .fetch_all(&self.pool)
+            .await
+            .context("load solver_setting_changes failed")
+    }
+
+    async fn record_market_makable_token_list(
+        &self,
+        url: String,
+        comment: Option<String>,
+    ) -> Result<i64> {
+        const QUERY: &str = "\
+            INSERT INTO market_makable_token_list_changes (changed_at, url, comment) \
+            VALUES ($1, $2, $3) \
+            RETURNING id;";
+        sqlx::query_scalar(QUERY)
+            .bind(Utc::now())
+            .bind(url)
+            .bind(comment)
+            .fetch_one(&self.pool)
+            .await
+            .context("insert market_makable_token_list_changes failed")
+    }
+
+    async fn latest_market_makable_token_list(&self) -> Result<Option<MarketMakableTokenList>> {
+        const QUERY: &str = "\
+            SELECT id, url \
+            FROM market_makable_token_list_changes \
+            ORDER BY id DESC \
+            LIMIT 1;";
+        let row: Option<(i64, String)> = sqlx::query_as(QUERY)
+            .fetch_optional(&self.pool)
+            .await
+            .context("load market_makable_token_list_changes failed")?;
+        Ok(row.map(|(revision, url)| MarketMakableTokenList { url, revision }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_latest_settings_changes() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let token = H160::from_low_u64_be(1);
+        let denied = TokenSettings {
+            listing: TokenListing::Denied,
+            fee_factor: Some(1.5),
+        };
+        db.record_token_settings(token, denied.clone(), Some("scam".to_string()))
+            .await
+            .unwrap();
+        let unlisted = TokenSettings {
+            listing: TokenListing::Unlisted,
+            fee_factor: None,
+        };
+        db.record_token_settings(token, unlisted.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.latest_token_settings().await.unwrap(),
+            vec![(token, unlisted)]
+        );
+
+        db.record_solver_enabled("NaiveSolver".to_string(), false, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.latest_solver_enabled().await.unwrap(),
+            vec![("NaiveSolver".to_string(), false)]
+        );
+
+        assert_eq!(db.latest_market_makable_token_list().await.unwrap(), None);
+        let revision = db
+            .record_market_makable_token_list("https://tokens.example/list.json".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.latest_market_makable_token_list().await.unwrap(),
+            Some(MarketMakableTokenList {
+                url: "https://tokens.example/list.json".to_string(),
+                revision,
+            })
+        );
+    }
+}
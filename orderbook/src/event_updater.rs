@@ -1,19 +1,25 @@
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
 use contracts::{
     gpv2_settlement::{self, Event as ContractEvent},
     GPv2Settlement,
 };
 use ethcontract::dyns::DynWeb3;
+use serde_json::{json, Value};
 use shared::{
+    current_block::{self, CurrentBlockStream},
     event_handling::{EventHandler, EventStoring},
+    health::HealthChecking,
     impl_event_retrieving,
     maintenance::Maintaining,
 };
+use std::sync::{Arc, RwLock};
 use tokio::sync::Mutex;
 
-pub struct EventUpdater<Database: EventStoring<ContractEvent>>(
-    Mutex<EventHandler<DynWeb3, GPv2SettlementContract, Database>>,
-);
+pub struct EventUpdater<Database: EventStoring<ContractEvent>> {
+    handler: Mutex<EventHandler<DynWeb3, GPv2SettlementContract, Database>>,
+    /// Copy of the handler's last handled block that can be read while an update is running.
+    last_handled_block: RwLock<Option<u64>>,
+}
 
 impl_event_retrieving! {
     pub GPv2SettlementContract for gpv2_settlement
@@ -24,12 +30,20 @@ where
     Database: EventStoring<ContractEvent>,
 {
     pub fn new(contract: GPv2Settlement, db: Database, start_sync_at_block: Option<u64>) -> Self {
-        Self(Mutex::new(EventHandler::new(
-            contract.raw_instance().web3(),
-            GPv2SettlementContract(contract),
-            db,
-            start_sync_at_block,
-        )))
+        Self {
+            handler: Mutex::new(EventHandler::new(
+                contract.raw_instance().web3(),
+                GPv2SettlementContract(contract),
+                db,
+                start_sync_at_block,
+            )),
+            last_handled_block: RwLock::new(None),
+        }
+    }
+
+    /// The last block up to which events were stored by a completed update.
+    pub fn last_handled_block(&self) -> Option<u64> {
+        *self.last_handled_block.read().unwrap()
     }
 }
 
@@ -39,6 +53,40 @@ where
     Database: EventStoring<ContractEvent>,
 {
     async fn run_maintenance(&self) -> Result<()> {
-        self.0.run_maintenance().await
+        let mut handler = self.handler.lock().await;
+        handler.update_events().await?;
+        *self.last_handled_block.write().unwrap() = handler.last_handled_block();
+        Ok(())
+    }
+}
+
+/// Reports how many blocks the stored events lag behind the current block.
+pub struct EventSyncHealth<Database: EventStoring<ContractEvent>> {
+    pub updater: Arc<EventUpdater<Database>>,
+    pub block_stream: CurrentBlockStream,
+    pub max_lag: u64,
+}
+
+#[async_trait::async_trait]
+impl<Database> HealthChecking for EventSyncHealth<Database>
+where
+    Database: EventStoring<ContractEvent>,
+{
+    async fn check_health(&self) -> Result<Value> {
+        let last_handled_block = self
+            .updater
+            .last_handled_block()
+            .ok_or_else(|| anyhow!("events have not been synced yet"))?;
+        let current_block = current_block::block_number(&self.block_stream.borrow())?;
+        let lag = current_block.saturating_sub(last_handled_block);
+        ensure!(
+            lag <= self.max_lag,
+            "events are {} blocks behind the current block",
+            lag
+        );
+        Ok(json!({
+            "lastHandledBlock": last_handled_block,
+            "lagBlocks": lag,
+        }))
     }
 }
@@ -5,7 +5,7 @@ use model::order::{OrderKind, BUY_ETH_ADDRESS};
 use primitive_types::{H160, U256};
 use shared::{bad_token::BadTokenDetecting, price_estimate::PriceEstimating};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use thiserror::Error;
 
 pub type Measurement = (U256, DateTime<Utc>);
@@ -25,6 +25,38 @@ pub struct MinFeeCalculator {
     now: Box<dyn Fn() -> DateTime<Utc> + Send + Sync>,
     discount_factor: f64,
     bad_token_detector: Arc<dyn BadTokenDetecting>,
+    fee_factors: Arc<FeeFactorOverrides>,
+}
+
+/// Factors by which the minimum fee of orders selling a token is multiplied, so that fees for
+/// single tokens can be adjusted while the service is running.
+#[derive(Debug, Default)]
+pub struct FeeFactorOverrides(RwLock<HashMap<H160, f64>>);
+
+impl FeeFactorOverrides {
+    pub fn get(&self, token: H160) -> Option<f64> {
+        self.0.read().unwrap().get(&token).copied()
+    }
+
+    /// Sets the factor for the token or removes it if `None`.
+    pub fn set(&self, token: H160, factor: Option<f64>) {
+        let mut factors = self.0.write().unwrap();
+        match factor {
+            Some(factor) => factors.insert(token, factor),
+            None => factors.remove(&token),
+        };
+    }
+
+    pub fn all(&self) -> HashMap<H160, f64> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn apply(&self, token: H160, fee: U256) -> U256 {
+        match self.get(token) {
+            Some(factor) => U256::from_f64_lossy(fee.to_f64_lossy() * factor),
+            None => fee,
+        }
+    }
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -108,6 +140,7 @@ impl EthAwareMinFeeCalculator {
         measurements: Arc<dyn MinFeeStoring>,
         discount_factor: f64,
         bad_token_detector: Arc<dyn BadTokenDetecting>,
+        fee_factors: Arc<FeeFactorOverrides>,
     ) -> Self {
         Self {
             calculator: MinFeeCalculator::new(
@@ -117,6 +150,7 @@ impl EthAwareMinFeeCalculator {
                 measurements,
                 discount_factor,
                 bad_token_detector,
+                fee_factors,
             ),
             weth: native_token,
         }
@@ -158,6 +192,7 @@ impl MinFeeCalculator {
         measurements: Arc<dyn MinFeeStoring>,
         discount_factor: f64,
         bad_token_detector: Arc<dyn BadTokenDetecting>,
+        fee_factors: Arc<FeeFactorOverrides>,
     ) -> Self {
         Self {
             price_estimator,
@@ -167,6 +202,7 @@ impl MinFeeCalculator {
             now: Box::new(Utc::now),
             discount_factor,
             bad_token_detector,
+            fee_factors,
         }
     }
 
@@ -257,7 +293,10 @@ impl MinFeeCalculating for MinFeeCalculator {
             .get_min_fee(sell_token, buy_token, amount, kind, official_valid_until)
             .await
         {
-            return Ok((past_fee, official_valid_until));
+            return Ok((
+                self.fee_factors.apply(sell_token, past_fee),
+                official_valid_until,
+            ));
         }
 
         let min_fee = match self
@@ -279,22 +318,27 @@ impl MinFeeCalculating for MinFeeCalculator {
                 min_fee,
             )
             .await;
-        Ok((min_fee, official_valid_until))
+        Ok((
+            self.fee_factors.apply(sell_token, min_fee),
+            official_valid_until,
+        ))
     }
 
     // Returns true if the fee satisfies a previous not yet expired estimate, or the fee is high enough given the current estimate.
+    // Measurements are stored without the fee factor of the token so that changing the factor
+    // takes effect immediately.
     async fn is_valid_fee(&self, sell_token: H160, fee: U256) -> bool {
         if let Ok(Some(past_fee)) = self
             .measurements
             .get_min_fee(sell_token, None, None, None, (self.now)())
             .await
         {
-            if fee >= past_fee {
+            if fee >= self.fee_factors.apply(sell_token, past_fee) {
                 return true;
             }
         }
         if let Ok(Some(current_fee)) = self.compute_min_fee(sell_token, None, None, None).await {
-            return fee >= current_fee;
+            return fee >= self.fee_factors.apply(sell_token, current_fee);
         }
         false
     }
@@ -438,6 +482,7 @@ mod tests {
                 now,
                 discount_factor: 1.0,
                 bad_token_detector: Arc::new(ListBasedDetector::deny_list(Vec::new())),
+                fee_factors: Default::default(),
             }
         }
     }
@@ -518,6 +563,7 @@ mod tests {
             now: Box::new(Utc::now),
             discount_factor: 1.0,
             bad_token_detector: Arc::new(ListBasedDetector::deny_list(vec![unsupported_token])),
+            fee_factors: Default::default(),
         };
 
         // Selling unsupported token
@@ -546,4 +592,32 @@ mod tests {
             Err(MinFeeCalculationError::UnsupportedToken(t)) if t == unsupported_token
         ));
     }
+
+    #[tokio::test]
+    async fn applies_fee_factor_overrides() {
+        let gas_price_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(100.0))));
+        let price_estimator = Arc::new(FakePriceEstimator(num::one()));
+        let fee_estimator = MinFeeCalculator::new_for_test(
+            gas_price_estimator,
+            price_estimator,
+            Box::new(Utc::now),
+        );
+
+        let token = H160::from_low_u64_be(1);
+        let (fee, _) = fee_estimator
+            .min_fee(token, None, None, None)
+            .await
+            .unwrap();
+
+        fee_estimator.fee_factors.set(token, Some(0.5));
+        let (discounted_fee, _) = fee_estimator
+            .min_fee(token, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(discounted_fee, fee / 2);
+        assert!(fee_estimator.is_valid_fee(token, discounted_fee).await);
+
+        fee_estimator.fee_factors.set(token, None);
+        assert!(!fee_estimator.is_valid_fee(token, discounted_fee).await);
+    }
 }
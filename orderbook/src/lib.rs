@@ -6,8 +6,13 @@ pub mod event_updater;
 pub mod fee;
 pub mod metrics;
 pub mod orderbook;
+pub mod runtime_settings;
 
-use crate::orderbook::Orderbook;
+use crate::{
+    api::{rate_limit::RateLimiter, AdminToken},
+    orderbook::Orderbook,
+    runtime_settings::RuntimeSettings,
+};
 use anyhow::{anyhow, Context as _, Result};
 use contracts::GPv2Settlement;
 use database::trades::TradeRetrieving;
@@ -16,12 +21,14 @@ use metrics::Metrics;
 use model::DomainSeparator;
 use prometheus::Registry;
 use shared::{
+    health::HealthReporter,
     metrics::{serve_metrics, DEFAULT_METRICS_PORT},
     price_estimate::PriceEstimating,
 };
 use std::{net::SocketAddr, sync::Arc};
 use tokio::{task, task::JoinHandle};
 
+#[allow(clippy::too_many_arguments)]
 pub fn serve_task(
     database: Arc<dyn TradeRetrieving>,
     orderbook: Arc<Orderbook>,
@@ -30,6 +37,10 @@ pub fn serve_task(
     address: SocketAddr,
     registry: Registry,
     metrics: Arc<Metrics>,
+    health: Arc<HealthReporter>,
+    runtime_settings: Arc<RuntimeSettings>,
+    admin_token: Option<AdminToken>,
+    rate_limiter: Arc<RateLimiter>,
 ) -> JoinHandle<()> {
     let filter = api::handle_all_routes(
         database,
@@ -37,6 +48,9 @@ pub fn serve_task(
         fee_calculator,
         price_estimator,
         metrics,
+        runtime_settings,
+        admin_token,
+        rate_limiter,
     );
     let mut metrics_address = address;
     tracing::info!(%address, "serving order book");
@@ -44,7 +58,7 @@ pub fn serve_task(
 
     tracing::info!(%metrics_address, "serving metrics");
     metrics_address.set_port(DEFAULT_METRICS_PORT);
-    serve_metrics(registry, metrics_address)
+    serve_metrics(registry, health, metrics_address)
 }
 
 /**
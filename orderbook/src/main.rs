@@ -1,15 +1,21 @@
 use contracts::{GPv2Settlement, WETH9};
+use ethcontract::dyns::DynTransport;
 use model::{
     order::{OrderUid, BUY_ETH_ADDRESS},
     DomainSeparator,
 };
 use orderbook::{
     account_balances::Web3BalanceFetcher,
+    api::{
+        rate_limit::{Budget, RateLimiter, RateLimits},
+        AdminToken,
+    },
     database::{self, orders::OrderFilter, Postgres},
-    event_updater::EventUpdater,
-    fee::EthAwareMinFeeCalculator,
+    event_updater::{EventSyncHealth, EventUpdater},
+    fee::{EthAwareMinFeeCalculator, FeeFactorOverrides},
     metrics::Metrics,
     orderbook::Orderbook,
+    runtime_settings::RuntimeSettings,
     serve_task, verify_deployed_contract_constants,
 };
 use primitive_types::H160;
@@ -20,9 +26,10 @@ use shared::{
         list_based::{ListBasedDetector, UnknownTokenStrategy},
         trace_call::TraceCallDetector,
     },
-    current_block::current_block_stream,
+    current_block::subscribed_current_block_stream,
+    health::{CurrentBlockHealth, HealthChecking, HealthReporter, HeartbeatHealth},
     maintenance::ServiceMaintenance,
-    price_estimate::BaselinePriceEstimator,
+    price_estimate::{caching::CachingPriceEstimator, BaselinePriceEstimator},
     recent_block_cache::CacheConfig,
     sources::{
         self,
@@ -32,11 +39,17 @@ use shared::{
         },
         PoolAggregator,
     },
+    transport::caching::CachingTransport,
     transport::create_instrumented_transport,
-    transport::http::HttpTransport,
+    transport::failover::FailoverTransport,
 };
 use std::{
-    collections::HashSet, iter::FromIterator as _, net::SocketAddr, sync::Arc, time::Duration,
+    collections::HashSet,
+    iter::FromIterator as _,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    sync::Arc,
+    time::Duration,
 };
 use structopt::StructOpt;
 use tokio::task;
@@ -99,6 +112,44 @@ struct Arguments {
     /// The number of pairs that are automatically updated in the pool cache.
     #[structopt(long, env, default_value = "200")]
     pub pool_cache_lru_size: usize,
+
+    /// The service is reported as not ready if the stored settlement events are more than this
+    /// many blocks behind the current block.
+    #[structopt(long, env, default_value = "10")]
+    pub health_max_event_sync_lag: u64,
+
+    /// Bearer token that authorizes requests to the admin api, which changes the token lists,
+    /// fee factors and enabled solvers at runtime. The admin api is disabled if not set.
+    #[structopt(long, env)]
+    pub admin_api_token: Option<AdminToken>,
+
+    /// Maximum number of requests per minute from a single IP address to routes that don't
+    /// estimate fees or prices. Unlimited if not set.
+    #[structopt(long, env)]
+    pub ip_rate_limit: Option<NonZeroU32>,
+
+    /// Maximum number of requests per minute from a single IP address to routes that estimate
+    /// fees or prices like fee, fee and quote and markets. Unlimited if not set.
+    #[structopt(long, env)]
+    pub ip_expensive_rate_limit: Option<NonZeroU32>,
+
+    /// Like `ip_rate_limit` but for all requests with the same `X-AppId` header.
+    #[structopt(long, env)]
+    pub app_id_rate_limit: Option<NonZeroU32>,
+
+    /// Like `ip_expensive_rate_limit` but for all requests with the same `X-AppId` header.
+    #[structopt(long, env)]
+    pub app_id_expensive_rate_limit: Option<NonZeroU32>,
+
+    /// The `X-AppId` headers that are limited by the app id rate limits and reported in the
+    /// metrics. Requests with other app ids are only limited per IP address.
+    #[structopt(long, env, use_delimiter = true)]
+    pub rate_limited_app_ids: Vec<String>,
+
+    /// IP addresses of reverse proxies whose `X-Forwarded-For` header is used to determine the
+    /// IP address that requests are rate limited by.
+    #[structopt(long, env, use_delimiter = true)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 pub async fn database_metrics(metrics: Arc<Metrics>, database: Postgres) -> ! {
@@ -115,17 +166,34 @@ pub async fn database_metrics(metrics: Arc<Metrics>, database: Postgres) -> ! {
     }
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Arguments::from_args();
+fn main() {
+    let args: Arguments = shared::config::load_arguments();
+    tokio::runtime::Runtime::new()
+        .expect("failed to start the async runtime")
+        .block_on(run(args));
+}
+
+async fn run(args: Arguments) {
     shared::tracing::initialize(args.shared.log_filter.as_str());
     tracing::info!("running order book with {:#?}", args);
 
     let registry = Registry::default();
     let metrics = Arc::new(Metrics::new(&registry).unwrap());
 
-    let transport =
-        create_instrumented_transport(HttpTransport::new(args.shared.node_url), metrics.clone());
+    let node_transport = FailoverTransport::http(
+        args.shared.node_urls(),
+        args.shared.failover_config(),
+        metrics.clone(),
+    );
+    let transport = create_instrumented_transport(node_transport.clone(), metrics.clone());
+    let transport = match args.shared.node_response_cache_size {
+        0 => DynTransport::new(transport),
+        cache_size => DynTransport::new(CachingTransport::new(
+            transport,
+            cache_size,
+            metrics.clone(),
+        )),
+    };
     let web3 = web3::Web3::new(transport);
     let settlement_contract = GPv2Settlement::deployed(&web3)
         .await
@@ -166,11 +234,11 @@ async fn main() {
         None
     };
 
-    let event_updater = EventUpdater::new(
+    let event_updater = Arc::new(EventUpdater::new(
         settlement_contract.clone(),
         database.as_ref().clone(),
         sync_start,
-    );
+    ));
     let balance_fetcher =
         Web3BalanceFetcher::new(web3.clone(), gp_allowance, settlement_contract.address());
 
@@ -204,24 +272,28 @@ async fn main() {
         base_tokens: base_tokens.clone(),
         settlement_contract: settlement_contract.address(),
     };
-    let caching_detector = CachingDetector::new(
+    let caching_detector = Arc::new(CachingDetector::new(
         Box::new(trace_call_detector),
         args.token_quality_cache_expiry,
-    );
+    ));
     let bad_token_detector = Arc::new(ListBasedDetector::new(
         allowed_tokens,
         unsupported_tokens,
         if args.skip_trace_api {
             UnknownTokenStrategy::Allow
         } else {
-            UnknownTokenStrategy::Forward(Box::new(caching_detector))
+            UnknownTokenStrategy::Forward(Box::new(caching_detector.clone()))
         },
     ));
 
-    let current_block_stream =
-        current_block_stream(web3.clone(), args.shared.block_stream_poll_interval_seconds)
-            .await
-            .unwrap();
+    let current_block_stream = subscribed_current_block_stream(
+        web3.clone(),
+        args.shared.node_ws_url.clone(),
+        args.shared.block_stream_poll_interval_seconds,
+        metrics.clone(),
+    )
+    .await
+    .unwrap();
 
     let pool_aggregator = PoolAggregator {
         pool_fetchers: pair_providers
@@ -250,13 +322,32 @@ async fn main() {
         .expect("failed to create pool cache"),
     );
 
-    let price_estimator = Arc::new(BaselinePriceEstimator::new(
-        pool_fetcher.clone(),
-        gas_price_estimator.clone(),
-        base_tokens,
+    let price_estimator = Arc::new(CachingPriceEstimator::new(
+        Arc::new(BaselinePriceEstimator::new(
+            pool_fetcher.clone(),
+            gas_price_estimator.clone(),
+            base_tokens,
+            bad_token_detector.clone(),
+            native_token.address(),
+        )),
+        current_block_stream.clone(),
+        metrics.clone(),
+    ));
+    let fee_factors = Arc::new(FeeFactorOverrides::default());
+    let runtime_settings = Arc::new(RuntimeSettings::new(
+        database.clone(),
         bad_token_detector.clone(),
-        native_token.address(),
+        if args.skip_trace_api {
+            None
+        } else {
+            Some(caching_detector)
+        },
+        fee_factors.clone(),
     ));
+    runtime_settings
+        .restore()
+        .await
+        .expect("failed to restore runtime settings");
     let fee_calculator = Arc::new(EthAwareMinFeeCalculator::new(
         price_estimator.clone(),
         gas_price_estimator,
@@ -264,6 +355,7 @@ async fn main() {
         database.clone(),
         args.shared.fee_discount_factor,
         bad_token_detector.clone(),
+        fee_factors,
     ));
 
     let orderbook = Arc::new(Orderbook::new(
@@ -275,14 +367,57 @@ async fn main() {
         bad_token_detector,
         Box::new(web3.clone()),
     ));
+    let rate_limiter = Arc::new(RateLimiter::new(RateLimits {
+        per_ip: Budget {
+            cheap: args.ip_rate_limit,
+            expensive: args.ip_expensive_rate_limit,
+        },
+        per_app_id: Budget {
+            cheap: args.app_id_rate_limit,
+            expensive: args.app_id_expensive_rate_limit,
+        },
+        app_ids: args.rate_limited_app_ids.into_iter().collect(),
+        trusted_proxies: args.trusted_proxies,
+    }));
     let service_maintainer = ServiceMaintenance {
         maintainers: vec![
             orderbook.clone(),
             database.clone(),
-            Arc::new(event_updater),
+            event_updater.clone(),
             pool_fetcher,
+            Arc::new(node_transport),
+            rate_limiter.clone(),
         ],
+        heartbeat: Default::default(),
     };
+    let health = Arc::new(HealthReporter::new(vec![
+        (
+            "current_block",
+            Arc::new(CurrentBlockHealth {
+                block_stream: current_block_stream.clone(),
+                max_age: args.shared.health_max_block_age,
+            }) as Arc<dyn HealthChecking>,
+        ),
+        (
+            "event_sync",
+            Arc::new(EventSyncHealth {
+                updater: event_updater,
+                block_stream: current_block_stream.clone(),
+                max_lag: args.health_max_event_sync_lag,
+            }) as Arc<dyn HealthChecking>,
+        ),
+        (
+            "database",
+            Arc::new(postgres.clone()) as Arc<dyn HealthChecking>,
+        ),
+        (
+            "maintenance",
+            Arc::new(HeartbeatHealth {
+                heartbeat: service_maintainer.heartbeat.clone(),
+                max_age: args.shared.health_max_block_age,
+            }) as Arc<dyn HealthChecking>,
+        ),
+    ]));
     check_database_connection(orderbook.as_ref()).await;
 
     let serve_task = serve_task(
@@ -293,6 +428,10 @@ async fn main() {
         args.bind_address,
         registry,
         metrics.clone(),
+        health,
+        runtime_settings,
+        args.admin_api_token,
+        rate_limiter,
     );
     let maintenance_task =
         task::spawn(service_maintainer.run_maintenance_on_new_block(current_block_stream));
@@ -1,13 +1,20 @@
+use crate::api::rate_limit::Cost;
 use anyhow::Result;
-use prometheus::{Histogram, HistogramOpts, HistogramVec, IntCounter, IntGaugeVec, Opts, Registry};
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+};
 use shared::{
-    sources::uniswap::pool_cache::PoolCacheMetrics, transport::instrumented::TransportMetrics,
+    current_block::{BlockSource, BlockStreamMetrics},
+    price_estimate::caching::PriceCacheMetrics,
+    sources::uniswap::pool_cache::PoolCacheMetrics,
+    transport::{caching::TransportCacheMetrics, instrumented::TransportMetrics},
 };
 use std::{
     convert::Infallible,
     sync::Arc,
     time::{Duration, Instant},
 };
+use strum::AsStaticRef;
 use warp::{reply::Response, Filter, Reply};
 
 pub struct Metrics {
@@ -16,9 +23,16 @@ pub struct Metrics {
     db_table_row_count: IntGaugeVec,
     /// Outgoing RPC request metrics
     rpc_requests: HistogramVec,
+    node_health: IntGaugeVec,
+    block_arrival_latency: HistogramVec,
+    transport_cache_hits: IntCounterVec,
+    transport_cache_misses: IntCounterVec,
     pool_cache_hits: IntCounter,
     pool_cache_misses: IntCounter,
+    price_estimate_cache_hits: IntCounter,
+    price_estimate_cache_misses: IntCounter,
     database_queries: HistogramVec,
+    client_requests: IntCounterVec,
 }
 
 impl Metrics {
@@ -43,6 +57,43 @@ impl Metrics {
         let rpc_requests = HistogramVec::new(opts, &["method"]).unwrap();
         registry.register(Box::new(rpc_requests.clone()))?;
 
+        let node_health = IntGaugeVec::new(
+            Opts::new(
+                "gp_v2_api_node_healthy",
+                "Whether the last request to a node succeeded labelled by node",
+            ),
+            &["node"],
+        )?;
+        registry.register(Box::new(node_health.clone()))?;
+
+        let block_arrival_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "gp_v2_api_block_arrival_latency_seconds",
+                "Time between the timestamp of a new block and observing it labelled by how it was observed",
+            )
+            .buckets(vec![0.5, 1., 2., 4., 8., 16., 32.]),
+            &["source"],
+        )?;
+        registry.register(Box::new(block_arrival_latency.clone()))?;
+
+        let transport_cache_hits = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_api_transport_cache_hits",
+                "Number of node requests served from the response cache labelled by method",
+            ),
+            &["method"],
+        )?;
+        registry.register(Box::new(transport_cache_hits.clone()))?;
+
+        let transport_cache_misses = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_api_transport_cache_misses",
+                "Number of cacheable node requests missing from the response cache labelled by method",
+            ),
+            &["method"],
+        )?;
+        registry.register(Box::new(transport_cache_misses.clone()))?;
+
         let pool_cache_hits = IntCounter::new(
             "gp_v2_api_pool_cache_hits",
             "Number of cache hits in the pool fetcher cache.",
@@ -55,6 +106,18 @@ impl Metrics {
         )?;
         registry.register(Box::new(pool_cache_misses.clone()))?;
 
+        let price_estimate_cache_hits = IntCounter::new(
+            "gp_v2_api_price_estimate_cache_hits",
+            "Number of cache hits in the price estimate cache.",
+        )?;
+        registry.register(Box::new(price_estimate_cache_hits.clone()))?;
+
+        let price_estimate_cache_misses = IntCounter::new(
+            "gp_v2_api_price_estimate_cache_misses",
+            "Number of cache misses in the price estimate cache.",
+        )?;
+        registry.register(Box::new(price_estimate_cache_misses.clone()))?;
+
         let opts = HistogramOpts::new(
             "gp_v2_api_database_queries",
             "Sql queries to our postgresql database.",
@@ -62,13 +125,29 @@ impl Metrics {
         let database_queries = HistogramVec::new(opts, &["type"]).unwrap();
         registry.register(Box::new(database_queries.clone()))?;
 
+        let client_requests = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_api_client_requests",
+                "Number of API requests labelled by the configured X-AppId of the client or other, the cost of the route and whether the request was rate limited",
+            ),
+            &["app_id", "cost", "result"],
+        )?;
+        registry.register(Box::new(client_requests.clone()))?;
+
         Ok(Self {
             api_requests,
             db_table_row_count,
             rpc_requests,
+            node_health,
+            block_arrival_latency,
+            transport_cache_hits,
+            transport_cache_misses,
             pool_cache_hits,
             pool_cache_misses,
+            price_estimate_cache_hits,
+            price_estimate_cache_misses,
             database_queries,
+            client_requests,
         })
     }
 
@@ -85,6 +164,31 @@ impl TransportMetrics for Metrics {
             .with_label_values(&[label])
             .observe(elapsed.as_secs_f64())
     }
+
+    fn report_node_health(&self, node: &str, healthy: bool) {
+        self.node_health
+            .with_label_values(&[node])
+            .set(healthy as i64)
+    }
+}
+
+impl BlockStreamMetrics for Metrics {
+    fn block_arrived(&self, source: BlockSource, latency: Duration) {
+        self.block_arrival_latency
+            .with_label_values(&[source.as_static()])
+            .observe(latency.as_secs_f64())
+    }
+}
+
+impl TransportCacheMetrics for Metrics {
+    fn request_looked_up(&self, method: &str, cache_hit: bool) {
+        let counter = if cache_hit {
+            &self.transport_cache_hits
+        } else {
+            &self.transport_cache_misses
+        };
+        counter.with_label_values(&[method]).inc()
+    }
 }
 
 impl PoolCacheMetrics for Metrics {
@@ -94,12 +198,31 @@ impl PoolCacheMetrics for Metrics {
     }
 }
 
+impl PriceCacheMetrics for Metrics {
+    fn price_estimate_looked_up(&self, cache_hit: bool) {
+        if cache_hit {
+            self.price_estimate_cache_hits.inc();
+        } else {
+            self.price_estimate_cache_misses.inc();
+        }
+    }
+}
+
 impl crate::database::instrumented::Metrics for Metrics {
     fn database_query_histogram(&self, label: &str) -> Histogram {
         self.database_queries.with_label_values(&[label])
     }
 }
 
+impl crate::api::rate_limit::Metrics for Metrics {
+    fn request_checked(&self, app_id: &str, cost: Cost, limited: bool) {
+        let result = if limited { "limited" } else { "allowed" };
+        self.client_requests
+            .with_label_values(&[app_id, cost.as_static(), result])
+            .inc()
+    }
+}
+
 // Response wrapper needed because we cannot inspect the reply's status code without consuming it
 struct MetricsReply {
     response: Response,
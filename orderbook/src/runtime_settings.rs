@@ -0,0 +1,330 @@
+//! Settings that can be changed through the admin api while the service is running. Every change
+//! is stored in the database for auditing and the latest changes are applied again on startup.
+
+use crate::fee::FeeFactorOverrides;
+use anyhow::{ensure, Context, Result};
+use model::api::{MarketMakableTokenList, SolverSettings};
+use primitive_types::H160;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use shared::bad_token::{
+    cache::CachingDetector,
+    list_based::{ListBasedDetector, TokenListing},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, RwLock},
+};
+use url::Url;
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenSettings {
+    pub listing: TokenListing,
+    /// Factor the minimum fee of orders selling the token is multiplied with.
+    pub fee_factor: Option<f64>,
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait SettingsChangeStoring: Send + Sync {
+    async fn record_token_settings(
+        &self,
+        token: H160,
+        settings: TokenSettings,
+        comment: Option<String>,
+    ) -> Result<()>;
+    async fn record_solver_enabled(
+        &self,
+        solver: String,
+        enabled: bool,
+        comment: Option<String>,
+    ) -> Result<()>;
+    /// The settings of the most recent change of every token.
+    async fn latest_token_settings(&self) -> Result<Vec<(H160, TokenSettings)>>;
+    /// Whether a solver is enabled according to the most recent change of every solver.
+    async fn latest_solver_enabled(&self) -> Result<Vec<(String, bool)>>;
+    /// Returns the revision of the stored list.
+    async fn record_market_makable_token_list(
+        &self,
+        url: String,
+        comment: Option<String>,
+    ) -> Result<i64>;
+    async fn latest_market_makable_token_list(&self) -> Result<Option<MarketMakableTokenList>>;
+}
+
+pub struct RuntimeSettings {
+    database: Arc<dyn SettingsChangeStoring>,
+    token_lists: Arc<ListBasedDetector>,
+    /// Classifications of the bad token detector that get invalidated when a token's settings
+    /// change, so that removing a token from the lists gets it checked again.
+    token_quality_cache: Option<Arc<CachingDetector>>,
+    fee_factors: Arc<FeeFactorOverrides>,
+    disabled_solvers: RwLock<BTreeSet<String>>,
+    market_makable_token_list: RwLock<Option<MarketMakableTokenList>>,
+}
+
+impl RuntimeSettings {
+    pub fn new(
+        database: Arc<dyn SettingsChangeStoring>,
+        token_lists: Arc<ListBasedDetector>,
+        token_quality_cache: Option<Arc<CachingDetector>>,
+        fee_factors: Arc<FeeFactorOverrides>,
+    ) -> Self {
+        Self {
+            database,
+            token_lists,
+            token_quality_cache,
+            fee_factors,
+            disabled_solvers: Default::default(),
+            market_makable_token_list: Default::default(),
+        }
+    }
+
+    /// Applies the changes stored in the database on top of the settings from the command line.
+    pub async fn restore(&self) -> Result<()> {
+        for (token, settings) in self.database.latest_token_settings().await? {
+            self.apply_token_settings(token, &settings);
+        }
+        for (solver, enabled) in self.database.latest_solver_enabled().await? {
+            self.apply_solver_enabled(solver, enabled);
+        }
+        if let Some(list) = self.database.latest_market_makable_token_list().await? {
+            *self.market_makable_token_list.write().unwrap() = Some(list);
+        }
+        Ok(())
+    }
+
+    pub fn token_settings(&self) -> BTreeMap<H160, TokenSettings> {
+        let mut settings = self
+            .token_lists
+            .listed_tokens()
+            .into_iter()
+            .map(|(token, listing)| {
+                let settings = TokenSettings {
+                    listing,
+                    fee_factor: None,
+                };
+                (token, settings)
+            })
+            .collect::<BTreeMap<_, _>>();
+        for (token, factor) in self.fee_factors.all() {
+            settings
+                .entry(token)
+                .or_insert(TokenSettings {
+                    listing: TokenListing::Unlisted,
+                    fee_factor: None,
+                })
+                .fee_factor = Some(factor);
+        }
+        settings
+    }
+
+    /// Records and applies the settings of the token.
+    pub async fn update_token(
+        &self,
+        token: H160,
+        settings: TokenSettings,
+        comment: Option<String>,
+    ) -> Result<()> {
+        if let Some(factor) = settings.fee_factor {
+            ensure!(
+                factor.is_finite() && factor >= 0.0,
+                "fee factor must be a non negative number"
+            );
+        }
+        self.database
+            .record_token_settings(token, settings.clone(), comment.clone())
+            .await
+            .context("failed to record token settings change")?;
+        tracing::info!(?token, ?settings, ?comment, "updated token settings");
+        self.apply_token_settings(token, &settings);
+        Ok(())
+    }
+
+    pub fn solver_settings(&self) -> SolverSettings {
+        SolverSettings {
+            disabled_solvers: self.disabled_solvers.read().unwrap().clone(),
+            market_makable_token_list: self.market_makable_token_list.read().unwrap().clone(),
+        }
+    }
+
+    /// Records and applies whether the solver with the given name is enabled.
+    pub async fn update_solver(
+        &self,
+        solver: String,
+        enabled: bool,
+        comment: Option<String>,
+    ) -> Result<()> {
+        self.database
+            .record_solver_enabled(solver.clone(), enabled, comment.clone())
+            .await
+            .context("failed to record solver settings change")?;
+        tracing::info!(%solver, %enabled, ?comment, "updated solver settings");
+        self.apply_solver_enabled(solver, enabled);
+        Ok(())
+    }
+
+    /// Records the url of the token list that solvers fetch instead of the one they were started
+    /// with.
+    pub async fn update_market_makable_token_list(
+        &self,
+        url: String,
+        comment: Option<String>,
+    ) -> Result<()> {
+        Url::parse(&url).context("invalid token list url")?;
+        let revision = self
+            .database
+            .record_market_makable_token_list(url.clone(), comment.clone())
+            .await
+            .context("failed to record market makable token list change")?;
+        tracing::info!(%url, %revision, ?comment, "updated market makable token list");
+        *self.market_makable_token_list.write().unwrap() =
+            Some(MarketMakableTokenList { url, revision });
+        Ok(())
+    }
+
+    fn apply_token_settings(&self, token: H160, settings: &TokenSettings) {
+        self.token_lists.set_listing(token, settings.listing);
+        self.fee_factors.set(token, settings.fee_factor);
+        if let Some(cache) = &self.token_quality_cache {
+            cache.invalidate(&token);
+        }
+    }
+
+    fn apply_solver_enabled(&self, solver: String, enabled: bool) {
+        let mut disabled_solvers = self.disabled_solvers.write().unwrap();
+        if enabled {
+            disabled_solvers.remove(&solver);
+        } else {
+            disabled_solvers.insert(solver);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::bad_token::BadTokenDetecting;
+
+    fn settings(database: MockSettingsChangeStoring) -> RuntimeSettings {
+        RuntimeSettings::new(
+            Arc::new(database),
+            Arc::new(ListBasedDetector::deny_list(Vec::new())),
+            None,
+            Default::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn updates_are_recorded_and_applied() {
+        let token = H160::from_low_u64_be(1);
+        let denied = TokenSettings {
+            listing: TokenListing::Denied,
+            fee_factor: Some(2.0),
+        };
+        let mut database = MockSettingsChangeStoring::new();
+        database
+            .expect_record_token_settings()
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        database
+            .expect_record_solver_enabled()
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        database
+            .expect_record_market_makable_token_list()
+            .times(1)
+            .returning(|_, _| Ok(3));
+        let settings = settings(database);
+
+        settings
+            .update_token(token, denied.clone(), Some("scam".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(settings.token_settings().get(&token), Some(&denied));
+        assert!(!settings.token_lists.detect(token).await.unwrap().is_good());
+        assert_eq!(settings.fee_factors.get(token), Some(2.0));
+
+        settings
+            .update_solver("NaiveSolver".to_string(), false, None)
+            .await
+            .unwrap();
+        assert!(settings
+            .solver_settings()
+            .disabled_solvers
+            .contains("NaiveSolver"));
+
+        settings
+            .update_market_makable_token_list("https://tokens.example/list.json".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            settings.solver_settings().market_makable_token_list,
+            Some(MarketMakableTokenList {
+                url: "https://tokens.example/list.json".to_string(),
+                revision: 3,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_settings_are_not_recorded() {
+        // Would panic if a change was recorded.
+        let settings = settings(MockSettingsChangeStoring::new());
+        let result = settings
+            .update_token(
+                H160::from_low_u64_be(1),
+                TokenSettings {
+                    listing: TokenListing::Unlisted,
+                    fee_factor: Some(-1.0),
+                },
+                None,
+            )
+            .await;
+        assert!(result.is_err());
+
+        let result = settings
+            .update_market_makable_token_list("not a url".to_string(), None)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn restores_latest_changes() {
+        let token = H160::from_low_u64_be(1);
+        let mut database = MockSettingsChangeStoring::new();
+        database.expect_latest_token_settings().returning(move || {
+            Ok(vec![(
+                token,
+                TokenSettings {
+                    listing: TokenListing::Allowed,
+                    fee_factor: None,
+                },
+            )])
+        });
+        database
+            .expect_latest_solver_enabled()
+            .returning(|| Ok(vec![("ParaSwap".to_string(), false)]));
+        let list = MarketMakableTokenList {
+            url: "https://tokens.example/list.json".to_string(),
+            revision: 1,
+        };
+        let list_ = list.clone();
+        database
+            .expect_latest_market_makable_token_list()
+            .returning(move || Ok(Some(list_.clone())));
+        let settings = settings(database);
+
+        settings.restore().await.unwrap();
+        assert_eq!(settings.token_lists.listing(token), TokenListing::Allowed);
+        assert_eq!(
+            settings.solver_settings().disabled_solvers,
+            vec!["ParaSwap".to_string()].into_iter().collect()
+        );
+        assert_eq!(
+            settings.solver_settings().market_makable_token_list,
+            Some(list)
+        );
+    }
+}
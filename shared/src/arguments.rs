@@ -1,8 +1,12 @@
 //! Contains command line arguments and related helpers that are shared between the binaries.
-use crate::{gas_price_estimation::GasEstimatorType, sources::BaselineSource};
+use crate::{
+    gas_price_estimation::GasEstimatorType, sources::BaselineSource,
+    transport::failover::FailoverConfig,
+};
 use ethcontract::{H160, U256};
 use std::{
     num::{NonZeroU64, ParseFloatError},
+    path::PathBuf,
     time::Duration,
 };
 use url::Url;
@@ -16,10 +20,62 @@ pub struct Arguments {
     )]
     pub log_filter: String,
 
+    /// A TOML or YAML file to read further arguments from. Its keys are the names of the
+    /// arguments with underscores. Command line arguments and environment variables take
+    /// precedence over it.
+    #[structopt(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+
+    /// The section of the `networks` table in the config file whose settings override the top
+    /// level ones.
+    #[structopt(long, env = "CONFIG_NETWORK")]
+    pub config_network: Option<String>,
+
+    /// Print the effective configuration and exit.
+    #[structopt(long)]
+    pub print_config: bool,
+
     /// The Ethereum node URL to connect to.
     #[structopt(long, env = "NODE_URL", default_value = "http://localhost:8545")]
     pub node_url: Url,
 
+    /// Further Ethereum node URLs that requests fail over to if the preceding nodes are
+    /// unavailable.
+    #[structopt(long, env = "BACKUP_NODE_URLS", use_delimiter = true)]
+    pub backup_node_urls: Vec<Url>,
+
+    /// The WebSocket URL of an Ethereum node to subscribe to new blocks with. The node is polled
+    /// for new blocks if it is not set or the subscription is disconnected.
+    #[structopt(long, env = "NODE_WS_URL")]
+    pub node_ws_url: Option<Url>,
+
+    /// How often idempotent requests to the nodes are retried.
+    #[structopt(long, env, default_value = "2")]
+    pub node_max_retries: usize,
+
+    /// The delay in seconds before retrying a node request. It doubles with every further retry.
+    #[structopt(
+        long,
+        env,
+        default_value = "0.1",
+        parse(try_from_str = duration_from_seconds),
+    )]
+    pub node_retry_backoff: Duration,
+
+    /// Nodes more than this many blocks behind the most advanced node are only used if no other
+    /// node is available.
+    #[structopt(long, env, default_value = "5")]
+    pub node_max_block_lag: u64,
+
+    /// The maximum number of requests per second sent to a single node.
+    #[structopt(long, env, parse(try_from_str = positive_finite_f64))]
+    pub node_max_requests_per_second: Option<f64>,
+
+    /// The maximum number of node responses for requests at a concrete block that are cached. 0
+    /// disables the cache.
+    #[structopt(long, env, default_value = "10000")]
+    pub node_response_cache_size: usize,
+
     /// Timeout for web3 operations on the node in seconds.
     #[structopt(
             long,
@@ -81,7 +137,8 @@ pub struct Arguments {
     #[structopt(long, env, default_value = "1", parse(try_from_str = duration_from_seconds))]
     pub pool_cache_delay_between_retries_seconds: Duration,
 
-    /// How often we poll the node to check if the current block has changed.
+    /// How often we poll the node to check if the current block has changed when not subscribed to
+    /// new blocks.
     #[structopt(
         long,
         env,
@@ -89,12 +146,50 @@ pub struct Arguments {
         parse(try_from_str = duration_from_seconds),
     )]
     pub block_stream_poll_interval_seconds: Duration,
+
+    /// The service is reported as not ready if the current block or the last successful
+    /// maintenance run is older than this many seconds.
+    #[structopt(
+        long,
+        env,
+        default_value = "120",
+        parse(try_from_str = duration_from_seconds),
+    )]
+    pub health_max_block_age: Duration,
+}
+
+impl Arguments {
+    /// The urls of all configured nodes in order of preference.
+    pub fn node_urls(&self) -> Vec<Url> {
+        std::iter::once(self.node_url.clone())
+            .chain(self.backup_node_urls.iter().cloned())
+            .collect()
+    }
+
+    pub fn failover_config(&self) -> FailoverConfig {
+        FailoverConfig {
+            max_retries: self.node_max_retries,
+            retry_backoff: self.node_retry_backoff,
+            max_block_lag: self.node_max_block_lag,
+            max_requests_per_second: self.node_max_requests_per_second,
+        }
+    }
 }
 
 pub fn duration_from_seconds(s: &str) -> Result<Duration, ParseFloatError> {
     Ok(Duration::from_secs_f32(s.parse()?))
 }
 
+pub fn positive_finite_f64(s: &str) -> anyhow::Result<f64> {
+    let value: f64 = s.parse()?;
+    anyhow::ensure!(
+        value.is_finite() && value > 0.,
+        "{} is not a positive finite number",
+        value
+    );
+    Ok(value)
+}
+
 pub fn wei_from_base_unit(s: &str) -> anyhow::Result<U256> {
     Ok(U256::from_dec_str(s)? * U256::exp10(18))
 }
@@ -103,3 +198,35 @@ pub fn wei_from_gwei(s: &str) -> anyhow::Result<f64> {
     let in_gwei: f64 = s.parse()?;
     Ok(in_gwei * 10e9)
 }
+
+/// Splits an argument like `first=second` into its two parts at the first `separator`.
+pub fn split_pair(s: &str, separator: char) -> anyhow::Result<(&str, &str)> {
+    let mut parts = s.splitn(2, separator);
+    match (parts.next(), parts.next()) {
+        (Some(first), Some(second)) => Ok((first, second)),
+        _ => Err(anyhow::anyhow!(
+            "expected pair separated by '{}'",
+            separator
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_positive_finite_f64() {
+        assert_eq!(positive_finite_f64("2.5").unwrap(), 2.5);
+        for invalid in &["0", "-1", "NaN", "inf", "a"] {
+            assert!(positive_finite_f64(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn splits_pair_at_first_separator() {
+        assert_eq!(split_pair("a=b=c", '=').unwrap(), ("a", "b=c"));
+        assert_eq!(split_pair("a:", ':').unwrap(), ("a", ""));
+        assert!(split_pair("a", '=').is_err());
+    }
+}
@@ -36,6 +36,11 @@ impl CachingDetector {
         }
     }
 
+    /// Forgets the classification of the token so that it gets detected again on the next use.
+    pub fn invalidate(&self, token: &H160) {
+        self.cache.lock().unwrap().remove(token);
+    }
+
     fn get_from_cache(&self, token: &H160, now: Instant) -> Option<TokenQuality> {
         match self.cache.lock().unwrap().get(token) {
             Some((instant, quality))
@@ -99,4 +104,15 @@ mod tests {
             .get_from_cache(&token, now + Duration::from_secs(3))
             .is_none());
     }
+
+    #[test]
+    fn invalidate_removes_from_cache() {
+        let inner = MockBadTokenDetecting::new();
+        let token = H160::from_low_u64_le(0);
+        let detector = CachingDetector::new(Box::new(inner), Duration::from_secs(2));
+        detector.insert_into_cache(token, TokenQuality::Good);
+        assert!(detector.get_from_cache(&token, Instant::now()).is_some());
+        detector.invalidate(&token);
+        assert!(detector.get_from_cache(&token, Instant::now()).is_none());
+    }
 }
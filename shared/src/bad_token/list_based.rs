@@ -1,6 +1,9 @@
 use super::{BadTokenDetecting, TokenQuality};
 use anyhow::Result;
 use primitive_types::H160;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::RwLock};
 
 /// If a token is neither in the allow nor the deny list treat it this way.
 pub enum UnknownTokenStrategy {
@@ -9,10 +12,22 @@ pub enum UnknownTokenStrategy {
     Forward(Box<dyn BadTokenDetecting>),
 }
 
+/// Which list a token is on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, JsonSchema, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenListing {
+    Allowed,
+    Denied,
+    Unlisted,
+}
+
 /// Classify tokens with explicit allow and deny lists.
+///
+/// The lists can be updated while the detector is in use.
 pub struct ListBasedDetector {
-    allow_list: Vec<H160>,
-    deny_list: Vec<H160>,
+    // std lock is fine because we don't hold it across await.
+    allow_list: RwLock<HashSet<H160>>,
+    deny_list: RwLock<HashSet<H160>>,
     strategy: UnknownTokenStrategy,
 }
 
@@ -28,32 +43,70 @@ impl ListBasedDetector {
             "token is allowed and denied"
         );
         Self {
-            allow_list,
-            deny_list,
+            allow_list: RwLock::new(allow_list.into_iter().collect()),
+            deny_list: RwLock::new(deny_list.into_iter().collect()),
             strategy,
         }
     }
 
     pub fn deny_list(list: Vec<H160>) -> Self {
-        Self {
-            allow_list: Vec::new(),
-            deny_list: list,
-            strategy: UnknownTokenStrategy::Allow,
+        Self::new(Vec::new(), list, UnknownTokenStrategy::Allow)
+    }
+
+    pub fn listing(&self, token: H160) -> TokenListing {
+        if self.allow_list.read().unwrap().contains(&token) {
+            TokenListing::Allowed
+        } else if self.deny_list.read().unwrap().contains(&token) {
+            TokenListing::Denied
+        } else {
+            TokenListing::Unlisted
         }
     }
+
+    /// Moves the token to the given list, removing it from the other one.
+    pub fn set_listing(&self, token: H160, listing: TokenListing) {
+        let mut allow_list = self.allow_list.write().unwrap();
+        let mut deny_list = self.deny_list.write().unwrap();
+        allow_list.remove(&token);
+        deny_list.remove(&token);
+        match listing {
+            TokenListing::Allowed => allow_list.insert(token),
+            TokenListing::Denied => deny_list.insert(token),
+            TokenListing::Unlisted => false,
+        };
+    }
+
+    /// All tokens that are on the allow or deny list.
+    pub fn listed_tokens(&self) -> Vec<(H160, TokenListing)> {
+        let allowed = self
+            .allow_list
+            .read()
+            .unwrap()
+            .iter()
+            .map(|token| (*token, TokenListing::Allowed))
+            .collect::<Vec<_>>();
+        let denied = self
+            .deny_list
+            .read()
+            .unwrap()
+            .iter()
+            .map(|token| (*token, TokenListing::Denied))
+            .collect::<Vec<_>>();
+        allowed.into_iter().chain(denied).collect()
+    }
 }
 
 #[async_trait::async_trait]
 impl BadTokenDetecting for ListBasedDetector {
     async fn detect(&self, token: ethcontract::H160) -> Result<TokenQuality> {
-        if self.allow_list.contains(&token) {
-            return Ok(TokenQuality::Good);
-        }
-
-        if self.deny_list.contains(&token) {
-            return Ok(TokenQuality::Bad {
-                reason: "deny listed".to_string(),
-            });
+        match self.listing(token) {
+            TokenListing::Allowed => return Ok(TokenQuality::Good),
+            TokenListing::Denied => {
+                return Ok(TokenQuality::Bad {
+                    reason: "deny listed".to_string(),
+                })
+            }
+            TokenListing::Unlisted => (),
         }
 
         match &self.strategy {
@@ -76,11 +129,11 @@ mod tests {
     fn uses_lists() {
         // Would panic if used.
         let inner = MockBadTokenDetecting::new();
-        let detector = ListBasedDetector {
-            allow_list: vec![H160::from_low_u64_le(0)],
-            deny_list: vec![H160::from_low_u64_le(1)],
-            strategy: UnknownTokenStrategy::Forward(Box::new(inner)),
-        };
+        let detector = ListBasedDetector::new(
+            vec![H160::from_low_u64_le(0)],
+            vec![H160::from_low_u64_le(1)],
+            UnknownTokenStrategy::Forward(Box::new(inner)),
+        );
 
         let result = detector
             .detect(H160::from_low_u64_le(0))
@@ -97,22 +150,14 @@ mod tests {
 
     #[test]
     fn not_in_list_default() {
-        let detector = ListBasedDetector {
-            allow_list: Vec::new(),
-            deny_list: Vec::new(),
-            strategy: UnknownTokenStrategy::Allow,
-        };
+        let detector = ListBasedDetector::new(Vec::new(), Vec::new(), UnknownTokenStrategy::Allow);
         let result = detector
             .detect(H160::from_low_u64_le(0))
             .now_or_never()
             .unwrap();
         assert!(result.unwrap().is_good());
 
-        let detector = ListBasedDetector {
-            allow_list: Vec::new(),
-            deny_list: Vec::new(),
-            strategy: UnknownTokenStrategy::Deny,
-        };
+        let detector = ListBasedDetector::new(Vec::new(), Vec::new(), UnknownTokenStrategy::Deny);
         let result = detector
             .detect(H160::from_low_u64_le(0))
             .now_or_never()
@@ -128,11 +173,11 @@ mod tests {
             .times(1)
             .returning(|_| Ok(TokenQuality::Good));
 
-        let detector = ListBasedDetector {
-            allow_list: Vec::new(),
-            deny_list: Vec::new(),
-            strategy: UnknownTokenStrategy::Forward(Box::new(inner)),
-        };
+        let detector = ListBasedDetector::new(
+            Vec::new(),
+            Vec::new(),
+            UnknownTokenStrategy::Forward(Box::new(inner)),
+        );
 
         let result = detector
             .detect(H160::from_low_u64_le(0))
@@ -140,4 +185,30 @@ mod tests {
             .unwrap();
         assert!(result.unwrap().is_good());
     }
+
+    #[test]
+    fn updates_lists() {
+        let token = H160::from_low_u64_le(0);
+        let detector = ListBasedDetector::new(vec![token], Vec::new(), UnknownTokenStrategy::Allow);
+        let detect = |detector: &ListBasedDetector| {
+            detector
+                .detect(token)
+                .now_or_never()
+                .unwrap()
+                .unwrap()
+                .is_good()
+        };
+
+        detector.set_listing(token, TokenListing::Denied);
+        assert_eq!(detector.listing(token), TokenListing::Denied);
+        assert_eq!(
+            detector.listed_tokens(),
+            vec![(token, TokenListing::Denied)]
+        );
+        assert!(!detect(&detector));
+
+        detector.set_listing(token, TokenListing::Unlisted);
+        assert!(detector.listed_tokens().is_empty());
+        assert!(detect(&detector));
+    }
 }
@@ -4,6 +4,7 @@ pub mod trace_call;
 
 use anyhow::Result;
 use primitive_types::H160;
+use std::sync::Arc;
 
 /// How well behaved a token is.
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -30,3 +31,13 @@ impl TokenQuality {
 pub trait BadTokenDetecting: Send + Sync {
     async fn detect(&self, token: H160) -> Result<TokenQuality>;
 }
+
+#[async_trait::async_trait]
+impl<T> BadTokenDetecting for Arc<T>
+where
+    T: BadTokenDetecting + ?Sized,
+{
+    async fn detect(&self, token: H160) -> Result<TokenQuality> {
+        self.as_ref().detect(token).await
+    }
+}
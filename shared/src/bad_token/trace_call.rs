@@ -282,7 +282,7 @@ mod tests {
     use super::*;
     use crate::{
         sources::uniswap::pair_provider::{SushiswapPairProvider, UniswapPairProvider},
-        transport::create_env_test_transport,
+        transport::{create_env_test_transport, create_fixture_test_transport},
     };
     use hex_literal::hex;
     use web3::types::{
@@ -417,6 +417,56 @@ mod tests {
         println!("{:?}", TraceCallDetector::arbitrary_recipient());
     }
 
+    const BASE_TOKENS: &[H160] = &[
+        H160(hex!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")), // weth
+        H160(hex!("6B175474E89094C44Da98b954EedeAC495271d0F")), // dai
+        H160(hex!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")), // usdc
+        H160(hex!("dAC17F958D2ee523a2206206994597C13D831ec7")), // usdt
+        H160(hex!("c00e94Cb662C3520282E6f5717214004A7f26888")), // comp
+        H160(hex!("9f8F72aA9304c8B593d555F12eF6589cC3A579A2")), // mkr
+        H160(hex!("2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599")), // wbtc
+    ];
+
+    async fn mainnet_detector(web3: Web3) -> TraceCallDetector {
+        let settlement = contracts::GPv2Settlement::deployed(&web3).await.unwrap();
+        let chain_id = web3.eth().chain_id().await.unwrap().as_u64();
+        let uniswap = Arc::new(UniswapPairProvider {
+            factory: contracts::UniswapV2Factory::deployed(&web3).await.unwrap(),
+            chain_id,
+        });
+        let sushiswap = Arc::new(SushiswapPairProvider {
+            factory: contracts::SushiswapV2Factory::deployed(&web3)
+                .await
+                .unwrap(),
+        });
+        TraceCallDetector {
+            web3,
+            settlement_contract: settlement.address(),
+            pools: vec![uniswap, sushiswap],
+            base_tokens: BASE_TOKENS.iter().copied().collect(),
+        }
+    }
+
+    // The synthetic fixture contains scripted node responses for the mainnet addresses rather than
+    // recorded mainnet state.
+    #[tokio::test]
+    async fn synthetic_tokens() {
+        let http = create_fixture_test_transport(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/synthetic_tokens.json"
+        ));
+        let detector = mainnet_detector(Web3::new(http)).await;
+
+        let weth = BASE_TOKENS[0];
+        assert_eq!(detector.detect(weth).await.unwrap(), TokenQuality::Good);
+        // Takes a fee on transfer.
+        let fee_token = H160(hex!("bae5f2d8a1299e5c4963eaff3312399253f27ccb"));
+        assert_eq!(
+            detector.detect(fee_token).await.unwrap(),
+            TokenQuality::bad("balance after in transfer does not match")
+        );
+    }
+
     // cargo test -p shared mainnet_tokens -- --nocapture --ignored
     #[tokio::test]
     #[ignore]
@@ -425,16 +475,6 @@ mod tests {
         let http = create_env_test_transport();
         let web3 = Web3::new(http);
 
-        let base_tokens = &[
-            H160(hex!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")), // weth
-            H160(hex!("6B175474E89094C44Da98b954EedeAC495271d0F")), // dai
-            H160(hex!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")), // usdc
-            H160(hex!("dAC17F958D2ee523a2206206994597C13D831ec7")), // usdt
-            H160(hex!("c00e94Cb662C3520282E6f5717214004A7f26888")), // comp
-            H160(hex!("9f8F72aA9304c8B593d555F12eF6589cC3A579A2")), // mkr
-            H160(hex!("2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599")), // wbtc
-        ];
-
         // tokens from our deny list
         let bad_tokens = &[
             H160(hex!("C12D1c73eE7DC3615BA4e37E4ABFdbDDFA38907E")),
@@ -546,26 +586,10 @@ mod tests {
         // - 0x4f9254c83eb525f9fcf346490bbb3ed28a81c667
         //   Not sure why deny listed.
 
-        let settlement = contracts::GPv2Settlement::deployed(&web3).await.unwrap();
-        let chain_id = web3.eth().chain_id().await.unwrap().as_u64();
-        let uniswap = Arc::new(UniswapPairProvider {
-            factory: contracts::UniswapV2Factory::deployed(&web3).await.unwrap(),
-            chain_id,
-        });
-        let sushiswap = Arc::new(SushiswapPairProvider {
-            factory: contracts::SushiswapV2Factory::deployed(&web3)
-                .await
-                .unwrap(),
-        });
-        let token_cache = TraceCallDetector {
-            web3,
-            settlement_contract: settlement.address(),
-            pools: vec![uniswap, sushiswap],
-            base_tokens: base_tokens.iter().copied().collect(),
-        };
+        let token_cache = mainnet_detector(web3).await;
 
         println!("testing good tokens");
-        for &token in base_tokens {
+        for &token in BASE_TOKENS {
             let result = token_cache.detect(token).await;
             println!("token {:?} is {:?}", token, result);
         }
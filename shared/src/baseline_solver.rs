@@ -146,6 +146,214 @@ pub fn estimate_spot_price<'a, L: BaselineSolvable>(
         })
 }
 
+/// The number of chunks an amount is divided into when splitting it over parallel routes.
+const SPLIT_STEPS: u64 = 20;
+
+/// The maximum number of parallel routes an amount is split over.
+pub const MAX_SPLIT_PARTS: usize = 3;
+
+pub struct SplitPart<'a, L> {
+    // The amount routed through this part (sell amount for sell estimates and buy amount for buy
+    // estimates)
+    pub amount: U256,
+    // The token path of this part
+    pub path: Vec<H160>,
+    // The resulting amount of this part and the liquidity it uses
+    pub estimate: Estimate<'a, U256, L>,
+}
+
+pub struct SplitEstimate<'a, L> {
+    // The total resulting amount of all parts
+    pub value: U256,
+    // The parallel routes the amount is split over, which never share any liquidity
+    pub parts: Vec<SplitPart<'a, L>>,
+}
+
+impl<'a, L: BaselineSolvable> SplitEstimate<'a, L> {
+    pub fn gas_cost(&self) -> usize {
+        // The fixed overhead is only paid once, no matter how many parts there are.
+        let cost_of_hops: usize = self
+            .parts
+            .iter()
+            .flat_map(|part| part.estimate.path.iter())
+            .map(|item| item.gas_cost())
+            .sum();
+        50_000 + cost_of_hops
+    }
+}
+
+// A path together with a fixed choice of liquidity for each of its hops.
+struct Route<'a, L> {
+    path: Vec<H160>,
+    liquidity: Vec<&'a L>,
+}
+
+impl<'a, L> Route<'a, L> {
+    fn is_disjoint(&self, other: &Route<'a, L>) -> bool {
+        !self.liquidity.iter().any(|item| {
+            other
+                .liquidity
+                .iter()
+                .any(|other| std::ptr::eq(*item, *other))
+        })
+    }
+}
+
+// Returns all routes along the given paths, using every combination of liquidity for their hops.
+fn routes<'a, L>(
+    paths: &HashSet<PathCandidate>,
+    liquidity: &'a HashMap<TokenPair, Vec<L>>,
+) -> Vec<Route<'a, L>> {
+    paths
+        .iter()
+        .flat_map(|path| {
+            path.windows(2)
+                .try_fold(vec![Vec::new()], |routes, tokens| {
+                    let candidates = liquidity.get(&TokenPair::new(tokens[0], tokens[1])?)?;
+                    Some(
+                        routes
+                            .iter()
+                            .flat_map(|route: &Vec<&'a L>| {
+                                candidates.iter().map(move |candidate| {
+                                    let mut route = route.clone();
+                                    route.push(candidate);
+                                    route
+                                })
+                            })
+                            .collect(),
+                    )
+                })
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |liquidity| Route {
+                    path: path.clone(),
+                    liquidity,
+                })
+        })
+        .collect()
+}
+
+// Greedily assigns chunks of the amount to the route with the best marginal result for that chunk
+// until the whole amount is distributed. This equalizes the marginal prices of all used routes up
+// to the size of a chunk. `maximize` specifies whether larger resulting amounts are better.
+// Returns None if the amount is too small to be split or cannot be routed.
+fn split<'a, L>(
+    amount: U256,
+    paths: &HashSet<PathCandidate>,
+    liquidity: &'a HashMap<TokenPair, Vec<L>>,
+    evaluate: impl Fn(U256, &Route<'a, L>) -> Option<U256>,
+    maximize: bool,
+) -> Option<SplitEstimate<'a, L>> {
+    let routes = routes(paths, liquidity);
+    let chunk = amount / SPLIT_STEPS;
+    if chunk.is_zero() {
+        return None;
+    }
+
+    let is_better = |lhs: &U256, rhs: &U256| if maximize { lhs > rhs } else { lhs < rhs };
+    let mut allocations = vec![U256::zero(); routes.len()];
+    let mut values = vec![U256::zero(); routes.len()];
+    for step in 0..SPLIT_STEPS {
+        let chunk = if step + 1 == SPLIT_STEPS {
+            amount - chunk * (SPLIT_STEPS - 1)
+        } else {
+            chunk
+        };
+        let active: Vec<_> = (0..routes.len())
+            .filter(|i| !allocations[*i].is_zero())
+            .collect();
+        let best = routes
+            .iter()
+            .enumerate()
+            .filter(|(i, route)| {
+                !allocations[*i].is_zero()
+                    || (active.len() < MAX_SPLIT_PARTS
+                        && active.iter().all(|j| route.is_disjoint(&routes[*j])))
+            })
+            .filter_map(|(i, route)| {
+                let value = evaluate(allocations[i] + chunk, route)?;
+                Some((i, value, value.saturating_sub(values[i])))
+            })
+            .fold(
+                None,
+                |best: Option<(usize, U256, U256)>, candidate| match best {
+                    Some(best) if !is_better(&candidate.2, &best.2) => Some(best),
+                    _ => Some(candidate),
+                },
+            )?;
+        allocations[best.0] += chunk;
+        values[best.0] = best.1;
+    }
+
+    let parts: Vec<_> = routes
+        .into_iter()
+        .zip(allocations.into_iter().zip(values))
+        .filter(|(_, (amount, _))| !amount.is_zero())
+        .map(|(route, (amount, value))| SplitPart {
+            amount,
+            path: route.path,
+            estimate: Estimate {
+                value,
+                path: route.liquidity,
+            },
+        })
+        .collect();
+    Some(SplitEstimate {
+        value: parts
+            .iter()
+            .fold(U256::zero(), |sum, part| sum + part.estimate.value),
+        parts,
+    })
+}
+
+// Given a sell amount and path candidates from the sell token to the buy token, estimates the buy
+// amount when splitting the sell amount over up to `MAX_SPLIT_PARTS` parallel routes.
+// Returns None if the amount is too small to be split or no route is valid.
+pub fn estimate_buy_amount_split<'a, L: BaselineSolvable>(
+    sell_amount: U256,
+    paths: &HashSet<PathCandidate>,
+    liquidity: &'a HashMap<TokenPair, Vec<L>>,
+) -> Option<SplitEstimate<'a, L>> {
+    split(
+        sell_amount,
+        paths,
+        liquidity,
+        |amount, route| {
+            route.path.windows(2).zip(&route.liquidity).try_fold(
+                amount,
+                |amount, (tokens, liquidity)| {
+                    liquidity.get_amount_out(tokens[1], (amount, tokens[0]))
+                },
+            )
+        },
+        true,
+    )
+}
+
+// Given a buy amount and path candidates from the sell token to the buy token, estimates the sell
+// amount when splitting the buy amount over up to `MAX_SPLIT_PARTS` parallel routes.
+// Returns None if the amount is too small to be split or no route is valid.
+pub fn estimate_sell_amount_split<'a, L: BaselineSolvable>(
+    buy_amount: U256,
+    paths: &HashSet<PathCandidate>,
+    liquidity: &'a HashMap<TokenPair, Vec<L>>,
+) -> Option<SplitEstimate<'a, L>> {
+    split(
+        buy_amount,
+        paths,
+        liquidity,
+        |amount, route| {
+            route.path.windows(2).zip(&route.liquidity).rev().try_fold(
+                amount,
+                |amount, (tokens, liquidity)| {
+                    liquidity.get_amount_in(tokens[0], (amount, tokens[1]))
+                },
+            )
+        },
+        false,
+    )
+}
+
 // Returns possible paths from sell_token to buy token, given a list of potential intermediate base tokens
 // and a maximum number of intermediate steps.
 pub fn path_candidates(
@@ -446,4 +654,50 @@ mod tests {
         let spot_price = estimate_spot_price(&path, &pools).unwrap();
         assert_eq!(spot_price.path, [&valid_pool]);
     }
+
+    #[test]
+    fn test_estimate_amount_split_over_parallel_pools() {
+        let sell_token = H160::from_low_u64_be(1);
+        let buy_token = H160::from_low_u64_be(2);
+
+        let path = vec![sell_token, buy_token];
+        let pair = TokenPair::new(sell_token, buy_token).unwrap();
+        let pools = hashmap! {
+            pair => vec![
+                Pool::uniswap(pair, (1_000_000, 1_000_000)),
+                Pool::uniswap(pair, (1_000_000, 1_000_000)),
+            ],
+        };
+        let paths = hashset! { path.clone() };
+
+        let single = estimate_buy_amount(100_000.into(), &path, &pools).unwrap();
+        let split = estimate_buy_amount_split(100_000.into(), &paths, &pools).unwrap();
+        assert_eq!(split.parts.len(), 2);
+        assert_eq!(split.parts[0].amount, 50_000.into());
+        assert_eq!(split.parts[1].amount, 50_000.into());
+        assert!(split.value > single.value);
+        assert_eq!(
+            split.gas_cost(),
+            single.gas_cost() + pools[&pair][0].gas_cost()
+        );
+
+        let single = estimate_sell_amount(100_000.into(), &path, &pools).unwrap();
+        let split = estimate_sell_amount_split(100_000.into(), &paths, &pools).unwrap();
+        assert_eq!(split.parts.len(), 2);
+        assert!(split.value < single.value);
+    }
+
+    #[test]
+    fn test_estimate_amount_split_returns_none_for_small_amounts() {
+        let sell_token = H160::from_low_u64_be(1);
+        let buy_token = H160::from_low_u64_be(2);
+
+        let pair = TokenPair::new(sell_token, buy_token).unwrap();
+        let pools = hashmap! {
+            pair => vec![Pool::uniswap(pair, (1_000_000, 1_000_000))],
+        };
+        let paths = hashset! { vec![sell_token, buy_token] };
+
+        assert!(estimate_buy_amount_split(10.into(), &paths, &pools).is_none());
+    }
 }
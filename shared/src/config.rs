@@ -0,0 +1,366 @@
+//! Loading of command line arguments from a TOML or YAML configuration file.
+//!
+//! The keys of the file are the names of the command line arguments with underscores, for example
+//! `node_url` or `base_tokens`. Lists are given as arrays. Settings in a section of the `networks`
+//! table override the top level settings if the network is selected with `--config-network`:
+//!
+//! ```toml
+//! base_tokens = ["0x6b175474e89094c44da98b954eedeac495271d0f"]
+//!
+//! [networks.mainnet]
+//! node_url = "https://mainnet.example.com"
+//! ```
+//!
+//! The settings are passed to the argument parser as command line arguments unless the argument is
+//! already given on the command line or through its environment variable, so command line
+//! arguments take precedence over environment variables, which take precedence over the
+//! configuration file. Flags are set with `true` and left unset with `false`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+};
+use structopt::{clap::App, StructOpt};
+
+const NETWORKS_KEY: &str = "networks";
+
+/// Parses the arguments from the command line, the configuration file given by `--config` and the
+/// environment, exiting with an error message if they are invalid.
+///
+/// With `--print-config` the effective configuration is printed and the process exits.
+pub fn load_arguments<T>() -> T
+where
+    T: StructOpt + Debug,
+{
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let args = match with_config_file_arguments(&T::clap(), args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: invalid configuration: {:?}", err);
+            std::process::exit(2);
+        }
+    };
+    let arguments = T::from_iter(&args);
+    if flag_present(&args, "--print-config") {
+        println!("{:#?}", arguments);
+        std::process::exit(0);
+    }
+    arguments
+}
+
+/// What the configuration file needs to know about the arguments of the binary.
+#[derive(Debug, Default)]
+struct ArgumentInfo {
+    /// The long names of the arguments that don't take a value.
+    flags: HashSet<String>,
+    /// The long names of the arguments whose environment variable is set.
+    set_by_env: HashSet<String>,
+}
+
+impl ArgumentInfo {
+    fn new(app: &App) -> Self {
+        // clap 2 has no public API to inspect the arguments of an app, so this reads its parser.
+        let flags = app
+            .p
+            .flags
+            .iter()
+            .filter_map(|flag| flag.s.long)
+            .map(str::to_string)
+            .collect();
+        let set_by_env = app
+            .p
+            .opts
+            .iter()
+            .filter(|opt| matches!(&opt.v.env, Some((name, _)) if std::env::var_os(name).is_some()))
+            .filter_map(|opt| opt.s.long)
+            .map(str::to_string)
+            .collect();
+        Self { flags, set_by_env }
+    }
+}
+
+/// Adds the settings of the configuration file to the arguments.
+fn with_config_file_arguments(app: &App, args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let path = match option_value(&args, "--config").or_else(|| std::env::var("CONFIG_FILE").ok()) {
+        Some(path) => PathBuf::from(path),
+        None => return Ok(args),
+    };
+    let network =
+        option_value(&args, "--config-network").or_else(|| std::env::var("CONFIG_NETWORK").ok());
+    let settings = read_config_file(&path, network.as_deref())
+        .with_context(|| format!("failed to load config file {}", path.display()))?;
+    insert_settings(args, settings, &ArgumentInfo::new(app))
+}
+
+/// Inserts the settings as arguments after the program name, skipping the ones that are already
+/// given on the command line or through their environment variable.
+fn insert_settings(
+    args: Vec<OsString>,
+    settings: BTreeMap<String, String>,
+    info: &ArgumentInfo,
+) -> Result<Vec<OsString>> {
+    let mut inserted = Vec::new();
+    for (name, value) in settings {
+        let argument = format!("--{}", name);
+        if option_value(&args, &argument).is_some()
+            || flag_present(&args, &argument)
+            || info.set_by_env.contains(&name)
+        {
+            continue;
+        }
+        if !info.flags.contains(&name) {
+            inserted.push(format!("{}={}", argument, value).into());
+            continue;
+        }
+        match value.as_str() {
+            "true" => inserted.push(argument.into()),
+            "false" => (),
+            _ => bail!("expected `true` or `false` for flag `{}`", name),
+        }
+    }
+    let mut args = args.into_iter();
+    Ok(args
+        .next()
+        .into_iter()
+        .chain(inserted)
+        .chain(args)
+        .collect())
+}
+
+fn read_config_file(path: &Path, network: Option<&str>) -> Result<BTreeMap<String, String>> {
+    let contents = fs::read_to_string(path)?;
+    let config = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml::from_str(&contents).context("invalid TOML")?,
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&contents).context("invalid YAML")?,
+        _ => bail!("unsupported config file format, expected .toml, .yaml or .yml"),
+    };
+    settings_for_network(config, network)
+}
+
+/// Merges the top level settings with the ones of the network and converts them into argument
+/// names and values.
+fn settings_for_network(config: Value, network: Option<&str>) -> Result<BTreeMap<String, String>> {
+    let mut config = match config {
+        Value::Object(config) => config,
+        _ => bail!("expected a table of settings"),
+    };
+    let mut networks = match config.remove(NETWORKS_KEY) {
+        Some(Value::Object(networks)) => networks,
+        Some(_) => bail!("expected `{}` to be a table of networks", NETWORKS_KEY),
+        None => Default::default(),
+    };
+    if let Some(network) = network {
+        match networks.remove(network) {
+            Some(Value::Object(overrides)) => config.extend(overrides),
+            Some(_) => bail!("expected network `{}` to be a table of settings", network),
+            None => bail!("no settings for network `{}`", network),
+        }
+    }
+
+    config
+        .into_iter()
+        .map(|(key, value)| {
+            let value =
+                argument_value(&value).with_context(|| format!("invalid value for `{}`", key))?;
+            Ok((key.replace('_', "-"), value))
+        })
+        .collect()
+}
+
+fn argument_value(value: &Value) -> Result<String> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::Bool(value) => Ok(value.to_string()),
+        Value::Array(values) => Ok(values
+            .iter()
+            .map(|value| match value {
+                Value::Array(_) | Value::Object(_) => {
+                    Err(anyhow!("nested lists are not supported"))
+                }
+                value => argument_value(value),
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join(",")),
+        Value::Null | Value::Object(_) => Err(anyhow!("expected a value or a list of values")),
+    }
+}
+
+/// The value of an option given as `--name value` or `--name=value`.
+fn option_value(args: &[OsString], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let mut args = args.iter().filter_map(|arg| arg.to_str());
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next().map(str::to_string);
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+fn flag_present(args: &[OsString], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::{btreemap, hashset};
+    use serde_json::json;
+
+    #[test]
+    fn merges_network_settings() {
+        let config: Value = toml::from_str(
+            r#"
+            node_url = "http://localhost:8545"
+            base_tokens = ["0x01", "0x02"]
+            gas_price_cap = 1.5
+
+            [networks.rinkeby]
+            node_url = "http://rinkeby:8545"
+            skip_trace_api = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings_for_network(config.clone(), None).unwrap(),
+            btreemap! {
+                "node-url".to_string() => "http://localhost:8545".to_string(),
+                "base-tokens".to_string() => "0x01,0x02".to_string(),
+                "gas-price-cap".to_string() => "1.5".to_string(),
+            }
+        );
+        assert_eq!(
+            settings_for_network(config.clone(), Some("rinkeby")).unwrap(),
+            btreemap! {
+                "node-url".to_string() => "http://rinkeby:8545".to_string(),
+                "base-tokens".to_string() => "0x01,0x02".to_string(),
+                "gas-price-cap".to_string() => "1.5".to_string(),
+                "skip-trace-api".to_string() => "true".to_string(),
+            }
+        );
+        assert!(settings_for_network(config, Some("xdai")).is_err());
+    }
+
+    #[test]
+    fn reads_yaml_like_toml() {
+        let yaml: Value =
+            serde_yaml::from_str("node_url: http://localhost:8545\nbase_tokens:\n  - '0x01'\n")
+                .unwrap();
+        assert_eq!(
+            settings_for_network(yaml, None).unwrap(),
+            btreemap! {
+                "node-url".to_string() => "http://localhost:8545".to_string(),
+                "base-tokens".to_string() => "0x01".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(settings_for_network(json!({ "node_url": null }), None).is_err());
+        assert!(settings_for_network(json!({ "base_tokens": [["0x01"]] }), None).is_err());
+        assert!(settings_for_network(json!(["node_url"]), None).is_err());
+    }
+
+    fn os_strings(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn command_line_and_environment_override_config_file() {
+        let args = os_strings(&["solver", "--node-url", "http://localhost:8545"]);
+        let settings = btreemap! {
+            "node-url".to_string() => "http://config:8545".to_string(),
+            "base-tokens".to_string() => "0x01,0x02".to_string(),
+            "log-filter".to_string() => "info".to_string(),
+        };
+        let info = ArgumentInfo {
+            set_by_env: hashset! {"log-filter".to_string()},
+            ..Default::default()
+        };
+        assert_eq!(
+            insert_settings(args, settings, &info).unwrap(),
+            os_strings(&[
+                "solver",
+                "--base-tokens=0x01,0x02",
+                "--node-url",
+                "http://localhost:8545"
+            ])
+        );
+    }
+
+    #[test]
+    fn sets_flags_without_value() {
+        let settings = btreemap! {
+            "print-config".to_string() => "true".to_string(),
+            "skip-event-sync".to_string() => "false".to_string(),
+            "skip-trace-api".to_string() => "true".to_string(),
+        };
+        let info = ArgumentInfo {
+            flags: hashset! {"print-config".to_string(), "skip-event-sync".to_string()},
+            ..Default::default()
+        };
+        assert_eq!(
+            insert_settings(os_strings(&["orderbook"]), settings, &info).unwrap(),
+            os_strings(&["orderbook", "--print-config", "--skip-trace-api=true"])
+        );
+
+        let settings = btreemap! {"print-config".to_string() => "1".to_string()};
+        assert!(insert_settings(os_strings(&["orderbook"]), settings, &info).is_err());
+    }
+
+    #[test]
+    fn parses_arguments_from_config_file() {
+        let path =
+            std::env::temp_dir().join("shared_config_parses_arguments_from_config_file.toml");
+        fs::write(
+            &path,
+            r#"
+            print_config = true
+            base_tokens = ["0x0000000000000000000000000000000000000001"]
+            "#,
+        )
+        .unwrap();
+        let args = vec![
+            OsString::from("orderbook"),
+            OsString::from("--config"),
+            path.clone().into(),
+        ];
+        let args = with_config_file_arguments(&crate::arguments::Arguments::clap(), args).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let arguments = crate::arguments::Arguments::from_iter_safe(&args).unwrap();
+        assert!(arguments.print_config);
+        if std::env::var_os("BASE_TOKENS").is_none() {
+            assert_eq!(
+                arguments.base_tokens,
+                vec![ethcontract::H160::from_low_u64_be(1)]
+            );
+        }
+    }
+
+    #[test]
+    fn parses_option_values() {
+        let args = ["solver", "--config", "a.toml", "--config-network=mainnet"]
+            .iter()
+            .map(OsString::from)
+            .collect::<Vec<_>>();
+        assert_eq!(option_value(&args, "--config"), Some("a.toml".to_string()));
+        assert_eq!(
+            option_value(&args, "--config-network"),
+            Some("mainnet".to_string())
+        );
+        assert_eq!(option_value(&args, "--print-config"), None);
+        assert!(!flag_present(&args, "--print-config"));
+    }
+}
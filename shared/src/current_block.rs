@@ -1,16 +1,46 @@
 use crate::Web3;
 use anyhow::{anyhow, Context as _, Result};
+use futures::StreamExt as _;
 use primitive_types::H256;
-use std::time::Duration;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use strum_macros::AsStaticStr;
 use tokio::sync::watch;
 use tokio_stream::wrappers::WatchStream;
+use url::Url;
 use web3::{
+    transports::WebSocket,
     types::{BlockId, BlockNumber},
     Transport,
 };
 
 pub type Block = web3::types::Block<H256>;
 
+/// How long to poll for new blocks after the WebSocket subscription failed before reconnecting.
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How many poll intervals to wait for a new head before considering the subscription stalled.
+const SUBSCRIPTION_TIMEOUT_POLL_INTERVALS: u32 = 5;
+
+/// How a new block was observed.
+#[derive(AsStaticStr, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockSource {
+    Poll,
+    Subscription,
+}
+
+pub trait BlockStreamMetrics: Send + Sync {
+    /// Called for every new block with the time between the block's timestamp and its arrival.
+    fn block_arrived(&self, source: BlockSource, latency: Duration);
+}
+
+struct NoopBlockStreamMetrics;
+impl BlockStreamMetrics for NoopBlockStreamMetrics {
+    fn block_arrived(&self, _: BlockSource, _: Duration) {}
+}
+
 /// Creates a cloneable stream that yields the current block whenever it changes.
 ///
 /// The stream is not guaranteed to yield *every* block individually without gaps but it does yield
@@ -24,37 +54,53 @@ pub type Block = web3::types::Block<H256>;
 pub async fn current_block_stream(
     web3: Web3,
     poll_interval: Duration,
+) -> Result<watch::Receiver<Block>> {
+    subscribed_current_block_stream(web3, None, poll_interval, Arc::new(NoopBlockStreamMetrics))
+        .await
+}
+
+/// Like `current_block_stream` but learns about new blocks from an `eth_subscribe("newHeads")`
+/// subscription on the node's WebSocket endpoint if one is given.
+///
+/// While the subscription is disconnected the node is polled instead and the subscription is
+/// reestablished periodically.
+pub async fn subscribed_current_block_stream(
+    web3: Web3,
+    ws_url: Option<Url>,
+    poll_interval: Duration,
+    metrics: Arc<dyn BlockStreamMetrics>,
 ) -> Result<watch::Receiver<Block>> {
     let first_block = web3.current_block().await?;
     let first_hash = first_block.hash.ok_or_else(|| anyhow!("missing hash"))?;
 
     let (sender, receiver) = watch::channel(first_block);
+    let updater = BlockUpdater {
+        web3,
+        sender,
+        previous_hash: Mutex::new(first_hash),
+        metrics,
+    };
 
     let update_future = async move {
-        let mut previous_hash = first_hash;
-        loop {
-            tokio::time::sleep(poll_interval).await;
-            let block = match web3.current_block().await {
-                Ok(block) => block,
-                Err(err) => {
-                    tracing::warn!("failed to get current block: {:?}", err);
-                    continue;
+        let result = match ws_url {
+            Some(ws_url) => loop {
+                let timeout = poll_interval * SUBSCRIPTION_TIMEOUT_POLL_INTERVALS;
+                match updater.follow_subscription(&ws_url, timeout).await {
+                    Err(err) if err.is::<ReceiversDropped>() => break Ok(()),
+                    Err(err) => tracing::warn!("new block subscription failed: {:?}", err),
+                    Ok(()) => tracing::warn!("new block subscription ended"),
                 }
-            };
-            let hash = match block.hash {
-                Some(hash) => hash,
-                None => {
-                    tracing::warn!("missing hash");
-                    continue;
+                if let Err(err) = updater
+                    .poll(poll_interval, Some(Instant::now() + RESUBSCRIBE_INTERVAL))
+                    .await
+                {
+                    break Err(err);
                 }
-            };
-            if hash == previous_hash {
-                continue;
-            }
-            if sender.send(block.clone()).is_err() {
-                break;
-            }
-            previous_hash = hash;
+            },
+            None => updater.poll(poll_interval, None).await,
+        };
+        if let Err(err) = result {
+            tracing::debug!("stopped updating current block: {}", err);
         }
     };
 
@@ -62,6 +108,91 @@ pub async fn current_block_stream(
     Ok(receiver)
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("all receivers of the current block stream were dropped")]
+struct ReceiversDropped;
+
+struct BlockUpdater {
+    web3: Web3,
+    sender: watch::Sender<Block>,
+    previous_hash: Mutex<H256>,
+    metrics: Arc<dyn BlockStreamMetrics>,
+}
+
+impl BlockUpdater {
+    /// Publishes the block if it differs from the current one.
+    fn update(&self, block: Block, source: BlockSource) -> Result<(), ReceiversDropped> {
+        let hash = match block.hash {
+            Some(hash) => hash,
+            None => {
+                tracing::warn!("missing hash");
+                return Ok(());
+            }
+        };
+        let mut previous_hash = self.previous_hash.lock().unwrap();
+        if *previous_hash == hash {
+            return Ok(());
+        }
+        let timestamp = UNIX_EPOCH + Duration::from_secs(block.timestamp.low_u64());
+        let latency = SystemTime::now()
+            .duration_since(timestamp)
+            .unwrap_or_default();
+        self.metrics.block_arrived(source, latency);
+        self.sender.send(block).map_err(|_| ReceiversDropped)?;
+        *previous_hash = hash;
+        Ok(())
+    }
+
+    /// Polls the node for the current block until the deadline, or forever if there is none.
+    async fn poll(
+        &self,
+        poll_interval: Duration,
+        deadline: Option<Instant>,
+    ) -> Result<(), ReceiversDropped> {
+        while deadline.map_or(true, |deadline| Instant::now() < deadline) {
+            tokio::time::sleep(poll_interval).await;
+            match self.web3.current_block().await {
+                Ok(block) => self.update(block, BlockSource::Poll)?,
+                Err(err) => tracing::warn!("failed to get current block: {:?}", err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes the blocks announced by a new heads subscription until it fails or no new head
+    /// arrives within the timeout, which happens when the connection silently stalls.
+    async fn follow_subscription(&self, ws_url: &Url, timeout: Duration) -> Result<()> {
+        let transport = WebSocket::new(ws_url.as_str())
+            .await
+            .context("failed to connect to node WebSocket")?;
+        let mut heads = web3::Web3::new(transport)
+            .eth_subscribe()
+            .subscribe_new_heads()
+            .await
+            .context("failed to subscribe to new heads")?;
+        // Blocks might have been missed while the subscription was disconnected.
+        self.update(self.web3.current_block().await?, BlockSource::Poll)?;
+        while let Some(header) = tokio::time::timeout(timeout, heads.next())
+            .await
+            .context("timed out waiting for new head")?
+        {
+            let hash = header
+                .context("failed to receive new head")?
+                .hash
+                .ok_or_else(|| anyhow!("missing hash"))?;
+            let block = self
+                .web3
+                .eth()
+                .block(BlockId::Hash(hash))
+                .await
+                .context("failed to get new block")?
+                .ok_or_else(|| anyhow!("new block {:?} not found", hash))?;
+            self.update(block, BlockSource::Subscription)?;
+        }
+        Ok(())
+    }
+}
+
 pub type CurrentBlockStream = watch::Receiver<Block>;
 
 pub fn into_stream(receiver: watch::Receiver<Block>) -> WatchStream<Block> {
@@ -124,9 +255,47 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transport::create_test_transport;
+    use crate::{
+        metrics::RecordingMetrics,
+        transport::{create_test_transport, mock::MockTransport},
+    };
+    use ethcontract::dyns::DynTransport;
     use futures::StreamExt;
 
+    impl BlockStreamMetrics for RecordingMetrics<BlockSource> {
+        fn block_arrived(&self, source: BlockSource, _: Duration) {
+            self.record(source);
+        }
+    }
+
+    fn block(hash: u64) -> Block {
+        Block {
+            hash: Some(H256::from_low_u64_be(hash)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn updater_publishes_only_new_blocks() {
+        let (sender, receiver) = watch::channel(block(1));
+        let metrics = Arc::new(RecordingMetrics::default());
+        let updater = BlockUpdater {
+            web3: Web3::new(DynTransport::new(MockTransport::new())),
+            sender,
+            previous_hash: Mutex::new(H256::from_low_u64_be(1)),
+            metrics: metrics.clone(),
+        };
+
+        updater.update(block(1), BlockSource::Poll).unwrap();
+        updater.update(block(2), BlockSource::Subscription).unwrap();
+        updater.update(block(2), BlockSource::Poll).unwrap();
+        assert_eq!(receiver.borrow().hash, block(2).hash);
+        assert_eq!(metrics.recorded(), vec![BlockSource::Subscription]);
+
+        drop(receiver);
+        assert!(updater.update(block(3), BlockSource::Poll).is_err());
+    }
+
     // cargo test current_block -- --ignored --nocapture
     #[tokio::test]
     #[ignore]
@@ -100,6 +100,11 @@ where
         Ok(BlockNumber::Specific(from_block)..=BlockNumber::Latest(current_block))
     }
 
+    /// The last block up to which events were handled.
+    pub fn last_handled_block(&self) -> Option<u64> {
+        self.last_handled_block
+    }
+
     /// Get new events from the contract and insert them into the database.
     pub async fn update_events(&mut self) -> Result<()> {
         let range = self.event_block_range().await?;
@@ -0,0 +1,234 @@
+//! Liveness and readiness reporting of the components of a service.
+//!
+//! `/health` always responds with 200 while the service is able to serve requests and `/ready`
+//! responds with 503 if any component is not ready. Both contain a JSON report of all components.
+
+use crate::current_block::{self, CurrentBlockStream};
+use anyhow::{anyhow, ensure, Result};
+use futures::future::join_all;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use warp::{hyper::StatusCode, reply, Filter, Rejection, Reply};
+
+#[async_trait::async_trait]
+pub trait HealthChecking: Send + Sync {
+    /// Returns details about the state of the component or an error if it is not ready.
+    async fn check_health(&self) -> Result<Value>;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub ready: bool,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+/// Collects the health of all named components of a service.
+#[derive(Default)]
+pub struct HealthReporter {
+    checks: Vec<(&'static str, Arc<dyn HealthChecking>)>,
+}
+
+impl HealthReporter {
+    pub fn new(checks: Vec<(&'static str, Arc<dyn HealthChecking>)>) -> Self {
+        Self { checks }
+    }
+
+    pub async fn report(&self) -> HealthReport {
+        let results = join_all(self.checks.iter().map(|(_, check)| check.check_health())).await;
+        let components = self
+            .checks
+            .iter()
+            .zip(results)
+            .map(|((name, _), result)| {
+                let health = match result {
+                    Ok(details) => ComponentHealth {
+                        ready: true,
+                        details: Some(details),
+                        error: None,
+                    },
+                    Err(err) => ComponentHealth {
+                        ready: false,
+                        details: None,
+                        error: Some(format!("{:?}", err)),
+                    },
+                };
+                (*name, health)
+            })
+            .collect::<BTreeMap<_, _>>();
+        HealthReport {
+            ready: components.values().all(|health| health.ready),
+            components,
+        }
+    }
+}
+
+// `/health` and `/ready` routes reporting the health of the service's components.
+pub fn handle_health(
+    reporter: Arc<HealthReporter>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let health = {
+        let reporter = reporter.clone();
+        warp::path("health").and_then(move || {
+            let reporter = reporter.clone();
+            async move {
+                let report = reporter.report().await;
+                Result::<_, Rejection>::Ok(reply::json(&report))
+            }
+        })
+    };
+    let ready = warp::path("ready").and_then(move || {
+        let reporter = reporter.clone();
+        async move {
+            let report = reporter.report().await;
+            let status = if report.ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            Result::<_, Rejection>::Ok(reply::with_status(reply::json(&report), status))
+        }
+    });
+    health
+        .map(|reply| Box::new(reply) as Box<dyn Reply>)
+        .or(ready.map(|reply| Box::new(reply) as Box<dyn Reply>))
+        .unify()
+}
+
+/// Records when a recurring task last completed successfully.
+#[derive(Clone, Debug, Default)]
+pub struct Heartbeat(Arc<Mutex<Option<Instant>>>);
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        *self.0.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// The time since the last beat if there was one.
+    pub fn age(&self) -> Option<Duration> {
+        self.0.lock().unwrap().map(|last| last.elapsed())
+    }
+}
+
+/// A component that is ready if its heartbeat is more recent than the maximum age.
+pub struct HeartbeatHealth {
+    pub heartbeat: Heartbeat,
+    pub max_age: Duration,
+}
+
+#[async_trait::async_trait]
+impl HealthChecking for HeartbeatHealth {
+    async fn check_health(&self) -> Result<Value> {
+        let age = self
+            .heartbeat
+            .age()
+            .ok_or_else(|| anyhow!("has not completed yet"))?;
+        ensure!(age <= self.max_age, "last completed {}s ago", age.as_secs());
+        Ok(json!({ "secondsSinceLastCompletion": age.as_secs() }))
+    }
+}
+
+/// A component that is ready if the timestamp of the current block is more recent than the
+/// maximum age.
+pub struct CurrentBlockHealth {
+    pub block_stream: CurrentBlockStream,
+    pub max_age: Duration,
+}
+
+#[async_trait::async_trait]
+impl HealthChecking for CurrentBlockHealth {
+    async fn check_health(&self) -> Result<Value> {
+        let block = self.block_stream.borrow().clone();
+        let number = current_block::block_number(&block)?;
+        let timestamp = UNIX_EPOCH + Duration::from_secs(block.timestamp.low_u64());
+        let age = SystemTime::now()
+            .duration_since(timestamp)
+            .unwrap_or_default();
+        ensure!(
+            age <= self.max_age,
+            "current block {} is {}s old",
+            number,
+            age.as_secs()
+        );
+        Ok(json!({
+            "number": number,
+            "hash": block.hash,
+            "ageSeconds": age.as_secs(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingCheck;
+
+    fn check(check: impl HealthChecking + 'static) -> Arc<dyn HealthChecking> {
+        Arc::new(check)
+    }
+
+    #[async_trait::async_trait]
+    impl HealthChecking for FailingCheck {
+        async fn check_health(&self) -> Result<Value> {
+            Err(anyhow!("failure"))
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_ready_only_if_all_components_are() {
+        let heartbeat = Heartbeat::default();
+        let healthy = HealthReporter::new(vec![(
+            "task",
+            check(HeartbeatHealth {
+                heartbeat: heartbeat.clone(),
+                max_age: Duration::from_secs(60),
+            }),
+        )]);
+        assert!(!healthy.report().await.ready);
+        heartbeat.beat();
+        assert!(healthy.report().await.ready);
+
+        let unhealthy = HealthReporter::new(vec![
+            (
+                "task",
+                check(HeartbeatHealth {
+                    heartbeat,
+                    max_age: Duration::from_secs(60),
+                }),
+            ),
+            ("failing", check(FailingCheck)),
+        ]);
+        let report = unhealthy.report().await;
+        assert!(!report.ready);
+        assert!(report.components["task"].ready);
+        assert!(!report.components["failing"].ready);
+    }
+
+    #[tokio::test]
+    async fn ready_endpoint_responds_with_unavailable_status() {
+        let filter = handle_health(Arc::new(HealthReporter::new(vec![(
+            "failing",
+            check(FailingCheck),
+        )])));
+        let health = warp::test::request().path("/health").reply(&filter).await;
+        assert_eq!(health.status(), StatusCode::OK);
+        let ready = warp::test::request().path("/ready").reply(&filter).await;
+        assert_eq!(ready.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
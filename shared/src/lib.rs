@@ -4,11 +4,13 @@ pub mod macros;
 pub mod arguments;
 pub mod bad_token;
 pub mod baseline_solver;
+pub mod config;
 pub mod conversions;
 pub mod current_block;
 pub mod ethcontract_error;
 pub mod event_handling;
 pub mod gas_price_estimation;
+pub mod health;
 pub mod http;
 pub mod maintenance;
 pub mod metrics;
@@ -27,17 +29,21 @@ pub mod web3_traits;
 
 use ethcontract::H160;
 use hex::{FromHex, FromHexError};
-use model::h160_hexadecimal;
+use model::h160_hexadecimal::{self, HexadecimalH160};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::str::FromStr;
 
-pub type Web3 =
-    web3::Web3<transport::instrumented::MetricTransport<transport::http::HttpTransport>>;
+pub type Web3 = web3::Web3<ethcontract::dyns::DynTransport>;
 
 /// Wraps H160 with FromStr and Deserialize that can handle a `0x` prefix.
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(transparent)]
-pub struct H160Wrapper(#[serde(with = "h160_hexadecimal")] pub H160);
+pub struct H160Wrapper(
+    #[serde(with = "h160_hexadecimal")]
+    #[schemars(with = "HexadecimalH160")]
+    pub H160,
+);
 impl FromStr for H160Wrapper {
     type Err = FromHexError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
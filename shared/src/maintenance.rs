@@ -1,4 +1,7 @@
-use crate::current_block::{self, Block, CurrentBlockStream};
+use crate::{
+    current_block::{self, Block, CurrentBlockStream},
+    health::Heartbeat,
+};
 use anyhow::Result;
 use futures::{future::join_all, Stream, StreamExt};
 use std::sync::Arc;
@@ -6,6 +9,8 @@ use std::sync::Arc;
 /// Collects all service components requiring maintenance on each new block
 pub struct ServiceMaintenance {
     pub maintainers: Vec<Arc<dyn Maintaining>>,
+    /// Beats whenever all maintainers ran successfully.
+    pub heartbeat: Heartbeat,
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -17,11 +22,16 @@ pub trait Maintaining: Send + Sync {
 #[async_trait::async_trait]
 impl Maintaining for ServiceMaintenance {
     async fn run_maintenance(&self) -> Result<()> {
+        let mut successful = true;
         for result in join_all(self.maintainers.iter().map(|m| m.run_maintenance())).await {
             if let Err(err) = result {
                 tracing::error!("Service Maintenance Error: {:?}", err);
+                successful = false;
             }
         }
+        if successful {
+            self.heartbeat.beat();
+        }
         Ok(())
     }
 }
@@ -71,9 +81,11 @@ mod tests {
                 Arc::new(ok_mock_maintenance),
                 Arc::new(err_mock_maintenance),
             ],
+            heartbeat: Default::default(),
         };
 
         assert!(service_maintenance.run_maintenance().await.is_ok());
+        assert!(service_maintenance.heartbeat.age().is_none());
     }
 
     #[tokio::test]
@@ -88,11 +100,14 @@ mod tests {
             .returning(|| Ok(()));
         let service_maintenance = ServiceMaintenance {
             maintainers: vec![Arc::new(mock_maintenance)],
+            heartbeat: Default::default(),
         };
+        let heartbeat = service_maintenance.heartbeat.clone();
 
         let block_stream = futures::stream::repeat(Block::default()).take(block_count);
         service_maintenance
             .run_maintenance_for_block_stream(block_stream)
             .await;
+        assert!(heartbeat.age().is_some());
     }
 }
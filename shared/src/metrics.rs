@@ -1,12 +1,18 @@
+use crate::health::{handle_health, HealthReporter};
 use prometheus::{Encoder, Registry};
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 use tokio::task::{self, JoinHandle};
 use warp::{Filter, Rejection, Reply};
 
 pub const DEFAULT_METRICS_PORT: u16 = 9586;
 
-pub fn serve_metrics(registry: Registry, address: SocketAddr) -> JoinHandle<()> {
-    let filter = handle_metrics(registry);
+/// Serves the metrics as well as the health and readiness of the service.
+pub fn serve_metrics(
+    registry: Registry,
+    health: Arc<HealthReporter>,
+    address: SocketAddr,
+) -> JoinHandle<()> {
+    let filter = handle_metrics(registry).or(handle_health(health));
     tracing::info!(%address, "serving metrics");
     task::spawn(warp::serve(filter).bind(address))
 }
@@ -30,3 +36,25 @@ pub fn handle_metrics(
         }
     })
 }
+
+/// Records the values reported to a metrics trait so that tests can assert on them.
+#[cfg(test)]
+pub struct RecordingMetrics<T>(std::sync::Mutex<Vec<T>>);
+
+#[cfg(test)]
+impl<T> Default for RecordingMetrics<T> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+#[cfg(test)]
+impl<T: Clone> RecordingMetrics<T> {
+    pub fn record(&self, value: T) {
+        self.0.lock().unwrap().push(value);
+    }
+
+    pub fn recorded(&self) -> Vec<T> {
+        self.0.lock().unwrap().clone()
+    }
+}
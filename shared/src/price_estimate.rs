@@ -1,8 +1,14 @@
+pub mod caching;
+pub mod chainlink;
+pub mod composite;
+pub mod static_prices;
+
 use crate::{
     bad_token::BadTokenDetecting,
     baseline_solver::{
-        estimate_buy_amount, estimate_sell_amount, estimate_spot_price, path_candidates,
-        token_path_to_pair_path, DEFAULT_MAX_HOPS,
+        estimate_buy_amount, estimate_buy_amount_split, estimate_sell_amount,
+        estimate_sell_amount_split, estimate_spot_price, path_candidates, token_path_to_pair_path,
+        DEFAULT_MAX_HOPS,
     },
     conversions::U256Ext,
     recent_block_cache::Block,
@@ -13,7 +19,7 @@ use ethcontract::{H160, U256};
 use futures::future::join_all;
 use gas_estimation::GasPriceEstimating;
 use model::{order::OrderKind, TokenPair};
-use num::{BigRational, ToPrimitive};
+use num::{BigRational, ToPrimitive, Zero as _};
 use std::{
     cmp::Reverse,
     collections::{HashMap, HashSet},
@@ -192,7 +198,7 @@ impl PriceEstimating for BaselinePriceEstimator {
         }
 
         let gas_price = self.gas_estimator.estimate().await?;
-        let path = match kind {
+        let paths = match kind {
             OrderKind::Buy => {
                 self.best_execution_buy_order(sell_token, buy_token, amount, gas_price)
                     .await?
@@ -204,7 +210,7 @@ impl PriceEstimating for BaselinePriceEstimator {
                     .0
             }
         };
-        let trades = path.len() - 1;
+        let trades: usize = paths.iter().map(|path| path.len() - 1).sum();
         // This could be more accurate by actually simulating the settlement (since different tokens might have more or less expensive transfer costs)
         // For the standard OZ token the cost is roughly 110k for a direct trade, 170k for a 1 hop trade, 230k for a 2 hop trade.
         const BASELINE_GAS_PER_HOP: u64 = 60_000;
@@ -218,71 +224,107 @@ impl PriceEstimating for BaselinePriceEstimator {
 }
 
 impl BaselinePriceEstimator {
+    /// Returns the paths the sell amount is split over and the resulting buy amount. If splitting
+    /// doesn't yield a better result after gas costs, this is the single best path.
     pub async fn best_execution_sell_order(
         &self,
         sell_token: H160,
         buy_token: H160,
         sell_amount: U256,
         gas_price: f64,
-    ) -> Result<(Vec<H160>, U256)> {
+    ) -> Result<(Vec<Vec<H160>>, U256)> {
         // Estimate with amount 0 to get a spot price (avoid potential endless recursion)
         let buy_token_price_in_native_token = self
             .estimate_price(buy_token, self.native_token, U256::zero(), OrderKind::Sell)
             .await?;
-        self.best_execution(
+        let net_proceeds = |buy_amount: U256, gas_cost: usize| {
+            let proceeds_in_native_token =
+                buy_amount.to_big_rational() * buy_token_price_in_native_token.clone();
+            let tx_cost_in_native_token = U256::from_f64_lossy(gas_price).to_big_rational()
+                * BigRational::from_integer(gas_cost.into());
+            proceeds_in_native_token - tx_cost_in_native_token
+        };
+        let (path_candidates, pools) = self.fetch_pools(sell_token, buy_token).await?;
+        let (path, buy_amount) = best_execution(
             sell_token,
             buy_token,
             sell_amount,
+            &path_candidates,
+            &pools,
             |amount, path, pools| {
-                estimate_buy_amount(amount, path, pools).map(|estimate| {
-                    let proceeds_in_native_token =
-                        estimate.value.to_big_rational() * buy_token_price_in_native_token.clone();
-                    let tx_cost_in_native_token = U256::from_f64_lossy(gas_price).to_big_rational()
-                        * BigRational::from_integer(estimate.gas_cost().into());
-                    proceeds_in_native_token - tx_cost_in_native_token
-                })
+                estimate_buy_amount(amount, path, pools)
+                    .map(|estimate| net_proceeds(estimate.value, estimate.gas_cost()))
             },
             |amount, path, pools| {
                 estimate_buy_amount(amount, path, pools).map(|estimate| estimate.value)
             },
-        )
-        .await
+        )?;
+
+        let split = estimate_buy_amount_split(sell_amount, &path_candidates, &pools)
+            .filter(|split| split.parts.len() > 1);
+        let single = estimate_buy_amount(sell_amount, &path, &pools);
+        if let (Some(split), Some(single)) = (split, single) {
+            if net_proceeds(split.value, split.gas_cost())
+                > net_proceeds(single.value, single.gas_cost())
+            {
+                let paths = split.parts.into_iter().map(|part| part.path).collect();
+                return Ok((paths, split.value));
+            }
+        }
+        Ok((vec![path], buy_amount))
     }
 
+    /// Returns the paths the buy amount is split over and the required sell amount. If splitting
+    /// doesn't yield a better result after gas costs, this is the single best path.
     pub async fn best_execution_buy_order(
         &self,
         sell_token: H160,
         buy_token: H160,
         buy_amount: U256,
         gas_price: f64,
-    ) -> Result<(Vec<H160>, U256)> {
+    ) -> Result<(Vec<Vec<H160>>, U256)> {
         // Estimate with amount 0 to get a spot price (avoid potential endless recursion)
         let sell_token_price_in_eth = self
             .estimate_price(sell_token, self.native_token, U256::zero(), OrderKind::Sell)
             .await?;
-        self.best_execution(
+        let total_cost = |sell_amount: U256, gas_cost: usize| {
+            let cost_in_native_token =
+                sell_amount.to_big_rational() * sell_token_price_in_eth.clone();
+            let tx_cost_in_native_token = U256::from_f64_lossy(gas_price).to_big_rational()
+                * BigRational::from_integer(gas_cost.into());
+            cost_in_native_token + tx_cost_in_native_token
+        };
+        let (path_candidates, pools) = self.fetch_pools(sell_token, buy_token).await?;
+        let (path, sell_amount) = best_execution(
             sell_token,
             buy_token,
             buy_amount,
+            &path_candidates,
+            &pools,
             |amount, path, pools| {
                 Reverse(
                     estimate_sell_amount(amount, path, pools)
-                        .map(|estimate| {
-                            let cost_in_native_token =
-                                estimate.value.to_big_rational() * sell_token_price_in_eth.clone();
-                            let tx_cost_in_native_token = U256::from_f64_lossy(gas_price)
-                                .to_big_rational()
-                                * BigRational::from_integer(estimate.gas_cost().into());
-                            cost_in_native_token + tx_cost_in_native_token
-                        })
+                        .map(|estimate| total_cost(estimate.value, estimate.gas_cost()))
                         .unwrap_or_else(|| U256::max_value().to_big_rational()),
                 )
             },
             |amount, path, pools| {
                 estimate_sell_amount(amount, path, pools).map(|estimate| estimate.value)
             },
-        )
-        .await
+        )?;
+
+        let split = estimate_sell_amount_split(buy_amount, &path_candidates, &pools)
+            .filter(|split| split.parts.len() > 1);
+        let single = estimate_sell_amount(buy_amount, &path, &pools);
+        if let (Some(split), Some(single)) = (split, single) {
+            if total_cost(split.value, split.gas_cost())
+                < total_cost(single.value, single.gas_cost())
+            {
+                let paths = split.parts.into_iter().map(|part| part.path).collect();
+                return Ok((paths, split.value));
+            }
+        }
+        Ok((vec![path], sell_amount))
     }
 
     pub async fn best_execution_spot_price(
@@ -290,29 +332,23 @@ impl BaselinePriceEstimator {
         sell_token: H160,
         buy_token: H160,
     ) -> Result<(Vec<H160>, BigRational)> {
-        self.best_execution(
+        let (path_candidates, pools) = self.fetch_pools(sell_token, buy_token).await?;
+        best_execution(
             sell_token,
             buy_token,
             U256::zero(),
+            &path_candidates,
+            &pools,
             |_, path, pools| estimate_spot_price(path, pools).map(|estimate| estimate.value),
             |_, path, pools| estimate_spot_price(path, pools).map(|estimate| estimate.value),
         )
-        .await
     }
 
-    async fn best_execution<AmountFn, CompareFn, O, Amount>(
+    async fn fetch_pools(
         &self,
         sell_token: H160,
         buy_token: H160,
-        amount: U256,
-        comparison: CompareFn,
-        resulting_amount: AmountFn,
-    ) -> Result<(Vec<H160>, Amount)>
-    where
-        AmountFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> Option<Amount>,
-        CompareFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> O,
-        O: Ord,
-    {
+    ) -> Result<(HashSet<Vec<H160>>, HashMap<TokenPair, Vec<Pool>>)> {
         let path_candidates =
             path_candidates(sell_token, buy_token, &self.base_tokens, DEFAULT_MAX_HOPS);
         let all_pairs = path_candidates
@@ -328,25 +364,63 @@ impl BaselinePriceEstimator {
                 pools.entry(pool.tokens).or_default().push(pool);
                 pools
             });
-        let best_path = path_candidates
-            .iter()
-            .max_by_key(|path| comparison(amount, path, &pools))
-            .ok_or(anyhow!(format!(
-                "No Uniswap path found between {:#x} and {:#x}",
-                sell_token, buy_token
-            )))?;
-        Ok((
-            best_path.clone(),
-            resulting_amount(amount, best_path, &pools).ok_or_else(|| {
-                anyhow!(format!(
-                    "No valid path found between {:#x} and {:#x}",
-                    sell_token, buy_token
-                ))
-            })?,
-        ))
+        Ok((path_candidates, pools))
     }
 }
 
+/// Converts the native prices of a sell and a buy token into the price `estimate_price` returns
+/// for `amount`.
+pub fn price_from_native_prices(
+    sell_price: BigRational,
+    buy_price: BigRational,
+    amount: U256,
+) -> Result<BigRational> {
+    // Zero amounts ask for the buy amount per sell amount and others for the inverse.
+    let (numerator, denominator) = if amount.is_zero() {
+        (sell_price, buy_price)
+    } else {
+        (buy_price, sell_price)
+    };
+    if denominator.is_zero() {
+        return Err(anyhow!(
+            "Attempt to create a rational with zero denominator."
+        ));
+    }
+    Ok(numerator / denominator)
+}
+
+fn best_execution<AmountFn, CompareFn, O, Amount>(
+    sell_token: H160,
+    buy_token: H160,
+    amount: U256,
+    path_candidates: &HashSet<Vec<H160>>,
+    pools: &HashMap<TokenPair, Vec<Pool>>,
+    comparison: CompareFn,
+    resulting_amount: AmountFn,
+) -> Result<(Vec<H160>, Amount)>
+where
+    AmountFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> Option<Amount>,
+    CompareFn: Fn(U256, &[H160], &HashMap<TokenPair, Vec<Pool>>) -> O,
+    O: Ord,
+{
+    let best_path = path_candidates
+        .iter()
+        .max_by_key(|path| comparison(amount, path, pools))
+        .ok_or(anyhow!(format!(
+            "No Uniswap path found between {:#x} and {:#x}",
+            sell_token, buy_token
+        )))?;
+    Ok((
+        best_path.clone(),
+        resulting_amount(amount, best_path, pools).ok_or_else(|| {
+            anyhow!(format!(
+                "No valid path found between {:#x} and {:#x}",
+                sell_token, buy_token
+            ))
+        })?,
+    ))
+}
+
 pub mod mocks {
     use super::*;
 
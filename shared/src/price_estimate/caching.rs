@@ -0,0 +1,297 @@
+use super::{PriceEstimating, PriceEstimationError};
+use crate::current_block::{self, CurrentBlockStream};
+use anyhow::anyhow;
+use ethcontract::{H160, U256};
+use futures::future::{BoxFuture, FutureExt as _, Shared};
+use model::order::OrderKind;
+use num::BigRational;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// The number of most significant bits of the amount that are part of the cache key. Requests for
+/// amounts that only differ in less significant bits share their estimate.
+const AMOUNT_SIGNIFICANT_BITS: usize = 16;
+
+pub trait PriceCacheMetrics: Send + Sync {
+    fn price_estimate_looked_up(&self, cache_hit: bool);
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Key {
+    sell_token: H160,
+    buy_token: H160,
+    amount_bucket: U256,
+    kind: OrderKind,
+}
+
+type SharedEstimate<T> = Shared<BoxFuture<'static, Result<T, Arc<PriceEstimationError>>>>;
+
+#[derive(Default)]
+struct Cache {
+    block: u64,
+    prices: HashMap<Key, SharedEstimate<BigRational>>,
+    gas: HashMap<Key, SharedEstimate<U256>>,
+}
+
+/// A price estimator caching the estimates of its inner estimator for the current block.
+///
+/// Concurrent identical requests are coalesced into a single request to the inner estimator. All
+/// estimates, including errors, are dropped once the block stream yields a new block.
+pub struct CachingPriceEstimator {
+    inner: Arc<dyn PriceEstimating>,
+    cache: Mutex<Cache>,
+    block_stream: CurrentBlockStream,
+    metrics: Arc<dyn PriceCacheMetrics>,
+}
+
+impl CachingPriceEstimator {
+    pub fn new(
+        inner: Arc<dyn PriceEstimating>,
+        block_stream: CurrentBlockStream,
+        metrics: Arc<dyn PriceCacheMetrics>,
+    ) -> Self {
+        Self {
+            inner,
+            cache: Default::default(),
+            block_stream,
+            metrics,
+        }
+    }
+
+    /// Returns the cached estimate for the key or starts a new one using `estimate`.
+    async fn get_or_estimate<T, S, E>(
+        &self,
+        key: Key,
+        select: S,
+        estimate: E,
+    ) -> Result<T, PriceEstimationError>
+    where
+        T: Clone + Send + Sync + 'static,
+        S: FnOnce(&mut Cache) -> &mut HashMap<Key, SharedEstimate<T>>,
+        E: FnOnce(Arc<dyn PriceEstimating>) -> BoxFuture<'static, Result<T, PriceEstimationError>>,
+    {
+        let block = current_block::block_number(&self.block_stream.borrow()).unwrap_or_default();
+        let estimate = {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.block != block {
+                *cache = Cache {
+                    block,
+                    ..Default::default()
+                };
+            }
+            let entries = select(&mut cache);
+            let cache_hit = entries.contains_key(&key);
+            self.metrics.price_estimate_looked_up(cache_hit);
+            entries
+                .entry(key)
+                .or_insert_with(|| {
+                    estimate(self.inner.clone())
+                        .map(|result| result.map_err(Arc::new))
+                        .boxed()
+                        .shared()
+                })
+                .clone()
+        };
+        estimate.await.map_err(|err| clone_error(&err))
+    }
+}
+
+fn amount_bucket(amount: U256) -> U256 {
+    let bits = amount.bits();
+    if bits <= AMOUNT_SIGNIFICANT_BITS {
+        return amount;
+    }
+    let shift = bits - AMOUNT_SIGNIFICANT_BITS;
+    (amount >> shift) << shift
+}
+
+fn clone_error(err: &PriceEstimationError) -> PriceEstimationError {
+    match err {
+        PriceEstimationError::UnsupportedToken(token) => {
+            PriceEstimationError::UnsupportedToken(*token)
+        }
+        PriceEstimationError::Other(err) => PriceEstimationError::Other(anyhow!("{:?}", err)),
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceEstimating for CachingPriceEstimator {
+    async fn estimate_price(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        kind: OrderKind,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let key = Key {
+            sell_token,
+            buy_token,
+            amount_bucket: amount_bucket(amount),
+            kind,
+        };
+        self.get_or_estimate(
+            key,
+            |cache| &mut cache.prices,
+            |inner| {
+                async move {
+                    inner
+                        .estimate_price(sell_token, buy_token, amount, kind)
+                        .await
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    async fn estimate_gas(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        kind: OrderKind,
+    ) -> Result<U256, PriceEstimationError> {
+        let key = Key {
+            sell_token,
+            buy_token,
+            amount_bucket: amount_bucket(amount),
+            kind,
+        };
+        self.get_or_estimate(
+            key,
+            |cache| &mut cache.gas,
+            |inner| {
+                async move {
+                    inner
+                        .estimate_gas(sell_token, buy_token, amount, kind)
+                        .await
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{current_block::Block, metrics::RecordingMetrics};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::watch;
+
+    #[derive(Default)]
+    struct CountingEstimator(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl PriceEstimating for CountingEstimator {
+        async fn estimate_price(
+            &self,
+            _: H160,
+            _: H160,
+            _: U256,
+            _: OrderKind,
+        ) -> Result<BigRational, PriceEstimationError> {
+            let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::task::yield_now().await;
+            Ok(BigRational::from_integer(calls.into()))
+        }
+
+        async fn estimate_gas(
+            &self,
+            _: H160,
+            _: H160,
+            _: U256,
+            _: OrderKind,
+        ) -> Result<U256, PriceEstimationError> {
+            Err(anyhow!("error").into())
+        }
+    }
+
+    impl PriceCacheMetrics for RecordingMetrics<bool> {
+        fn price_estimate_looked_up(&self, cache_hit: bool) {
+            self.record(cache_hit);
+        }
+    }
+
+    fn block(number: u64) -> Block {
+        Block {
+            number: Some(number.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn buckets_amounts_by_most_significant_bits() {
+        assert_eq!(amount_bucket(0.into()), 0.into());
+        assert_eq!(amount_bucket(65_535.into()), 65_535.into());
+        assert_eq!(amount_bucket(65_537.into()), 65_536.into());
+        assert_eq!(
+            amount_bucket(U256::exp10(18) + 1),
+            amount_bucket(U256::exp10(18))
+        );
+    }
+
+    #[tokio::test]
+    async fn coalesces_requests_and_invalidates_on_new_block() {
+        let inner = Arc::new(CountingEstimator::default());
+        let metrics = Arc::new(RecordingMetrics::default());
+        let (sender, receiver) = watch::channel(block(1));
+        let estimator = CachingPriceEstimator::new(inner.clone(), receiver, metrics.clone());
+        let estimate = |amount: u64| {
+            estimator.estimate_price(
+                H160::from_low_u64_be(1),
+                H160::from_low_u64_be(2),
+                amount.into(),
+                OrderKind::Sell,
+            )
+        };
+
+        let (first, second) = futures::join!(estimate(100), estimate(100));
+        assert_eq!(first.unwrap(), BigRational::from_integer(1.into()));
+        assert_eq!(second.unwrap(), BigRational::from_integer(1.into()));
+        assert_eq!(
+            estimate(100).await.unwrap(),
+            BigRational::from_integer(1.into())
+        );
+        assert_eq!(
+            estimate(200).await.unwrap(),
+            BigRational::from_integer(2.into())
+        );
+        assert_eq!(inner.0.load(Ordering::SeqCst), 2);
+        let mut lookups = metrics.recorded();
+        lookups.sort_unstable();
+        assert_eq!(lookups, vec![false, false, true, true]);
+
+        sender.send(block(2)).unwrap();
+        assert_eq!(
+            estimate(100).await.unwrap(),
+            BigRational::from_integer(3.into())
+        );
+        assert_eq!(inner.0.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn shares_errors_with_coalesced_requests() {
+        let inner = Arc::new(CountingEstimator::default());
+        let (_sender, receiver) = watch::channel(block(1));
+        let estimator = CachingPriceEstimator::new(
+            inner,
+            receiver,
+            Arc::new(RecordingMetrics::<bool>::default()),
+        );
+        let estimate = || {
+            estimator.estimate_gas(
+                H160::from_low_u64_be(1),
+                H160::from_low_u64_be(2),
+                1.into(),
+                OrderKind::Sell,
+            )
+        };
+        let (first, second) = futures::join!(estimate(), estimate());
+        assert!(first.is_err());
+        assert!(second.is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use super::{price_from_native_prices, PriceEstimating, PriceEstimationError};
+use crate::{
+    arguments::split_pair, conversions::U256Ext, time::now_in_epoch_seconds,
+    token_info::TokenInfoFetching,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use contracts::ChainlinkAggregatorV3;
+use ethcontract::{H160, I256, U256};
+use model::order::OrderKind;
+use num::{BigInt, BigRational};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// The number of decimals of the native token, which Chainlink's ETH feeds are denominated in.
+const NATIVE_TOKEN_DECIMALS: u32 = 18;
+
+/// The latest answer of a price feed.
+#[derive(Clone, Debug)]
+pub struct FeedRound {
+    pub answer: I256,
+    pub decimals: u8,
+    /// Unix timestamp of when the answer was last updated.
+    pub updated_at: u64,
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait PriceFeed: Send + Sync {
+    async fn latest_round(&self) -> Result<FeedRound>;
+}
+
+#[async_trait::async_trait]
+impl PriceFeed for ChainlinkAggregatorV3 {
+    async fn latest_round(&self) -> Result<FeedRound> {
+        let (decimals, (_, answer, _, updated_at, _)) =
+            futures::try_join!(self.decimals().call(), self.latest_round_data().call())
+                .context("failed to read Chainlink feed")?;
+        Ok(FeedRound {
+            answer,
+            decimals,
+            updated_at: updated_at.low_u64(),
+        })
+    }
+}
+
+/// A price estimator using on chain Chainlink feeds that quote tokens in the native token. As the
+/// prices are spot prices the traded amount is ignored.
+pub struct ChainlinkPriceEstimator {
+    feeds: HashMap<H160, Box<dyn PriceFeed>>,
+    token_info: Arc<dyn TokenInfoFetching>,
+    native_token: H160,
+    /// Answers that were last updated longer ago than this are not used.
+    max_age: Duration,
+}
+
+impl ChainlinkPriceEstimator {
+    pub fn new(
+        feeds: HashMap<H160, Box<dyn PriceFeed>>,
+        token_info: Arc<dyn TokenInfoFetching>,
+        native_token: H160,
+        max_age: Duration,
+    ) -> Self {
+        Self {
+            feeds,
+            token_info,
+            native_token,
+            max_age,
+        }
+    }
+
+    /// Creates an estimator reading the Chainlink aggregators at the given addresses, keyed by the
+    /// token they quote in the native token.
+    pub fn from_aggregators(
+        web3: &crate::Web3,
+        aggregators: impl IntoIterator<Item = (H160, H160)>,
+        token_info: Arc<dyn TokenInfoFetching>,
+        native_token: H160,
+        max_age: Duration,
+    ) -> Self {
+        let feeds = aggregators
+            .into_iter()
+            .map(|(token, aggregator)| {
+                let feed: Box<dyn PriceFeed> =
+                    Box::new(ChainlinkAggregatorV3::at(web3, aggregator));
+                (token, feed)
+            })
+            .collect();
+        Self::new(feeds, token_info, native_token, max_age)
+    }
+
+    /// The value of the smallest unit of the token in the smallest unit of the native token.
+    async fn native_price(&self, token: H160) -> Result<BigRational> {
+        if token == self.native_token {
+            return Ok(num::one());
+        }
+        let feed = self
+            .feeds
+            .get(&token)
+            .ok_or_else(|| anyhow!("no Chainlink feed for token {:?}", token))?;
+        let round = feed.latest_round().await?;
+        let age = u64::from(now_in_epoch_seconds()).saturating_sub(round.updated_at);
+        ensure!(
+            age <= self.max_age.as_secs(),
+            "Chainlink answer for token {:?} is {}s old",
+            token,
+            age
+        );
+        let decimals = self
+            .token_info
+            .get_token_infos(&[token])
+            .await
+            .get(&token)
+            .and_then(|info| info.decimals)
+            .ok_or_else(|| anyhow!("decimals of token {:?} unknown", token))?;
+        native_price_from_answer(round.answer, round.decimals, decimals)
+    }
+}
+
+/// Parses a `token=aggregator` pair, where the aggregator is the address of a Chainlink feed quoting
+/// the token in the native token.
+pub fn parse_feed(s: &str) -> Result<(H160, H160)> {
+    let (token, aggregator) = split_pair(s, '=')?;
+    Ok((
+        token.parse().context("invalid token address")?,
+        aggregator.parse().context("invalid aggregator address")?,
+    ))
+}
+
+/// Converts an answer quoting a whole token in whole native tokens into a price of the smallest
+/// units.
+fn native_price_from_answer(
+    answer: I256,
+    answer_decimals: u8,
+    token_decimals: u8,
+) -> Result<BigRational> {
+    ensure!(
+        answer > I256::zero(),
+        "Chainlink answer {} is not positive",
+        answer
+    );
+    let exp10 = |decimals: u32| BigInt::from(10).pow(decimals);
+    Ok(BigRational::new(
+        answer.into_raw().to_big_int() * exp10(NATIVE_TOKEN_DECIMALS),
+        exp10(answer_decimals as u32 + token_decimals as u32),
+    ))
+}
+
+#[async_trait::async_trait]
+impl PriceEstimating for ChainlinkPriceEstimator {
+    async fn estimate_price(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        _: OrderKind,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let (sell_price, buy_price) =
+            futures::try_join!(self.native_price(sell_token), self.native_price(buy_token))?;
+        Ok(price_from_native_prices(sell_price, buy_price, amount)?)
+    }
+
+    async fn estimate_gas(
+        &self,
+        _: H160,
+        _: H160,
+        _: U256,
+        _: OrderKind,
+    ) -> Result<U256, PriceEstimationError> {
+        Err(anyhow!("Chainlink feeds can't estimate gas").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_info::{MockTokenInfoFetching, TokenInfo};
+    use maplit::hashmap;
+
+    fn feed(answer: i64, updated_at: u64) -> Box<dyn PriceFeed> {
+        let mut feed = MockPriceFeed::new();
+        feed.expect_latest_round().returning(move || {
+            Ok(FeedRound {
+                answer: I256::from(answer),
+                decimals: 8,
+                updated_at,
+            })
+        });
+        Box::new(feed)
+    }
+
+    fn token_info() -> Arc<dyn TokenInfoFetching> {
+        let mut token_info = MockTokenInfoFetching::new();
+        token_info.expect_get_token_infos().returning(|tokens| {
+            tokens
+                .iter()
+                .map(|token| {
+                    let decimals = if token.to_low_u64_be() == 1 { 6 } else { 18 };
+                    (
+                        *token,
+                        TokenInfo {
+                            decimals: Some(decimals),
+                        },
+                    )
+                })
+                .collect()
+        });
+        Arc::new(token_info)
+    }
+
+    #[test]
+    fn converts_answer_to_price_of_smallest_units() {
+        // 1 USDC (6 decimals) = 0.0005 ETH with an answer of 8 decimals.
+        assert_eq!(
+            native_price_from_answer(I256::from(50_000), 8, 6).unwrap(),
+            BigRational::from_integer(500_000_000.into())
+        );
+        assert!(native_price_from_answer(I256::from(-1), 8, 6).is_err());
+        assert!(native_price_from_answer(I256::zero(), 8, 6).is_err());
+    }
+
+    #[tokio::test]
+    async fn estimates_prices_from_feeds() {
+        let native_token = H160::from_low_u64_be(0);
+        let now = now_in_epoch_seconds() as u64;
+        let estimator = ChainlinkPriceEstimator::new(
+            hashmap! {
+                H160::from_low_u64_be(1) => feed(50_000, now),
+                H160::from_low_u64_be(2) => feed(200_000_000, now),
+                H160::from_low_u64_be(3) => feed(50_000, now - 3600),
+            },
+            token_info(),
+            native_token,
+            Duration::from_secs(600),
+        );
+
+        let prices = estimator
+            .estimate_prices(
+                &[
+                    H160::from_low_u64_be(1),
+                    H160::from_low_u64_be(2),
+                    H160::from_low_u64_be(3),
+                    H160::from_low_u64_be(4),
+                    native_token,
+                ],
+                native_token,
+            )
+            .await;
+        assert_eq!(
+            prices[0].as_ref().unwrap(),
+            &BigRational::from_integer(500_000_000.into())
+        );
+        assert_eq!(
+            prices[1].as_ref().unwrap(),
+            &BigRational::from_integer(2.into())
+        );
+        assert!(prices[2].is_err());
+        assert!(prices[3].is_err());
+        assert_eq!(prices[4].as_ref().unwrap(), &num::one());
+
+        // Selling 1 unit of token 2 gets 2 native units, so buying them costs 1/2 unit.
+        let price = estimator
+            .estimate_price(
+                H160::from_low_u64_be(2),
+                native_token,
+                1.into(),
+                OrderKind::Buy,
+            )
+            .await
+            .unwrap();
+        assert_eq!(price, BigRational::new(1.into(), 2.into()));
+    }
+}
@@ -0,0 +1,226 @@
+use super::{PriceEstimating, PriceEstimationError};
+use anyhow::anyhow;
+use ethcontract::{H160, U256};
+use model::order::OrderKind;
+use num::BigRational;
+use std::sync::Arc;
+use strum::AsStaticRef;
+use strum_macros::AsStaticStr;
+
+/// The kind of estimator that provided a price.
+#[derive(AsStaticStr, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PriceSource {
+    /// Routing over AMM pools.
+    Baseline,
+    /// An on chain price feed.
+    Oracle,
+    /// The configured static price table.
+    Static,
+}
+
+pub trait PriceSourceMetrics: Send + Sync {
+    fn price_estimated(&self, source: PriceSource);
+}
+
+/// A price estimator that asks its inner estimators in order and uses the first price it gets.
+///
+/// Unsupported tokens are not retried with the next estimator, as they are tokens that must not be
+/// traded rather than ones the estimator has no price for.
+pub struct CompositePriceEstimator {
+    estimators: Vec<(PriceSource, Arc<dyn PriceEstimating>)>,
+    metrics: Arc<dyn PriceSourceMetrics>,
+}
+
+impl CompositePriceEstimator {
+    pub fn new(
+        estimators: Vec<(PriceSource, Arc<dyn PriceEstimating>)>,
+        metrics: Arc<dyn PriceSourceMetrics>,
+    ) -> Self {
+        Self {
+            estimators,
+            metrics,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceEstimating for CompositePriceEstimator {
+    async fn estimate_price(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        kind: OrderKind,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let mut first_error = None;
+        for (source, estimator) in &self.estimators {
+            match estimator
+                .estimate_price(sell_token, buy_token, amount, kind)
+                .await
+            {
+                Ok(price) => {
+                    tracing::debug!(
+                        "price for {:?} -> {:?} provided by {} estimator",
+                        sell_token,
+                        buy_token,
+                        source.as_static()
+                    );
+                    self.metrics.price_estimated(*source);
+                    return Ok(price);
+                }
+                Err(err @ PriceEstimationError::UnsupportedToken(_)) => return Err(err),
+                Err(err) => {
+                    tracing::debug!("{} price estimation failed: {:?}", source.as_static(), err);
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(|| anyhow!("no price estimators configured").into()))
+    }
+
+    async fn estimate_gas(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        kind: OrderKind,
+    ) -> Result<U256, PriceEstimationError> {
+        let mut first_error = None;
+        for (_, estimator) in &self.estimators {
+            match estimator
+                .estimate_gas(sell_token, buy_token, amount, kind)
+                .await
+            {
+                Ok(gas) => return Ok(gas),
+                Err(err @ PriceEstimationError::UnsupportedToken(_)) => return Err(err),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(|| anyhow!("no price estimators configured").into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        metrics::RecordingMetrics,
+        price_estimate::mocks::{FailingPriceEstimator, FakePriceEstimator},
+    };
+
+    impl PriceSourceMetrics for RecordingMetrics<PriceSource> {
+        fn price_estimated(&self, source: PriceSource) {
+            self.record(source);
+        }
+    }
+
+    struct UnsupportedTokenEstimator;
+
+    #[async_trait::async_trait]
+    impl PriceEstimating for UnsupportedTokenEstimator {
+        async fn estimate_price(
+            &self,
+            sell_token: H160,
+            _: H160,
+            _: U256,
+            _: OrderKind,
+        ) -> Result<BigRational, PriceEstimationError> {
+            Err(PriceEstimationError::UnsupportedToken(sell_token))
+        }
+
+        async fn estimate_gas(
+            &self,
+            sell_token: H160,
+            _: H160,
+            _: U256,
+            _: OrderKind,
+        ) -> Result<U256, PriceEstimationError> {
+            Err(PriceEstimationError::UnsupportedToken(sell_token))
+        }
+    }
+
+    fn dyn_estimator(estimator: impl PriceEstimating + 'static) -> Arc<dyn PriceEstimating> {
+        Arc::new(estimator)
+    }
+
+    fn price(value: i64) -> BigRational {
+        BigRational::from_integer(value.into())
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_next_source_and_records_it() {
+        let metrics = Arc::new(RecordingMetrics::default());
+        let estimator = CompositePriceEstimator::new(
+            vec![
+                (
+                    PriceSource::Baseline,
+                    dyn_estimator(FailingPriceEstimator()),
+                ),
+                (
+                    PriceSource::Oracle,
+                    dyn_estimator(FakePriceEstimator(price(2))),
+                ),
+                (
+                    PriceSource::Static,
+                    dyn_estimator(FakePriceEstimator(price(3))),
+                ),
+            ],
+            metrics.clone(),
+        );
+
+        let result = estimator
+            .estimate_price(H160::zero(), H160::zero(), 0.into(), OrderKind::Sell)
+            .await;
+        assert_eq!(result.unwrap(), price(2));
+        assert_eq!(metrics.recorded(), vec![PriceSource::Oracle]);
+
+        let gas = estimator
+            .estimate_gas(H160::zero(), H160::zero(), 1.into(), OrderKind::Sell)
+            .await;
+        assert_eq!(gas.unwrap(), 100_000.into());
+    }
+
+    #[tokio::test]
+    async fn does_not_fall_back_for_unsupported_tokens() {
+        let metrics = Arc::new(RecordingMetrics::default());
+        let estimator = CompositePriceEstimator::new(
+            vec![
+                (
+                    PriceSource::Baseline,
+                    dyn_estimator(UnsupportedTokenEstimator),
+                ),
+                (
+                    PriceSource::Static,
+                    dyn_estimator(FakePriceEstimator(price(3))),
+                ),
+            ],
+            metrics.clone(),
+        );
+
+        let result = estimator
+            .estimate_price(H160::zero(), H160::zero(), 0.into(), OrderKind::Sell)
+            .await;
+        assert!(matches!(
+            result,
+            Err(PriceEstimationError::UnsupportedToken(_))
+        ));
+        assert!(metrics.recorded().is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_first_error_if_all_sources_fail() {
+        let estimator = CompositePriceEstimator::new(
+            vec![(
+                PriceSource::Baseline,
+                dyn_estimator(FailingPriceEstimator()),
+            )],
+            Arc::new(RecordingMetrics::default()),
+        );
+        assert!(estimator
+            .estimate_price(H160::zero(), H160::zero(), 0.into(), OrderKind::Sell)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use super::{price_from_native_prices, PriceEstimating, PriceEstimationError};
+use crate::arguments::split_pair;
+use anyhow::{anyhow, Context, Result};
+use ethcontract::{H160, U256};
+use model::order::OrderKind;
+use num::BigRational;
+use std::collections::HashMap;
+
+/// A price estimator using a fixed table of prices in the native token. As the prices are spot
+/// prices the traded amount is ignored.
+pub struct StaticPriceEstimator {
+    /// The value of the smallest unit of each token in the smallest unit of the native token.
+    prices: HashMap<H160, BigRational>,
+    native_token: H160,
+}
+
+impl StaticPriceEstimator {
+    pub fn new(prices: HashMap<H160, BigRational>, native_token: H160) -> Self {
+        Self {
+            prices,
+            native_token,
+        }
+    }
+
+    fn native_price(&self, token: H160) -> Result<BigRational> {
+        if token == self.native_token {
+            return Ok(num::one());
+        }
+        self.prices
+            .get(&token)
+            .cloned()
+            .ok_or_else(|| anyhow!("no static price for token {:?}", token))
+    }
+}
+
+/// Parses a `token=price` pair, where the price is the value of the smallest unit of the token in
+/// wei.
+pub fn parse_static_price(s: &str) -> Result<(H160, BigRational)> {
+    let (token, price) = split_pair(s, '=')?;
+    let price = price.parse::<f64>().context("invalid price")?;
+    Ok((
+        token.parse().context("invalid token address")?,
+        BigRational::from_float(price).ok_or_else(|| anyhow!("price is not finite"))?,
+    ))
+}
+
+#[async_trait::async_trait]
+impl PriceEstimating for StaticPriceEstimator {
+    async fn estimate_price(
+        &self,
+        sell_token: H160,
+        buy_token: H160,
+        amount: U256,
+        _: OrderKind,
+    ) -> Result<BigRational, PriceEstimationError> {
+        let sell_price = self.native_price(sell_token)?;
+        let buy_price = self.native_price(buy_token)?;
+        Ok(price_from_native_prices(sell_price, buy_price, amount)?)
+    }
+
+    async fn estimate_gas(
+        &self,
+        _: H160,
+        _: H160,
+        _: U256,
+        _: OrderKind,
+    ) -> Result<U256, PriceEstimationError> {
+        Err(anyhow!("static prices can't estimate gas").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+
+    #[tokio::test]
+    async fn estimates_prices_from_table() {
+        let native_token = H160::from_low_u64_be(0);
+        let estimator = StaticPriceEstimator::new(
+            hashmap! { H160::from_low_u64_be(1) => BigRational::from_integer(4.into()) },
+            native_token,
+        );
+        let prices = estimator
+            .estimate_prices(
+                &[H160::from_low_u64_be(1), H160::from_low_u64_be(2)],
+                native_token,
+            )
+            .await;
+        assert_eq!(
+            prices[0].as_ref().unwrap(),
+            &BigRational::from_integer(4.into())
+        );
+        assert!(prices[1].is_err());
+    }
+
+    #[test]
+    fn parses_static_price() {
+        assert_eq!(
+            parse_static_price("0x0000000000000000000000000000000000000001=0.5").unwrap(),
+            (
+                H160::from_low_u64_be(1),
+                BigRational::new(1.into(), 2.into())
+            )
+        );
+        assert!(parse_static_price("0x0000000000000000000000000000000000000001").is_err());
+    }
+}
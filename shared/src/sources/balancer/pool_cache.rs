@@ -136,7 +136,16 @@ fn handle_results(results: Vec<FetchedWeightedPool>) -> Result<Vec<WeightedPool>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ethcontract_error;
+    use crate::{
+        ethcontract_error,
+        sources::balancer::{
+            info_fetching::MockPoolInfoFetching,
+            pool_init::{BalancerRegisteredPools, PoolInitializing},
+        },
+        transport::{create_env_test_transport, create_fixture_test_transport},
+    };
+    use hex_literal::hex;
+    use maplit::hashset;
 
     #[test]
     fn pool_fetcher_forwards_node_error() {
@@ -175,4 +184,86 @@ mod tests {
         ];
         assert_eq!(handle_results(results).unwrap().len(), 1);
     }
+
+    struct InitialPools(Vec<RegisteredWeightedPool>);
+
+    #[async_trait::async_trait]
+    impl PoolInitializing for InitialPools {
+        async fn initialize_pools(&self) -> Result<BalancerRegisteredPools> {
+            Ok(BalancerRegisteredPools {
+                weighted_2token_pools: self.0.clone(),
+                fetched_block_number: 12_500_000,
+                ..Default::default()
+            })
+        }
+    }
+
+    async fn fetch_bal_weth_pool(web3: Web3) -> Vec<WeightedPool> {
+        let bal = H160(hex!("ba100000625a3754423978a60c9317c58a424e3d"));
+        let weth = H160(hex!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"));
+        let pool = RegisteredWeightedPool {
+            pool_id: H256(hex!(
+                "5c6ee304399dbdb9c8ef030ab642b10820db8f56000200000000000000000014"
+            )),
+            pool_address: H160(hex!("5c6ee304399dbdb9c8ef030ab642b10820db8f56")),
+            tokens: vec![bal, weth],
+            normalized_weights: vec![
+                Bfp::from_wei(800_000_000_000_000_000u128.into()),
+                Bfp::from_wei(200_000_000_000_000_000u128.into()),
+            ],
+            scaling_exponents: vec![0, 0],
+            block_created: 12_272_146,
+        };
+        let pool_registry = BalancerPoolRegistry::new(
+            web3.clone(),
+            InitialPools(vec![pool.clone()]),
+            Arc::new(MockPoolInfoFetching::new()),
+        )
+        .await
+        .unwrap();
+        let reserve_fetcher = PoolReserveFetcher::new(Arc::new(pool_registry), web3)
+            .await
+            .unwrap();
+
+        let pools = reserve_fetcher
+            .fetch_values(hashset! {pool.pool_id}, Block::Number(12_500_000))
+            .await
+            .unwrap();
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].pool_id, pool.pool_id);
+        pools
+    }
+
+    // cargo test -p shared mainnet_pool_reserve_fetcher -- --nocapture --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn mainnet_pool_reserve_fetcher() {
+        let pools = fetch_bal_weth_pool(Web3::new(create_env_test_transport())).await;
+        println!("{:?}", pools[0]);
+    }
+
+    // The synthetic fixture contains scripted node responses for the mainnet addresses rather than
+    // recorded mainnet state.
+    #[tokio::test]
+    async fn synthetic_pool_reserve_fetcher() {
+        let pools = fetch_bal_weth_pool(Web3::new(create_fixture_test_transport(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/synthetic_pool_reserve_fetcher.json"
+        ))))
+        .await;
+        let bal = H160(hex!("ba100000625a3754423978a60c9317c58a424e3d"));
+        let weth = H160(hex!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"));
+        assert_eq!(
+            pools[0].swap_fee_percentage,
+            Bfp::from_wei(2_500_000_000_000_000u128.into())
+        );
+        assert_eq!(
+            pools[0].reserves[&bal].balance,
+            U256::from(14_523_987_650_123_456_789_012_345u128)
+        );
+        assert_eq!(
+            pools[0].reserves[&weth].balance,
+            U256::from(9_876_543_210_987_654_321_098u128)
+        );
+    }
 }
@@ -295,8 +295,15 @@ fn handle_results(results: Vec<FetchedPool>) -> Result<Vec<Pool>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{conversions::big_rational_to_float, ethcontract_error};
+    use crate::{
+        conversions::big_rational_to_float,
+        ethcontract_error,
+        sources::uniswap::pair_provider::UniswapPairProvider,
+        transport::{create_env_test_transport, create_fixture_test_transport},
+    };
     use assert_approx_eq::assert_approx_eq;
+    use hex_literal::hex;
+    use maplit::hashset;
 
     #[test]
     fn test_get_amounts_out() {
@@ -460,4 +467,51 @@ mod tests {
         ];
         assert_eq!(handle_results(results).unwrap().len(), 1);
     }
+
+    async fn fetch_usdc_weth_pool(web3: Web3) -> (TokenPair, Vec<Pool>) {
+        let pool_fetcher = PoolFetcher {
+            pair_provider: Arc::new(UniswapPairProvider {
+                factory: contracts::UniswapV2Factory::deployed(&web3).await.unwrap(),
+                chain_id: 1,
+            }),
+            web3,
+        };
+
+        let usdc = H160(hex!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"));
+        let weth = H160(hex!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"));
+        let pair = TokenPair::new(usdc, weth).unwrap();
+        let pools = pool_fetcher
+            .fetch(hashset! {pair}, Block::Number(12_000_000))
+            .await
+            .unwrap();
+        (pair, pools)
+    }
+
+    // cargo test -p shared mainnet_pool_fetcher -- --nocapture --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn mainnet_pool_fetcher() {
+        let (_, pools) = fetch_usdc_weth_pool(Web3::new(create_env_test_transport())).await;
+        assert_eq!(pools.len(), 1);
+        println!("{:?}", pools[0]);
+    }
+
+    // The synthetic fixture contains scripted node responses for the mainnet addresses rather than
+    // recorded mainnet state.
+    #[tokio::test]
+    async fn synthetic_pool_fetcher() {
+        let (pair, pools) =
+            fetch_usdc_weth_pool(Web3::new(create_fixture_test_transport(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/fixtures/synthetic_pool_fetcher.json"
+            ))))
+            .await;
+        assert_eq!(
+            pools,
+            vec![Pool::uniswap(
+                pair,
+                (48_123_456_789_012, 29_876_543_210_987_654_321_000)
+            )]
+        );
+    }
 }
@@ -31,6 +31,27 @@ pub async fn trace_many(requests: Vec<CallRequest>, web3: &Web3) -> Result<Vec<B
     serde_json::from_value(response).context("failed to decode trace_callMany response")
 }
 
+// Use the trace_call api https://openethereum.github.io/JSONRPC-trace-module#trace_call to
+// simulate a single call request at the specified block.
+// Err if communication with the node failed.
+pub async fn trace_call(
+    request: CallRequest,
+    block: BlockNumber,
+    web3: &Web3,
+) -> Result<BlockTrace> {
+    let transport = web3.transport();
+    let params = vec![
+        serde_json::to_value(request)?,
+        serde_json::to_value(vec![TraceType::Trace])?,
+        serde_json::to_value(block)?,
+    ];
+    let response = transport
+        .execute("trace_call", params)
+        .await
+        .context("trace_call failed")?;
+    serde_json::from_value(response).context("failed to decode trace_call response")
+}
+
 // Check the return value of trace_many for whether all top level transactions succeeded (did not
 // revert).
 // Err if the response is missing trace data.
@@ -1,14 +1,18 @@
+pub mod caching;
 pub mod dummy;
+pub mod failover;
 pub mod http;
 pub mod instrumented;
 pub mod mock;
+pub mod recording;
 
 use self::{
     http::HttpTransport,
     instrumented::{MetricTransport, TransportMetrics},
+    recording::{RecordingTransport, ReplayTransport},
 };
-use ethcontract::web3::Transport;
-use std::{convert::TryInto as _, sync::Arc, time::Duration};
+use ethcontract::{dyns::DynTransport, web3::Transport};
+use std::{convert::TryInto as _, path::Path, sync::Arc, time::Duration};
 
 /// Convenience method to create our standard instrumented transport
 pub fn create_instrumented_transport<T>(
@@ -25,21 +29,38 @@ where
 struct NoopTransportMetrics;
 impl TransportMetrics for NoopTransportMetrics {
     fn report_query(&self, _: &str, _: Duration) {}
+    fn report_node_health(&self, _: &str, _: bool) {}
 }
 
 /// Convenience method to create a compatible transport without metrics (noop)
-pub fn create_test_transport(url: &str) -> MetricTransport<HttpTransport>
+pub fn create_test_transport(url: &str) -> DynTransport
 where
 {
     let transport = HttpTransport::new(url.try_into().unwrap());
-    MetricTransport::new(transport, Arc::new(NoopTransportMetrics))
+    DynTransport::new(MetricTransport::new(
+        transport,
+        Arc::new(NoopTransportMetrics),
+    ))
 }
 
 /// Like above but takes url from the environment NODE_URL.
-pub fn create_env_test_transport() -> MetricTransport<HttpTransport>
+pub fn create_env_test_transport() -> DynTransport
 where
 {
     let env = std::env::var("NODE_URL").unwrap();
-    let transport = HttpTransport::new(env.parse().unwrap());
-    MetricTransport::new(transport, Arc::new(NoopTransportMetrics))
+    create_test_transport(&env)
+}
+
+/// Creates a transport replaying the requests recorded in the fixture file. If the fixture does not
+/// exist yet, requests are sent to the node at the environment NODE_URL and recorded to it.
+pub fn create_fixture_test_transport(fixture: impl AsRef<Path>) -> DynTransport {
+    let fixture = fixture.as_ref();
+    if fixture.exists() {
+        DynTransport::new(ReplayTransport::load(fixture).unwrap())
+    } else {
+        DynTransport::new(RecordingTransport::new(
+            create_env_test_transport(),
+            fixture,
+        ))
+    }
 }
@@ -0,0 +1,290 @@
+//! Web3 transport caching the responses of requests whose result is determined by a concrete block.
+
+use ethcontract::{
+    futures::future::{self, BoxFuture, FutureExt as _},
+    jsonrpc::{Call, MethodCall, Params},
+    web3::{self, BatchTransport, RequestId, Transport},
+};
+use lru::LruCache;
+use serde_json::Value;
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::{Arc, Mutex},
+};
+
+pub trait TransportCacheMetrics: Send + Sync {
+    fn request_looked_up(&self, method: &str, cache_hit: bool);
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Key {
+    method: String,
+    /// The serialized parameters, as JSON values are not hashable.
+    params: String,
+}
+
+/// A transport memoizing the responses to `eth_call`, `eth_getCode` and `eth_getBlockByNumber`
+/// requests for a concrete block number. Requests for tags like `latest` or `pending` as well as
+/// failed requests are never cached.
+///
+/// Stack it on top of a `MetricTransport` so that only requests that reach the node are measured.
+#[derive(Clone)]
+pub struct CachingTransport<T> {
+    inner: T,
+    cache: Arc<Mutex<LruCache<Key, Value>>>,
+    metrics: Arc<dyn TransportCacheMetrics>,
+}
+
+impl<T> CachingTransport<T> {
+    /// Creates a transport caching at most `cache_size` responses, evicting the least recently used
+    /// ones first.
+    pub fn new(inner: T, cache_size: usize, metrics: Arc<dyn TransportCacheMetrics>) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Mutex::new(LruCache::new(cache_size))),
+            metrics,
+        }
+    }
+
+    /// Looks up the response to the call. Returns the cache key for calls that can be cached but
+    /// were not.
+    fn lookup(&self, call: &Call) -> Result<Value, Option<Key>> {
+        let key = cache_key(call).ok_or(None)?;
+        let cached = self.cache.lock().unwrap().get(&key).cloned();
+        self.metrics
+            .request_looked_up(&key.method, cached.is_some());
+        cached.ok_or(Some(key))
+    }
+}
+
+/// The position of the block parameter for the methods whose responses can be cached.
+fn block_param_index(method: &str) -> Option<usize> {
+    match method {
+        "eth_call" | "eth_getCode" => Some(1),
+        "eth_getBlockByNumber" => Some(0),
+        _ => None,
+    }
+}
+
+fn cache_key(call: &Call) -> Option<Key> {
+    let (method, params) = match call {
+        Call::MethodCall(MethodCall {
+            method,
+            params: Params::Array(params),
+            ..
+        }) => (method, params),
+        _ => return None,
+    };
+    // Block numbers are hex encoded quantities while tags are plain strings like "latest".
+    match params.get(block_param_index(method)?)? {
+        Value::String(block) if block.starts_with("0x") => (),
+        _ => return None,
+    }
+    Some(Key {
+        method: method.clone(),
+        params: serde_json::to_string(params).ok()?,
+    })
+}
+
+/// Inserts the response into the cache if it can be cached.
+fn insert(cache: &Mutex<LruCache<Key, Value>>, key: Option<Key>, result: &web3::Result<Value>) {
+    match (key, result) {
+        // Blocks that don't exist yet are returned as `null`.
+        (Some(key), Ok(value)) if !value.is_null() => {
+            cache.lock().unwrap().put(key, value.clone());
+        }
+        _ => (),
+    }
+}
+
+impl<T> Debug for CachingTransport<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("CachingTransport")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<T> Transport for CachingTransport<T>
+where
+    T: Transport,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, call: Call) -> Self::Out {
+        let key = match self.lookup(&call) {
+            Ok(value) => return future::ready(Ok(value)).boxed(),
+            Err(key) => key,
+        };
+        let cache = self.cache.clone();
+        self.inner
+            .send(id, call)
+            .inspect(move |result| insert(&cache, key, result))
+            .boxed()
+    }
+}
+
+impl<T> BatchTransport for CachingTransport<T>
+where
+    T: BatchTransport,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        // Only the requests that are not cached are sent to the inner transport. Their responses
+        // are merged back into the position of the request in the batch.
+        let mut responses = Vec::new();
+        let mut misses = Vec::new();
+        for (id, call) in requests {
+            match self.lookup(&call) {
+                Ok(value) => responses.push(Some(Ok(value))),
+                Err(key) => {
+                    misses.push((responses.len(), key, (id, call)));
+                    responses.push(None);
+                }
+            }
+        }
+        if misses.is_empty() {
+            return future::ready(Ok(responses.into_iter().flatten().collect())).boxed();
+        }
+
+        let cache = self.cache.clone();
+        let (positions, requests): (Vec<_>, Vec<_>) = misses
+            .into_iter()
+            .map(|(position, key, request)| ((position, key), request))
+            .unzip();
+        self.inner
+            .send_batch(requests)
+            .map(move |results| {
+                let results = results?;
+                if results.len() != positions.len() {
+                    return Err(web3::Error::InvalidResponse(format!(
+                        "expected {} responses but got {}",
+                        positions.len(),
+                        results.len()
+                    )));
+                }
+                for ((position, key), result) in positions.into_iter().zip(results) {
+                    insert(&cache, key, &result);
+                    responses[position] = Some(result);
+                }
+                Ok(responses.into_iter().flatten().collect())
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::RecordingMetrics, transport::mock::MockTransport};
+    use serde_json::json;
+
+    impl TransportCacheMetrics for RecordingMetrics<(String, bool)> {
+        fn request_looked_up(&self, method: &str, cache_hit: bool) {
+            self.record((method.to_string(), cache_hit));
+        }
+    }
+
+    fn prepare_call(transport: &impl Transport, method: &str, params: Vec<Value>) -> Call {
+        transport.prepare(method, params).1
+    }
+
+    #[test]
+    fn only_caches_requests_for_concrete_blocks() {
+        let transport = MockTransport::new();
+        let call = |method, params| prepare_call(&transport, method, params);
+        assert!(cache_key(&call("eth_call", vec![json!({}), json!("0x1")])).is_some());
+        assert!(cache_key(&call("eth_getCode", vec![json!("0x00"), json!("0x1")])).is_some());
+        assert!(cache_key(&call(
+            "eth_getBlockByNumber",
+            vec![json!("0x1"), json!(false)]
+        ))
+        .is_some());
+        assert!(cache_key(&call("eth_call", vec![json!({}), json!("latest")])).is_none());
+        assert!(cache_key(&call("eth_call", vec![json!({})])).is_none());
+        assert!(cache_key(&call(
+            "eth_getBlockByNumber",
+            vec![json!("pending"), json!(false)]
+        ))
+        .is_none());
+        assert!(cache_key(&call("eth_blockNumber", vec![])).is_none());
+    }
+
+    #[tokio::test]
+    async fn caches_single_requests() {
+        let mock = MockTransport::new();
+        mock.mock()
+            .expect_execute()
+            .times(2)
+            .returning(|_, _| Ok(json!("0x42")));
+        let metrics = Arc::new(RecordingMetrics::default());
+        let transport = CachingTransport::new(mock, 10, metrics.clone());
+
+        for _ in 0..2 {
+            let result = transport
+                .execute("eth_call", vec![json!({}), json!("0x1")])
+                .await;
+            assert_eq!(result.unwrap(), json!("0x42"));
+        }
+        transport
+            .execute("eth_call", vec![json!({}), json!("latest")])
+            .await
+            .unwrap();
+        assert_eq!(
+            metrics.recorded(),
+            vec![
+                ("eth_call".to_string(), false),
+                ("eth_call".to_string(), true)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_sends_uncached_batch_requests() {
+        let mock = MockTransport::new();
+        mock.mock()
+            .expect_execute_batch()
+            .times(1)
+            .withf(|requests| requests.len() == 2)
+            .returning(|_| Ok(vec![Ok(json!("0x1")), Ok(json!("0x2"))]));
+        mock.mock()
+            .expect_execute_batch()
+            .times(1)
+            .withf(|requests| {
+                *requests == [("eth_call".to_string(), vec![json!({}), json!("latest")])]
+            })
+            .returning(|_| Ok(vec![Ok(json!("0x3"))]));
+        let transport = CachingTransport::new(
+            mock,
+            10,
+            Arc::new(RecordingMetrics::<(String, bool)>::default()),
+        );
+
+        let batch = |transport: &CachingTransport<MockTransport>| {
+            transport.send_batch(vec![
+                transport.prepare("eth_call", vec![json!({}), json!("0x1")]),
+                transport.prepare("eth_call", vec![json!({}), json!("latest")]),
+            ])
+        };
+        let first = batch(&transport).await.unwrap();
+        assert_eq!(first[0].as_ref().unwrap(), &json!("0x1"));
+        assert_eq!(first[1].as_ref().unwrap(), &json!("0x2"));
+        let second = batch(&transport).await.unwrap();
+        assert_eq!(second[0].as_ref().unwrap(), &json!("0x1"));
+        assert_eq!(second[1].as_ref().unwrap(), &json!("0x3"));
+    }
+}
@@ -0,0 +1,471 @@
+//! Transport distributing requests over several nodes.
+//!
+//! Requests are sent to the first available node in the configured order. A node is unavailable
+//! while its last request failed or while its block number lags behind the most advanced node.
+//! Idempotent requests are retried with exponential backoff on the next node in order of
+//! preference.
+
+use super::{http::HttpTransport, instrumented::TransportMetrics};
+use crate::maintenance::Maintaining;
+use derivative::Derivative;
+use ethcontract::jsonrpc::types::{Call, Value};
+use ethcontract::web3::{error, BatchTransport, Error as Web3Error, RequestId, Transport};
+use futures::future::{BoxFuture, FutureExt as _};
+use reqwest::Url;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use web3::types::U64;
+
+/// Prefixes of methods that change state on the node or the chain and must not be retried.
+const NON_IDEMPOTENT_METHOD_PREFIXES: &[&str] =
+    &["eth_send", "eth_sign", "personal_", "evm_", "miner_"];
+
+#[derive(Clone, Debug)]
+pub struct FailoverConfig {
+    /// How often idempotent requests are retried, possibly on a different node.
+    pub max_retries: usize,
+    /// The delay before the first retry. It doubles with every further retry.
+    pub retry_backoff: Duration,
+    /// Nodes more than this many blocks behind the most advanced node are only used if no other
+    /// node is available.
+    pub max_block_lag: u64,
+    /// The maximum number of requests per second sent to a single node. Further requests are
+    /// delayed.
+    pub max_requests_per_second: Option<f64>,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(100),
+            max_block_lag: 5,
+            max_requests_per_second: None,
+        }
+    }
+}
+
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct FailoverTransport<T = HttpTransport> {
+    inner: Arc<Inner<T>>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Inner<T> {
+    nodes: Vec<Node<T>>,
+    config: FailoverConfig,
+    #[derivative(Debug = "ignore")]
+    metrics: Arc<dyn TransportMetrics>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    label: String,
+    transport: T,
+    healthy: AtomicBool,
+    /// The last block number reported by the node or 0 if it is unknown.
+    block_number: AtomicU64,
+    rate_limiter: Option<RateLimiter>,
+}
+
+#[derive(Debug)]
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1. / requests_per_second),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Reserves the next free slot for a request and returns when it starts.
+    fn reserve(&self) -> Instant {
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let slot = (*next_slot).max(Instant::now());
+        *next_slot = slot + self.interval;
+        slot
+    }
+}
+
+impl FailoverTransport<HttpTransport> {
+    /// Creates a transport for the nodes at the given urls, in order of preference.
+    pub fn http(
+        urls: Vec<Url>,
+        config: FailoverConfig,
+        metrics: Arc<dyn TransportMetrics>,
+    ) -> Self {
+        let nodes = urls
+            .into_iter()
+            .map(|url| {
+                // Only the host is used as label as the url could contain API keys.
+                let label = url.host_str().unwrap_or_default().to_string();
+                (label, HttpTransport::new(url))
+            })
+            .collect();
+        Self::new(nodes, config, metrics)
+    }
+}
+
+impl<T> FailoverTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    /// Creates a transport for the labeled nodes, in order of preference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no nodes.
+    pub fn new(
+        nodes: Vec<(String, T)>,
+        config: FailoverConfig,
+        metrics: Arc<dyn TransportMetrics>,
+    ) -> Self {
+        assert!(
+            !nodes.is_empty(),
+            "failover transport needs at least one node"
+        );
+        let nodes = nodes
+            .into_iter()
+            .map(|(label, transport)| {
+                metrics.report_node_health(&label, true);
+                Node {
+                    label,
+                    transport,
+                    healthy: AtomicBool::new(true),
+                    block_number: AtomicU64::new(0),
+                    rate_limiter: config.max_requests_per_second.map(RateLimiter::new),
+                }
+            })
+            .collect();
+        Self {
+            inner: Arc::new(Inner {
+                nodes,
+                config,
+                metrics,
+            }),
+        }
+    }
+
+    /// Queries the block number of every node, which determines which nodes are lagging, and
+    /// updates the health of the nodes.
+    pub async fn update_block_numbers(&self) {
+        futures::future::join_all(self.inner.nodes.iter().map(|node| async move {
+            let block_number = node
+                .transport
+                .execute("eth_blockNumber", vec![])
+                .await
+                .and_then(|value| {
+                    serde_json::from_value::<U64>(value)
+                        .map_err(|err| Web3Error::Decoder(err.to_string()))
+                });
+            match block_number {
+                Ok(block_number) => {
+                    node.block_number
+                        .store(block_number.as_u64(), Ordering::SeqCst);
+                    self.inner.set_health(node, true);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "failed to get block number of node {}: {:?}",
+                        node.label,
+                        err
+                    );
+                    self.inner.set_health(node, false);
+                }
+            }
+        }))
+        .await;
+    }
+}
+
+impl<T> Inner<T> {
+    fn set_health(&self, node: &Node<T>, healthy: bool) {
+        if node.healthy.swap(healthy, Ordering::SeqCst) != healthy {
+            tracing::info!(
+                "node {} is now {}",
+                node.label,
+                if healthy { "healthy" } else { "unhealthy" }
+            );
+            self.metrics.report_node_health(&node.label, healthy);
+        }
+    }
+
+    /// Returns the nodes with the available ones first, each group in the configured order.
+    fn nodes_by_preference(&self) -> Vec<&Node<T>> {
+        let max_block_number = self
+            .nodes
+            .iter()
+            .map(|node| node.block_number.load(Ordering::SeqCst))
+            .max()
+            .unwrap_or_default();
+        let is_available = |node: &Node<T>| {
+            let block_number = node.block_number.load(Ordering::SeqCst);
+            let lagging =
+                block_number != 0 && max_block_number - block_number > self.config.max_block_lag;
+            node.healthy.load(Ordering::SeqCst) && !lagging
+        };
+        let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+        nodes.sort_by_key(|node| !is_available(node));
+        nodes
+    }
+}
+
+/// Sends the request to the preferred node, retrying it on the next ones if it is idempotent.
+async fn execute<T, R, F>(inner: Arc<Inner<T>>, idempotent: bool, request: F) -> error::Result<R>
+where
+    F: Fn(&T) -> BoxFuture<'static, error::Result<R>>,
+{
+    let nodes = inner.nodes_by_preference();
+    let attempts = if idempotent {
+        inner.config.max_retries + 1
+    } else {
+        1
+    };
+    let mut backoff = inner.config.retry_backoff;
+    let mut attempt = 0;
+    loop {
+        let node = nodes[attempt % nodes.len()];
+        if let Some(rate_limiter) = &node.rate_limiter {
+            tokio::time::sleep_until(rate_limiter.reserve().into()).await;
+        }
+        let result = request(&node.transport).await;
+        match &result {
+            // RPC errors are answers of a working node, like reverted calls.
+            Ok(_) | Err(Web3Error::Rpc(_)) => {
+                inner.set_health(node, true);
+                return result;
+            }
+            Err(err) => {
+                tracing::warn!("request to node {} failed: {:?}", node.label, err);
+                inner.set_health(node, false);
+            }
+        }
+        attempt += 1;
+        if attempt >= attempts {
+            return result;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+fn is_idempotent(call: &Call) -> bool {
+    match call {
+        Call::MethodCall(call) => !NON_IDEMPOTENT_METHOD_PREFIXES
+            .iter()
+            .any(|prefix| call.method.starts_with(prefix)),
+        Call::Notification(_) | Call::Invalid { .. } => false,
+    }
+}
+
+impl<T> Transport for FailoverTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Out = BoxFuture<'static, error::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.nodes[0].transport.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let idempotent = is_idempotent(&request);
+        execute(self.inner.clone(), idempotent, move |transport: &T| {
+            transport.send(id, request.clone()).boxed()
+        })
+        .boxed()
+    }
+}
+
+impl<T> BatchTransport for FailoverTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, error::Result<Vec<error::Result<Value>>>>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let requests = requests.into_iter().collect::<Vec<_>>();
+        let idempotent = requests.iter().all(|(_, call)| is_idempotent(call));
+        execute(self.inner.clone(), idempotent, move |transport: &T| {
+            transport.send_batch(requests.clone()).boxed()
+        })
+        .boxed()
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Maintaining for FailoverTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    async fn run_maintenance(&self) -> anyhow::Result<()> {
+        self.update_block_numbers().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::RecordingMetrics, transport::mock::MockTransport};
+    use serde_json::json;
+
+    impl TransportMetrics for RecordingMetrics<(String, bool)> {
+        fn report_query(&self, _: &str, _: Duration) {}
+
+        fn report_node_health(&self, node: &str, healthy: bool) {
+            self.record((node.to_string(), healthy));
+        }
+    }
+
+    fn config() -> FailoverConfig {
+        FailoverConfig {
+            retry_backoff: Duration::from_millis(0),
+            ..Default::default()
+        }
+    }
+
+    fn node(label: &str) -> (String, MockTransport) {
+        (label.to_string(), MockTransport::new())
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_node_and_prefers_it() {
+        let nodes = vec![node("a"), node("b")];
+        nodes[0]
+            .1
+            .mock()
+            .expect_execute()
+            .times(1)
+            .returning(|_, _| Err(Web3Error::Transport("down".to_string())));
+        nodes[1]
+            .1
+            .mock()
+            .expect_execute()
+            .times(2)
+            .returning(|_, _| Ok(json!("0x1")));
+        let metrics = Arc::new(RecordingMetrics::default());
+        let transport = FailoverTransport::new(nodes, config(), metrics.clone());
+
+        assert_eq!(
+            transport.execute("eth_chainId", vec![]).await.unwrap(),
+            json!("0x1")
+        );
+        // The failed node is not asked again.
+        assert_eq!(
+            transport.execute("eth_chainId", vec![]).await.unwrap(),
+            json!("0x1")
+        );
+        assert_eq!(
+            metrics.recorded(),
+            vec![
+                ("a".to_string(), true),
+                ("b".to_string(), true),
+                ("a".to_string(), false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_idempotent_requests() {
+        let nodes = vec![node("a"), node("b")];
+        nodes[0]
+            .1
+            .mock()
+            .expect_execute()
+            .times(1)
+            .returning(|_, _| Err(Web3Error::Transport("down".to_string())));
+        nodes[1].1.mock().expect_execute().times(0);
+        let transport = FailoverTransport::new(
+            nodes,
+            config(),
+            Arc::new(RecordingMetrics::<(String, bool)>::default()),
+        );
+
+        assert!(transport
+            .execute("eth_sendRawTransaction", vec![])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn does_not_fail_over_on_rpc_errors() {
+        let nodes = vec![node("a"), node("b")];
+        nodes[0]
+            .1
+            .mock()
+            .expect_execute()
+            .times(1)
+            .returning(|_, _| Err(Web3Error::Rpc(ethcontract::jsonrpc::Error::internal_error())));
+        nodes[1].1.mock().expect_execute().times(0);
+        let transport = FailoverTransport::new(
+            nodes,
+            config(),
+            Arc::new(RecordingMetrics::<(String, bool)>::default()),
+        );
+
+        assert!(transport.execute("eth_call", vec![]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn avoids_lagging_nodes() {
+        let nodes = vec![node("a"), node("b")];
+        nodes[0]
+            .1
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_blockNumber")
+            .returning(|_, _| Ok(json!("0x1")));
+        nodes[1]
+            .1
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_blockNumber")
+            .returning(|_, _| Ok(json!("0x10")));
+        nodes[1]
+            .1
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_chainId")
+            .times(1)
+            .returning(|_, _| Ok(json!("0x1")));
+        let transport = FailoverTransport::new(
+            nodes,
+            config(),
+            Arc::new(RecordingMetrics::<(String, bool)>::default()),
+        );
+
+        transport.update_block_numbers().await;
+        assert!(transport.execute("eth_chainId", vec![]).await.is_ok());
+    }
+
+    #[test]
+    fn rate_limiter_spaces_requests() {
+        let rate_limiter = RateLimiter::new(10.);
+        let first = rate_limiter.reserve();
+        let second = rate_limiter.reserve();
+        assert_eq!(second - first, Duration::from_millis(100));
+    }
+}
@@ -10,6 +10,7 @@ use std::{
 
 pub trait TransportMetrics: Send + Sync {
     fn report_query(&self, label: &str, elapsed: Duration);
+    fn report_node_health(&self, node: &str, healthy: bool);
 }
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
@@ -0,0 +1,476 @@
+//! Web3 transports for recording JSON-RPC traffic to a fixture file and replaying it, so that tests
+//! against captured node data can run offline and deterministically.
+
+use anyhow::{Context, Result};
+use ethcontract::{
+    futures::future::{self, BoxFuture, FutureExt as _, Ready},
+    jsonrpc::{self, Call, Id, MethodCall, Params},
+    web3::{self, BatchTransport, RequestId, Transport},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug, Formatter},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// A single JSON-RPC request without its id, which differs between runs.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Request {
+    pub method: String,
+    pub params: Vec<Value>,
+}
+
+/// The outcome of a request.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Response {
+    Result(Value),
+    /// An error response from the node.
+    RpcError(jsonrpc::Error),
+    /// Any other error, like the node being unreachable.
+    Error(String),
+}
+
+/// The responses to a batch of requests or the error of the whole batch.
+pub type BatchResponses = Result<Vec<Response>, String>;
+
+/// A recorded request or batch of requests together with the responses.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Entry {
+    Single {
+        request: Request,
+        response: Response,
+    },
+    Batch {
+        requests: Vec<Request>,
+        responses: BatchResponses,
+    },
+}
+
+impl Response {
+    fn from_result(result: &web3::Result<Value>) -> Self {
+        match result {
+            Ok(value) => Response::Result(value.clone()),
+            Err(web3::Error::Rpc(err)) => Response::RpcError(err.clone()),
+            Err(err) => Response::Error(err.to_string()),
+        }
+    }
+
+    fn into_result(self) -> web3::Result<Value> {
+        match self {
+            Response::Result(value) => Ok(value),
+            Response::RpcError(err) => Err(web3::Error::Rpc(err)),
+            Response::Error(err) => Err(web3::Error::Transport(err)),
+        }
+    }
+}
+
+/// A transport forwarding requests to an inner transport and appending every request and its
+/// response to a fixture file.
+///
+/// The whole fixture is rewritten after every response so that it is complete even if the test
+/// does not shut down cleanly.
+#[derive(Clone)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    fixture: Arc<Fixture>,
+}
+
+struct Fixture {
+    path: PathBuf,
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Fixture {
+    fn record(&self, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(entry);
+        let result = serde_json::to_string_pretty(&*entries)
+            .context("failed to serialize fixture")
+            .and_then(|json| fs::write(&self.path, json).context("failed to write fixture"));
+        if let Err(err) = result {
+            tracing::error!(
+                "failed to record fixture {}: {:?}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+impl<T> RecordingTransport<T> {
+    /// Creates a transport recording to the file at `path`, replacing any existing fixture.
+    pub fn new(inner: T, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            fixture: Arc::new(Fixture {
+                path: path.into(),
+                entries: Default::default(),
+            }),
+        }
+    }
+}
+
+impl<T> Debug for RecordingTransport<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("RecordingTransport")
+            .field("path", &self.fixture.path)
+            .finish()
+    }
+}
+
+impl<T> Transport for RecordingTransport<T>
+where
+    T: Transport,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, call: Call) -> Self::Out {
+        let request = match extract_request(&call) {
+            Ok(request) => request,
+            Err(err) => return future::err(err).boxed(),
+        };
+        let fixture = self.fixture.clone();
+        self.inner
+            .send(id, call)
+            .inspect(move |result| {
+                fixture.record(Entry::Single {
+                    request,
+                    response: Response::from_result(result),
+                })
+            })
+            .boxed()
+    }
+}
+
+impl<T> BatchTransport for RecordingTransport<T>
+where
+    T: BatchTransport,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let calls = requests.into_iter().collect::<Vec<_>>();
+        let requests = match calls
+            .iter()
+            .map(|(_, call)| extract_request(call))
+            .collect::<web3::Result<_>>()
+        {
+            Ok(requests) => requests,
+            Err(err) => return future::err(err).boxed(),
+        };
+        let fixture = self.fixture.clone();
+        self.inner
+            .send_batch(calls)
+            .inspect(move |result| {
+                let responses = match result {
+                    Ok(results) => Ok(results.iter().map(Response::from_result).collect()),
+                    Err(err) => Err(err.to_string()),
+                };
+                fixture.record(Entry::Batch {
+                    requests,
+                    responses,
+                })
+            })
+            .boxed()
+    }
+}
+
+/// A transport serving the responses recorded by a `RecordingTransport`.
+///
+/// Requests are matched by method and parameters. Repeated identical requests get the recorded
+/// responses in the order they were recorded, and the last one once all others were served.
+/// Batches match regardless of the order of their requests because they are often built from hash
+/// sets. Requests that were never recorded fail with a transport error.
+#[derive(Clone, Default)]
+pub struct ReplayTransport(Arc<ReplayInner>);
+
+#[derive(Default)]
+struct ReplayInner {
+    singles: Mutex<Recorded<Request, Response>>,
+    batches: Mutex<Recorded<Vec<Request>, BatchResponses>>,
+    current_id: AtomicUsize,
+}
+
+impl ReplayTransport {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        let mut singles = Recorded::default();
+        let mut batches = Recorded::default();
+        for entry in entries {
+            match entry {
+                Entry::Single { request, response } => singles.push(request, response),
+                Entry::Batch {
+                    requests,
+                    responses,
+                } => batches.push(requests, responses),
+            }
+        }
+        Self(Arc::new(ReplayInner {
+            singles: Mutex::new(singles),
+            batches: Mutex::new(batches),
+            current_id: Default::default(),
+        }))
+    }
+
+    /// Loads the fixture file written by a `RecordingTransport`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("failed to read fixture {}", path.display()))?;
+        let entries = serde_json::from_str(&json)
+            .with_context(|| format!("failed to parse fixture {}", path.display()))?;
+        Ok(Self::new(entries))
+    }
+}
+
+/// Recorded values grouped by request. This is a list instead of a map because JSON values are
+/// not hashable.
+struct Recorded<K, V>(Vec<(K, VecDeque<V>)>);
+
+impl<K, V> Default for Recorded<K, V> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<K: PartialEq, V: Clone> Recorded<K, V> {
+    fn push(&mut self, key: K, value: V) {
+        match self.0.iter_mut().find(|(key_, _)| *key_ == key) {
+            Some((_, values)) => values.push_back(value),
+            None => self.0.push((key, vec![value].into())),
+        }
+    }
+
+    /// Removes the next recorded value for the first key matching the predicate, keeping the last
+    /// one for further requests.
+    fn next(&mut self, matches: impl Fn(&K) -> bool) -> Option<(&K, V)> {
+        let (key, values) = self.0.iter_mut().find(|(key, _)| matches(key))?;
+        let value = if values.len() > 1 {
+            values.pop_front()?
+        } else {
+            values.front().cloned()?
+        };
+        Some((key, value))
+    }
+}
+
+impl Debug for ReplayTransport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("ReplayTransport").finish()
+    }
+}
+
+impl Transport for ReplayTransport {
+    type Out = Ready<web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        let id = self.0.current_id.fetch_add(1, Ordering::SeqCst);
+        (
+            id,
+            Call::MethodCall(MethodCall {
+                jsonrpc: None,
+                method: method.to_owned(),
+                params: Params::Array(params),
+                id: Id::Num(id as u64),
+            }),
+        )
+    }
+
+    fn send(&self, _: RequestId, call: Call) -> Self::Out {
+        let request = match extract_request(&call) {
+            Ok(request) => request,
+            Err(err) => return future::err(err),
+        };
+        let mut singles = self.0.singles.lock().unwrap();
+        let response = singles.next(|recorded| *recorded == request);
+        future::ready(match response {
+            Some((_, response)) => response.into_result(),
+            None => Err(web3::Error::Transport(format!(
+                "no recorded response for {:?}",
+                request
+            ))),
+        })
+    }
+}
+
+impl BatchTransport for ReplayTransport {
+    type Batch = Ready<web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<T>(&self, requests: T) -> Self::Batch
+    where
+        T: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let requests = match requests
+            .into_iter()
+            .map(|(_, call)| extract_request(&call))
+            .collect::<web3::Result<Vec<_>>>()
+        {
+            Ok(requests) => requests,
+            Err(err) => return future::err(err),
+        };
+        let mut batches = self.0.batches.lock().unwrap();
+        let responses = batches.next(|recorded| recorded_indices(recorded, &requests).is_some());
+        future::ready(match responses {
+            Some((recorded, Ok(responses))) => Ok(recorded_indices(recorded, &requests)
+                .expect("batch matched")
+                .into_iter()
+                .map(|index| responses[index].clone().into_result())
+                .collect()),
+            Some((_, Err(err))) => Err(web3::Error::Transport(err)),
+            None => Err(web3::Error::Transport(format!(
+                "no recorded response for batch {:?}",
+                requests
+            ))),
+        })
+    }
+}
+
+/// Returns for every request the index of the same request in the recorded batch, or `None` if the
+/// batches don't consist of the same requests.
+fn recorded_indices(recorded: &[Request], requests: &[Request]) -> Option<Vec<usize>> {
+    if recorded.len() != requests.len() {
+        return None;
+    }
+    let mut used = vec![false; recorded.len()];
+    requests
+        .iter()
+        .map(|request| {
+            let index = (0..recorded.len()).find(|&i| !used[i] && recorded[i] == *request)?;
+            used[index] = true;
+            Some(index)
+        })
+        .collect()
+}
+
+fn extract_request(call: &Call) -> web3::Result<Request> {
+    Ok(match call {
+        Call::MethodCall(MethodCall {
+            method,
+            params: Params::Array(params),
+            ..
+        }) => Request {
+            method: method.clone(),
+            params: params.clone(),
+        },
+        Call::MethodCall(MethodCall { method, .. }) => Request {
+            method: method.clone(),
+            params: Vec::new(),
+        },
+        _ => {
+            return Err(web3::Error::Transport(format!(
+                "unexpected call {:?}",
+                call
+            )))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use serde_json::json;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_requests() {
+        let mock = MockTransport::new();
+        let mut responses = vec![json!("0x1"), json!("0x2")].into_iter();
+        mock.mock()
+            .expect_execute()
+            .times(2)
+            .returning(move |_, _| Ok(responses.next().unwrap()));
+        mock.mock().expect_execute_batch().times(1).returning(|_| {
+            Ok(vec![
+                Ok(json!(true)),
+                Err(web3::Error::Rpc(jsonrpc::Error::internal_error())),
+            ])
+        });
+
+        let path = fixture_path("replays_recorded_requests");
+        let recording = RecordingTransport::new(mock, &path);
+        recording.execute("eth_blockNumber", vec![]).await.unwrap();
+        recording.execute("eth_blockNumber", vec![]).await.unwrap();
+        recording
+            .send_batch(vec![
+                recording.prepare("eth_call", vec![json!({ "to": "0x00" })]),
+                recording.prepare("eth_call", vec![json!({ "to": "0x01" })]),
+            ])
+            .await
+            .unwrap();
+
+        let replay = ReplayTransport::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        for expected in &["0x1", "0x2", "0x2"] {
+            assert_eq!(
+                replay.execute("eth_blockNumber", vec![]).await.unwrap(),
+                json!(expected)
+            );
+        }
+        let responses = replay
+            .send_batch(vec![
+                replay.prepare("eth_call", vec![json!({ "to": "0x00" })]),
+                replay.prepare("eth_call", vec![json!({ "to": "0x01" })]),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(responses[0].as_ref().unwrap(), &json!(true));
+        assert!(matches!(responses[1], Err(web3::Error::Rpc(_))));
+        let responses = replay
+            .send_batch(vec![
+                replay.prepare("eth_call", vec![json!({ "to": "0x01" })]),
+                replay.prepare("eth_call", vec![json!({ "to": "0x00" })]),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(responses[0], Err(web3::Error::Rpc(_))));
+        assert_eq!(responses[1].as_ref().unwrap(), &json!(true));
+    }
+
+    #[tokio::test]
+    async fn fails_unrecorded_requests() {
+        let replay = ReplayTransport::new(vec![Entry::Single {
+            request: Request {
+                method: "eth_chainId".to_string(),
+                params: vec![],
+            },
+            response: Response::Result(json!("0x1")),
+        }]);
+        assert!(replay.execute("eth_chainId", vec![]).await.is_ok());
+        assert!(replay
+            .execute("eth_chainId", vec![json!("unexpected")])
+            .await
+            .is_err());
+        assert!(replay
+            .send_batch(vec![replay.prepare("eth_chainId", vec![])])
+            .await
+            .is_err());
+        let notification = Call::Notification(jsonrpc::Notification {
+            jsonrpc: None,
+            method: "eth_chainId".to_string(),
+            params: Params::None,
+        });
+        assert!(replay.send(0, notification).await.is_err());
+    }
+}
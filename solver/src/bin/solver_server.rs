@@ -0,0 +1,64 @@
+//! Serves the built-in solvers over the HTTP solver protocol so that they can be used in place of
+//! an external solver.
+
+use ethcontract::H160;
+use solver::{
+    solver::{BaselineSolver, NaiveSolver, Solver},
+    solver_server,
+};
+use std::{net::SocketAddr, sync::Arc};
+use structopt::{clap::arg_enum, StructOpt};
+
+arg_enum! {
+    #[derive(Debug)]
+    enum ServedSolver {
+        Naive,
+        Baseline,
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct Arguments {
+    #[structopt(
+        long,
+        env = "LOG_FILTER",
+        default_value = "warn,solver=debug,solver_server=debug"
+    )]
+    log_filter: String,
+
+    /// The address the server binds to.
+    #[structopt(long, env, default_value = "0.0.0.0:8000")]
+    bind_address: SocketAddr,
+
+    /// The solver that solves the received instances.
+    #[structopt(
+        long,
+        env,
+        default_value = "Naive",
+        possible_values = &ServedSolver::variants(),
+        case_insensitive = true,
+    )]
+    solver: ServedSolver,
+
+    /// Base tokens used for finding multi-hop paths between AMMs.
+    #[structopt(long, env = "BASE_TOKENS", use_delimiter = true)]
+    base_tokens: Vec<H160>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Arguments::from_args();
+    shared::tracing::initialize(args.log_filter.as_str());
+    tracing::info!("running solver server with {:#?}", args);
+
+    let solver: Arc<dyn Solver + Send + Sync> = match args.solver {
+        ServedSolver::Naive => Arc::new(NaiveSolver::new(args.base_tokens.into_iter().collect())),
+        ServedSolver::Baseline => {
+            Arc::new(BaselineSolver::new(args.base_tokens.into_iter().collect()))
+        }
+    };
+    tracing::info!(address = %args.bind_address, "serving {}", solver.name());
+    warp::serve(solver_server::handle_solve(solver))
+        .run(args.bind_address)
+        .await;
+}
@@ -1,13 +1,16 @@
+pub mod objective;
 pub mod solver_settlements;
 
-use self::solver_settlements::{RatedSettlement, SettlementWithSolver};
+use self::{
+    objective::{Objective, ScoreRecorder},
+    solver_settlements::{RatedSettlement, SettlementWithSolver},
+};
 use crate::{
-    chain,
     liquidity::Liquidity,
     liquidity_collector::LiquidityCollector,
     metrics::SolverMetrics,
     settlement::Settlement,
-    settlement_simulation,
+    settlement_simulation::{self, SettlementSimulating},
     settlement_submission::{self, retry::is_transaction_failure},
     solver::Solver,
 };
@@ -22,13 +25,13 @@ use num::BigRational;
 use primitive_types::H160;
 use shared::{
     current_block::{self, CurrentBlockStream},
+    health::Heartbeat,
     price_estimate::PriceEstimating,
     recent_block_cache::Block,
     token_list::TokenList,
-    Web3,
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -44,15 +47,23 @@ pub struct Driver {
     native_token: H160,
     min_order_age: Duration,
     metrics: Arc<dyn SolverMetrics>,
-    web3: Web3,
-    network_id: String,
+    settlement_simulator: Arc<dyn SettlementSimulating>,
     max_merged_settlements: usize,
+    max_settlements_per_run: usize,
     solver_time_limit: Duration,
     gas_price_cap: f64,
     market_makable_token_list: Option<TokenList>,
+    /// Revision of the list that was set through the orderbook's admin api and replaced the one
+    /// the driver was created with.
+    market_makable_token_list_revision: Option<i64>,
+    chain_id: u64,
     inflight_trades: HashSet<OrderUid>,
     block_stream: CurrentBlockStream,
     fee_discount_factor: f64,
+    objective: Objective,
+    score_recorder: Option<ScoreRecorder>,
+    run_loop_heartbeat: Heartbeat,
+    disabled_solvers: BTreeSet<String>,
 }
 impl Driver {
     #[allow(clippy::too_many_arguments)]
@@ -67,14 +78,17 @@ impl Driver {
         native_token: H160,
         min_order_age: Duration,
         metrics: Arc<dyn SolverMetrics>,
-        web3: Web3,
-        network_id: String,
+        settlement_simulator: Arc<dyn SettlementSimulating>,
         max_merged_settlements: usize,
+        max_settlements_per_run: usize,
         solver_time_limit: Duration,
         gas_price_cap: f64,
         market_makable_token_list: Option<TokenList>,
+        chain_id: u64,
         block_stream: CurrentBlockStream,
         fee_discount_factor: f64,
+        objective: Objective,
+        score_recorder: Option<ScoreRecorder>,
     ) -> Self {
         Self {
             settlement_contract,
@@ -87,22 +101,36 @@ impl Driver {
             native_token,
             min_order_age,
             metrics,
-            web3,
-            network_id,
+            settlement_simulator,
             max_merged_settlements,
+            max_settlements_per_run,
             solver_time_limit,
             gas_price_cap,
             market_makable_token_list,
+            market_makable_token_list_revision: None,
+            chain_id,
             inflight_trades: HashSet::new(),
             block_stream,
             fee_discount_factor,
+            objective,
+            score_recorder,
+            run_loop_heartbeat: Default::default(),
+            disabled_solvers: Default::default(),
         }
     }
 
+    /// Beats whenever `run_forever` completes a successful single run.
+    pub fn run_loop_heartbeat(&self) -> Heartbeat {
+        self.run_loop_heartbeat.clone()
+    }
+
     pub async fn run_forever(&mut self) -> ! {
         loop {
             match self.single_run().await {
-                Ok(()) => tracing::debug!("single run finished ok"),
+                Ok(()) => {
+                    tracing::debug!("single run finished ok");
+                    self.run_loop_heartbeat.beat();
+                }
                 Err(err) => tracing::error!("single run errored: {:?}", err),
             }
             tokio::time::sleep(self.settle_interval).await;
@@ -115,8 +143,14 @@ impl Driver {
         liquidity: Vec<Liquidity>,
         gas_price: f64,
     ) -> impl Iterator<Item = (&'static str, Result<Vec<Settlement>>)> {
-        join_all(self.solver.iter().map(|solver| {
-            let liquidity = liquidity.clone();
+        let enabled_solvers = self
+            .solver
+            .iter()
+            .filter(|solver| !self.disabled_solvers.contains(solver.name()));
+        join_all(enabled_solvers.map(|solver| {
+            let liquidity = self
+                .liquidity_collector
+                .liquidity_for_solver(solver.name(), &liquidity);
             let metrics = &self.metrics;
             async move {
                 let start_time = Instant::now();
@@ -137,41 +171,58 @@ impl Driver {
         .into_iter()
     }
 
-    async fn submit_settlement(&self, rated_settlement: RatedSettlement) -> Result<()> {
-        let SettlementWithSolver { name, settlement } = rated_settlement.clone().settlement;
-        let trades = settlement.trades().to_vec();
-        match settlement_submission::submit(
+    // Submits the settlements with consecutive nonces and returns the successfully submitted ones.
+    async fn submit_settlements(
+        &self,
+        rated_settlements: Vec<RatedSettlement>,
+    ) -> Vec<RatedSettlement> {
+        let results = settlement_submission::submit_many(
             &self.settlement_contract,
             self.gas_price_estimator.as_ref(),
             self.target_confirm_time,
             self.gas_price_cap,
-            rated_settlement,
+            rated_settlements.clone(),
         )
-        .await
-        {
-            Ok(_) => {
-                trades
-                    .iter()
-                    .for_each(|trade| self.metrics.order_settled(&trade.order, name));
-                self.metrics.settlement_submitted(true, name);
-                Ok(())
-            }
-            Err(err) => {
-                // Since we simulate and only submit solutions when they used to pass before, there is no
-                // point in logging transaction failures in the form of race conditions as hard errors.
-                if err
-                    .downcast_ref::<MethodError>()
-                    .map(|e| is_transaction_failure(&e.inner))
-                    .unwrap_or(false)
-                {
-                    tracing::warn!("Failed to submit settlement: {:?}", err)
-                } else {
-                    tracing::error!("Failed to submit settlement: {:?}", err)
-                };
-                self.metrics.settlement_submitted(false, name);
-                Err(err)
-            }
-        }
+        .await;
+
+        rated_settlements
+            .into_iter()
+            .zip(results)
+            .filter_map(|(rated_settlement, result)| {
+                // Merged settlements count as a submission of every contributing solver.
+                let settlement = &rated_settlement.settlement;
+                let solvers = settlement.solvers();
+                match result {
+                    Ok(()) => {
+                        settlement.settlement.trades().iter().for_each(|trade| {
+                            let solver = settlement.trade_solver(&trade.order.order_meta_data.uid);
+                            self.metrics.order_settled(&trade.order, solver)
+                        });
+                        for solver in solvers {
+                            self.metrics.settlement_submitted(true, solver);
+                        }
+                        Some(rated_settlement)
+                    }
+                    Err(err) => {
+                        // Since we simulate and only submit solutions when they used to pass before, there is no
+                        // point in logging transaction failures in the form of race conditions as hard errors.
+                        if err
+                            .downcast_ref::<MethodError>()
+                            .map(|e| is_transaction_failure(&e.inner))
+                            .unwrap_or(false)
+                        {
+                            tracing::warn!("Failed to submit settlement: {:?}", err)
+                        } else {
+                            tracing::error!("Failed to submit settlement: {:?}", err)
+                        };
+                        for solver in solvers {
+                            self.metrics.settlement_submitted(false, solver);
+                        }
+                        None
+                    }
+                }
+            })
+            .collect()
     }
 
     async fn can_settle_without_liquidity(&self, settlement: &RatedSettlement) -> Result<bool> {
@@ -185,18 +236,54 @@ impl Driver {
             return Ok(false);
         }
 
-        let simulations = settlement_simulation::simulate_settlements(
-            chain![settlement.without_onchain_liquidity().into()],
-            &self.settlement_contract,
-            &self.web3,
-            &self.network_id,
-            settlement_simulation::Block::LatestWithoutTenderly,
-        )
-        .await
-        .context("failed to simulate settlement")?;
+        let simulations = self
+            .settlement_simulator
+            .simulate_settlements(
+                vec![settlement.without_onchain_liquidity(&self.objective).into()],
+                settlement_simulation::Block::LatestWithoutTenderly,
+            )
+            .await
+            .context("failed to simulate settlement")?;
         Ok(simulations[0].is_ok())
     }
 
+    // Simulates the settlements merged into one, which executes their interactions one after
+    // another, to catch settlements that change each other's outcome through liquidity they share.
+    // Only the best settlement is kept if that simulation fails.
+    async fn settlements_that_succeed_together(
+        &self,
+        mut settlements: Vec<RatedSettlement>,
+    ) -> Vec<RatedSettlement> {
+        if settlements.len() <= 1 {
+            return settlements;
+        }
+        let result = async {
+            let merged = settlements[1..].iter().try_fold(
+                Settlement::from(settlements[0].clone()),
+                |merged, settlement| merged.merge(settlement.clone().into()),
+            )?;
+            self.settlement_simulator
+                .simulate_settlements(
+                    vec![merged.into()],
+                    settlement_simulation::Block::LatestWithoutTenderly,
+                )
+                .await
+                .context("failed to simulate settlements together")?
+                .pop()
+                .context("missing simulation result")?
+        }
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(
+                "{} settlements fail together, only submitting the best one: {:?}",
+                settlements.len(),
+                err
+            );
+            settlements.truncate(1);
+        }
+        settlements
+    }
+
     // Split settlements into successfully simulating ones and errors.
     async fn simulate_settlements(
         &self,
@@ -205,17 +292,17 @@ impl Driver {
         Vec<SettlementWithSolver>,
         Vec<(SettlementWithSolver, Error)>,
     )> {
-        let simulations = settlement_simulation::simulate_settlements(
-            settlements
-                .iter()
-                .map(|settlement| settlement.settlement.clone().into()),
-            &self.settlement_contract,
-            &self.web3,
-            &self.network_id,
-            settlement_simulation::Block::LatestWithoutTenderly,
-        )
-        .await
-        .context("failed to simulate settlements")?;
+        let simulations = self
+            .settlement_simulator
+            .simulate_settlements(
+                settlements
+                    .iter()
+                    .map(|settlement| settlement.settlement.clone().into())
+                    .collect(),
+                settlement_simulation::Block::LatestWithoutTenderly,
+            )
+            .await
+            .context("failed to simulate settlements")?;
 
         Ok(settlements
             .into_iter()
@@ -236,16 +323,18 @@ impl Driver {
         errors: Vec<(SettlementWithSolver, Error)>,
         current_block_during_liquidity_fetch: u64,
     ) {
-        let simulations = match settlement_simulation::simulate_settlements(
-            errors
-                .iter()
-                .map(|(settlement, _)| settlement.clone().into()),
-            &self.settlement_contract,
-            &self.web3,
-            &self.network_id,
-            settlement_simulation::Block::FixedWithTenderly(current_block_during_liquidity_fetch),
-        )
-        .await
+        let simulations = match self
+            .settlement_simulator
+            .simulate_settlements(
+                errors
+                    .iter()
+                    .map(|(settlement, _)| settlement.clone().into())
+                    .collect(),
+                settlement_simulation::Block::FixedWithTenderly(
+                    current_block_during_liquidity_fetch,
+                ),
+            )
+            .await
         {
             Ok(simulations) => simulations,
             Err(err) => {
@@ -269,6 +358,24 @@ impl Driver {
                 current_block_during_liquidity_fetch,
                 error_at_earlier_block,
             );
+            match self
+                .settlement_simulator
+                .diagnose_failure(
+                    &settlement.settlement,
+                    settlement_simulation::Block::FixedWithTenderly(
+                        current_block_during_liquidity_fetch,
+                    ),
+                )
+                .await
+            {
+                Ok(Some(failure)) => {
+                    tracing::error!("{} settlement failure: {}", settlement.name, failure);
+                    self.metrics
+                        .settlement_simulation_failure_classified(settlement.name, &failure);
+                }
+                Ok(None) => tracing::warn!("settlement failure could not be reproduced"),
+                Err(err) => tracing::warn!("failed to diagnose settlement failure: {:?}", err),
+            }
             // This is an additional debug log so that the log message doesn't get too long as
             // settlement information is recoverable through tenderly anyway.
             tracing::warn!("settlement failure for: \n{:#?}", settlement);
@@ -279,12 +386,11 @@ impl Driver {
     // Should help us to identify how much we can save by parallelizing execution.
     fn report_matched_but_unsettled_orders(
         &self,
-        submitted: &Settlement,
+        submitted: impl Iterator<Item = Settlement>,
         all: impl Iterator<Item = Settlement>,
     ) {
         let submitted: HashSet<_> = submitted
-            .trades()
-            .iter()
+            .flat_map(|solution| solution.trades().to_vec())
             .map(|trade| trade.order.order_meta_data.uid)
             .collect();
         let all_matched: HashSet<_> = all
@@ -296,6 +402,20 @@ impl Driver {
             .orders_matched_but_not_settled(matched_but_not_settled.len())
     }
 
+    // The number of tokens traded through on-chain liquidity that are not on the trusted token
+    // list. Without a token list no token is considered untrusted.
+    fn untrusted_tokens(&self, settlement: &Settlement) -> usize {
+        match &self.market_makable_token_list {
+            Some(list) => settlement
+                .encoder
+                .onchain_liquidity_tokens()
+                .iter()
+                .filter(|token| list.get(token).is_none())
+                .count(),
+            None => 0,
+        }
+    }
+
     // Rate settlements, ignoring those for which the rating procedure failed.
     async fn rate_settlements(
         &self,
@@ -323,21 +443,32 @@ impl Driver {
                 )
                 .await
                 .ok()?;
+                let untrusted_liquidity_penalty = self.objective.untrusted_liquidity_penalty(
+                    settlement.settlement.encoder.has_onchain_liquidity(),
+                    self.untrusted_tokens(&settlement.settlement),
+                );
                 let solver_name = settlement.name;
-                let rated_settlement = RatedSettlement {
+                let mut rated_settlement = RatedSettlement {
                     settlement,
                     surplus,
                     solver_fees: unsubsidized_solver_fees,
                     gas_estimate,
                     gas_price: gas_price_wei.clone(),
+                    untrusted_liquidity_penalty,
+                    objective_value: num::zero(),
                 };
+                rated_settlement.objective_value = self
+                    .objective
+                    .objective_value(&rated_settlement.components());
                 tracing::info!(
-                    "Objective value for solver {} is {}: surplus={}, gas_estimate={}, gas_price={}",
+                    "Objective value for solver {} is {}: surplus={}, solver_fees={}, gas_estimate={}, gas_price={}, untrusted_liquidity_penalty={}",
                     solver_name,
-                    rated_settlement.objective_value(),
+                    rated_settlement.objective_value,
                     rated_settlement.surplus,
+                    rated_settlement.solver_fees,
                     rated_settlement.gas_estimate,
                     rated_settlement.gas_price,
+                    rated_settlement.untrusted_liquidity_penalty,
                 );
                 Some(rated_settlement)
             })
@@ -345,8 +476,84 @@ impl Driver {
             .await
     }
 
+    // Combines settlements of different solvers into a single settlement, which gets simulated
+    // and rated like any other settlement so that it is only used if it is better.
+    async fn merge_settlements_across_solvers(
+        &self,
+        rated_settlements: &[RatedSettlement],
+        prices: &HashMap<H160, BigRational>,
+        gas_price_wei: f64,
+    ) -> Option<RatedSettlement> {
+        let merged = solver_settlements::merge_settlements_across_solvers(
+            self.max_merged_settlements,
+            rated_settlements,
+        )?;
+        tracing::debug!("merged settlements across solvers:\n {:?}", merged);
+
+        let (settlements, errors) = match self.simulate_settlements(vec![merged]).await {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::warn!("failed to simulate cross solver settlement: {:?}", err);
+                return None;
+            }
+        };
+        for (_, err) in errors {
+            tracing::debug!("cross solver settlement failed simulation: {:?}", err);
+        }
+        self.rate_settlements(settlements, prices, gas_price_wei)
+            .await
+            .pop()
+    }
+
+    /// Updates the disabled solvers and the market makable token list that were set through the
+    /// orderbook's admin api. Keeps the previous settings if they can't be fetched so that an
+    /// unreachable orderbook doesn't enable disabled solvers.
+    async fn update_solver_settings(&mut self) {
+        match self
+            .liquidity_collector
+            .orderbook_api
+            .get_solver_settings()
+            .await
+        {
+            Ok(settings) => {
+                if settings.disabled_solvers != self.disabled_solvers {
+                    tracing::info!(
+                        "disabled solvers changed to {:?}",
+                        settings.disabled_solvers
+                    );
+                }
+                self.disabled_solvers = settings.disabled_solvers;
+                if let Some(list) = settings.market_makable_token_list {
+                    if Some(list.revision) != self.market_makable_token_list_revision {
+                        self.update_market_makable_token_list(&list.url, list.revision)
+                            .await;
+                    }
+                }
+            }
+            Err(err) => tracing::warn!("failed to get solver settings: {:?}", err),
+        }
+    }
+
+    /// Keeps the previous list if the new one can't be fetched. Fetching is tried again in the
+    /// next run.
+    async fn update_market_makable_token_list(&mut self, url: &str, revision: i64) {
+        match TokenList::from_url(url, self.chain_id).await {
+            Ok(list) => {
+                tracing::info!("market makable token list changed to {}", url);
+                self.market_makable_token_list = Some(list);
+                self.market_makable_token_list_revision = Some(revision);
+            }
+            Err(err) => tracing::warn!(
+                "failed to fetch market makable token list {}: {:?}",
+                url,
+                err
+            ),
+        }
+    }
+
     pub async fn single_run(&mut self) -> Result<()> {
         tracing::debug!("starting single run");
+        self.update_solver_settings().await;
         let current_block_during_liquidity_fetch =
             current_block::block_number(&self.block_stream.borrow())?;
 
@@ -414,39 +621,76 @@ impl Driver {
             self.metrics.settlement_simulation_failed(settlement.name);
         }
 
-        let rated_settlements = self
+        let mut rated_settlements = self
             .rate_settlements(settlements, &estimated_prices, gas_price_wei)
             .await;
-
-        self.inflight_trades.clear();
-        if let Some(mut settlement) = rated_settlements
-            .clone()
-            .into_iter()
-            .max_by(|a, b| a.objective_value().cmp(&b.objective_value()))
+        if let Some(merged) = self
+            .merge_settlements_across_solvers(&rated_settlements, &estimated_prices, gas_price_wei)
+            .await
         {
-            // If we have enough buffer in the settlement contract to not use on-chain interactions, remove those
-            if self
-                .can_settle_without_liquidity(&settlement)
-                .await
-                .unwrap_or(false)
-            {
-                settlement = settlement.without_onchain_liquidity();
-                tracing::info!("settlement without onchain liquidity");
+            rated_settlements.push(merged);
+        }
+
+        if let Some(recorder) = &self.score_recorder {
+            if let Err(err) = recorder.record(
+                current_block_during_liquidity_fetch,
+                &self.objective,
+                &rated_settlements,
+            ) {
+                tracing::error!("failed to record settlement scores: {:?}", err);
             }
+        }
+        let objective = &self.objective;
+        rated_settlements.retain(|settlement| {
+            let acceptable = objective.is_acceptable(settlement);
+            if !acceptable {
+                tracing::info!(
+                    "settlement of solver {} does not reach the minimum profit",
+                    settlement.settlement.name
+                );
+            }
+            acceptable
+        });
 
-            tracing::info!("winning settlement: {:?}", settlement);
-            if self.submit_settlement(settlement.clone()).await.is_ok() {
-                self.inflight_trades = settlement
-                    .settlement
-                    .settlement
-                    .trades()
-                    .iter()
-                    .map(|t| t.order.order_meta_data.uid)
-                    .collect::<HashSet<OrderUid>>();
+        self.inflight_trades.clear();
+        let winning_settlements = solver_settlements::select_independent_settlements(
+            self.max_settlements_per_run,
+            rated_settlements.clone(),
+        );
+        if !winning_settlements.is_empty() {
+            let mut settlements_to_submit = Vec::with_capacity(winning_settlements.len());
+            // Settlements without on-chain liquidity are simulated against the same buffers, so
+            // two of them could each pass alone and together drain a buffer.
+            let mut settles_without_liquidity = false;
+            for mut settlement in winning_settlements {
+                // If we have enough buffer in the settlement contract to not use on-chain interactions, remove those
+                if !settles_without_liquidity
+                    && self
+                        .can_settle_without_liquidity(&settlement)
+                        .await
+                        .unwrap_or(false)
+                {
+                    settlement = settlement.without_onchain_liquidity(&self.objective);
+                    settles_without_liquidity = true;
+                    tracing::info!("settlement without onchain liquidity");
+                }
+
+                tracing::info!("winning settlement: {:?}", settlement);
+                settlements_to_submit.push(settlement);
             }
+            let settlements_to_submit = self
+                .settlements_that_succeed_together(settlements_to_submit)
+                .await;
+
+            let submitted = self.submit_settlements(settlements_to_submit).await;
+            self.inflight_trades = submitted
+                .iter()
+                .flat_map(|settlement| settlement.settlement.settlement.trades())
+                .map(|t| t.order.order_meta_data.uid)
+                .collect::<HashSet<OrderUid>>();
 
             self.report_matched_but_unsettled_orders(
-                &Settlement::from(settlement),
+                submitted.into_iter().map(Settlement::from),
                 rated_settlements.into_iter().map(Settlement::from),
             );
         }
@@ -0,0 +1,263 @@
+use super::solver_settlements::{compute_objective_value, RatedSettlement};
+use anyhow::{Context, Result};
+use model::order::OrderUid;
+use num::{BigRational, Zero};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+use structopt::clap::arg_enum;
+
+arg_enum! {
+    /// How the objective value of a rated settlement is computed from its components.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum ObjectiveType {
+        // surplus + solver fees - gas cost
+        SurplusFeesCost,
+        // surplus - gas cost
+        Surplus,
+        // surplus + fee weight * solver fees - gas cost
+        FeeWeighted,
+    }
+}
+
+/// The individual values that make up the objective value of a settlement. Surplus, fees and
+/// penalties are denominated in wei.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectiveComponents {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub surplus: BigRational,
+    // The unsubsidized solver fees, meaning with the fee discount reversed.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub solver_fees: BigRational,
+    // In gas units.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub gas_estimate: BigRational,
+    // In wei per gas unit.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub gas_price: BigRational,
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub untrusted_liquidity_penalty: BigRational,
+}
+
+impl ObjectiveComponents {
+    // The part of the objective value the protocol earns: fees minus what the settlement costs.
+    pub fn profit(&self) -> BigRational {
+        compute_objective_value(
+            &BigRational::zero(),
+            &self.solver_fees,
+            &self.gas_estimate,
+            &self.gas_price,
+        )
+    }
+}
+
+/// Configuration of how settlements are scored against each other.
+#[derive(Clone, Debug)]
+pub struct Objective {
+    pub objective_type: ObjectiveType,
+    // Only used for `ObjectiveType::FeeWeighted`.
+    pub fee_weight: BigRational,
+    // Settlements whose profit is below this value are not considered for submission.
+    pub min_profit: Option<BigRational>,
+    // Subtracted from the objective value for every token traded through on-chain liquidity that
+    // is not on the trusted token list.
+    pub untrusted_liquidity_penalty: BigRational,
+}
+
+impl Default for Objective {
+    fn default() -> Self {
+        Self {
+            objective_type: ObjectiveType::SurplusFeesCost,
+            fee_weight: num::one(),
+            min_profit: None,
+            untrusted_liquidity_penalty: num::zero(),
+        }
+    }
+}
+
+impl Objective {
+    pub fn objective_value(&self, components: &ObjectiveComponents) -> BigRational {
+        let solver_fees = match self.objective_type {
+            ObjectiveType::SurplusFeesCost => components.solver_fees.clone(),
+            ObjectiveType::Surplus => BigRational::zero(),
+            ObjectiveType::FeeWeighted => &self.fee_weight * &components.solver_fees,
+        };
+        compute_objective_value(
+            &components.surplus,
+            &solver_fees,
+            &components.gas_estimate,
+            &components.gas_price,
+        ) - &components.untrusted_liquidity_penalty
+    }
+
+    // The penalty for a settlement using on-chain liquidity with the given number of untrusted
+    // tokens.
+    pub fn untrusted_liquidity_penalty(
+        &self,
+        uses_onchain_liquidity: bool,
+        untrusted_tokens: usize,
+    ) -> BigRational {
+        if !uses_onchain_liquidity {
+            return BigRational::zero();
+        }
+        &self.untrusted_liquidity_penalty * BigRational::from_integer(untrusted_tokens.into())
+    }
+
+    // Whether a settlement passes the minimum profit threshold.
+    pub fn is_acceptable(&self, settlement: &RatedSettlement) -> bool {
+        match &self.min_profit {
+            Some(min_profit) => settlement.components().profit() >= *min_profit,
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedScore<'a> {
+    solver: &'static str,
+    orders: Vec<OrderUid>,
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    objective_value: &'a BigRational,
+    accepted: bool,
+    #[serde(flatten)]
+    components: ObjectiveComponents,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedAuction<'a> {
+    block: u64,
+    settlements: Vec<RecordedScore<'a>>,
+}
+
+/// Appends the score components of all rated settlements of a run to a file as JSON lines so that
+/// changes to the objective function can be evaluated offline.
+pub struct ScoreRecorder {
+    file: Mutex<BufWriter<File>>,
+}
+
+impl ScoreRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open score record file {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub fn record(
+        &self,
+        block: u64,
+        objective: &Objective,
+        settlements: &[RatedSettlement],
+    ) -> Result<()> {
+        let auction = RecordedAuction {
+            block,
+            settlements: settlements
+                .iter()
+                .map(|settlement| RecordedScore {
+                    solver: settlement.settlement.name,
+                    orders: settlement
+                        .settlement
+                        .settlement
+                        .trades()
+                        .iter()
+                        .map(|trade| trade.order.order_meta_data.uid)
+                        .collect(),
+                    objective_value: &settlement.objective_value,
+                    accepted: objective.is_acceptable(settlement),
+                    components: settlement.components(),
+                })
+                .collect(),
+        };
+        let mut file = self.file.lock().unwrap();
+        serde_json::to_writer(&mut *file, &auction)?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn components(surplus: i64, solver_fees: i64, gas_cost: i64) -> ObjectiveComponents {
+        ObjectiveComponents {
+            surplus: BigRational::from_integer(surplus.into()),
+            solver_fees: BigRational::from_integer(solver_fees.into()),
+            gas_estimate: BigRational::from_integer(gas_cost.into()),
+            gas_price: num::one(),
+            untrusted_liquidity_penalty: num::zero(),
+        }
+    }
+
+    #[test]
+    fn objective_value_depends_on_objective_type() {
+        let components = components(100, 10, 30);
+        let objective = |objective_type| Objective {
+            objective_type,
+            fee_weight: BigRational::new(1.into(), 2.into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            objective(ObjectiveType::SurplusFeesCost).objective_value(&components),
+            BigRational::from_integer(80.into())
+        );
+        assert_eq!(
+            objective(ObjectiveType::Surplus).objective_value(&components),
+            BigRational::from_integer(70.into())
+        );
+        assert_eq!(
+            objective(ObjectiveType::FeeWeighted).objective_value(&components),
+            BigRational::from_integer(75.into())
+        );
+    }
+
+    #[test]
+    fn untrusted_liquidity_is_penalized() {
+        let objective = Objective {
+            untrusted_liquidity_penalty: BigRational::from_integer(5.into()),
+            ..Default::default()
+        };
+        assert!(objective.untrusted_liquidity_penalty(false, 2).is_zero());
+        let penalty = objective.untrusted_liquidity_penalty(true, 2);
+        assert_eq!(penalty, BigRational::from_integer(10.into()));
+
+        let components = ObjectiveComponents {
+            untrusted_liquidity_penalty: penalty,
+            ..components(100, 10, 30)
+        };
+        assert_eq!(
+            objective.objective_value(&components),
+            BigRational::from_integer(70.into())
+        );
+    }
+
+    #[test]
+    fn serializes_components_as_rationals() {
+        let components = ObjectiveComponents {
+            surplus: BigRational::new(1.into(), 3.into()),
+            ..components(0, 2, 1)
+        };
+        assert_eq!(
+            serde_json::to_value(&components).unwrap(),
+            serde_json::json!({
+                "surplus": "1/3",
+                "solverFees": "2",
+                "gasEstimate": "1",
+                "gasPrice": "1",
+                "untrustedLiquidityPenalty": "0",
+            })
+        );
+    }
+}
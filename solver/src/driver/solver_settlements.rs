@@ -1,10 +1,15 @@
+use super::objective::{Objective, ObjectiveComponents};
 use crate::{encoding::EncodedSettlement, settlement::Settlement};
 use anyhow::Result;
 use ethcontract::U256;
-use num::BigRational;
+use model::order::OrderUid;
+use num::{BigRational, Signed, Zero};
 use primitive_types::H160;
 use shared::conversions::U256Ext;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 // Return None if the result is an error or there are no settlements remaining after removing
 // settlements with no trades.
@@ -38,7 +43,11 @@ impl From<SolverWithSettlements> for Vec<SettlementWithSolver> {
         instance
             .settlements
             .into_iter()
-            .map(|settlement| SettlementWithSolver { name, settlement })
+            .map(|settlement| SettlementWithSolver {
+                name,
+                settlement,
+                trade_solvers: HashMap::new(),
+            })
             .collect()
     }
 }
@@ -47,6 +56,9 @@ impl From<SolverWithSettlements> for Vec<SettlementWithSolver> {
 pub struct SettlementWithSolver {
     pub name: &'static str,
     pub settlement: Settlement,
+    // The solvers that found the trades of a cross solver merge. Empty if all trades are from
+    // `name`.
+    pub trade_solvers: HashMap<OrderUid, &'static str>,
 }
 
 impl From<SettlementWithSolver> for EncodedSettlement {
@@ -66,23 +78,48 @@ impl SettlementWithSolver {
         Self {
             name: self.name,
             settlement: self.settlement.without_onchain_liquidity(),
+            trade_solvers: self.trade_solvers.clone(),
         }
     }
+
+    /// The solver that found the trade for the specified order.
+    pub fn trade_solver(&self, order: &OrderUid) -> &'static str {
+        self.trade_solvers.get(order).copied().unwrap_or(self.name)
+    }
+
+    /// The solvers that contributed trades to this settlement.
+    pub fn solvers(&self) -> Vec<&'static str> {
+        let mut solvers = self
+            .settlement
+            .trades()
+            .iter()
+            .map(|trade| self.trade_solver(&trade.order.order_meta_data.uid))
+            .collect::<Vec<_>>();
+        solvers.sort_unstable();
+        solvers.dedup();
+        if solvers.is_empty() {
+            solvers.push(self.name);
+        }
+        solvers
+    }
 }
 
 // Each individual settlement has an objective value.
 #[derive(Debug, Clone)]
 pub struct RatedSettlement {
     pub settlement: SettlementWithSolver,
-    pub surplus: BigRational,     // In wei.
-    pub solver_fees: BigRational, // In wei.
-    pub gas_estimate: U256,       // In gas units.
-    pub gas_price: BigRational,   // In wei per gas unit.
+    pub surplus: BigRational,                     // In wei.
+    pub solver_fees: BigRational,                 // In wei.
+    pub gas_estimate: U256,                       // In gas units.
+    pub gas_price: BigRational,                   // In wei per gas unit.
+    pub untrusted_liquidity_penalty: BigRational, // In wei.
+    // Computed from the other values by the configured `Objective`.
+    pub objective_value: BigRational,
 }
 
 // Helper function for RatedSettlement to allow unit testing objective value computation
 // without a Settlement.
-fn compute_objective_value(
+pub(super) fn compute_objective_value(
     surplus: &BigRational,
     solver_fees: &BigRational,
     gas_estimate: &BigRational,
@@ -93,14 +130,14 @@ fn compute_objective_value(
 }
 
 impl RatedSettlement {
-    pub fn objective_value(&self) -> BigRational {
-        let gas_estimate = self.gas_estimate.to_big_rational();
-        compute_objective_value(
-            &self.surplus,
-            &self.solver_fees,
-            &gas_estimate,
-            &self.gas_price,
-        )
+    pub fn components(&self) -> ObjectiveComponents {
+        ObjectiveComponents {
+            surplus: self.surplus.clone(),
+            solver_fees: self.solver_fees.clone(),
+            gas_estimate: self.gas_estimate.to_big_rational(),
+            gas_price: self.gas_price.clone(),
+            untrusted_liquidity_penalty: self.untrusted_liquidity_penalty.clone(),
+        }
     }
 }
 
@@ -117,14 +154,20 @@ impl From<RatedSettlement> for Settlement {
 }
 
 impl RatedSettlement {
-    pub fn without_onchain_liquidity(&self) -> Self {
-        RatedSettlement {
+    pub fn without_onchain_liquidity(&self, objective: &Objective) -> Self {
+        let mut rated_settlement = RatedSettlement {
             settlement: self.settlement.without_onchain_liquidity(),
             surplus: self.surplus.clone(),
             solver_fees: self.solver_fees.clone(),
             gas_estimate: self.gas_estimate, // TODO: This becomes an overestimate!
             gas_price: self.gas_price.clone(),
-        }
+            // The penalty only applies to settlements using on-chain liquidity.
+            untrusted_liquidity_penalty: num::zero(),
+            objective_value: num::zero(),
+        };
+        rated_settlement.objective_value =
+            objective.objective_value(&rated_settlement.components());
+        rated_settlement
     }
 }
 
@@ -174,6 +217,183 @@ fn merge_at_most_settlements(
     }
 }
 
+/// The name used for settlements that combine settlements of several solvers.
+pub const CROSS_SOLVER_MERGE_NAME: &str = "CrossSolverMerge";
+
+// Limits the number of settlements considered for cross solver merging, as finding the best
+// combination is exponential in the number of candidates.
+const MAX_CROSS_SOLVER_MERGE_CANDIDATES: usize = 20;
+
+// Combines non-conflicting settlements of different solvers. Settlements conflict if they share
+// an order or have different clearing prices for the same token (see `Settlement::merge`).
+//
+// Instead of merging greedily, the combination of at most `max_merged_settlements` settlements
+// with the highest total objective value is selected. This relies on the objective value being
+// (approximately) additive for settlements without shared orders. Returns `None` if there is no
+// combination that involves more than one solver.
+pub fn merge_settlements_across_solvers(
+    max_merged_settlements: usize,
+    rated_settlements: &[RatedSettlement],
+) -> Option<SettlementWithSolver> {
+    let mut candidates = rated_settlements
+        .iter()
+        .map(|rated| (rated.objective_value.clone(), &rated.settlement))
+        .filter(|(objective_value, _)| objective_value.is_positive())
+        .collect::<Vec<_>>();
+    candidates.sort_by(|(a, _), (b, _)| b.cmp(a));
+    candidates.truncate(MAX_CROSS_SOLVER_MERGE_CANDIDATES);
+
+    let values = candidates
+        .iter()
+        .map(|(value, _)| value.clone())
+        .collect::<Vec<_>>();
+    let compatible = |i: usize, j: usize| {
+        candidates[i]
+            .1
+            .settlement
+            .clone()
+            .merge(candidates[j].1.settlement.clone())
+            .is_ok()
+    };
+    let compatibility = (0..candidates.len())
+        .map(|i| {
+            (0..candidates.len())
+                .map(|j| i != j && compatible(i, j))
+                .collect()
+        })
+        .collect::<Vec<Vec<_>>>();
+    let names = candidates
+        .iter()
+        .map(|(_, settlement)| settlement.name)
+        .collect::<Vec<_>>();
+
+    let best = best_combination(max_merged_settlements, &values, &compatibility, &names)?;
+    let trade_solvers = best
+        .iter()
+        .flat_map(|&index| {
+            let candidate = candidates[index].1;
+            candidate.settlement.trades().iter().map(move |trade| {
+                let uid = trade.order.order_meta_data.uid;
+                (uid, candidate.trade_solver(&uid))
+            })
+        })
+        .collect();
+    let mut merged = best
+        .into_iter()
+        .map(|index| candidates[index].1.settlement.clone());
+    let first = merged.next()?;
+    let settlement = merged
+        .try_fold(first, |merged, next| merged.merge(next))
+        .map_err(|err| tracing::error!("failed to merge compatible settlements: {:?}", err))
+        .ok()?;
+    Some(SettlementWithSolver {
+        name: CROSS_SOLVER_MERGE_NAME,
+        settlement,
+        trade_solvers,
+    })
+}
+
+// Branch and bound search for the set of pairwise compatible candidates with the highest total
+// value. Candidates must be sorted by descending value. Only sets with candidates of at least two
+// different solvers are considered.
+fn best_combination(
+    max_size: usize,
+    values: &[BigRational],
+    compatibility: &[Vec<bool>],
+    names: &[&'static str],
+) -> Option<Vec<usize>> {
+    struct Search<'a> {
+        max_size: usize,
+        values: &'a [BigRational],
+        compatibility: &'a [Vec<bool>],
+        names: &'a [&'static str],
+        best: Option<(BigRational, Vec<usize>)>,
+    }
+
+    impl Search<'_> {
+        fn run(&mut self, start: usize, current: &mut Vec<usize>, value: BigRational) {
+            let is_cross_solver = current
+                .iter()
+                .any(|&index| self.names[index] != self.names[current[0]]);
+            if is_cross_solver
+                && self
+                    .best
+                    .as_ref()
+                    .map(|(best, _)| value > *best)
+                    .unwrap_or(true)
+            {
+                self.best = Some((value.clone(), current.clone()));
+            }
+            if current.len() >= self.max_size {
+                return;
+            }
+
+            for next in start..self.values.len() {
+                // Since candidates are sorted, the remaining slots can at most be filled with
+                // settlements of the value of `next`.
+                let remaining = (self.max_size - current.len()) as u64;
+                let bound =
+                    &value + &self.values[next] * BigRational::from_integer(remaining.into());
+                if matches!(&self.best, Some((best, _)) if bound <= *best) {
+                    return;
+                }
+                if !current.iter().all(|&index| self.compatibility[index][next]) {
+                    continue;
+                }
+                current.push(next);
+                self.run(next + 1, current, &value + &self.values[next]);
+                current.pop();
+            }
+        }
+    }
+
+    let mut search = Search {
+        max_size,
+        values,
+        compatibility,
+        names,
+        best: None,
+    };
+    search.run(0, &mut Vec::new(), BigRational::zero());
+    search.best.map(|(_, combination)| combination)
+}
+
+// Selects the settlements to be submitted in a single run ordered by descending objective value.
+// The settlement with the highest objective value is always selected. Further settlements are only
+// added if they have a positive objective value and don't touch any of the tokens of the previously
+// selected ones, including the intermediate tokens of AMM routes, so that they don't share orders
+// or the AMM pools we know about. Aggregators can route through other pools, which is why the
+// driver also simulates the selected settlements together before submitting them.
+pub fn select_independent_settlements(
+    max_settlements: usize,
+    mut rated_settlements: Vec<RatedSettlement>,
+) -> Vec<RatedSettlement> {
+    rated_settlements.sort_by_cached_key(|settlement| -&settlement.objective_value);
+
+    let mut used_tokens = HashSet::new();
+    let mut selected = Vec::new();
+    for rated_settlement in rated_settlements {
+        if selected.len() >= max_settlements {
+            break;
+        }
+        let tokens = rated_settlement
+            .settlement
+            .settlement
+            .encoder
+            .touched_tokens();
+        let is_first = selected.is_empty();
+        if !is_first
+            && (!rated_settlement.objective_value.is_positive()
+                || !used_tokens.is_disjoint(&tokens))
+        {
+            continue;
+        }
+        used_tokens.extend(tokens);
+        selected.push(rated_settlement);
+    }
+    selected
+}
+
 pub fn filter_settlements_without_old_orders(
     min_order_age: Duration,
     settlements: &mut Vec<SettlementWithSolver>,
@@ -198,7 +418,6 @@ mod tests {
     use num::rational::BigRational;
     use num::traits::FromPrimitive;
     use primitive_types::U256;
-    use std::collections::HashSet;
 
     #[test]
     fn merges_settlements_with_highest_objective_value() {
@@ -277,6 +496,194 @@ mod tests {
         assert!(merge_at_most_settlements(1, settlements.into_iter()).is_none());
     }
 
+    fn rated_settlement(
+        name: &'static str,
+        prices: HashMap<H160, U256>,
+        order_uids: &[u8],
+        objective_value: u64,
+    ) -> RatedSettlement {
+        let trades = order_uids
+            .iter()
+            .map(|uid| Trade {
+                order: Order {
+                    order_meta_data: OrderMetaData {
+                        uid: OrderUid([*uid; 56]),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .collect();
+        RatedSettlement {
+            settlement: SettlementWithSolver {
+                name,
+                settlement: Settlement::with_trades(prices, trades),
+                trade_solvers: HashMap::new(),
+            },
+            surplus: BigRational::from_integer(objective_value.into()),
+            solver_fees: num::zero(),
+            gas_estimate: 0.into(),
+            gas_price: num::zero(),
+            untrusted_liquidity_penalty: num::zero(),
+            objective_value: BigRational::from_integer(objective_value.into()),
+        }
+    }
+
+    #[test]
+    fn without_onchain_liquidity_recomputes_objective_value() {
+        let objective = Objective {
+            untrusted_liquidity_penalty: BigRational::from_integer(3.into()),
+            ..Default::default()
+        };
+        let mut rated = rated_settlement("a", HashMap::new(), &[1], 10);
+        rated.untrusted_liquidity_penalty = BigRational::from_integer(3.into());
+        rated.objective_value = objective.objective_value(&rated.components());
+
+        let rated = rated.without_onchain_liquidity(&objective);
+        assert!(rated.untrusted_liquidity_penalty.is_zero());
+        assert_eq!(rated.objective_value, BigRational::from_integer(10.into()));
+    }
+
+    fn merged_uids(settlement: &SettlementWithSolver) -> HashSet<u8> {
+        settlement
+            .settlement
+            .trades()
+            .iter()
+            .map(|trade| trade.order.order_meta_data.uid.0[0])
+            .collect()
+    }
+
+    #[test]
+    fn cross_solver_merge_skips_conflicting_price_vectors() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+
+        let settlements = vec![
+            rated_settlement("a", hashmap! { token0 => 1.into() }, &[1], 10),
+            // Conflicts with the first settlement on the price of token0.
+            rated_settlement("b", hashmap! { token0 => 2.into() }, &[2], 8),
+            rated_settlement("c", hashmap! { token1 => 1.into() }, &[3], 5),
+        ];
+
+        let merged = merge_settlements_across_solvers(3, &settlements).unwrap();
+        assert_eq!(merged.name, CROSS_SOLVER_MERGE_NAME);
+        assert_eq!(merged_uids(&merged), maplit::hashset! { 1, 3 });
+        assert_eq!(merged.trade_solver(&OrderUid([1; 56])), "a");
+        assert_eq!(merged.trade_solver(&OrderUid([3; 56])), "c");
+        assert_eq!(merged.solvers(), vec!["a", "c"]);
+        assert_eq!(merged.settlement.clearing_price(token0), Some(1.into()));
+    }
+
+    #[test]
+    fn cross_solver_merge_prefers_best_combination_over_greedy() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+        let token2 = H160::from_low_u64_be(2);
+
+        let settlements = vec![
+            // The best individual settlement conflicts with both other settlements, which
+            // together are worth more.
+            rated_settlement(
+                "a",
+                hashmap! { token0 => 1.into(), token1 => 1.into() },
+                &[1],
+                10,
+            ),
+            rated_settlement("b", hashmap! { token0 => 2.into() }, &[2], 7),
+            rated_settlement(
+                "c",
+                hashmap! { token1 => 2.into(), token2 => 1.into() },
+                &[3],
+                7,
+            ),
+        ];
+
+        let merged = merge_settlements_across_solvers(3, &settlements).unwrap();
+        assert_eq!(merged_uids(&merged), maplit::hashset! { 2, 3 });
+    }
+
+    #[test]
+    fn cross_solver_merge_requires_different_solvers_and_no_shared_orders() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+
+        // Same solver only.
+        let settlements = vec![
+            rated_settlement("a", hashmap! { token0 => 1.into() }, &[1], 10),
+            rated_settlement("a", hashmap! { token1 => 1.into() }, &[2], 10),
+        ];
+        assert!(merge_settlements_across_solvers(2, &settlements).is_none());
+
+        // Shared order.
+        let settlements = vec![
+            rated_settlement("a", hashmap! { token0 => 1.into() }, &[1], 10),
+            rated_settlement("b", hashmap! { token1 => 1.into() }, &[1], 10),
+        ];
+        assert!(merge_settlements_across_solvers(2, &settlements).is_none());
+
+        // Merging disabled.
+        let settlements = vec![
+            rated_settlement("a", hashmap! { token0 => 1.into() }, &[1], 10),
+            rated_settlement("b", hashmap! { token1 => 1.into() }, &[2], 10),
+        ];
+        assert!(merge_settlements_across_solvers(1, &settlements).is_none());
+        assert!(merge_settlements_across_solvers(2, &settlements).is_some());
+    }
+
+    #[test]
+    fn selects_independent_settlements_by_objective_value() {
+        let token = H160::from_low_u64_be;
+
+        let mut settlements = vec![
+            rated_settlement("a", hashmap! { token(0) => 1.into() }, &[1], 5),
+            rated_settlement("b", hashmap! { token(1) => 1.into() }, &[2], 10),
+            // Shares a token with the best settlement.
+            rated_settlement(
+                "c",
+                hashmap! { token(1) => 1.into(), token(2) => 1.into() },
+                &[3],
+                8,
+            ),
+            rated_settlement("d", hashmap! { token(3) => 1.into() }, &[4], 0),
+            rated_settlement("e", hashmap! { token(4) => 1.into() }, &[5], 1),
+        ];
+        // Routes through a token of the best settlement.
+        let mut multi_hop = rated_settlement("f", hashmap! { token(5) => 1.into() }, &[6], 2);
+        multi_hop
+            .settlement
+            .settlement
+            .encoder
+            .add_onchain_liquidity_tokens(vec![token(5), token(1), token(6)]);
+        settlements.push(multi_hop);
+
+        let names = |selected: Vec<RatedSettlement>| {
+            selected
+                .into_iter()
+                .map(|settlement| settlement.settlement.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(select_independent_settlements(1, settlements.clone())),
+            ["b"]
+        );
+        assert_eq!(
+            names(select_independent_settlements(10, settlements)),
+            ["b", "a", "e"]
+        );
+    }
+
+    #[test]
+    fn always_selects_best_settlement() {
+        let settlements = vec![rated_settlement(
+            "a",
+            hashmap! { H160::zero() => 1.into() },
+            &[1],
+            0,
+        )];
+        assert_eq!(select_independent_settlements(2, settlements).len(), 1);
+    }
+
     #[test]
     fn compute_objective_value() {
         // Surplus1 is 1.003 ETH
@@ -4,12 +4,14 @@ pub mod interactions;
 pub mod liquidity;
 pub mod liquidity_collector;
 pub mod metrics;
+pub mod order_policy;
 pub mod orderbook;
 pub mod pending_transactions;
 pub mod settlement;
 pub mod settlement_simulation;
 pub mod settlement_submission;
 pub mod solver;
+pub mod solver_server;
 mod util;
 
 use anyhow::Result;
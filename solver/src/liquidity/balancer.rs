@@ -120,6 +120,7 @@ impl SettlementHandling<WeightedProductOrder> for SettlementHandler {
         let (asset_in, amount_in) = execution.input;
         let (asset_out, amount_out) = execution.output;
 
+        encoder.add_onchain_liquidity_tokens(vec![asset_in, asset_out]);
         encoder.append_to_execution_plan(self.allowances.approve_token(asset_in, amount_in)?);
         encoder.append_to_execution_plan(BalancerSwapGivenOutInteraction {
             settlement: self.contracts.settlement.clone(),
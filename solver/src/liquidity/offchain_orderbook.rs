@@ -12,20 +12,27 @@ use super::{LimitOrder, SettlementHandling};
 use std::collections::HashSet;
 
 impl OrderBookApi {
-    /// Returns a list of limit orders coming from the offchain orderbook API
-    pub async fn get_liquidity(
+    /// Returns the orders from the offchain orderbook API that are not already being settled
+    pub async fn get_solvable_orders(
         &self,
         inflight_trades: &HashSet<OrderUid>,
-    ) -> Result<Vec<LimitOrder>> {
+    ) -> Result<Vec<Order>> {
         Ok(self
             .get_orders()
             .await
             .context("failed to get orderbook")?
             .into_iter()
             .filter_map(|order| inflight_order_filter(order, inflight_trades))
-            .map(|altered_order| normalize_limit_order(altered_order, self.get_native_token()))
             .collect())
     }
+
+    /// Returns the limit orders for orders coming from the offchain orderbook API
+    pub fn limit_orders(&self, orders: Vec<Order>) -> Vec<LimitOrder> {
+        orders
+            .into_iter()
+            .map(|order| normalize_limit_order(order, self.get_native_token()))
+            .collect()
+    }
 }
 
 struct OrderSettlementHandler {
@@ -143,6 +143,7 @@ impl Inner {
 impl SettlementHandling<ConstantProductOrder> for Inner {
     // Creates the required interaction to convert the given input into output. Applies 0.1% slippage tolerance to the output.
     fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        encoder.add_onchain_liquidity_tokens(vec![execution.input.0, execution.output.0]);
         let (approval, swap) = self.settle(execution.input, execution.output);
         encoder.append_to_execution_plan(approval);
         encoder.append_to_execution_plan(swap);
@@ -1,6 +1,7 @@
 use crate::{
     liquidity::Liquidity,
     liquidity::{balancer::BalancerV2Liquidity, uniswap::UniswapLikeLiquidity},
+    order_policy::OrderPolicy,
     orderbook::OrderBookApi,
 };
 use anyhow::{Context, Result};
@@ -12,6 +13,8 @@ pub struct LiquidityCollector {
     pub uniswap_like_liquidity: Vec<UniswapLikeLiquidity>,
     pub orderbook_api: OrderBookApi,
     pub balancer_v2_liquidity: Option<BalancerV2Liquidity>,
+    /// Filters and sorts the orders of the orderbook before they are turned into liquidity.
+    pub order_policy: Option<OrderPolicy>,
 }
 
 impl LiquidityCollector {
@@ -20,11 +23,15 @@ impl LiquidityCollector {
         at_block: Block,
         inflight_trades: &HashSet<OrderUid>,
     ) -> Result<Vec<Liquidity>> {
-        let limit_orders = self
+        let orders = self
             .orderbook_api
-            .get_liquidity(inflight_trades)
-            .await
-            .context("failed to get orderbook")?;
+            .get_solvable_orders(inflight_trades)
+            .await?;
+        let orders = match &self.order_policy {
+            Some(order_policy) => order_policy.apply(orders).await,
+            None => orders,
+        };
+        let limit_orders = self.orderbook_api.limit_orders(orders);
         tracing::info!("got {} orders: {:?}", limit_orders.len(), limit_orders);
 
         let mut amms = vec![];
@@ -56,4 +63,12 @@ impl LiquidityCollector {
             .chain(amms.into_iter())
             .collect())
     }
+
+    /// Returns the liquidity the specified solver is allowed to use according to the order policy.
+    pub fn liquidity_for_solver(&self, solver: &str, liquidity: &[Liquidity]) -> Vec<Liquidity> {
+        match &self.order_policy {
+            Some(order_policy) => order_policy.liquidity_for_solver(solver, liquidity),
+            None => liquidity.to_vec(),
+        }
+    }
 }
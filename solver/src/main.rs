@@ -1,14 +1,21 @@
 use contracts::{IUniswapLikeRouter, WETH9};
-use ethcontract::{Account, PrivateKey, H160, U256};
+use ethcontract::{dyns::DynTransport, Account, PrivateKey, H160, U256};
+use num::BigRational;
 use prometheus::Registry;
 use reqwest::Url;
 use shared::{
     bad_token::list_based::ListBasedDetector,
-    current_block::current_block_stream,
+    current_block::subscribed_current_block_stream,
+    health::{CurrentBlockHealth, HealthChecking, HealthReporter, HeartbeatHealth},
     maintenance::{Maintaining, ServiceMaintenance},
     metrics::serve_metrics,
     network::network_name,
-    price_estimate::BaselinePriceEstimator,
+    price_estimate::{
+        chainlink::{self, ChainlinkPriceEstimator},
+        composite::{CompositePriceEstimator, PriceSource},
+        static_prices::{self, StaticPriceEstimator},
+        BaselinePriceEstimator, PriceEstimating,
+    },
     recent_block_cache::CacheConfig,
     sources::{
         self,
@@ -21,18 +28,27 @@ use shared::{
     },
     token_info::{CachedTokenInfoFetcher, TokenInfoFetcher},
     token_list::TokenList,
+    transport::caching::CachingTransport,
     transport::create_instrumented_transport,
-    transport::http::HttpTransport,
+    transport::failover::FailoverTransport,
 };
 use solver::{
-    driver::Driver,
+    driver::{
+        objective::{Objective, ObjectiveType, ScoreRecorder},
+        Driver,
+    },
     liquidity::{balancer::BalancerV2Liquidity, uniswap::UniswapLikeLiquidity},
     liquidity_collector::LiquidityCollector,
     metrics::Metrics,
+    order_policy::{self, OrderPolicy, OrderPolicyConfig, OrderPriority},
+    settlement_simulation::{
+        local::StateOrigin, LocalSettlementSimulator, NodeSettlementSimulator,
+        SettlementSimulating, SimulatorType,
+    },
     solver::SolverType,
 };
 use std::{collections::HashMap, iter::FromIterator as _};
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -87,6 +103,16 @@ struct Arguments {
     )]
     settle_interval: Duration,
 
+    /// The service is reported as not ready if the driver's run loop did not complete successfully
+    /// for longer than this many seconds.
+    #[structopt(
+        long,
+        env,
+        default_value = "300",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    health_max_run_loop_age: Duration,
+
     /// Which type of solver to use
     #[structopt(
         long,
@@ -118,10 +144,16 @@ struct Arguments {
     )]
     metrics_port: u16,
 
-    /// The port at which we serve our metrics
+    /// The maximum number of settlements that get merged into a single one, both for settlements
+    /// of the same solver and across solvers.
     #[structopt(long, env = "MAX_MERGED_SETTLEMENTS", default_value = "5")]
     max_merged_settlements: usize,
 
+    /// The maximum number of independent settlements that get submitted in a single run. They are
+    /// submitted concurrently with consecutive nonces.
+    #[structopt(long, env, default_value = "1")]
+    max_settlements_per_run: usize,
+
     /// The maximum amount of time a solver is allowed to take.
     #[structopt(
         long,
@@ -167,12 +199,146 @@ struct Arguments {
 
     /// The slippage tolerance we apply to the price quoted by Paraswap
     #[structopt(long, env, default_value = "10")]
-    paraswap_slippage_bps: usize,
+    paraswap_slippage_bps: u16,
+
+    /// Settle orders with the same sell token, buy token and kind with a single swap in the
+    /// aggregator solvers (1Inch, ParaSwap, Matcha) and prioritize the orders with the highest
+    /// estimated surplus instead of picking random ones.
+    #[structopt(long, env, parse(try_from_str), default_value = "false")]
+    aggregator_order_batching: bool,
+
+    /// Minimum value in ETH an order trading the token has to sell in order to be passed to the
+    /// solvers, specified as comma separated `token=value` pairs.
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        parse(try_from_str = order_policy::parse_token_value)
+    )]
+    min_order_values: Vec<(H160, U256)>,
+
+    /// The maximum number of orders per owner that are passed to the solvers.
+    #[structopt(long, env)]
+    max_orders_per_owner: Option<usize>,
+
+    /// How orders are prioritized when passing them to the solvers:
+    /// `Age`: oldest first.
+    /// `Fee`: highest fee value first.
+    /// `Surplus`: highest estimated surplus first.
+    /// If unset, the order of the orderbook is kept.
+    #[structopt(
+        long,
+        env,
+        possible_values = &OrderPriority::variants(),
+        case_insensitive = true,
+    )]
+    order_priority: Option<OrderPriority>,
+
+    /// Restricts the orders passed to a solver to the ones trading only the listed tokens,
+    /// specified as comma separated `solver:token` pairs using the solver names.
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        parse(try_from_str = order_policy::parse_solver_token)
+    )]
+    solver_allowed_tokens: Vec<(String, H160)>,
+
+    /// Excludes orders trading the listed tokens from being passed to a solver, specified as comma
+    /// separated `solver:token` pairs using the solver names.
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        parse(try_from_str = order_policy::parse_solver_token)
+    )]
+    solver_denied_tokens: Vec<(String, H160)>,
+
+    /// Chainlink feeds quoting tokens in the native token that are used to estimate prices of
+    /// tokens without a route to the native token, specified as comma separated
+    /// `token=aggregator` pairs.
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        parse(try_from_str = chainlink::parse_feed)
+    )]
+    chainlink_feeds: Vec<(H160, H160)>,
+
+    /// Chainlink answers that were last updated longer ago than this many seconds are ignored.
+    #[structopt(
+        long,
+        env,
+        default_value = "86400",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    chainlink_max_age: Duration,
+
+    /// Prices used for tokens neither routing nor Chainlink feeds can price, specified as comma
+    /// separated `token=price` pairs where the price is the value of the smallest unit of the token
+    /// in wei.
+    #[structopt(
+        long,
+        env,
+        use_delimiter = true,
+        parse(try_from_str = static_prices::parse_static_price)
+    )]
+    static_prices: Vec<(H160, BigRational)>,
+
+    /// How settlements are simulated before submission.
+    /// `Node`: uses an `eth_call` against the node.
+    /// `Local`: executes settlements in an embedded EVM with state forked from the node.
+    #[structopt(
+        long,
+        env,
+        default_value = "Node",
+        possible_values = &SimulatorType::variants(),
+        case_insensitive = true,
+    )]
+    settlement_simulator: SimulatorType,
+
+    /// How settlements are scored against each other:
+    /// `SurplusFeesCost`: surplus plus unsubsidized fees minus gas cost.
+    /// `Surplus`: surplus minus gas cost.
+    /// `FeeWeighted`: surplus plus the weighted unsubsidized fees minus gas cost.
+    #[structopt(
+        long,
+        env,
+        default_value = "SurplusFeesCost",
+        possible_values = &ObjectiveType::variants(),
+        case_insensitive = true,
+    )]
+    objective: ObjectiveType,
+
+    /// The weight of the solver fees when using the `FeeWeighted` objective.
+    #[structopt(long, env, default_value = "1")]
+    objective_fee_weight: f64,
+
+    /// If set, settlements whose unsubsidized fees minus gas cost are lower than this value in wei
+    /// are not submitted.
+    #[structopt(long, env)]
+    min_settlement_profit: Option<f64>,
+
+    /// Penalty in wei that gets subtracted from the objective value for every token that a
+    /// settlement trades through on-chain liquidity and that is not on the market makable token
+    /// list.
+    #[structopt(long, env, default_value = "0")]
+    untrusted_liquidity_penalty: f64,
+
+    /// If set, the score components of all rated settlements are appended to this file as JSON
+    /// lines for offline evaluation.
+    #[structopt(long, env)]
+    score_record_file: Option<PathBuf>,
+}
+
+fn main() {
+    let args: Arguments = shared::config::load_arguments();
+    tokio::runtime::Runtime::new()
+        .expect("failed to start the async runtime")
+        .block_on(run(args));
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Arguments::from_args();
+async fn run(args: Arguments) {
     shared::tracing::initialize(args.shared.log_filter.as_str());
     tracing::info!("running solver with {:#?}", args);
 
@@ -180,8 +346,20 @@ async fn main() {
     let metrics = Arc::new(Metrics::new(&registry).expect("Couldn't register metrics"));
 
     // TODO: custom transport that allows setting timeout
-    let transport =
-        create_instrumented_transport(HttpTransport::new(args.shared.node_url), metrics.clone());
+    let node_transport = FailoverTransport::http(
+        args.shared.node_urls(),
+        args.shared.failover_config(),
+        metrics.clone(),
+    );
+    let transport = create_instrumented_transport(node_transport.clone(), metrics.clone());
+    let transport = match args.shared.node_response_cache_size {
+        0 => DynTransport::new(transport),
+        cache_size => DynTransport::new(CachingTransport::new(
+            transport,
+            cache_size,
+            metrics.clone(),
+        )),
+    };
     let web3 = web3::Web3::new(transport);
     let chain_id = web3
         .eth()
@@ -224,10 +402,14 @@ async fn main() {
         .expect("failed to create gas price estimator"),
     );
 
-    let current_block_stream =
-        current_block_stream(web3.clone(), args.shared.block_stream_poll_interval_seconds)
-            .await
-            .unwrap();
+    let current_block_stream = subscribed_current_block_stream(
+        web3.clone(),
+        args.shared.node_ws_url.clone(),
+        args.shared.block_stream_poll_interval_seconds,
+        metrics.clone(),
+    )
+    .await
+    .unwrap();
 
     let cache_config = CacheConfig {
         number_of_blocks_to_cache: args.shared.pool_cache_blocks,
@@ -294,13 +476,41 @@ async fn main() {
         (None, None)
     };
 
-    let price_estimator = Arc::new(BaselinePriceEstimator::new(
-        pool_aggregator,
-        gas_price_estimator.clone(),
-        base_tokens.clone(),
-        // Order book already filters bad tokens
-        Arc::new(ListBasedDetector::deny_list(Vec::new())),
-        native_token_contract.address(),
+    let mut price_estimators: Vec<(PriceSource, Arc<dyn PriceEstimating>)> = vec![(
+        PriceSource::Baseline,
+        Arc::new(BaselinePriceEstimator::new(
+            pool_aggregator,
+            gas_price_estimator.clone(),
+            base_tokens.clone(),
+            // Order book already filters bad tokens
+            Arc::new(ListBasedDetector::deny_list(Vec::new())),
+            native_token_contract.address(),
+        )),
+    )];
+    if !args.chainlink_feeds.is_empty() {
+        price_estimators.push((
+            PriceSource::Oracle,
+            Arc::new(ChainlinkPriceEstimator::from_aggregators(
+                &web3,
+                args.chainlink_feeds,
+                token_info_fetcher.clone(),
+                native_token_contract.address(),
+                args.chainlink_max_age,
+            )),
+        ));
+    }
+    if !args.static_prices.is_empty() {
+        price_estimators.push((
+            PriceSource::Static,
+            Arc::new(StaticPriceEstimator::new(
+                args.static_prices.into_iter().collect(),
+                native_token_contract.address(),
+            )),
+        ));
+    }
+    let price_estimator = Arc::new(CompositePriceEstimator::new(
+        price_estimators,
+        metrics.clone(),
     ));
     let uniswap_like_liquidity = build_amm_artifacts(
         &pool_caches,
@@ -327,17 +537,52 @@ async fn main() {
         args.disabled_one_inch_protocols,
         account.address(),
         args.paraswap_slippage_bps,
+        args.aggregator_order_batching,
+        metrics.clone(),
     )
     .expect("failure creating solvers");
     let liquidity_collector = LiquidityCollector {
         uniswap_like_liquidity,
         orderbook_api,
         balancer_v2_liquidity,
+        order_policy: Some(OrderPolicy::new(
+            OrderPolicyConfig {
+                min_order_values: args.min_order_values.into_iter().collect(),
+                max_orders_per_owner: args.max_orders_per_owner,
+                priority: args.order_priority,
+                allowed_tokens: group_by_solver(args.solver_allowed_tokens),
+                denied_tokens: group_by_solver(args.solver_denied_tokens),
+            },
+            price_estimator.clone(),
+            native_token_contract.address(),
+            metrics.clone(),
+        )),
     };
     let market_makable_token_list = TokenList::from_url(&args.market_makable_token_list, chain_id)
         .await
         .map_err(|err| tracing::error!("Couldn't fetch market makable token list: {}", err))
         .ok();
+    let to_rational = |value: f64| BigRational::from_float(value).expect("value is not finite");
+    let objective = Objective {
+        objective_type: args.objective,
+        fee_weight: to_rational(args.objective_fee_weight),
+        min_profit: args.min_settlement_profit.map(to_rational),
+        untrusted_liquidity_penalty: to_rational(args.untrusted_liquidity_penalty),
+    };
+    let score_recorder = args.score_record_file.as_ref().map(|path| {
+        ScoreRecorder::create(path).expect("failed to create settlement score recorder")
+    });
+    let settlement_simulator: Arc<dyn SettlementSimulating> = match args.settlement_simulator {
+        SimulatorType::Node => Arc::new(NodeSettlementSimulator {
+            contract: settlement_contract.clone(),
+            web3: web3.clone(),
+            network_id,
+        }),
+        SimulatorType::Local => Arc::new(LocalSettlementSimulator::new(
+            settlement_contract.clone(),
+            StateOrigin::Node(web3.clone()),
+        )),
+    };
     let mut driver = Driver::new(
         settlement_contract,
         liquidity_collector,
@@ -349,14 +594,17 @@ async fn main() {
         native_token_contract.address(),
         args.min_order_age,
         metrics,
-        web3,
-        network_id,
+        settlement_simulator,
         args.max_merged_settlements,
+        args.max_settlements_per_run,
         args.solver_time_limit,
         args.gas_price_cap,
         market_makable_token_list,
+        chain_id,
         current_block_stream.clone(),
         args.shared.fee_discount_factor,
+        objective,
+        score_recorder,
     );
 
     let maintainer = ServiceMaintenance {
@@ -364,11 +612,38 @@ async fn main() {
             .into_iter()
             .map(|(_, cache)| cache as Arc<dyn Maintaining>)
             .chain(balancer_pool_maintainer)
+            .chain(std::iter::once(
+                Arc::new(node_transport) as Arc<dyn Maintaining>
+            ))
             .collect(),
+        heartbeat: Default::default(),
     };
+    let health = Arc::new(HealthReporter::new(vec![
+        (
+            "current_block",
+            Arc::new(CurrentBlockHealth {
+                block_stream: current_block_stream.clone(),
+                max_age: args.shared.health_max_block_age,
+            }) as Arc<dyn HealthChecking>,
+        ),
+        (
+            "maintenance",
+            Arc::new(HeartbeatHealth {
+                heartbeat: maintainer.heartbeat.clone(),
+                max_age: args.shared.health_max_block_age,
+            }) as Arc<dyn HealthChecking>,
+        ),
+        (
+            "driver",
+            Arc::new(HeartbeatHealth {
+                heartbeat: driver.run_loop_heartbeat(),
+                max_age: args.health_max_run_loop_age,
+            }) as Arc<dyn HealthChecking>,
+        ),
+    ]));
     tokio::task::spawn(maintainer.run_maintenance_on_new_block(current_block_stream));
 
-    serve_metrics(registry, ([0, 0, 0, 0], args.metrics_port).into());
+    serve_metrics(registry, health, ([0, 0, 0, 0], args.metrics_port).into());
     driver.run_forever().await;
 }
 
@@ -410,3 +685,11 @@ async fn build_amm_artifacts(
     }
     res
 }
+
+fn group_by_solver(solver_tokens: Vec<(String, H160)>) -> HashMap<String, HashSet<H160>> {
+    let mut result = HashMap::<_, HashSet<_>>::new();
+    for (solver, token) in solver_tokens {
+        result.entry(solver).or_default().insert(token);
+    }
+    result
+}
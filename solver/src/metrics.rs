@@ -9,14 +9,19 @@ use prometheus::{
     HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
 };
 use shared::{
+    current_block::{BlockSource, BlockStreamMetrics},
+    price_estimate::composite::{PriceSource, PriceSourceMetrics},
     sources::{
         balancer::pool_cache::WeightedPoolCacheMetrics, uniswap::pool_cache::PoolCacheMetrics,
     },
-    transport::instrumented::TransportMetrics,
+    transport::{caching::TransportCacheMetrics, instrumented::TransportMetrics},
 };
 use strum::{AsStaticRef, VariantNames};
 
-use crate::liquidity::Liquidity;
+use crate::{
+    liquidity::Liquidity, order_policy::OrderFilterReason,
+    settlement_simulation::failure::SimulationFailure, solver::AggregatorOutcome,
+};
 
 pub trait SolverMetrics {
     fn liquidity_fetched(&self, liquidity: &[Liquidity]);
@@ -24,8 +29,15 @@ pub trait SolverMetrics {
     fn order_settled(&self, order: &Order, solver: &'static str);
     fn settlement_simulation_succeeded(&self, solver: &'static str);
     fn settlement_simulation_failed(&self, solver: &'static str);
+    fn settlement_simulation_failure_classified(
+        &self,
+        solver: &'static str,
+        failure: &SimulationFailure,
+    );
     fn settlement_submitted(&self, successful: bool, solver: &'static str);
     fn orders_matched_but_not_settled(&self, count: usize);
+    fn aggregator_order_handled(&self, aggregator: &'static str, outcome: AggregatorOutcome);
+    fn orders_filtered(&self, reason: OrderFilterReason, count: usize);
 }
 
 // TODO add labeled interaction counter once we support more than one interaction
@@ -35,9 +47,18 @@ pub struct Metrics {
     solver_computation_time: IntCounterVec,
     liquidity: IntGaugeVec,
     settlement_simulations: IntCounterVec,
+    settlement_simulation_failures: IntCounterVec,
+    token_simulation_failures: IntCounter,
     settlement_submissions: IntCounterVec,
     matched_but_unsettled_orders: IntCounter,
+    aggregator_orders: IntCounterVec,
+    filtered_orders: IntCounterVec,
+    price_estimates: IntCounterVec,
     transport_requests: HistogramVec,
+    node_health: IntGaugeVec,
+    block_arrival_latency: HistogramVec,
+    transport_cache_hits: IntCounterVec,
+    transport_cache_misses: IntCounterVec,
     pool_cache_hits: IntCounter,
     pool_cache_misses: IntCounter,
 }
@@ -83,6 +104,21 @@ impl Metrics {
         )?;
         registry.register(Box::new(settlement_simulations.clone()))?;
 
+        let settlement_simulation_failures = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_solver_settlement_simulation_failures",
+                "Settlement simulation failure counts labeled by the component that caused the revert",
+            ),
+            &["kind", "solver_type"],
+        )?;
+        registry.register(Box::new(settlement_simulation_failures.clone()))?;
+
+        let token_simulation_failures = IntCounter::new(
+            "gp_v2_solver_token_simulation_failures",
+            "Settlement simulation failure counts caused by a failing token transfer or approval",
+        )?;
+        registry.register(Box::new(token_simulation_failures.clone()))?;
+
         let settlement_submissions = IntCounterVec::new(
             Opts::new(
                 "gp_v2_solver_settlement_submissions",
@@ -98,6 +134,33 @@ impl Metrics {
         )?;
         registry.register(Box::new(matched_but_unsettled_orders.clone()))?;
 
+        let aggregator_orders = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_solver_aggregator_orders",
+                "Counter for the orders handled by aggregator solvers labeled by their outcome",
+            ),
+            &["result", "aggregator"],
+        )?;
+        registry.register(Box::new(aggregator_orders.clone()))?;
+
+        let filtered_orders = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_solver_filtered_orders",
+                "Counter for the orders not passed to solvers by the order policy labeled by the reason",
+            ),
+            &["reason"],
+        )?;
+        registry.register(Box::new(filtered_orders.clone()))?;
+
+        let price_estimates = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_solver_price_estimates",
+                "Counter for the estimated prices labeled by the source that provided them",
+            ),
+            &["source"],
+        )?;
+        registry.register(Box::new(price_estimates.clone()))?;
+
         let opts = HistogramOpts::new(
             "gp_v2_solver_transport_requests",
             "RPC Request durations labelled by method",
@@ -105,6 +168,43 @@ impl Metrics {
         let transport_requests = HistogramVec::new(opts, &["method"]).unwrap();
         registry.register(Box::new(transport_requests.clone()))?;
 
+        let node_health = IntGaugeVec::new(
+            Opts::new(
+                "gp_v2_solver_node_healthy",
+                "Whether the last request to a node succeeded labelled by node",
+            ),
+            &["node"],
+        )?;
+        registry.register(Box::new(node_health.clone()))?;
+
+        let block_arrival_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "gp_v2_solver_block_arrival_latency_seconds",
+                "Time between the timestamp of a new block and observing it labelled by how it was observed",
+            )
+            .buckets(vec![0.5, 1., 2., 4., 8., 16., 32.]),
+            &["source"],
+        )?;
+        registry.register(Box::new(block_arrival_latency.clone()))?;
+
+        let transport_cache_hits = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_solver_transport_cache_hits",
+                "Number of node requests served from the response cache labelled by method",
+            ),
+            &["method"],
+        )?;
+        registry.register(Box::new(transport_cache_hits.clone()))?;
+
+        let transport_cache_misses = IntCounterVec::new(
+            Opts::new(
+                "gp_v2_solver_transport_cache_misses",
+                "Number of cacheable node requests missing from the response cache labelled by method",
+            ),
+            &["method"],
+        )?;
+        registry.register(Box::new(transport_cache_misses.clone()))?;
+
         let pool_cache_hits = IntCounter::new(
             "gp_v2_solver_pool_cache_hits",
             "Number of cache hits in the pool fetcher cache.",
@@ -123,9 +223,18 @@ impl Metrics {
             solver_computation_time,
             liquidity,
             settlement_simulations,
+            settlement_simulation_failures,
+            token_simulation_failures,
             settlement_submissions,
             matched_but_unsettled_orders,
+            aggregator_orders,
+            filtered_orders,
+            price_estimates,
             transport_requests,
+            node_health,
+            block_arrival_latency,
+            transport_cache_hits,
+            transport_cache_misses,
             pool_cache_hits,
             pool_cache_misses,
         })
@@ -180,6 +289,21 @@ impl SolverMetrics for Metrics {
             .inc()
     }
 
+    fn settlement_simulation_failure_classified(
+        &self,
+        solver: &'static str,
+        failure: &SimulationFailure,
+    ) {
+        self.settlement_simulation_failures
+            .with_label_values(&[failure.kind.as_static(), solver])
+            .inc();
+        // The token is part of the logged failure. It is not a label because the number of
+        // tokens is unbounded.
+        if failure.token.is_some() {
+            self.token_simulation_failures.inc();
+        }
+    }
+
     fn settlement_submitted(&self, successful: bool, solver: &'static str) {
         let result = if successful { "success" } else { "failures" };
         self.settlement_submissions
@@ -190,6 +314,18 @@ impl SolverMetrics for Metrics {
     fn orders_matched_but_not_settled(&self, count: usize) {
         self.matched_but_unsettled_orders.inc_by(count as u64);
     }
+
+    fn aggregator_order_handled(&self, aggregator: &'static str, outcome: AggregatorOutcome) {
+        self.aggregator_orders
+            .with_label_values(&[outcome.as_static(), aggregator])
+            .inc()
+    }
+
+    fn orders_filtered(&self, reason: OrderFilterReason, count: usize) {
+        self.filtered_orders
+            .with_label_values(&[reason.as_static()])
+            .inc_by(count as u64)
+    }
 }
 
 impl TransportMetrics for Metrics {
@@ -198,6 +334,39 @@ impl TransportMetrics for Metrics {
             .with_label_values(&[label])
             .observe(elapsed.as_secs_f64())
     }
+
+    fn report_node_health(&self, node: &str, healthy: bool) {
+        self.node_health
+            .with_label_values(&[node])
+            .set(healthy as i64)
+    }
+}
+
+impl BlockStreamMetrics for Metrics {
+    fn block_arrived(&self, source: BlockSource, latency: Duration) {
+        self.block_arrival_latency
+            .with_label_values(&[source.as_static()])
+            .observe(latency.as_secs_f64())
+    }
+}
+
+impl TransportCacheMetrics for Metrics {
+    fn request_looked_up(&self, method: &str, cache_hit: bool) {
+        let counter = if cache_hit {
+            &self.transport_cache_hits
+        } else {
+            &self.transport_cache_misses
+        };
+        counter.with_label_values(&[method]).inc()
+    }
+}
+
+impl PriceSourceMetrics for Metrics {
+    fn price_estimated(&self, source: PriceSource) {
+        self.price_estimates
+            .with_label_values(&[source.as_static()])
+            .inc()
+    }
 }
 
 impl PoolCacheMetrics for Metrics {
@@ -225,8 +394,11 @@ impl SolverMetrics for NoopMetrics {
     fn order_settled(&self, _: &Order, _: &'static str) {}
     fn settlement_simulation_succeeded(&self, _: &'static str) {}
     fn settlement_simulation_failed(&self, _: &'static str) {}
+    fn settlement_simulation_failure_classified(&self, _: &'static str, _: &SimulationFailure) {}
     fn settlement_submitted(&self, _: bool, _: &'static str) {}
     fn orders_matched_but_not_settled(&self, _: usize) {}
+    fn aggregator_order_handled(&self, _: &'static str, _: AggregatorOutcome) {}
+    fn orders_filtered(&self, _: OrderFilterReason, _: usize) {}
 }
 
 #[cfg(test)]
@@ -241,7 +413,18 @@ mod tests {
         metrics.order_settled(&Default::default(), "test");
         metrics.settlement_simulation_succeeded("test");
         metrics.settlement_simulation_failed("test");
+        metrics.settlement_simulation_failure_classified(
+            "test",
+            &SimulationFailure {
+                token: Some(Default::default()),
+                ..SimulationFailure::from_reason(None)
+            },
+        );
         metrics.settlement_submitted(true, "test");
         metrics.orders_matched_but_not_settled(20);
+        metrics.aggregator_order_handled("test", AggregatorOutcome::Settled);
+        metrics.orders_filtered(OrderFilterReason::MinValue, 2);
+        metrics.price_estimated(PriceSource::Oracle);
+        metrics.request_looked_up("eth_call", true);
     }
 }
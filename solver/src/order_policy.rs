@@ -0,0 +1,402 @@
+//! Policies deciding which orders of the orderbook are passed to the solvers and in which order.
+
+use crate::{liquidity::Liquidity, metrics::SolverMetrics};
+use anyhow::{Context, Result};
+use ethcontract::{H160, U256};
+use model::order::{Order, BUY_ETH_ADDRESS};
+use num::BigRational;
+use shared::{arguments::split_pair, conversions::U256Ext, price_estimate::PriceEstimating};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use structopt::clap::arg_enum;
+use strum_macros::AsStaticStr;
+
+arg_enum! {
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum OrderPriority {
+        Age,
+        Fee,
+        Surplus,
+    }
+}
+
+/// The reason why orders were not passed to (some of) the solvers.
+#[derive(AsStaticStr, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OrderFilterReason {
+    /// The order sells less than the minimum value configured for its tokens.
+    MinValue,
+    /// The owner has more orders than allowed and the order has a lower priority.
+    MaxOrdersPerOwner,
+    /// The order trades a token that is not allowed for a solver.
+    SolverTokenList,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OrderPolicyConfig {
+    /// The minimum value in wei of the native token an order trading the token has to sell.
+    pub min_order_values: HashMap<H160, U256>,
+    /// The maximum number of orders per owner that are passed to the solvers. The orders with
+    /// the highest priority are kept.
+    pub max_orders_per_owner: Option<usize>,
+    /// How orders are sorted before being passed to the solvers. If unset, the order of the
+    /// orderbook is kept.
+    pub priority: Option<OrderPriority>,
+    /// Per solver name, the tokens orders passed to the solver are allowed to trade.
+    pub allowed_tokens: HashMap<String, HashSet<H160>>,
+    /// Per solver name, the tokens orders passed to the solver are not allowed to trade.
+    pub denied_tokens: HashMap<String, HashSet<H160>>,
+}
+
+/// Filters and sorts the orders of the orderbook according to its configuration before they
+/// are passed to the solvers.
+pub struct OrderPolicy {
+    config: OrderPolicyConfig,
+    price_estimator: Arc<dyn PriceEstimating>,
+    native_token: H160,
+    metrics: Arc<dyn SolverMetrics + Send + Sync>,
+}
+
+impl OrderPolicy {
+    pub fn new(
+        config: OrderPolicyConfig,
+        price_estimator: Arc<dyn PriceEstimating>,
+        native_token: H160,
+        metrics: Arc<dyn SolverMetrics + Send + Sync>,
+    ) -> Self {
+        Self {
+            config,
+            price_estimator,
+            native_token,
+            metrics,
+        }
+    }
+
+    /// Removes the orders that should not be solved and sorts the remaining ones by priority.
+    pub async fn apply(&self, orders: Vec<Order>) -> Vec<Order> {
+        let prices = if self.needs_prices() {
+            self.estimate_prices(&orders).await
+        } else {
+            HashMap::new()
+        };
+        let (orders, filtered) = apply_policy(&self.config, orders, &prices, self.native_token);
+        for (reason, count) in filtered {
+            tracing::debug!("filtered {} orders because of {:?}", count, reason);
+            self.metrics.orders_filtered(reason, count);
+        }
+        orders
+    }
+
+    /// Returns the liquidity the specified solver is allowed to use, i.e. without the limit orders
+    /// trading tokens that are not allowed for the solver.
+    pub fn liquidity_for_solver(&self, solver: &str, liquidity: &[Liquidity]) -> Vec<Liquidity> {
+        let allowed = self.config.allowed_tokens.get(solver);
+        let denied = self.config.denied_tokens.get(solver);
+        let is_allowed = |token: &H160| {
+            allowed.map(|tokens| tokens.contains(token)).unwrap_or(true)
+                && !denied.map(|tokens| tokens.contains(token)).unwrap_or(false)
+        };
+
+        let (liquidity, filtered): (Vec<_>, Vec<_>) =
+            liquidity
+                .iter()
+                .cloned()
+                .partition(|liquidity| match liquidity {
+                    Liquidity::Limit(order) => {
+                        is_allowed(&order.sell_token) && is_allowed(&order.buy_token)
+                    }
+                    _ => true,
+                });
+        if !filtered.is_empty() {
+            tracing::debug!("filtered {} orders for solver {}", filtered.len(), solver);
+            self.metrics
+                .orders_filtered(OrderFilterReason::SolverTokenList, filtered.len());
+        }
+        liquidity
+    }
+
+    fn needs_prices(&self) -> bool {
+        !self.config.min_order_values.is_empty()
+            || matches!(
+                self.config.priority,
+                Some(OrderPriority::Fee) | Some(OrderPriority::Surplus)
+            )
+    }
+
+    async fn estimate_prices(&self, orders: &[Order]) -> HashMap<H160, BigRational> {
+        let tokens = orders
+            .iter()
+            .flat_map(|order| {
+                vec![
+                    order.order_creation.sell_token,
+                    buy_token(order, self.native_token),
+                ]
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        self.price_estimator
+            .estimate_prices(&tokens, self.native_token)
+            .await
+            .into_iter()
+            .zip(tokens)
+            .filter_map(|(result, token)| Some((token, result.ok()?)))
+            .collect()
+    }
+}
+
+/// Applies the policy to the orders given the prices of their tokens in the native token. Returns
+/// the remaining orders and how many orders were removed for which reason.
+fn apply_policy(
+    config: &OrderPolicyConfig,
+    orders: Vec<Order>,
+    prices: &HashMap<H160, BigRational>,
+    native_token: H160,
+) -> (Vec<Order>, Vec<(OrderFilterReason, usize)>) {
+    let mut filtered = Vec::new();
+
+    let order_count = orders.len();
+    let mut orders = orders
+        .into_iter()
+        .filter(|order| has_min_value(config, order, prices, native_token))
+        .collect::<Vec<_>>();
+    filtered.push((OrderFilterReason::MinValue, order_count - orders.len()));
+
+    match config.priority {
+        Some(OrderPriority::Age) => {
+            orders.sort_by_key(|order| order.order_meta_data.creation_date);
+        }
+        Some(OrderPriority::Fee) => {
+            orders.sort_by_cached_key(|order| Reverse(fee_value(order, prices)));
+        }
+        Some(OrderPriority::Surplus) => {
+            orders.sort_by_cached_key(|order| {
+                Reverse(estimated_surplus(order, prices, native_token))
+            });
+        }
+        None => (),
+    }
+
+    if let Some(max_orders_per_owner) = config.max_orders_per_owner {
+        let order_count = orders.len();
+        let mut owner_counts = HashMap::<H160, usize>::new();
+        orders.retain(|order| {
+            let count = owner_counts.entry(order.order_meta_data.owner).or_default();
+            *count += 1;
+            *count <= max_orders_per_owner
+        });
+        filtered.push((
+            OrderFilterReason::MaxOrdersPerOwner,
+            order_count - orders.len(),
+        ));
+    }
+
+    filtered.retain(|(_, count)| *count > 0);
+    (orders, filtered)
+}
+
+/// The token an order buys with the native token in place of ETH, as the prices are for ERC20
+/// tokens.
+fn buy_token(order: &Order, native_token: H160) -> H160 {
+    if order.order_creation.buy_token == BUY_ETH_ADDRESS {
+        native_token
+    } else {
+        order.order_creation.buy_token
+    }
+}
+
+/// Checks that the order sells at least the largest minimum value configured for its tokens.
+/// Orders whose value can't be estimated only pass if no minimum is configured for them.
+fn has_min_value(
+    config: &OrderPolicyConfig,
+    order: &Order,
+    prices: &HashMap<H160, BigRational>,
+    native_token: H160,
+) -> bool {
+    let sell_token = order.order_creation.sell_token;
+    let min_value = [sell_token, buy_token(order, native_token)]
+        .iter()
+        .filter_map(|token| config.min_order_values.get(token))
+        .max();
+    let min_value = match min_value {
+        Some(min_value) => min_value,
+        None => return true,
+    };
+    prices
+        .get(&sell_token)
+        .map(|price| {
+            price * order.order_creation.sell_amount.to_big_rational()
+                >= min_value.to_big_rational()
+        })
+        .unwrap_or(false)
+}
+
+/// The value of the order's fee in the native token.
+fn fee_value(order: &Order, prices: &HashMap<H160, BigRational>) -> Option<BigRational> {
+    let price = prices.get(&order.order_creation.sell_token)?;
+    Some(price * order.order_creation.fee_amount.to_big_rational())
+}
+
+/// The surplus of the order in the native token if it was executed at the estimated prices.
+fn estimated_surplus(
+    order: &Order,
+    prices: &HashMap<H160, BigRational>,
+    native_token: H160,
+) -> Option<BigRational> {
+    let sell_price = prices.get(&order.order_creation.sell_token)?;
+    let buy_price = prices.get(&buy_token(order, native_token))?;
+    Some(
+        order.order_creation.sell_amount.to_big_rational() * sell_price
+            - order.order_creation.buy_amount.to_big_rational() * buy_price,
+    )
+}
+
+/// Parses a `token=value` pair, where the value is in whole units of the native token.
+pub fn parse_token_value(s: &str) -> Result<(H160, U256)> {
+    let (token, value) = split_pair(s, '=')?;
+    Ok((
+        token.parse().context("invalid token address")?,
+        shared::arguments::wei_from_base_unit(value)?,
+    ))
+}
+
+/// Parses a `solver:token` pair, where the solver is identified by its name.
+pub fn parse_solver_token(s: &str) -> Result<(String, H160)> {
+    let (solver, token) = split_pair(s, ':')?;
+    Ok((
+        solver.to_string(),
+        token.parse().context("invalid token address")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use maplit::{hashmap, hashset};
+    use model::order::{OrderCreation, OrderMetaData};
+
+    fn order(owner: u64, sell_amount: u64, fee_amount: u64, created: i64) -> Order {
+        Order {
+            order_creation: OrderCreation {
+                sell_token: H160::from_low_u64_be(1),
+                buy_token: H160::from_low_u64_be(2),
+                sell_amount: sell_amount.into(),
+                buy_amount: 100.into(),
+                fee_amount: fee_amount.into(),
+                ..Default::default()
+            },
+            order_meta_data: OrderMetaData {
+                owner: H160::from_low_u64_be(owner),
+                creation_date: DateTime::from_utc(NaiveDateTime::from_timestamp(created, 0), Utc),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn prices() -> HashMap<H160, BigRational> {
+        hashmap! {
+            H160::from_low_u64_be(1) => BigRational::from_integer(1.into()),
+            H160::from_low_u64_be(2) => BigRational::from_integer(1.into()),
+        }
+    }
+
+    #[test]
+    fn filters_orders_below_min_value() {
+        let config = OrderPolicyConfig {
+            min_order_values: hashmap! {
+                H160::from_low_u64_be(1) => 100.into(),
+                H160::from_low_u64_be(2) => 150.into(),
+            },
+            ..Default::default()
+        };
+        let orders = vec![order(1, 100, 0, 0), order(1, 150, 0, 0)];
+        let (orders, filtered) = apply_policy(&config, orders, &prices(), H160::zero());
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].order_creation.sell_amount, 150.into());
+        assert_eq!(filtered, vec![(OrderFilterReason::MinValue, 1)]);
+
+        // Without a price the value of the order is unknown.
+        let orders = vec![order(1, 150, 0, 0)];
+        let (orders, _) = apply_policy(&config, orders, &HashMap::new(), H160::zero());
+        assert!(orders.is_empty());
+    }
+
+    #[test]
+    fn keeps_orders_with_highest_priority_per_owner() {
+        let orders = vec![
+            order(1, 200, 1, 3),
+            order(1, 110, 3, 1),
+            order(1, 150, 2, 2),
+            order(2, 100, 0, 4),
+        ];
+        let sell_amounts = |priority| {
+            let config = OrderPolicyConfig {
+                max_orders_per_owner: Some(2),
+                priority: Some(priority),
+                ..Default::default()
+            };
+            let (orders, filtered) = apply_policy(&config, orders.clone(), &prices(), H160::zero());
+            assert_eq!(filtered, vec![(OrderFilterReason::MaxOrdersPerOwner, 1)]);
+            orders
+                .iter()
+                .map(|order| order.order_creation.sell_amount.as_u64())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sell_amounts(OrderPriority::Age), vec![110, 150, 100]);
+        assert_eq!(sell_amounts(OrderPriority::Fee), vec![110, 150, 100]);
+        assert_eq!(sell_amounts(OrderPriority::Surplus), vec![200, 150, 100]);
+    }
+
+    #[test]
+    fn filters_liquidity_by_solver_token_lists() {
+        let policy = OrderPolicy::new(
+            OrderPolicyConfig {
+                allowed_tokens: hashmap! {
+                    "Allowing".to_string() => hashset! { H160::from_low_u64_be(1) },
+                },
+                denied_tokens: hashmap! {
+                    "Denying".to_string() => hashset! { H160::from_low_u64_be(2) },
+                },
+                ..Default::default()
+            },
+            Arc::new(shared::price_estimate::mocks::FailingPriceEstimator()),
+            H160::zero(),
+            Arc::new(crate::metrics::NoopMetrics::default()),
+        );
+        let limit_order = |sell_token, buy_token| {
+            Liquidity::Limit(crate::liquidity::LimitOrder {
+                sell_token: H160::from_low_u64_be(sell_token),
+                buy_token: H160::from_low_u64_be(buy_token),
+                ..Default::default()
+            })
+        };
+        let liquidity = vec![limit_order(1, 3), limit_order(3, 1), limit_order(1, 1)];
+
+        assert_eq!(policy.liquidity_for_solver("Allowing", &liquidity).len(), 1);
+        assert_eq!(policy.liquidity_for_solver("Denying", &liquidity).len(), 3);
+        assert_eq!(
+            policy
+                .liquidity_for_solver("Denying", &[limit_order(2, 1)])
+                .len(),
+            0
+        );
+        assert_eq!(policy.liquidity_for_solver("Other", &liquidity).len(), 3);
+    }
+
+    #[test]
+    fn parses_pairs() {
+        assert_eq!(
+            parse_token_value("0x0000000000000000000000000000000000000001=2").unwrap(),
+            (H160::from_low_u64_be(1), U256::exp10(18) * 2)
+        );
+        assert_eq!(
+            parse_solver_token("1Inch:0x0000000000000000000000000000000000000001").unwrap(),
+            ("1Inch".to_string(), H160::from_low_u64_be(1))
+        );
+        assert!(parse_solver_token("1Inch").is_err());
+    }
+}
@@ -1,31 +1,29 @@
 use contracts::WETH9;
-use model::order::Order;
-use reqwest::{Client, Url};
+use model::{api::SolverSettings, order::Order};
+use orderbook_client::OrderbookClient;
+use reqwest::Url;
 use std::time::Duration;
 
 pub struct OrderBookApi {
-    base: Url,
-    client: Client,
+    client: OrderbookClient,
     native_token: WETH9,
 }
 
 impl OrderBookApi {
     /// base: protocol and host of the url. example: `https://example.com`
     pub fn new(base: Url, request_timeout: Duration, native_token: WETH9) -> Self {
-        // Unwrap because we cannot handle client creation failing.
-        let client = Client::builder().timeout(request_timeout).build().unwrap();
         Self {
-            base,
-            client,
+            client: OrderbookClient::new(base, request_timeout),
             native_token,
         }
     }
 
-    pub async fn get_orders(&self) -> reqwest::Result<Vec<Order>> {
-        const PATH: &str = "/api/v1/solvable_orders";
-        let mut url = self.base.clone();
-        url.set_path(PATH);
-        self.client.get(url).send().await?.json().await
+    pub async fn get_orders(&self) -> anyhow::Result<Vec<Order>> {
+        self.client.get_solvable_orders().await
+    }
+
+    pub async fn get_solver_settings(&self) -> anyhow::Result<SolverSettings> {
+        self.client.get_solver_settings().await
     }
 
     pub fn get_native_token(&self) -> WETH9 {
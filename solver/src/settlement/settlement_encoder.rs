@@ -6,7 +6,7 @@ use num::{BigRational, Zero};
 use primitive_types::{H160, U256};
 use shared::conversions::U256Ext;
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     iter,
     sync::Arc,
 };
@@ -32,6 +32,8 @@ pub struct SettlementEncoder {
     // would make the trait not be object safe which prevents using it through `dyn`.
     // TODO: Can we fix this in a better way?
     execution_plan: Vec<Arc<dyn Interaction>>,
+    // The tokens that are bought or sold through the on-chain liquidity of the execution plan.
+    onchain_liquidity_tokens: HashSet<H160>,
     unwraps: Vec<UnwrapWethInteraction>,
 }
 
@@ -57,6 +59,7 @@ impl SettlementEncoder {
             clearing_prices,
             trades: Vec::new(),
             execution_plan: Vec::new(),
+            onchain_liquidity_tokens: HashSet::new(),
             unwraps: Vec::new(),
         }
     }
@@ -75,6 +78,7 @@ impl SettlementEncoder {
             clearing_prices: self.clearing_prices.clone(),
             trades: self.trades.clone(),
             execution_plan: Vec::new(),
+            onchain_liquidity_tokens: HashSet::new(),
             unwraps: self.unwraps.clone(),
         }
     }
@@ -87,6 +91,27 @@ impl SettlementEncoder {
         &self.trades
     }
 
+    // Whether the settlement interacts with on-chain liquidity. Unwraps are not counted as they are
+    // not a source of liquidity.
+    pub fn has_onchain_liquidity(&self) -> bool {
+        !self.execution_plan.is_empty()
+    }
+
+    pub fn onchain_liquidity_tokens(&self) -> &HashSet<H160> {
+        &self.onchain_liquidity_tokens
+    }
+
+    /// The tokens that are traded or moved by interactions, including the intermediate tokens of
+    /// multi hop AMM routes.
+    pub fn touched_tokens(&self) -> HashSet<H160> {
+        self.clearing_prices
+            .keys()
+            .chain(&self.onchain_liquidity_tokens)
+            .copied()
+            .chain(self.unwraps.iter().map(|unwrap| unwrap.weth.address()))
+            .collect()
+    }
+
     // Fails if any used token doesn't have a price.
     pub fn add_trade(&mut self, order: Order, executed_amount: U256) -> Result<()> {
         let sell_token_index = self
@@ -109,6 +134,12 @@ impl SettlementEncoder {
         self.execution_plan.push(Arc::new(interaction));
     }
 
+    // Marks tokens as traded through on-chain liquidity. Has to be called for the tokens of every
+    // liquidity interaction that gets added to the execution plan.
+    pub fn add_onchain_liquidity_tokens(&mut self, tokens: impl IntoIterator<Item = H160>) {
+        self.onchain_liquidity_tokens.extend(tokens);
+    }
+
     pub fn add_unwrap(&mut self, unwrap: UnwrapWethInteraction) {
         for existing_unwrap in self.unwraps.iter_mut() {
             if existing_unwrap.merge(&unwrap).is_ok() {
@@ -270,6 +301,8 @@ impl SettlementEncoder {
         self.sort_tokens_and_update_indices();
 
         self.execution_plan.append(&mut other.execution_plan);
+        self.onchain_liquidity_tokens
+            .extend(other.onchain_liquidity_tokens);
 
         for unwrap in other.unwraps {
             self.add_unwrap(unwrap);
@@ -457,6 +490,7 @@ pub mod tests {
         order13.order_meta_data.uid.0[0] = 0;
         encoder0.add_trade(order13, 13.into()).unwrap();
         encoder0.append_to_execution_plan(NoopInteraction {});
+        encoder0.add_onchain_liquidity_tokens(vec![token(1), token(3)]);
         encoder0.add_unwrap(UnwrapWethInteraction {
             weth: weth.clone(),
             amount: 1.into(),
@@ -471,6 +505,7 @@ pub mod tests {
         order24.order_meta_data.uid.0[0] = 1;
         encoder1.add_trade(order24, 24.into()).unwrap();
         encoder1.append_to_execution_plan(NoopInteraction {});
+        encoder1.add_onchain_liquidity_tokens(vec![token(4)]);
         encoder1.add_unwrap(UnwrapWethInteraction {
             weth,
             amount: 2.into(),
@@ -485,6 +520,10 @@ pub mod tests {
         assert_eq!(merged.tokens, [token(1), token(2), token(3), token(4)]);
         assert_eq!(merged.trades.len(), 2);
         assert_eq!(merged.execution_plan.len(), 2);
+        assert_eq!(
+            merged.onchain_liquidity_tokens,
+            maplit::hashset! { token(1), token(3), token(4) }
+        );
         assert_eq!(merged.unwraps[0].amount, 3.into());
     }
 
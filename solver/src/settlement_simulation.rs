@@ -1,13 +1,30 @@
-use crate::encoding::EncodedSettlement;
-use anyhow::{Error, Result};
+pub mod failure;
+pub mod local;
+
+use self::failure::SimulationFailure;
+use crate::{encoding::EncodedSettlement, settlement::Settlement};
+use anyhow::{Context as _, Error, Result};
 use contracts::GPv2Settlement;
 use ethcontract::{batch::CallBatch, dyns::DynTransport, transaction::TransactionBuilder};
 use futures::FutureExt;
 use shared::Web3;
-use web3::types::{BlockId, BlockNumber};
+use std::convert::TryFrom;
+use structopt::clap::arg_enum;
+use web3::types::{BlockId, BlockNumber, CallRequest};
+
+pub use self::local::{LocalSettlementSimulator, SimulationOutcome, StateSnapshot};
 
 const SIMULATE_BATCH_SIZE: usize = 10;
 
+arg_enum! {
+    #[derive(Debug)]
+    pub enum SimulatorType {
+        Node,
+        Local,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum Block {
     // Simulate the transactions at this block and attach a tenderly link to errors.
     FixedWithTenderly(u64),
@@ -15,6 +32,82 @@ pub enum Block {
     LatestWithoutTenderly,
 }
 
+/// A backend that can simulate settlements before they get submitted.
+///
+/// Implementations are interchangeable so that the driver and tests can
+/// decide whether simulations go through the node or through an in-process
+/// EVM.
+#[async_trait::async_trait]
+pub trait SettlementSimulating: Send + Sync {
+    /// Simulates the settlements at the specified block, returning one result
+    /// per settlement in the same order.
+    async fn simulate_settlements(
+        &self,
+        settlements: Vec<EncodedSettlement>,
+        block: Block,
+    ) -> Result<Vec<Result<()>>>;
+
+    /// Simulates a settlement that is expected to fail and classifies why it
+    /// failed. Returns `None` if the settlement does not fail at that block.
+    async fn diagnose_failure(
+        &self,
+        settlement: &Settlement,
+        block: Block,
+    ) -> Result<Option<SimulationFailure>>;
+}
+
+/// Simulates settlements with an `eth_call` against the node.
+pub struct NodeSettlementSimulator {
+    pub contract: GPv2Settlement,
+    pub web3: Web3,
+    pub network_id: String,
+}
+
+#[async_trait::async_trait]
+impl SettlementSimulating for NodeSettlementSimulator {
+    async fn simulate_settlements(
+        &self,
+        settlements: Vec<EncodedSettlement>,
+        block: Block,
+    ) -> Result<Vec<Result<()>>> {
+        simulate_settlements(
+            settlements.into_iter(),
+            &self.contract,
+            &self.web3,
+            &self.network_id,
+            block,
+        )
+        .await
+    }
+
+    async fn diagnose_failure(
+        &self,
+        settlement: &Settlement,
+        block: Block,
+    ) -> Result<Option<SimulationFailure>> {
+        let encoded = EncodedSettlement::from(settlement.clone());
+        let tx = crate::settlement_submission::retry::settle_method_builder(
+            &self.contract,
+            encoded.clone(),
+        )
+        .tx;
+        let request = CallRequest {
+            from: tx.from.map(|account| account.address()),
+            to: tx.to,
+            data: tx.data,
+            ..Default::default()
+        };
+        let block = match block {
+            Block::FixedWithTenderly(block) => BlockNumber::Number(block.into()),
+            Block::LatestWithoutTenderly => BlockNumber::Latest,
+        };
+        let trace = shared::trace_many::trace_call(request, block, &self.web3)
+            .await
+            .context("failed to trace settlement")?;
+        failure::attribute_failure(settlement, &encoded, &trace)
+    }
+}
+
 /// Simulate the settlement using a web3 `call`.
 // Clippy claims we don't need to collect `futures` but we do or the lifetimes with `join!` don't
 // work out.
@@ -73,12 +166,56 @@ pub fn tenderly_link(
     )
 }
 
+/// Decodes the reason string of a Solidity `Error(string)` revert.
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+    let data = output.strip_prefix(&ERROR_SELECTOR)?;
+    let offset = word_as_usize(data.get(0..32)?)?;
+    let length = word_as_usize(data.get(offset..offset.checked_add(32)?)?)?;
+    let start = offset + 32;
+    let reason = data.get(start..start.checked_add(length)?)?;
+    String::from_utf8(reason.to_vec()).ok()
+}
+
+fn word_as_usize(word: &[u8]) -> Option<usize> {
+    let (high, low) = word.split_at(24);
+    if high.iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(low);
+    usize::try_from(u64::from_be_bytes(bytes)).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ethcontract::{Account, PrivateKey};
+    use hex_literal::hex;
     use shared::transport::create_env_test_transport;
 
+    #[test]
+    fn decodes_error_string_revert_reason() {
+        let output = hex!(
+            "08c379a0
+             0000000000000000000000000000000000000000000000000000000000000020
+             000000000000000000000000000000000000000000000000000000000000001f
+             475076323a206c696d6974207072696365206e6f742072657370656374656400"
+        );
+        assert_eq!(
+            decode_revert_reason(&output).unwrap(),
+            "GPv2: limit price not respected"
+        );
+    }
+
+    #[test]
+    fn does_not_decode_malformed_revert_reason() {
+        assert_eq!(decode_revert_reason(&[]), None);
+        assert_eq!(decode_revert_reason(&hex!("08c379a0")), None);
+        assert_eq!(decode_revert_reason(&hex!("deadbeef")), None);
+    }
+
     // cargo test -p solver settlement_simulation::tests::mainnet -- --ignored --nocapture
     #[tokio::test]
     #[ignore]
@@ -0,0 +1,464 @@
+//! Classification and attribution of settlement simulation failures.
+//!
+//! A failing simulation by itself only tells us that a settlement reverted. In
+//! order to automatically identify misbehaving solvers and tokens, failures are
+//! classified based on their revert reason and attributed to the interaction
+//! or trade of the settlement that caused the revert using a call trace.
+
+use crate::{encoding::EncodedSettlement, settlement::Settlement};
+use anyhow::{anyhow, Result};
+use hex_literal::hex;
+use model::order::OrderUid;
+use primitive_types::H160;
+use std::fmt::{self, Display, Formatter};
+use strum_macros::AsStaticStr;
+use web3::types::{Action, BlockTrace, Call, TransactionTrace};
+
+const ERC20_TRANSFER: [u8; 4] = hex!("a9059cbb");
+const ERC20_TRANSFER_FROM: [u8; 4] = hex!("23b872dd");
+const ERC20_APPROVE: [u8; 4] = hex!("095ea7b3");
+
+/// The component in which a settlement reverted.
+#[derive(AsStaticStr, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FailureKind {
+    /// The settlement contract itself rejected the settlement, for example
+    /// because of a limit price violation.
+    Settlement,
+    /// An ERC20 token transfer or approval failed.
+    Erc20,
+    /// An AMM interaction (Uniswap, Sushiswap, Balancer) failed.
+    Amm,
+    Unknown,
+}
+
+/// The part of the settlement that caused it to revert.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FailureLocation {
+    /// An interaction at the specified index of the specified stage.
+    Interaction {
+        stage: usize,
+        index: usize,
+        target: H160,
+    },
+    /// The token transfer of the trade at the specified index.
+    Trade { index: usize, order: OrderUid },
+    /// The revert could not be attributed to a specific part of the settlement.
+    Unknown,
+}
+
+/// A classified settlement simulation failure.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub struct SimulationFailure {
+    pub kind: FailureKind,
+    /// The decoded revert reason, if any.
+    pub reason: Option<String>,
+    pub location: FailureLocation,
+    /// The token whose transfer or approval failed.
+    pub token: Option<H160>,
+}
+
+impl SimulationFailure {
+    /// Creates a failure that is only classified by its revert reason.
+    pub fn from_reason(reason: Option<String>) -> Self {
+        Self {
+            kind: reason
+                .as_deref()
+                .map(classify_reason)
+                .unwrap_or(FailureKind::Unknown),
+            reason,
+            location: FailureLocation::Unknown,
+            token: None,
+        }
+    }
+}
+
+impl Display for SimulationFailure {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} failure with reason {:?}",
+            self.kind,
+            self.reason.as_deref().unwrap_or("<none>")
+        )?;
+        match &self.location {
+            FailureLocation::Interaction {
+                stage,
+                index,
+                target,
+            } => write!(
+                f,
+                " in interaction {} of stage {} calling {:?}",
+                index, stage, target
+            )?,
+            FailureLocation::Trade { index, order } => {
+                write!(f, " in trade {} for order {}", index, order)?
+            }
+            FailureLocation::Unknown => (),
+        }
+        if let Some(token) = self.token {
+            write!(f, " involving token {:?}", token)?;
+        }
+        Ok(())
+    }
+}
+
+/// Classifies a revert reason string based on well known error messages.
+pub fn classify_reason(reason: &str) -> FailureKind {
+    const AMM_PREFIXES: &[&str] = &["UniswapV2", "BAL#", "Balancer"];
+    const ERC20_PATTERNS: &[&str] = &[
+        "ERC20",
+        "SafeERC20",
+        "TRANSFER_FAILED",
+        "TRANSFER_FROM_FAILED",
+        "transfer amount exceeds",
+        "insufficient allowance",
+        "insufficient balance",
+    ];
+
+    if reason.starts_with("GPv2") {
+        FailureKind::Settlement
+    } else if AMM_PREFIXES.iter().any(|prefix| reason.starts_with(prefix)) {
+        FailureKind::Amm
+    } else if ERC20_PATTERNS
+        .iter()
+        .any(|pattern| reason.contains(pattern))
+    {
+        FailureKind::Erc20
+    } else {
+        FailureKind::Unknown
+    }
+}
+
+/// Attributes a failing settlement to the interaction or trade that caused it
+/// to revert based on the `trace_call` trace of the settlement transaction.
+///
+/// Returns `None` if the trace shows that the settlement did not revert.
+pub fn attribute_failure(
+    settlement: &Settlement,
+    encoded: &EncodedSettlement,
+    trace: &BlockTrace,
+) -> Result<Option<SimulationFailure>> {
+    let traces = trace
+        .trace
+        .as_ref()
+        .ok_or_else(|| anyhow!("trace not set"))?;
+    let root = traces
+        .first()
+        .ok_or_else(|| anyhow!("expected at least one trace"))?;
+    if root.error.is_none() {
+        return Ok(None);
+    }
+
+    let reason = super::decode_revert_reason(&trace.output.0);
+    let mut failure = SimulationFailure::from_reason(reason);
+
+    // Calls made directly by the settlement contract are interactions and the
+    // vault relayer transfers. We are interested in the first one failing, as
+    // that is the one that makes the settlement revert.
+    let failing_call = match traces
+        .iter()
+        .find(|trace| trace.trace_address.len() == 1 && trace.error.is_some())
+    {
+        Some(trace) => trace,
+        // The revert happened within the settlement contract itself.
+        None => return Ok(Some(failure)),
+    };
+    // The deepest failing call within it is the origin of the revert.
+    let origin = traces
+        .iter()
+        .filter(|trace| {
+            trace.error.is_some() && trace.trace_address.starts_with(&failing_call.trace_address)
+        })
+        .max_by_key(|trace| trace.trace_address.len())
+        .unwrap_or(failing_call);
+
+    let erc20_call = as_call(origin).filter(|call| is_erc20_call(call));
+    if let Some(call) = erc20_call {
+        failure.token = Some(call.to);
+        if failure.kind == FailureKind::Unknown {
+            failure.kind = FailureKind::Erc20;
+        }
+    }
+
+    failure.location = match as_call(failing_call).and_then(|call| find_interaction(encoded, call))
+    {
+        Some(location) => location,
+        None => erc20_call
+            .and_then(|call| find_trade(settlement, call))
+            .unwrap_or(FailureLocation::Unknown),
+    };
+
+    Ok(Some(failure))
+}
+
+fn as_call(trace: &TransactionTrace) -> Option<&Call> {
+    match &trace.action {
+        Action::Call(call) => Some(call),
+        _ => None,
+    }
+}
+
+fn is_erc20_call(call: &Call) -> bool {
+    [ERC20_TRANSFER, ERC20_TRANSFER_FROM, ERC20_APPROVE]
+        .iter()
+        .any(|selector| call.input.0.starts_with(selector))
+}
+
+fn find_interaction(encoded: &EncodedSettlement, call: &Call) -> Option<FailureLocation> {
+    encoded
+        .interactions
+        .iter()
+        .enumerate()
+        .flat_map(|(stage, interactions)| {
+            interactions
+                .iter()
+                .enumerate()
+                .map(move |(index, interaction)| (stage, index, interaction))
+        })
+        .find(|(_, _, (target, _, calldata))| *target == call.to && calldata.0 == call.input.0)
+        .map(
+            |(stage, index, (target, _, _))| FailureLocation::Interaction {
+                stage,
+                index,
+                target: *target,
+            },
+        )
+}
+
+/// Finds the trade whose in or out transfer is the specified ERC20 call.
+fn find_trade(settlement: &Settlement, call: &Call) -> Option<FailureLocation> {
+    let input = &call.input.0;
+    let address_argument = |index: usize| -> Option<H160> {
+        let start = 4 + 32 * index;
+        input.get(start + 12..start + 32).map(H160::from_slice)
+    };
+
+    settlement
+        .trades()
+        .iter()
+        .enumerate()
+        .find(|(_, trade)| {
+            let order = &trade.order;
+            if input.starts_with(&ERC20_TRANSFER_FROM) {
+                order.order_creation.sell_token == call.to
+                    && address_argument(0) == Some(order.order_meta_data.owner)
+            } else if input.starts_with(&ERC20_TRANSFER) {
+                let receiver = order
+                    .order_creation
+                    .receiver
+                    .unwrap_or(order.order_meta_data.owner);
+                order.order_creation.buy_token == call.to && address_argument(0) == Some(receiver)
+            } else {
+                false
+            }
+        })
+        .map(|(index, trade)| FailureLocation::Trade {
+            index,
+            order: trade.order.order_meta_data.uid,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement::Trade;
+    use ethcontract::Bytes;
+    use maplit::hashmap;
+    use model::order::{Order, OrderCreation, OrderMetaData};
+    use serde_json::json;
+
+    fn trace(output: &str, calls: serde_json::Value) -> BlockTrace {
+        serde_json::from_value(json!({
+            "output": output,
+            "trace": calls,
+        }))
+        .unwrap()
+    }
+
+    fn call(trace_address: &[usize], to: H160, input: &[u8], error: bool) -> serde_json::Value {
+        let mut trace = json!({
+            "traceAddress": trace_address,
+            "subtraces": 0,
+            "action": {
+                "callType": "call",
+                "from": "0x0000000000000000000000000000000000000000",
+                "gas": "0x00",
+                "input": format!("0x{}", hex::encode(input)),
+                "to": to,
+                "value": "0x00"
+            },
+            "type": "call",
+        });
+        if error {
+            trace["error"] = json!("Reverted");
+        }
+        trace
+    }
+
+    fn address(byte: u8) -> H160 {
+        H160([byte; 20])
+    }
+
+    fn transfer_from(from: H160) -> Vec<u8> {
+        let mut input = ERC20_TRANSFER_FROM.to_vec();
+        input.extend_from_slice(&[0; 12]);
+        input.extend_from_slice(from.as_bytes());
+        input.extend_from_slice(&[0; 64]);
+        input
+    }
+
+    #[test]
+    fn classifies_revert_reasons() {
+        assert_eq!(
+            classify_reason("GPv2: limit price not respected"),
+            FailureKind::Settlement
+        );
+        assert_eq!(
+            classify_reason("UniswapV2Router: EXCESSIVE_INPUT_AMOUNT"),
+            FailureKind::Amm
+        );
+        assert_eq!(classify_reason("BAL#507"), FailureKind::Amm);
+        assert_eq!(
+            classify_reason("ERC20: transfer amount exceeds balance"),
+            FailureKind::Erc20
+        );
+        assert_eq!(classify_reason("something else"), FailureKind::Unknown);
+    }
+
+    #[test]
+    fn successful_trace_is_not_a_failure() {
+        let trace = trace("0x", json!([call(&[], address(0), &[], false)]));
+        let result = attribute_failure(
+            &Settlement::new(Default::default()),
+            &Default::default(),
+            &trace,
+        )
+        .unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn attributes_failure_to_interaction() {
+        let target = address(1);
+        let calldata = vec![1, 2, 3];
+        let encoded = EncodedSettlement {
+            interactions: [
+                Vec::new(),
+                vec![
+                    (address(2), 0.into(), Bytes(Vec::new())),
+                    (target, 0.into(), Bytes(calldata.clone())),
+                ],
+                Vec::new(),
+            ],
+            ..Default::default()
+        };
+        let trace = trace(
+            "0x",
+            json!([
+                call(&[], address(0), &[], true),
+                call(&[0], address(2), &[], false),
+                call(&[1], target, &calldata, true),
+                call(&[1, 0], address(3), &[0xff], true),
+            ]),
+        );
+
+        let failure = attribute_failure(&Settlement::new(Default::default()), &encoded, &trace)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            failure,
+            SimulationFailure {
+                kind: FailureKind::Unknown,
+                reason: None,
+                location: FailureLocation::Interaction {
+                    stage: 1,
+                    index: 1,
+                    target,
+                },
+                token: None,
+            }
+        );
+    }
+
+    #[test]
+    fn attributes_failing_transfer_to_trade() {
+        let owner = address(1);
+        let sell_token = address(2);
+        let buy_token = address(3);
+        let vault_relayer = address(4);
+        let order = Order {
+            order_meta_data: OrderMetaData {
+                owner,
+                uid: OrderUid([42; 56]),
+                ..Default::default()
+            },
+            order_creation: OrderCreation {
+                sell_token,
+                buy_token,
+                ..Default::default()
+            },
+        };
+        let settlement = Settlement::with_trades(
+            hashmap! { sell_token => 1.into(), buy_token => 1.into() },
+            vec![
+                Trade::default(),
+                Trade {
+                    order,
+                    ..Default::default()
+                },
+            ],
+        );
+        let trace = trace(
+            "0x",
+            json!([
+                call(&[], address(0), &[], true),
+                call(&[0], vault_relayer, &[], true),
+                call(&[0, 0], sell_token, &transfer_from(owner), true),
+            ]),
+        );
+
+        let failure = attribute_failure(&settlement, &Default::default(), &trace)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            failure,
+            SimulationFailure {
+                kind: FailureKind::Erc20,
+                reason: None,
+                location: FailureLocation::Trade {
+                    index: 1,
+                    order: OrderUid([42; 56]),
+                },
+                token: Some(sell_token),
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_settlement_revert_without_failing_subcall() {
+        let output = format!(
+            "0x08c379a0{:064x}{:064x}{}",
+            32,
+            11,
+            hex::encode(
+                b"GPv2: error"
+                    .iter()
+                    .chain([0u8; 21].iter())
+                    .copied()
+                    .collect::<Vec<_>>()
+            )
+        );
+        let trace = trace(&output, json!([call(&[], address(0), &[], true)]));
+
+        let failure = attribute_failure(
+            &Settlement::new(Default::default()),
+            &Default::default(),
+            &trace,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(failure.kind, FailureKind::Settlement);
+        assert_eq!(failure.reason.as_deref(), Some("GPv2: error"));
+        assert_eq!(failure.location, FailureLocation::Unknown);
+    }
+}
@@ -0,0 +1,728 @@
+//! An in-process settlement simulator.
+//!
+//! Instead of relying on an `eth_call` against an (archive) node, settlements
+//! are executed in an embedded EVM. The EVM state is forked lazily: accounts
+//! and storage slots are only fetched once the execution touches them, either
+//! from the node at the simulated block or from a previously recorded state
+//! snapshot. This makes it possible to run simulations offline in tests and to
+//! inspect the effects of a settlement (gas used and token balance changes)
+//! which is not possible with a plain `eth_call`.
+//!
+//! The EVM implements the Istanbul rules. Gas costs of state access changed with Berlin so the
+//! reported gas used can be lower than on the node.
+
+use super::{decode_revert_reason, failure::SimulationFailure, Block, SettlementSimulating};
+use crate::{
+    encoding::EncodedSettlement, settlement::Settlement,
+    settlement_submission::retry::settle_method_builder,
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use contracts::GPv2Settlement;
+use evm::{
+    backend::{Backend, Basic, Log},
+    executor::{MemoryStackState, PrecompileOutput, StackExecutor, StackSubstateMetadata},
+    Config, Context, ExitError, ExitReason, ExitSucceed,
+};
+use hex_literal::hex;
+use num::BigInt;
+use primitive_types::{H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use shared::{conversions::U256Ext, Web3};
+use std::{
+    cell::Cell,
+    collections::{hash_map::Entry, HashMap},
+    sync::{Arc, Mutex},
+};
+use tokio::runtime::Handle;
+use web3::types::{BlockId, BlockNumber, Bytes};
+
+/// The gas limit used for simulated settlements.
+const SIMULATION_GAS_LIMIT: u64 = 8_000_000;
+
+/// `keccak256("Transfer(address,address,uint256)")`
+const TRANSFER_EVENT_TOPIC: H256 = H256(hex!(
+    "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+));
+
+/// Token balance changes caused by a settlement, keyed by token and then by
+/// owner.
+pub type BalanceChanges = HashMap<H160, HashMap<H160, BigInt>>;
+
+/// The effects of executing a single settlement in the local EVM.
+#[derive(Clone, Debug)]
+pub struct SimulationOutcome {
+    /// `Ok` if the settlement executed successfully, otherwise the decoded
+    /// revert reason or a description of the execution error.
+    pub result: Result<(), String>,
+    /// The decoded `Error(string)` revert reason for reverted settlements.
+    pub revert_reason: Option<String>,
+    pub gas_used: u64,
+    /// Changes in ERC20 balances as observed through `Transfer` events. This
+    /// is empty for reverted settlements.
+    pub balance_changes: BalanceChanges,
+}
+
+/// The block environment in which a settlement is executed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockEnvironment {
+    pub number: u64,
+    pub timestamp: U256,
+    pub gas_limit: U256,
+    pub coinbase: H160,
+    pub difficulty: U256,
+    pub chain_id: U256,
+}
+
+/// The state of a single account.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountState {
+    pub balance: U256,
+    pub nonce: U256,
+    pub code: Bytes,
+    pub storage: HashMap<H256, H256>,
+}
+
+/// A recorded snapshot of all state needed to simulate a settlement.
+///
+/// Snapshots can be created from a simulation that was forked from a node
+/// with [`LocalSettlementSimulator::record`] and then stored as JSON fixtures
+/// so that tests can run without a node. Accounts or storage slots that are
+/// missing from the snapshot are treated as empty.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+    pub block: BlockEnvironment,
+    pub accounts: HashMap<H160, AccountState>,
+}
+
+/// Where the local EVM gets its state from.
+#[derive(Clone)]
+pub enum StateOrigin {
+    /// Fork lazily from the node at the simulated block.
+    Node(Web3),
+    /// Serve state from a recorded snapshot. The simulated block is ignored.
+    Recorded(Arc<StateSnapshot>),
+}
+
+pub struct LocalSettlementSimulator {
+    contract: GPv2Settlement,
+    origin: StateOrigin,
+}
+
+impl LocalSettlementSimulator {
+    pub fn new(contract: GPv2Settlement, origin: StateOrigin) -> Self {
+        Self { contract, origin }
+    }
+
+    /// Simulates a single settlement, returning its effects.
+    pub async fn simulate(
+        &self,
+        settlement: EncodedSettlement,
+        block: Block,
+    ) -> Result<SimulationOutcome> {
+        Ok(self.execute(settlement, block).await?.0)
+    }
+
+    /// Simulates a single settlement and additionally returns a snapshot of
+    /// all state that the execution touched. The snapshot can be used with
+    /// [`StateOrigin::Recorded`] to replay the simulation offline.
+    pub async fn record(
+        &self,
+        settlement: EncodedSettlement,
+        block: Block,
+    ) -> Result<(SimulationOutcome, StateSnapshot)> {
+        self.execute(settlement, block).await
+    }
+
+    async fn execute(
+        &self,
+        settlement: EncodedSettlement,
+        block: Block,
+    ) -> Result<(SimulationOutcome, StateSnapshot)> {
+        let tx = settle_method_builder(&self.contract, settlement).tx;
+        let caller = tx
+            .from
+            .as_ref()
+            .ok_or_else(|| anyhow!("no default sender address"))?
+            .address();
+        let calldata = tx.data.ok_or_else(|| anyhow!("no calldata"))?.0;
+        let target = self.contract.address();
+
+        let (environment, source) = match &self.origin {
+            StateOrigin::Node(web3) => {
+                let environment = block_environment(web3, block).await?;
+                let source = NodeStateSource {
+                    web3: web3.clone(),
+                    block: environment.number,
+                    handle: Handle::current(),
+                };
+                (environment, Arc::new(source) as Arc<dyn StateSource>)
+            }
+            StateOrigin::Recorded(snapshot) => (
+                snapshot.block.clone(),
+                snapshot.clone() as Arc<dyn StateSource>,
+            ),
+        };
+
+        // The EVM backend is synchronous so lazily fetching state from the
+        // node blocks. Run the execution on a blocking thread so that we don't
+        // stall the async runtime.
+        tokio::task::spawn_blocking(move || {
+            let backend = ForkedBackend::new(source, environment, caller);
+            let outcome = execute_call(&backend, caller, target, calldata)?;
+            Ok((outcome, backend.snapshot()))
+        })
+        .await
+        .context("simulation task panicked")?
+    }
+}
+
+#[async_trait::async_trait]
+impl SettlementSimulating for LocalSettlementSimulator {
+    async fn simulate_settlements(
+        &self,
+        settlements: Vec<EncodedSettlement>,
+        block: Block,
+    ) -> Result<Vec<Result<()>>> {
+        let simulations = settlements
+            .into_iter()
+            .map(|settlement| self.simulate(settlement, block));
+        Ok(futures::future::join_all(simulations)
+            .await
+            .into_iter()
+            .map(|outcome| match outcome?.result {
+                Ok(()) => Ok(()),
+                Err(reason) => Err(anyhow!("settlement reverted: {}", reason)),
+            })
+            .collect())
+    }
+
+    async fn diagnose_failure(
+        &self,
+        settlement: &Settlement,
+        block: Block,
+    ) -> Result<Option<SimulationFailure>> {
+        // The local EVM doesn't trace calls, so failures can only be
+        // classified by their revert reason.
+        let outcome = self.simulate(settlement.clone().into(), block).await?;
+        Ok(match outcome.result {
+            Ok(()) => None,
+            Err(_) => Some(SimulationFailure::from_reason(outcome.revert_reason)),
+        })
+    }
+}
+
+async fn block_environment(web3: &Web3, block: Block) -> Result<BlockEnvironment> {
+    let id = match block {
+        Block::FixedWithTenderly(number) => BlockNumber::Number(number.into()),
+        Block::LatestWithoutTenderly => BlockNumber::Latest,
+    };
+    let (header, chain_id) =
+        futures::try_join!(web3.eth().block(BlockId::Number(id)), web3.eth().chain_id())?;
+    let header = header.ok_or_else(|| anyhow!("block {:?} not found", id))?;
+    Ok(BlockEnvironment {
+        number: header
+            .number
+            .ok_or_else(|| anyhow!("block without number"))?
+            .as_u64(),
+        timestamp: header.timestamp,
+        gas_limit: header.gas_limit,
+        coinbase: header.author,
+        difficulty: header.difficulty,
+        chain_id,
+    })
+}
+
+fn execute_call(
+    backend: &ForkedBackend,
+    caller: H160,
+    target: H160,
+    calldata: Vec<u8>,
+) -> Result<SimulationOutcome> {
+    let config = Config::istanbul();
+    let metadata = StackSubstateMetadata::new(SIMULATION_GAS_LIMIT, &config);
+    let state = MemoryStackState::new(metadata, backend);
+    let mut executor = StackExecutor::new_with_precompile(state, &config, precompiles);
+    UNSUPPORTED_PRECOMPILE.with(|unsupported| unsupported.set(None));
+
+    let (reason, output) =
+        executor.transact_call(caller, target, U256::zero(), calldata, SIMULATION_GAS_LIMIT);
+    let gas_used = executor.used_gas();
+    let (_, logs) = executor.into_state().deconstruct();
+
+    // Errors fetching state are surfaced as empty accounts to the EVM, so
+    // make sure we don't report an execution on incomplete state.
+    backend.take_error()?;
+    if let Some(address) = UNSUPPORTED_PRECOMPILE.with(Cell::take) {
+        bail!("called unsupported precompile {:?}", address);
+    }
+
+    let revert_reason = decode_revert_reason(&output);
+    let result = match reason {
+        ExitReason::Succeed(_) => Ok(()),
+        ExitReason::Revert(_) => Err(revert_reason
+            .clone()
+            .unwrap_or_else(|| format!("reverted with 0x{}", hex::encode(&output)))),
+        ExitReason::Error(err) => Err(format!("execution error {:?}", err)),
+        ExitReason::Fatal(err) => bail!("fatal EVM error {:?}", err),
+    };
+    let balance_changes = if result.is_ok() {
+        balance_changes(logs)
+    } else {
+        BalanceChanges::new()
+    };
+
+    Ok(SimulationOutcome {
+        revert_reason: revert_reason.filter(|_| result.is_err()),
+        result,
+        gas_used,
+        balance_changes,
+    })
+}
+
+/// Computes ERC20 balance changes from `Transfer` event logs.
+fn balance_changes(logs: impl IntoIterator<Item = Log>) -> BalanceChanges {
+    let mut changes = BalanceChanges::new();
+    for log in logs {
+        if log.topics.len() != 3 || log.topics[0] != TRANSFER_EVENT_TOPIC || log.data.len() != 32 {
+            continue;
+        }
+        let from = H160::from(log.topics[1]);
+        let to = H160::from(log.topics[2]);
+        let amount = U256::from_big_endian(&log.data).to_big_int();
+
+        let token = changes.entry(log.address).or_default();
+        *token.entry(from).or_default() -= &amount;
+        *token.entry(to).or_default() += amount;
+    }
+    changes
+}
+
+thread_local! {
+    /// The first precompile without a local implementation that the current execution called.
+    static UNSUPPORTED_PRECOMPILE: Cell<Option<H160>> = Cell::new(None);
+}
+
+/// Precompiled contracts supported by the local EVM.
+///
+/// Only `ecrecover`, which is used to verify order signatures, and the trivial `identity` are
+/// implemented. Calls to the other precompiles fail the simulation instead of silently diverging
+/// from the node.
+fn precompiles(
+    address: H160,
+    input: &[u8],
+    target_gas: Option<u64>,
+    _: &Context,
+) -> Option<Result<PrecompileOutput, ExitError>> {
+    const ECRECOVER_COST: u64 = 3000;
+    const IDENTITY_BASE_COST: u64 = 15;
+    const IDENTITY_WORD_COST: u64 = 3;
+    const ISTANBUL_PRECOMPILES: u64 = 9;
+
+    if address[..19].iter().any(|byte| *byte != 0)
+        || !(1..=ISTANBUL_PRECOMPILES).contains(&(address[19] as u64))
+    {
+        return None;
+    }
+    let (cost, output) = match address[19] {
+        1 => (ECRECOVER_COST, ecrecover(input).unwrap_or_default()),
+        4 => {
+            let words = (input.len() as u64 + 31) / 32;
+            (
+                IDENTITY_BASE_COST + IDENTITY_WORD_COST * words,
+                input.to_vec(),
+            )
+        }
+        _ => {
+            UNSUPPORTED_PRECOMPILE.with(|unsupported| {
+                unsupported.set(unsupported.get().or(Some(address)));
+            });
+            return Some(Err(ExitError::Other("unsupported precompile".into())));
+        }
+    };
+    if matches!(target_gas, Some(gas) if gas < cost) {
+        return Some(Err(ExitError::OutOfGas));
+    }
+    Some(Ok(PrecompileOutput {
+        exit_status: ExitSucceed::Returned,
+        cost,
+        output,
+        logs: Vec::new(),
+    }))
+}
+
+fn ecrecover(input: &[u8]) -> Option<Vec<u8>> {
+    let mut input = input.to_vec();
+    input.resize(128, 0);
+    let (hash, v, signature) = (&input[0..32], &input[32..64], &input[64..128]);
+    if v[..31].iter().any(|byte| *byte != 0) || !matches!(v[31], 27 | 28) {
+        return None;
+    }
+    let signer = web3::signing::recover(hash, signature, (v[31] - 27) as i32).ok()?;
+    Some(H256::from(signer).0.to_vec())
+}
+
+/// A source of account state for the local EVM.
+///
+/// Methods are synchronous since the EVM backend is.
+trait StateSource: Send + Sync {
+    fn account(&self, address: H160) -> Result<AccountState>;
+    fn storage(&self, address: H160, slot: H256) -> Result<H256>;
+}
+
+impl StateSource for StateSnapshot {
+    fn account(&self, address: H160) -> Result<AccountState> {
+        Ok(self
+            .accounts
+            .get(&address)
+            .map(|account| AccountState {
+                storage: HashMap::new(),
+                ..account.clone()
+            })
+            .unwrap_or_default())
+    }
+
+    fn storage(&self, address: H160, slot: H256) -> Result<H256> {
+        Ok(self
+            .accounts
+            .get(&address)
+            .and_then(|account| account.storage.get(&slot))
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+struct NodeStateSource {
+    web3: Web3,
+    block: u64,
+    handle: Handle,
+}
+
+impl NodeStateSource {
+    fn block(&self) -> Option<BlockNumber> {
+        Some(BlockNumber::Number(self.block.into()))
+    }
+}
+
+impl StateSource for NodeStateSource {
+    fn account(&self, address: H160) -> Result<AccountState> {
+        let eth = self.web3.eth();
+        let (balance, nonce, code) = self.handle.block_on(async {
+            futures::try_join!(
+                eth.balance(address, self.block()),
+                eth.transaction_count(address, self.block()),
+                eth.code(address, self.block()),
+            )
+        })?;
+        Ok(AccountState {
+            balance,
+            nonce,
+            code,
+            storage: HashMap::new(),
+        })
+    }
+
+    fn storage(&self, address: H160, slot: H256) -> Result<H256> {
+        let value = self.handle.block_on(self.web3.eth().storage(
+            address,
+            U256::from_big_endian(slot.as_bytes()),
+            self.block(),
+        ))?;
+        Ok(value)
+    }
+}
+
+/// An EVM backend that lazily fetches and caches state from a `StateSource`.
+///
+/// The `evm::backend::Backend` trait is infallible, so fetching errors are
+/// recorded and replaced with empty state. They must be checked with
+/// `take_error` after the execution.
+struct ForkedBackend {
+    source: Arc<dyn StateSource>,
+    environment: BlockEnvironment,
+    origin: H160,
+    accounts: Mutex<HashMap<H160, AccountState>>,
+    error: Mutex<Option<anyhow::Error>>,
+}
+
+impl ForkedBackend {
+    fn new(source: Arc<dyn StateSource>, environment: BlockEnvironment, origin: H160) -> Self {
+        Self {
+            source,
+            environment,
+            origin,
+            accounts: Default::default(),
+            error: Default::default(),
+        }
+    }
+
+    fn with_account<T>(&self, address: H160, f: impl FnOnce(&mut AccountState) -> T) -> T {
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = match accounts.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let account = self.source.account(address).unwrap_or_else(|err| {
+                    self.record_error(err.context(format!("failed to fetch {:?}", address)));
+                    Default::default()
+                });
+                entry.insert(account)
+            }
+        };
+        f(account)
+    }
+
+    fn record_error(&self, err: anyhow::Error) {
+        self.error.lock().unwrap().get_or_insert(err);
+    }
+
+    fn take_error(&self) -> Result<()> {
+        match self.error.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a snapshot of all the state that has been fetched so far.
+    fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            block: self.environment.clone(),
+            accounts: self.accounts.lock().unwrap().clone(),
+        }
+    }
+}
+
+impl Backend for ForkedBackend {
+    fn gas_price(&self) -> U256 {
+        U256::zero()
+    }
+
+    fn origin(&self) -> H160 {
+        self.origin
+    }
+
+    fn block_hash(&self, _: U256) -> H256 {
+        // Settlements don't depend on block hashes.
+        H256::zero()
+    }
+
+    fn block_number(&self) -> U256 {
+        self.environment.number.into()
+    }
+
+    fn block_coinbase(&self) -> H160 {
+        self.environment.coinbase
+    }
+
+    fn block_timestamp(&self) -> U256 {
+        self.environment.timestamp
+    }
+
+    fn block_difficulty(&self) -> U256 {
+        self.environment.difficulty
+    }
+
+    fn block_gas_limit(&self) -> U256 {
+        self.environment.gas_limit
+    }
+
+    fn chain_id(&self) -> U256 {
+        self.environment.chain_id
+    }
+
+    fn exists(&self, address: H160) -> bool {
+        self.with_account(address, |account| {
+            !account.balance.is_zero() || !account.nonce.is_zero() || !account.code.0.is_empty()
+        })
+    }
+
+    fn basic(&self, address: H160) -> Basic {
+        self.with_account(address, |account| Basic {
+            balance: account.balance,
+            nonce: account.nonce,
+        })
+    }
+
+    fn code(&self, address: H160) -> Vec<u8> {
+        self.with_account(address, |account| account.code.0.clone())
+    }
+
+    fn storage(&self, address: H160, index: H256) -> H256 {
+        self.with_account(address, |account| match account.storage.entry(index) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let value = self.source.storage(address, index).unwrap_or_else(|err| {
+                    self.record_error(err.context(format!(
+                        "failed to fetch storage {:?} of {:?}",
+                        index, address
+                    )));
+                    H256::zero()
+                });
+                *entry.insert(value)
+            }
+        })
+    }
+
+    fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
+        Some(self.storage(address, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethcontract::{Account, PrivateKey};
+    use maplit::hashmap;
+    use shared::transport::create_env_test_transport;
+
+    fn address(byte: u8) -> H160 {
+        H160([byte; 20])
+    }
+
+    fn transfer_log(token: H160, from: H160, to: H160, amount: u64) -> Log {
+        let mut data = [0u8; 32];
+        U256::from(amount).to_big_endian(&mut data);
+        Log {
+            address: token,
+            topics: vec![TRANSFER_EVENT_TOPIC, from.into(), to.into()],
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn computes_balance_changes_from_transfer_logs() {
+        let token = address(1);
+        let logs = vec![
+            transfer_log(token, address(2), address(3), 10),
+            transfer_log(token, address(3), address(4), 4),
+            // Not a transfer event.
+            Log {
+                address: token,
+                topics: vec![H256::zero()],
+                data: Vec::new(),
+            },
+        ];
+
+        assert_eq!(
+            balance_changes(logs),
+            hashmap! {
+                token => hashmap! {
+                    address(2) => BigInt::from(-10),
+                    address(3) => BigInt::from(6),
+                    address(4) => BigInt::from(4),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn snapshot_state_source_defaults_to_empty_state() {
+        let snapshot = StateSnapshot {
+            block: Default::default(),
+            accounts: hashmap! {
+                address(1) => AccountState {
+                    balance: 42.into(),
+                    storage: hashmap! { H256::zero() => H256([1; 32]) },
+                    ..Default::default()
+                },
+            },
+        };
+
+        assert_eq!(snapshot.account(address(1)).unwrap().balance, 42.into());
+        assert_eq!(snapshot.account(address(2)).unwrap().balance, 0.into());
+        assert_eq!(
+            snapshot.storage(address(1), H256::zero()).unwrap(),
+            H256([1; 32])
+        );
+        assert_eq!(
+            snapshot.storage(address(1), H256([1; 32])).unwrap(),
+            H256::zero()
+        );
+    }
+
+    #[test]
+    fn forked_backend_caches_state_and_records_errors() {
+        struct FailingStateSource;
+        impl StateSource for FailingStateSource {
+            fn account(&self, _: H160) -> Result<AccountState> {
+                bail!("no state")
+            }
+            fn storage(&self, _: H160, _: H256) -> Result<H256> {
+                bail!("no state")
+            }
+        }
+
+        let backend =
+            ForkedBackend::new(Arc::new(FailingStateSource), Default::default(), address(0));
+        assert_eq!(backend.basic(address(1)).balance, 0.into());
+        assert!(backend.take_error().is_err());
+        // The account is cached so the source is not queried again.
+        assert_eq!(backend.basic(address(1)).balance, 0.into());
+        assert!(backend.take_error().is_ok());
+        assert_eq!(backend.snapshot().accounts.len(), 1);
+    }
+
+    #[test]
+    fn unsupported_precompiles_fail_the_simulation() {
+        let context = Context {
+            address: address(0),
+            caller: address(0),
+            apparent_value: 0.into(),
+        };
+        let precompile = H160::from_low_u64_be;
+        let output = precompiles(precompile(4), &[1, 2, 3], None, &context)
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.output, vec![1, 2, 3]);
+        assert_eq!(output.cost, 18);
+        assert!(precompiles(precompile(10), &[], None, &context).is_none());
+        assert!(UNSUPPORTED_PRECOMPILE.with(Cell::take).is_none());
+
+        assert!(matches!(
+            precompiles(precompile(2), &[], None, &context),
+            Some(Err(_))
+        ));
+        assert_eq!(UNSUPPORTED_PRECOMPILE.with(Cell::take), Some(precompile(2)));
+    }
+
+    #[test]
+    fn ecrecover_rejects_invalid_recovery_id() {
+        let mut input = [0u8; 128];
+        input[63] = 29;
+        assert_eq!(ecrecover(&input), None);
+    }
+
+    // cargo test -p solver settlement_simulation::local::tests::mainnet -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn mainnet() {
+        let web3 = Web3::new(create_env_test_transport());
+        let mut contract = GPv2Settlement::deployed(&web3).await.unwrap();
+        contract.defaults_mut().from = Some(Account::Offline(
+            PrivateKey::from_raw([1; 32]).unwrap(),
+            None,
+        ));
+
+        let simulator = LocalSettlementSimulator::new(contract, StateOrigin::Node(web3));
+        let (outcome, snapshot) = simulator
+            .record(EncodedSettlement::default(), Block::LatestWithoutTenderly)
+            .await
+            .unwrap();
+        // The default sender is not a registered solver.
+        assert_eq!(outcome.result, Err("GPv2: not a solver".to_string()));
+        assert_eq!(outcome.revert_reason.as_deref(), Some("GPv2: not a solver"));
+        assert!(outcome.balance_changes.is_empty());
+
+        // Replaying the recorded state yields the same result.
+        let simulator = LocalSettlementSimulator::new(
+            simulator.contract.clone(),
+            StateOrigin::Recorded(Arc::new(snapshot)),
+        );
+        let replayed = simulator
+            .simulate(EncodedSettlement::default(), Block::LatestWithoutTenderly)
+            .await
+            .unwrap();
+        assert_eq!(replayed.result, outcome.result);
+    }
+}
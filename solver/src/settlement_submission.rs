@@ -2,10 +2,10 @@ pub mod archerapi;
 mod gas_price_stream;
 pub mod retry;
 
-use self::retry::{CancelSender, SettlementSender};
+use self::retry::{CancelSender, NoopSender, SettlementSender, NOOP_GAS_LIMIT};
 use super::driver::solver_settlements::RatedSettlement;
 use crate::{encoding::EncodedSettlement, pending_transactions::Fee};
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use contracts::GPv2Settlement;
 use ethcontract::{dyns::DynTransport, errors::ExecutionError, Web3};
 use futures::stream::StreamExt;
@@ -28,20 +28,84 @@ pub async fn estimate_gas(
         .await
 }
 
-// Submit a settlement to the contract, updating the transaction with gas prices if they increase.
-pub async fn submit(
+// Submit several independent settlements at once with consecutive nonces, updating the
+// transactions with gas prices if they increase. Returns the submission result of every settlement
+// in order.
+//
+// If a submission fails without its transaction being mined, its nonce remains unused and all
+// transactions with later nonces would be stuck. In that case the nonce gets replaced with a noop
+// transaction so that the later settlements can still be mined.
+pub async fn submit_many(
+    contract: &GPv2Settlement,
+    gas: &dyn GasPriceEstimating,
+    target_confirm_time: Duration,
+    gas_price_cap: f64,
+    settlements: Vec<RatedSettlement>,
+) -> Vec<Result<()>> {
+    let first_nonce = match transaction_count(contract)
+        .await
+        .context("failed to get transaction_count")
+    {
+        Ok(nonce) => nonce,
+        Err(err) => {
+            let message = format!("{:?}", err);
+            return settlements
+                .iter()
+                .map(|_| Err(anyhow!("{}", message)))
+                .collect();
+        }
+    };
+    let last_nonce = first_nonce + settlements.len();
+
+    let submissions = settlements
+        .into_iter()
+        .enumerate()
+        .map(|(i, settlement)| async move {
+            let nonce = first_nonce + i;
+            tracing::info!("submitting settlement with nonce {}", nonce);
+            let result = submit_with_nonce(
+                contract,
+                gas,
+                target_confirm_time,
+                gas_price_cap,
+                settlement,
+                nonce,
+            )
+            .await;
+            if result.is_err() && nonce + 1 < last_nonce {
+                match transaction_count(contract).await {
+                    Ok(count) if count <= nonce => {
+                        tracing::warn!(
+                            "nonce {} unused after failed submission, replacing it so that later settlements can be mined",
+                            nonce
+                        );
+                        if let Err(err) =
+                            replace_nonce(contract, gas, target_confirm_time, gas_price_cap, nonce)
+                                .await
+                        {
+                            tracing::error!("failed to replace nonce {}: {:?}", nonce, err);
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(err) => tracing::error!("failed to get transaction_count: {:?}", err),
+                }
+            }
+            result
+        });
+    futures::future::join_all(submissions).await
+}
+
+async fn submit_with_nonce(
     contract: &GPv2Settlement,
     gas: &dyn GasPriceEstimating,
     target_confirm_time: Duration,
     gas_price_cap: f64,
     settlement: RatedSettlement,
+    nonce: U256,
 ) -> Result<()> {
     let gas_estimate = settlement.gas_estimate;
     let settlement: EncodedSettlement = settlement.into();
 
-    let nonce = transaction_count(contract)
-        .await
-        .context("failed to get transaction_count")?;
     let address = &contract
         .defaults()
         .from
@@ -91,13 +155,57 @@ pub async fn submit(
 
     match transaction_retry::retry(settlement_sender, cancel_future, stream).await {
         Some(RetryResult::Submitted(result)) => {
-            tracing::info!("completed settlement submission");
+            tracing::info!("completed settlement submission with nonce {}", nonce);
             result.0.context("settlement transaction failed")
         }
         _ => unreachable!(),
     }
 }
 
+// Replaces a (possibly pending) transaction with the specified nonce with a noop transaction.
+async fn replace_nonce(
+    contract: &GPv2Settlement,
+    gas: &dyn GasPriceEstimating,
+    target_confirm_time: Duration,
+    gas_price_cap: f64,
+    nonce: U256,
+) -> Result<()> {
+    let account = contract
+        .defaults()
+        .from
+        .clone()
+        .expect("no default sender address");
+    let web3 = contract.raw_instance().web3();
+    let pending_gas_price =
+        recover_gas_price_from_pending_transaction(&web3, &account.address(), nonce)
+            .await
+            .context("failed to get pending gas price")?
+            .map(|gas_price| {
+                transaction_retry::gas_price_increase::minimum_increase(gas_price.to_f64_lossy())
+            });
+    let sender = NoopSender {
+        web3,
+        account,
+        nonce,
+    };
+    let stream = gas_price_stream(
+        Instant::now() + target_confirm_time,
+        gas_price_cap,
+        NOOP_GAS_LIMIT.to_f64_lossy(),
+        gas,
+        pending_gas_price,
+    )
+    .boxed();
+
+    match transaction_retry::retry(sender, std::future::pending::<CancelSender>(), stream).await {
+        Some(RetryResult::Submitted(result)) => {
+            tracing::info!("replaced nonce {} with noop transaction", nonce);
+            result.0.context("noop transaction failed")
+        }
+        _ => unreachable!(),
+    }
+}
+
 async fn transaction_count(contract: &GPv2Settlement) -> Result<U256> {
     let defaults = contract.defaults();
     let address = defaults.from.as_ref().unwrap().address();
@@ -2,12 +2,12 @@ use super::EncodedSettlement;
 use anyhow::Result;
 use contracts::GPv2Settlement;
 use ethcontract::{
-    dyns::DynMethodBuilder,
+    dyns::{DynMethodBuilder, DynTransport},
     errors::{ExecutionError, MethodError},
     jsonrpc::types::Error as RpcError,
-    transaction::{confirm::ConfirmParams, ResolveCondition},
+    transaction::{confirm::ConfirmParams, ResolveCondition, TransactionBuilder},
     web3::error::Error as Web3Error,
-    GasPrice,
+    Account, GasPrice, Web3,
 };
 use primitive_types::U256;
 use transaction_retry::{TransactionResult, TransactionSending};
@@ -73,6 +73,35 @@ impl<'a> TransactionSending for SettlementSender<'a> {
     }
 }
 
+/// The gas limit of a plain ether transfer.
+pub const NOOP_GAS_LIMIT: U256 = U256([21_000, 0, 0, 0]);
+
+/// Sends an empty transaction to ourselves in order to use up a nonce.
+pub struct NoopSender {
+    pub web3: Web3<DynTransport>,
+    pub account: Account,
+    pub nonce: U256,
+}
+#[async_trait::async_trait]
+impl TransactionSending for NoopSender {
+    type Output = SettleResult;
+    async fn send(&self, gas_price: f64) -> Self::Output {
+        tracing::info!("submitting noop transaction at gas price {}", gas_price);
+        let result = TransactionBuilder::new(self.web3.clone())
+            .from(self.account.clone())
+            .to(self.account.address())
+            .nonce(self.nonce)
+            .gas_price(GasPrice::Value(U256::from_f64_lossy(gas_price)))
+            .gas(NOOP_GAS_LIMIT)
+            .resolve(ResolveCondition::Confirmed(ConfirmParams::mined()))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| MethodError::from_parts("noop()".into(), err));
+        SettleResult(result)
+    }
+}
+
 pub fn settle_method_builder(
     contract: &GPv2Settlement,
     settlement: EncodedSettlement,
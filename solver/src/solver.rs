@@ -1,18 +1,22 @@
-use crate::{liquidity::Liquidity, settlement::Settlement};
+use crate::{
+    interactions::allowances::{AllowanceManager, AllowanceManaging},
+    liquidity::{slippage::MAX_SLIPPAGE_BPS, Liquidity},
+    metrics::SolverMetrics,
+    settlement::Settlement,
+};
+use aggregator_solver::AggregatorSolver;
 use anyhow::Result;
-use baseline_solver::BaselineSolver;
 use contracts::GPv2Settlement;
 use ethcontract::{H160, U256};
 use http_solver::{HttpSolver, SolverConfig};
-use matcha_solver::MatchaSolver;
-use naive_solver::NaiveSolver;
+use matcha_solver::{MatchaSolver, STANDARD_MATCHA_SLIPPAGE_BPS};
 use oneinch_solver::OneInchSolver;
 use paraswap_solver::ParaswapSolver;
 use reqwest::Url;
 use shared::{
     conversions::U256Ext, price_estimate::PriceEstimating, token_info::TokenInfoFetching, Web3,
 };
-use single_order_solver::SingleOrderSolver;
+use single_order_solver::{OrderBatching, SingleOrderSolver};
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
@@ -20,8 +24,9 @@ use std::{
 };
 use structopt::clap::arg_enum;
 
+mod aggregator_solver;
 mod baseline_solver;
-mod http_solver;
+pub mod http_solver;
 mod matcha_solver;
 mod naive_solver;
 mod oneinch_solver;
@@ -29,6 +34,11 @@ mod paraswap_solver;
 mod single_order_solver;
 mod solver_utils;
 
+pub use self::{
+    aggregator_solver::AggregatorOutcome, baseline_solver::BaselineSolver,
+    naive_solver::NaiveSolver,
+};
+
 // For solvers that enforce a timeout internally we set their timeout to the global solver timeout
 // minus this duration to account for additional delay for example from the network.
 const TIMEOUT_SAFETY_BUFFER: Duration = Duration::from_secs(5);
@@ -81,7 +91,9 @@ pub fn create(
     min_order_size_one_inch: U256,
     disabled_one_inch_protocols: Vec<String>,
     solver_address: H160,
-    paraswap_slippage_bps: usize,
+    paraswap_slippage_bps: u16,
+    aggregator_order_batching: bool,
+    metrics: Arc<dyn SolverMetrics + Send + Sync>,
 ) -> Result<Vec<Box<dyn Solver>>> {
     // Tiny helper function to help out with type inference. Otherwise, all
     // `Box::new(...)` expressions would have to be cast `as Box<dyn Solver>`.
@@ -109,24 +121,51 @@ pub fn create(
             network_id.clone(),
             chain_id,
             fee_discount_factor,
+            Box::new(AllowanceManager::new(
+                web3.clone(),
+                settlement_contract.address(),
+            )),
         )
     };
 
+    // Helper function to create allowance managers for aggregator solvers.
+    let allowance_fetcher = || -> Box<dyn AllowanceManaging> {
+        Box::new(AllowanceManager::new(
+            web3.clone(),
+            settlement_contract.address(),
+        ))
+    };
+
+    let order_batching = if aggregator_order_batching {
+        Some(OrderBatching {
+            price_estimator: price_estimator.clone(),
+            native_token,
+        })
+    } else {
+        None
+    };
+
     solvers
         .into_iter()
         .map(|solver_type| match solver_type {
-            SolverType::Naive => boxed(NaiveSolver {}),
+            SolverType::Naive => boxed(NaiveSolver::new(base_tokens.clone())),
             SolverType::Baseline => boxed(BaselineSolver::new(base_tokens.clone())),
             SolverType::Mip => boxed(create_http_solver(mip_solver_url.clone())),
             SolverType::Quasimodo => boxed(create_http_solver(quasimodo_solver_url.clone())),
             SolverType::OneInch => {
-                let one_inch_solver: SingleOrderSolver<_> = OneInchSolver::with_disabled_protocols(
-                    web3.clone(),
-                    settlement_contract.clone(),
-                    chain_id,
-                    disabled_one_inch_protocols.clone(),
-                )?
-                .into();
+                let one_inch_solver = SingleOrderSolver::new(
+                    AggregatorSolver::new(
+                        OneInchSolver::with_disabled_protocols(
+                            settlement_contract.clone(),
+                            chain_id,
+                            disabled_one_inch_protocols.clone(),
+                        )?,
+                        allowance_fetcher(),
+                        MAX_SLIPPAGE_BPS,
+                        metrics.clone(),
+                    ),
+                    order_batching.clone(),
+                );
                 // We only want to use 1Inch for high value orders
                 boxed(SellVolumeFilteringSolver::new(
                     Box::new(one_inch_solver),
@@ -135,25 +174,31 @@ pub fn create(
                     min_order_size_one_inch,
                 ))
             }
-            SolverType::Matcha => {
-                let matcha_solver =
-                    MatchaSolver::new(web3.clone(), settlement_contract.clone(), chain_id).unwrap();
-                boxed(SingleOrderSolver::from(matcha_solver))
-            }
-            SolverType::Paraswap => boxed(SingleOrderSolver::from(ParaswapSolver::new(
-                web3.clone(),
-                settlement_contract.clone(),
-                solver_address,
-                token_info_fetcher.clone(),
-                paraswap_slippage_bps,
-            ))),
+            SolverType::Matcha => boxed(SingleOrderSolver::new(
+                AggregatorSolver::new(
+                    MatchaSolver::new(chain_id)?,
+                    allowance_fetcher(),
+                    STANDARD_MATCHA_SLIPPAGE_BPS,
+                    metrics.clone(),
+                ),
+                order_batching.clone(),
+            )),
+            SolverType::Paraswap => boxed(SingleOrderSolver::new(
+                AggregatorSolver::new(
+                    ParaswapSolver::new(solver_address, token_info_fetcher.clone()),
+                    allowance_fetcher(),
+                    paraswap_slippage_bps,
+                    metrics.clone(),
+                ),
+                order_batching.clone(),
+            )),
         })
         .collect()
 }
 
 /// Returns a naive solver to be used e.g. in e2e tests.
 pub fn naive_solver() -> Box<dyn Solver> {
-    Box::new(NaiveSolver {})
+    Box::new(NaiveSolver::default())
 }
 
 /// Dummy solver returning no settlements
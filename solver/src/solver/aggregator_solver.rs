@@ -0,0 +1,546 @@
+//! Module containing a generic solver for DEX aggregators.
+//!
+//! Aggregator solvers quote a single GPv2 order, or several orders trading the
+//! same tokens, with the aggregator's API and produce a settlement that swaps
+//! the orders' tokens directly against it at a uniform clearing price.
+//! Everything that is not specific to an aggregator (limit price checks,
+//! slippage, token approvals, retries and metrics) is handled here, so adding
+//! a new aggregator only requires implementing [`SwapQuoting`] and
+//! [`SwapBuilding`] on top of its API client.
+
+use super::single_order_solver::SingleOrderSolving;
+use crate::{
+    interactions::allowances::AllowanceManaging,
+    liquidity::LimitOrder,
+    metrics::SolverMetrics,
+    settlement::{Interaction, Settlement},
+};
+use anyhow::{anyhow, ensure, Result};
+use ethcontract::{H160, U256};
+use maplit::hashmap;
+use model::order::OrderKind;
+use std::sync::Arc;
+use strum_macros::AsStaticStr;
+
+/// Number of times a settlement is attempted again after the aggregator
+/// returned a retryable error.
+const MAX_RETRIES: usize = 2;
+
+/// A request to an aggregator for swapping the tokens of a single order.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SwapRequest {
+    pub sell_token: H160,
+    pub buy_token: H160,
+    pub kind: OrderKind,
+    /// The sell amount for sell orders and the buy amount for buy orders.
+    pub amount: U256,
+    /// The slippage tolerance of the swap transaction in basis points.
+    pub slippage_bps: u16,
+}
+
+impl SwapRequest {
+    /// Creates a request for a single swap settling all orders, which need to
+    /// have the same sell token, buy token and kind.
+    fn from_orders(orders: &[LimitOrder], slippage_bps: u16) -> Result<Self> {
+        let first = orders.first().ok_or_else(|| anyhow!("no orders to swap"))?;
+        ensure!(
+            orders
+                .iter()
+                .all(|order| order.sell_token == first.sell_token
+                    && order.buy_token == first.buy_token
+                    && order.kind == first.kind),
+            "orders of a swap need the same sell token, buy token and kind"
+        );
+        let amount = orders.iter().try_fold(U256::zero(), |amount, order| {
+            amount
+                .checked_add(order.full_execution_amount())
+                .ok_or_else(|| anyhow!("Overflow when summing order amounts"))
+        })?;
+        Ok(Self {
+            sell_token: first.sell_token,
+            buy_token: first.buy_token,
+            kind: first.kind,
+            amount,
+            slippage_bps,
+        })
+    }
+
+    /// Returns the maximum sell amount and the minimum buy amount a swap
+    /// transaction for the quoted amounts may execute with. Slippage is
+    /// applied to the sell amount of buy orders and to the buy amount of sell
+    /// orders, the amount fixed by the order stays unchanged.
+    pub fn amounts_with_slippage(
+        &self,
+        sell_amount: U256,
+        buy_amount: U256,
+    ) -> Result<(U256, U256)> {
+        let bps = U256::from(self.slippage_bps);
+        let denominator = U256::from(10000);
+        let apply = |amount: U256, factor: U256| {
+            amount
+                .checked_mul(factor)
+                .map(|amount| amount / denominator)
+                .ok_or_else(|| anyhow!("Overflow during slippage computation"))
+        };
+        Ok(match self.kind {
+            OrderKind::Buy => (apply(sell_amount, denominator + bps)?, buy_amount),
+            OrderKind::Sell => (
+                sell_amount,
+                apply(buy_amount, denominator.saturating_sub(bps))?,
+            ),
+        })
+    }
+}
+
+/// A quote of an aggregator for a swap request.
+#[derive(Clone, Debug)]
+pub struct Quote<T> {
+    pub sell_amount: U256,
+    pub buy_amount: U256,
+    /// Aggregator specific data needed to build the swap transaction.
+    pub data: T,
+}
+
+/// A swap transaction built by an aggregator for a quote.
+#[derive(Clone, Debug)]
+pub struct SwapTransaction<T> {
+    /// The address that transfers the sell token out of the settlement
+    /// contract and therefore needs an allowance for it.
+    pub spender: H160,
+    pub interaction: T,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AggregatorError {
+    /// An error after which quoting the order again may succeed, for example
+    /// because the price changed between quoting and building the transaction.
+    #[error("retryable aggregator error: {0:?}")]
+    Retryable(anyhow::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl AggregatorError {
+    fn into_inner(self) -> anyhow::Error {
+        match self {
+            AggregatorError::Retryable(err) | AggregatorError::Other(err) => err,
+        }
+    }
+}
+
+/// The result of settling an order against an aggregator used for metrics.
+#[derive(AsStaticStr, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AggregatorOutcome {
+    Settled,
+    LimitPriceViolated,
+    Retried,
+    Failed,
+}
+
+/// Aggregators that can price the swap of a single order.
+#[async_trait::async_trait]
+pub trait SwapQuoting: Send + Sync {
+    type Quote: Send + Sync;
+
+    /// Displayable name of the aggregator used for logging and metrics.
+    fn name(&self) -> &'static str;
+
+    /// Whether orders of the specified kind can be settled by the aggregator.
+    fn supports(&self, _kind: OrderKind) -> bool {
+        true
+    }
+
+    async fn quote(&self, request: &SwapRequest) -> Result<Quote<Self::Quote>, AggregatorError>;
+}
+
+/// Aggregators that can build a swap transaction for one of their quotes.
+#[async_trait::async_trait]
+pub trait SwapBuilding: SwapQuoting {
+    type Interaction: Interaction + 'static;
+
+    async fn build_swap(
+        &self,
+        request: &SwapRequest,
+        quote: Quote<Self::Quote>,
+    ) -> Result<SwapTransaction<Self::Interaction>, AggregatorError>;
+}
+
+/// A GPv2 solver that matches GP orders to direct swaps of an aggregator.
+pub struct AggregatorSolver<A> {
+    api: A,
+    allowance_fetcher: Box<dyn AllowanceManaging>,
+    slippage_bps: u16,
+    metrics: Arc<dyn SolverMetrics + Send + Sync>,
+}
+
+impl<A: SwapBuilding> AggregatorSolver<A> {
+    pub fn new(
+        api: A,
+        allowance_fetcher: Box<dyn AllowanceManaging>,
+        slippage_bps: u16,
+        metrics: Arc<dyn SolverMetrics + Send + Sync>,
+    ) -> Self {
+        Self {
+            api,
+            allowance_fetcher,
+            slippage_bps,
+            metrics,
+        }
+    }
+
+    async fn try_settle_orders(
+        &self,
+        mut orders: Vec<LimitOrder>,
+    ) -> Result<Option<Settlement>, AggregatorError> {
+        loop {
+            let request = SwapRequest::from_orders(&orders, self.slippage_bps)?;
+            tracing::debug!("querying {} with {:?}", self.api.name(), request);
+            let quote = self.api.quote(&request).await?;
+            tracing::debug!(
+                "{} quoted {} sell for {} buy",
+                self.api.name(),
+                quote.sell_amount,
+                quote.buy_amount
+            );
+
+            let (sell_amount, buy_amount) =
+                uniform_clearing_amounts(&request, orders.len(), &quote);
+            let order_count = orders.len();
+            orders.retain(|order| satisfies_limit_price(order, sell_amount, buy_amount));
+            if orders.is_empty() {
+                tracing::debug!("Order limit price not respected");
+                return Ok(None);
+            }
+            if orders.len() < order_count {
+                // The swap gets smaller and its price better without the
+                // removed orders, so the remaining orders need a new quote.
+                tracing::debug!("Removed orders whose limit price is not respected");
+                continue;
+            }
+
+            let swap_sell_amount = quote.sell_amount;
+            let swap = self.api.build_swap(&request, quote).await?;
+            let mut settlement = Settlement::new(hashmap! {
+                request.sell_token => buy_amount,
+                request.buy_token => sell_amount,
+            });
+            for order in &orders {
+                settlement.with_liquidity(order, order.full_execution_amount())?;
+            }
+
+            settlement
+                .encoder
+                .add_onchain_liquidity_tokens(vec![request.sell_token, request.buy_token]);
+            settlement.encoder.append_to_execution_plan(
+                self.allowance_fetcher
+                    .get_approval(request.sell_token, swap.spender, swap_sell_amount)
+                    .await?,
+            );
+            settlement
+                .encoder
+                .append_to_execution_plan(swap.interaction);
+            return Ok(Some(settlement));
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: SwapBuilding> SingleOrderSolving for AggregatorSolver<A> {
+    async fn settle_order(&self, order: LimitOrder) -> Result<Option<Settlement>> {
+        self.settle_orders(vec![order]).await
+    }
+
+    async fn settle_orders(&self, orders: Vec<LimitOrder>) -> Result<Option<Settlement>> {
+        match orders.first() {
+            Some(order) if self.api.supports(order.kind) => (),
+            _ => return Ok(None),
+        }
+
+        let mut retries = 0;
+        let result = loop {
+            match self.try_settle_orders(orders.clone()).await {
+                Err(AggregatorError::Retryable(err)) if retries < MAX_RETRIES => {
+                    tracing::debug!("Retrying {} settlement due to: {:?}", self.name(), err);
+                    self.metrics
+                        .aggregator_order_handled(self.name(), AggregatorOutcome::Retried);
+                    retries += 1;
+                }
+                result => break result,
+            }
+        };
+
+        let outcome = match &result {
+            Ok(Some(_)) => AggregatorOutcome::Settled,
+            Ok(None) => AggregatorOutcome::LimitPriceViolated,
+            Err(_) => AggregatorOutcome::Failed,
+        };
+        self.metrics.aggregator_order_handled(self.name(), outcome);
+        result.map_err(AggregatorError::into_inner)
+    }
+
+    fn name(&self) -> &'static str {
+        self.api.name()
+    }
+}
+
+/// Returns the sell and buy amount of the uniform clearing price at which the
+/// output of a swap for `order_count` orders is distributed across them.
+///
+/// The contract rounds the amount every order receives (sell orders) or pays
+/// (buy orders) by up to one unit in favour of the user, so the swap's price
+/// is worsened by one unit per additional order to make sure that the orders
+/// never receive more than the swap buys or pay less than it sells. For a
+/// single order this is exactly the quoted price.
+fn uniform_clearing_amounts<T>(
+    request: &SwapRequest,
+    order_count: usize,
+    quote: &Quote<T>,
+) -> (U256, U256) {
+    let rounding_slack = U256::from(order_count.saturating_sub(1));
+    match request.kind {
+        OrderKind::Sell => (
+            quote.sell_amount,
+            quote.buy_amount.saturating_sub(rounding_slack),
+        ),
+        OrderKind::Buy => (
+            quote.sell_amount.saturating_add(rounding_slack),
+            quote.buy_amount,
+        ),
+    }
+}
+
+/// Checks that the quoted price is at least as good as the order's limit
+/// price, i.e. `order.sell / order.buy >= quote.sell / quote.buy`.
+fn satisfies_limit_price(order: &LimitOrder, sell_amount: U256, buy_amount: U256) -> bool {
+    order.sell_amount.full_mul(buy_amount) >= sell_amount.full_mul(order.buy_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoding::EncodedInteraction,
+        interactions::allowances::{Approval, MockAllowanceManaging},
+        liquidity::tests::CapturingSettlementHandler,
+        metrics::NoopMetrics,
+    };
+    use std::sync::Mutex;
+
+    #[derive(Debug)]
+    struct NoopInteraction;
+
+    impl Interaction for NoopInteraction {
+        fn encode(&self) -> Vec<EncodedInteraction> {
+            Vec::new()
+        }
+    }
+
+    /// An aggregator quoting sell orders at a fixed `sell:buy` rate and failing
+    /// a number of times with a retryable error when building swaps.
+    struct DummyAggregator {
+        rate: (U256, U256),
+        retryable_failures: Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl SwapQuoting for DummyAggregator {
+        type Quote = ();
+
+        fn name(&self) -> &'static str {
+            "Dummy"
+        }
+
+        fn supports(&self, kind: OrderKind) -> bool {
+            kind == OrderKind::Sell
+        }
+
+        async fn quote(&self, request: &SwapRequest) -> Result<Quote<()>, AggregatorError> {
+            Ok(Quote {
+                sell_amount: request.amount,
+                buy_amount: request.amount * self.rate.1 / self.rate.0,
+                data: (),
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SwapBuilding for DummyAggregator {
+        type Interaction = NoopInteraction;
+
+        async fn build_swap(
+            &self,
+            _: &SwapRequest,
+            _: Quote<()>,
+        ) -> Result<SwapTransaction<NoopInteraction>, AggregatorError> {
+            let mut failures = self.retryable_failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(AggregatorError::Retryable(anyhow!("price changed")));
+            }
+            Ok(SwapTransaction {
+                spender: H160::from_low_u64_be(3),
+                interaction: NoopInteraction,
+            })
+        }
+    }
+
+    fn solver(rate: (u64, u64), retryable_failures: usize) -> AggregatorSolver<DummyAggregator> {
+        let mut allowance_fetcher = MockAllowanceManaging::new();
+        allowance_fetcher
+            .expect_get_approval()
+            .returning(|_, _, _| Ok(Approval::AllowanceSufficient));
+        AggregatorSolver::new(
+            DummyAggregator {
+                rate: (rate.0.into(), rate.1.into()),
+                retryable_failures: Mutex::new(retryable_failures),
+            },
+            Box::new(allowance_fetcher),
+            10,
+            Arc::new(NoopMetrics::default()),
+        )
+    }
+
+    fn order(kind: OrderKind) -> LimitOrder {
+        LimitOrder {
+            sell_token: H160::from_low_u64_be(1),
+            buy_token: H160::from_low_u64_be(2),
+            sell_amount: 100.into(),
+            buy_amount: 90.into(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn settles_order_at_quoted_price() {
+        let settlement = solver((100, 95), 0)
+            .settle_order(order(OrderKind::Sell))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            settlement.clearing_prices(),
+            &hashmap! {
+                H160::from_low_u64_be(1) => 95.into(),
+                H160::from_low_u64_be(2) => 100.into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn respects_limit_price_and_supported_kinds() {
+        let solver = solver((100, 89), 0);
+        assert!(solver
+            .settle_order(order(OrderKind::Sell))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(solver
+            .settle_order(order(OrderKind::Buy))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn settles_orders_at_uniform_price_of_one_swap() {
+        let handlers = vec![
+            CapturingSettlementHandler::arc(),
+            CapturingSettlementHandler::arc(),
+            CapturingSettlementHandler::arc(),
+        ];
+        let orders = [90, 96, 94]
+            .iter()
+            .zip(&handlers)
+            .map(|(buy_amount, handler)| LimitOrder {
+                buy_amount: (*buy_amount).into(),
+                settlement_handling: handler.clone(),
+                ..order(OrderKind::Sell)
+            })
+            .collect();
+        let settlement = solver((100, 95), 0)
+            .settle_orders(orders)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The order buying 96 can't be settled at the price of swapping 300, so it is removed and
+        // the remaining two orders share the swap of 200 for 190, minus one unit for rounding.
+        assert_eq!(handlers[0].calls(), vec![U256::from(100)]);
+        assert!(handlers[1].calls().is_empty());
+        assert_eq!(handlers[2].calls(), vec![U256::from(100)]);
+        assert_eq!(
+            settlement.clearing_prices(),
+            &hashmap! {
+                H160::from_low_u64_be(1) => 189.into(),
+                H160::from_low_u64_be(2) => 200.into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_retryable_errors() {
+        assert!(solver((100, 95), MAX_RETRIES)
+            .settle_order(order(OrderKind::Sell))
+            .await
+            .is_ok());
+        assert!(solver((100, 95), MAX_RETRIES + 1)
+            .settle_order(order(OrderKind::Sell))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_satisfies_limit_price() {
+        let order = LimitOrder {
+            sell_amount: 100.into(),
+            buy_amount: 95.into(),
+            ..Default::default()
+        };
+        assert!(!satisfies_limit_price(&order, 100.into(), 90.into()));
+        assert!(satisfies_limit_price(&order, 100.into(), 100.into()));
+        assert!(satisfies_limit_price(&order, 100.into(), 95.into()));
+    }
+
+    #[test]
+    fn applies_slippage_by_order_kind() {
+        let request = |kind| SwapRequest {
+            sell_token: Default::default(),
+            buy_token: Default::default(),
+            kind,
+            amount: Default::default(),
+            slippage_bps: 100,
+        };
+        assert_eq!(
+            request(OrderKind::Sell)
+                .amounts_with_slippage(1000.into(), 1000.into())
+                .unwrap(),
+            (1000.into(), 990.into())
+        );
+        assert_eq!(
+            request(OrderKind::Buy)
+                .amounts_with_slippage(1000.into(), 1000.into())
+                .unwrap(),
+            (1010.into(), 1000.into())
+        );
+    }
+
+    #[test]
+    fn swap_request_requires_orders_of_the_same_kind_and_tokens() {
+        let orders = vec![order(OrderKind::Sell), order(OrderKind::Sell)];
+        assert_eq!(
+            SwapRequest::from_orders(&orders, 0).unwrap().amount,
+            200.into()
+        );
+
+        let orders = vec![order(OrderKind::Sell), order(OrderKind::Buy)];
+        assert!(SwapRequest::from_orders(&orders, 0).is_err());
+        let other_token = LimitOrder {
+            buy_token: H160::from_low_u64_be(3),
+            ..order(OrderKind::Sell)
+        };
+        let orders = vec![order(OrderKind::Sell), other_token];
+        assert!(SwapRequest::from_orders(&orders, 0).is_err());
+        assert!(SwapRequest::from_orders(&[], 0).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+mod batching;
+
 use crate::{
     liquidity::{
         AmmOrderExecution, ConstantProductOrder, LimitOrder, Liquidity, WeightedProductOrder,
@@ -12,8 +14,8 @@ use model::TokenPair;
 use num::BigRational;
 use shared::{
     baseline_solver::{
-        estimate_buy_amount, estimate_sell_amount, path_candidates, BaselineSolvable,
-        DEFAULT_MAX_HOPS,
+        estimate_buy_amount, estimate_buy_amount_split, estimate_sell_amount,
+        estimate_sell_amount_split, path_candidates, BaselineSolvable, DEFAULT_MAX_HOPS,
     },
     sources::{
         balancer::swap::{fixed_point::Bfp, WeightedPoolRef},
@@ -129,25 +131,47 @@ impl BaselineSolver {
         let mut settlements = Vec::new();
 
         // Return a solution for the first settle-able user order
-        for order in user_orders {
-            let solution = match self.settle_order(&order, &amm_map) {
-                Some(solution) => solution,
-                None => continue,
-            };
-
-            // Check limit price
-            if solution.executed_buy_amount >= order.buy_amount
-                && solution.executed_sell_amount <= order.sell_amount
-            {
-                match solution.into_settlement(&order) {
-                    Ok(settlement) => settlements.push(settlement),
-                    Err(err) => {
-                        tracing::error!("baseline_solver failed to create settlement: {:?}", err)
+        for order in &user_orders {
+            let solution = self.settle_order(order, &amm_map);
+            // Splitting the order over parallel routes costs more gas, so it is only returned in
+            // addition to the single route solution if it results in a better price.
+            let split_solution = self.split_order(order, &amm_map).filter(|split| {
+                solution
+                    .as_ref()
+                    .map(|solution| split.has_better_price_than(solution))
+                    .unwrap_or(true)
+            });
+
+            for solution in solution.into_iter().chain(split_solution) {
+                // Check limit price
+                if solution.executed_buy_amount >= order.buy_amount
+                    && solution.executed_sell_amount <= order.sell_amount
+                {
+                    match solution.into_settlement(order) {
+                        Ok(settlement) => settlements.push(settlement),
+                        Err(err) => {
+                            tracing::error!(
+                                "baseline_solver failed to create settlement: {:?}",
+                                err
+                            )
+                        }
                     }
                 }
             }
         }
 
+        // Additionally batch all orders of a token pair so that they share a single route.
+        for (pair, orders) in organize_orders_by_token_pair(user_orders) {
+            if orders.len() < 2 {
+                continue;
+            }
+            if let Some(settlement) =
+                batching::solve_pair(pair, orders, &amm_map, &self.base_tokens)
+            {
+                settlements.push(settlement);
+            }
+        }
+
         settlements
     }
 
@@ -180,12 +204,61 @@ impl BaselineSolver {
             }
         };
         Some(Solution {
-            path: path.into_iter().cloned().collect(),
+            parts: vec![(executed_sell_amount, path.into_iter().cloned().collect())],
             executed_sell_amount,
             executed_buy_amount,
         })
     }
 
+    fn split_order(
+        &self,
+        order: &LimitOrder,
+        amms: &HashMap<TokenPair, Vec<Amm>>,
+    ) -> Option<Solution> {
+        let candidates = path_candidates(
+            order.sell_token,
+            order.buy_token,
+            &self.base_tokens,
+            DEFAULT_MAX_HOPS,
+        );
+
+        let solution = match order.kind {
+            model::order::OrderKind::Buy => {
+                let split = estimate_sell_amount_split(order.buy_amount, &candidates, amms)?;
+                Solution {
+                    parts: split
+                        .parts
+                        .into_iter()
+                        .map(|part| {
+                            let path = part.estimate.path.into_iter().cloned().collect();
+                            (part.estimate.value, path)
+                        })
+                        .collect(),
+                    executed_sell_amount: split.value,
+                    executed_buy_amount: order.buy_amount,
+                }
+            }
+            model::order::OrderKind::Sell => {
+                let split = estimate_buy_amount_split(order.sell_amount, &candidates, amms)?;
+                Solution {
+                    parts: split
+                        .parts
+                        .into_iter()
+                        .map(|part| {
+                            (
+                                part.amount,
+                                part.estimate.path.into_iter().cloned().collect(),
+                            )
+                        })
+                        .collect(),
+                    executed_sell_amount: order.sell_amount,
+                    executed_buy_amount: split.value,
+                }
+            }
+        };
+        Some(solution).filter(|solution| solution.parts.len() > 1)
+    }
+
     #[cfg(test)]
     fn must_solve(&self, liquidity: Vec<Liquidity>) -> Settlement {
         self.solve(liquidity).into_iter().next().unwrap()
@@ -193,12 +266,21 @@ impl BaselineSolver {
 }
 
 struct Solution {
-    path: Vec<Amm>,
+    // The sell amount routed through each path.
+    parts: Vec<(U256, Vec<Amm>)>,
     executed_sell_amount: U256,
     executed_buy_amount: U256,
 }
 
 impl Solution {
+    fn has_better_price_than(&self, other: &Solution) -> bool {
+        self.executed_buy_amount
+            .full_mul(other.executed_sell_amount)
+            > other
+                .executed_buy_amount
+                .full_mul(self.executed_sell_amount)
+    }
+
     fn into_settlement(self, order: &LimitOrder) -> Result<Settlement> {
         let mut settlement = Settlement::new(hashmap! {
             order.sell_token => self.executed_buy_amount,
@@ -206,30 +288,50 @@ impl Solution {
         });
 
         settlement.with_liquidity(order, order.full_execution_amount())?;
-
-        let (mut sell_amount, mut sell_token) = (self.executed_sell_amount, order.sell_token);
-        for amm in self.path {
-            let buy_token = amm.tokens.other(&sell_token).expect("Inconsistent path");
-            let buy_amount = amm
-                .get_amount_out(buy_token, (sell_amount, sell_token))
-                .expect("Path was found, so amount must be calculable");
-            let execution = AmmOrderExecution {
-                input: (sell_token, sell_amount),
-                output: (buy_token, buy_amount),
-            };
-            match &amm.order {
-                AmmOrder::ConstantProduct(order) => settlement.with_liquidity(order, execution),
-                AmmOrder::WeightedProduct(order) => settlement.with_liquidity(order, execution),
-            }?;
-            sell_amount = buy_amount;
-            sell_token = buy_token;
+        for (sell_amount, path) in &self.parts {
+            execute_path(&mut settlement, path, (*sell_amount, order.sell_token))?;
         }
 
         Ok(settlement)
     }
 }
 
-fn amm_to_pool(amm: &ConstantProductOrder) -> Pool {
+/// Adds the executions of selling `sell_amount` of `sell_token` along the path to the settlement.
+fn execute_path<'a>(
+    settlement: &mut Settlement,
+    path: impl IntoIterator<Item = &'a Amm>,
+    (mut sell_amount, mut sell_token): (U256, H160),
+) -> Result<()> {
+    for amm in path {
+        let buy_token = amm.tokens.other(&sell_token).expect("Inconsistent path");
+        let buy_amount = amm
+            .get_amount_out(buy_token, (sell_amount, sell_token))
+            .expect("Path was found, so amount must be calculable");
+        let execution = AmmOrderExecution {
+            input: (sell_token, sell_amount),
+            output: (buy_token, buy_amount),
+        };
+        match &amm.order {
+            AmmOrder::ConstantProduct(order) => settlement.with_liquidity(order, execution),
+            AmmOrder::WeightedProduct(order) => settlement.with_liquidity(order, execution),
+        }?;
+        sell_amount = buy_amount;
+        sell_token = buy_token;
+    }
+    Ok(())
+}
+
+fn organize_orders_by_token_pair(orders: Vec<LimitOrder>) -> HashMap<TokenPair, Vec<LimitOrder>> {
+    let mut result = HashMap::<_, Vec<_>>::new();
+    for order in orders {
+        if let Some(pair) = TokenPair::new(order.sell_token, order.buy_token) {
+            result.entry(pair).or_default().push(order);
+        }
+    }
+    result
+}
+
+pub(super) fn amm_to_pool(amm: &ConstantProductOrder) -> Pool {
     Pool {
         tokens: amm.tokens,
         reserves: amm.reserves,
@@ -342,8 +444,17 @@ mod tests {
         assert_eq!(order_handler[0].clone().calls().len(), 0);
         assert_eq!(order_handler[1].clone().calls()[0], 100_000.into());
 
+        // The direct AMM is only used by the additional settlement that splits the second order
+        // over both routes.
+        assert_eq!(
+            amm_handler[0].clone().calls(),
+            vec![AmmOrderExecution {
+                input: (sell_token, 20_000.into()),
+                output: (buy_token, 19_550.into()),
+            }]
+        );
+
         // Second & Third AMM are matched
-        assert_eq!(amm_handler[0].clone().calls().len(), 0);
         assert_eq!(
             amm_handler[1].clone().calls()[0],
             AmmOrderExecution {
@@ -447,8 +558,17 @@ mod tests {
         assert_eq!(order_handler[0].clone().calls().len(), 0);
         assert_eq!(order_handler[1].clone().calls()[0], 100_000.into());
 
+        // The direct AMM is only used by the additional settlement that splits the second order
+        // over both routes.
+        assert_eq!(
+            amm_handler[0].clone().calls(),
+            vec![AmmOrderExecution {
+                input: (sell_token, 20_470.into()),
+                output: (buy_token, 20_000.into()),
+            }]
+        );
+
         // Second & Third AMM are matched
-        assert_eq!(amm_handler[0].clone().calls().len(), 0);
         assert_eq!(
             amm_handler[1].clone().calls()[0],
             AmmOrderExecution {
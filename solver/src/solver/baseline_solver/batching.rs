@@ -0,0 +1,443 @@
+//! Batching of all orders on a token pair into a single settlement. Opposing orders are matched
+//! against each other first and only the net amount is routed along the best path, so all orders
+//! share the gas of a single route and get one uniform clearing price.
+
+use super::{execute_path, Amm};
+use crate::{liquidity::LimitOrder, settlement::Settlement};
+use ethcontract::{H160, U256};
+use model::{order::OrderKind, TokenPair};
+use num::{BigInt, BigRational, Integer as _, One as _, Signed as _, Zero as _};
+use shared::{
+    baseline_solver::{
+        estimate_buy_amount, estimate_spot_price, path_candidates, Estimate, DEFAULT_MAX_HOPS,
+    },
+    conversions::{big_int_to_u256, u256_to_big_int},
+};
+use std::collections::{HashMap, HashSet};
+
+/// The net volumes of a batch of orders relative to the token with excess `e` and the token with
+/// shortage `s`. At a price `r` (amount of `s` per unit of `e`), the orders sell
+/// `net_excess + net_shortage / r` more of `e` than they buy.
+///
+/// The contract rounds every trade in favour of the user: sell orders receive the rounded up buy
+/// amount and buy orders pay the rounded down sell amount. Each order can therefore cost the
+/// settlement up to one atom more than at the exact price, which is covered by a rounding slack of
+/// one atom per order on both tokens.
+struct NetVolumes {
+    excess: H160,
+    shortage: H160,
+    // Sold `e` of sell orders minus bought `e` of buy orders.
+    net_excess: BigInt,
+    // Bought `s` of buy orders minus sold `s` of sell orders.
+    net_shortage: BigInt,
+    // The maximum amount of `e` the orders can sell.
+    max_excess: U256,
+    // The number of orders.
+    rounding_slack: BigInt,
+}
+
+impl NetVolumes {
+    fn new(orders: &[LimitOrder], excess: H160, shortage: H160) -> Self {
+        let mut volumes = Self {
+            excess,
+            shortage,
+            net_excess: BigInt::zero(),
+            net_shortage: BigInt::zero(),
+            max_excess: U256::zero(),
+            rounding_slack: orders.len().into(),
+        };
+        for order in orders {
+            match order.kind {
+                OrderKind::Sell if order.sell_token == excess => {
+                    volumes.net_excess += u256_to_big_int(&order.sell_amount)
+                }
+                OrderKind::Sell => volumes.net_shortage -= u256_to_big_int(&order.sell_amount),
+                OrderKind::Buy if order.buy_token == excess => {
+                    volumes.net_excess -= u256_to_big_int(&order.buy_amount)
+                }
+                OrderKind::Buy => volumes.net_shortage += u256_to_big_int(&order.buy_amount),
+            }
+            if order.sell_token == excess {
+                volumes.max_excess = volumes.max_excess.saturating_add(order.sell_amount);
+            }
+        }
+        volumes
+    }
+
+    /// Whether routing `x` of the excess token for `y` of the shortage token doesn't sell more of
+    /// the excess token than the orders provide at the effective price of the route, i.e.
+    /// `x + n <= net_excess + net_shortage * x / y` with `n` being the rounding slack.
+    fn can_route(&self, x: &BigInt, y: &BigInt) -> bool {
+        x * (y - &self.net_shortage) + &self.rounding_slack * y <= &self.net_excess * y
+    }
+
+    fn is_excess_at(&self, price: &BigRational) -> bool {
+        BigRational::from_integer(self.net_excess.clone()) * price
+            + BigRational::from_integer(self.net_shortage.clone())
+            > BigRational::zero()
+    }
+
+    ///
+    /// Returns the smallest price of the shortage token such that both tokens are balanced when
+    /// `x` of the excess token are swapped for `y` of the shortage token and the excess token is
+    /// priced at `y`. With `D` being the net amount of `e` the orders sell at that price and `n`
+    /// the rounding slack, this requires `x + n <= D` and `D * y / p_s + n <= y`, which are linear
+    /// in `p_s`. Returns `None` if no integer price satisfies both, which can happen even if the
+    /// route is balanced at a rational price.
+    ///
+    fn shortage_price(&self, x: &BigInt, y: &BigInt) -> Option<BigInt> {
+        let n = &self.rounding_slack;
+        let constraints = vec![
+            (self.net_shortage.clone(), (x + n - &self.net_excess) * y),
+            (y - n - &self.net_shortage, &self.net_excess * y),
+        ];
+        let mut lower = BigInt::one();
+        let mut upper: Option<BigInt> = None;
+        // Each constraint is of the form `coefficient * p_s >= bound`.
+        for (coefficient, bound) in constraints {
+            if coefficient.is_positive() {
+                lower = lower.max(bound.div_ceil(&coefficient));
+            } else if coefficient.is_negative() {
+                let bound = bound.div_floor(&coefficient);
+                upper = Some(match upper {
+                    Some(upper) => upper.min(bound),
+                    None => bound,
+                });
+            } else if bound.is_positive() {
+                return None;
+            }
+        }
+        match upper {
+            Some(upper) if upper < lower => None,
+            _ => Some(lower),
+        }
+    }
+}
+
+///
+/// Computes a single settlement for orders on the same token pair in either direction. Orders
+/// whose limit price can't be met are removed until all remaining orders can be settled. Returns
+/// `None` if fewer than two orders remain, since single orders are settled individually.
+///
+pub(super) fn solve_pair(
+    pair: TokenPair,
+    mut orders: Vec<LimitOrder>,
+    amms: &HashMap<TokenPair, Vec<Amm>>,
+    base_tokens: &HashSet<H160>,
+) -> Option<Settlement> {
+    let (token_a, token_b) = pair.get();
+    let spot_price = path_candidates(token_a, token_b, base_tokens, DEFAULT_MAX_HOPS)
+        .iter()
+        .filter_map(|path| estimate_spot_price(path, amms))
+        .map(|estimate| estimate.value)
+        .max()?;
+
+    while orders.len() > 1 {
+        let volumes = match NetVolumes::new(&orders, token_a, token_b) {
+            volumes if volumes.is_excess_at(&spot_price) => volumes,
+            _ => NetVolumes::new(&orders, token_b, token_a),
+        };
+        let (x, estimate, shortage_price) = route_net_volume(&volumes, amms, base_tokens)?;
+        let prices = maplit::hashmap! {
+            volumes.excess => estimate.value,
+            volumes.shortage => big_int_to_u256(&shortage_price).ok()?,
+        };
+
+        // Remove the order whose limit price is violated the most and try again.
+        let violated_the_most = orders
+            .iter()
+            .enumerate()
+            .filter_map(|(index, order)| Some((index, limit_violation(order, &prices)?)))
+            .max_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
+        if let Some((index, _)) = violated_the_most {
+            orders.swap_remove(index);
+            continue;
+        }
+
+        let mut settlement = Settlement::new(prices);
+        for order in &orders {
+            settlement
+                .with_liquidity(order, order.full_execution_amount())
+                .ok()?;
+        }
+        execute_path(&mut settlement, estimate.path, (x, volumes.excess)).ok()?;
+        return Some(settlement);
+    }
+
+    None
+}
+
+///
+/// Finds the largest amount of the excess token that can be routed along the best path such that
+/// the orders can be balanced with uniform integer clearing prices. This is found with a binary
+/// search since the effective price of the path gets worse the more is swapped. Only amounts for
+/// which a shortage price exists are kept as the lower bound, so the result can always be settled.
+///
+fn route_net_volume<'a>(
+    volumes: &NetVolumes,
+    amms: &'a HashMap<TokenPair, Vec<Amm>>,
+    base_tokens: &HashSet<H160>,
+) -> Option<(U256, Estimate<'a, U256, Amm>, BigInt)> {
+    let candidates = path_candidates(
+        volumes.excess,
+        volumes.shortage,
+        base_tokens,
+        DEFAULT_MAX_HOPS,
+    );
+    let estimate = |x: U256| {
+        candidates
+            .iter()
+            .filter_map(|path| estimate_buy_amount(x, path, amms))
+            .max_by_key(|estimate| estimate.value)
+    };
+    let shortage_price = |x: U256| {
+        let (x, y) = (u256_to_big_int(&x), u256_to_big_int(&estimate(x)?.value));
+        if !volumes.can_route(&x, &y) {
+            return None;
+        }
+        volumes.shortage_price(&x, &y)
+    };
+    let can_route = |x: U256| shortage_price(x).is_some();
+
+    let (mut low, mut high) = (U256::zero(), volumes.max_excess);
+    if can_route(high) {
+        low = high;
+    }
+    while high - low > U256::one() {
+        let middle = low + (high - low) / 2;
+        if can_route(middle) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    if low.is_zero() {
+        return None;
+    }
+
+    Some((low, estimate(low)?, shortage_price(low)?))
+}
+
+/// By how much an order's limit price is violated. Orders whose sell token has no value violate
+/// their limit price infinitely.
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum LimitViolation {
+    Finite(BigRational),
+    Infinite,
+}
+
+/// Returns by how much an order's limit price is violated at the given prices, or `None` if it
+/// is satisfied.
+fn limit_violation(order: &LimitOrder, prices: &HashMap<H160, U256>) -> Option<LimitViolation> {
+    let sell_value =
+        u256_to_big_int(&order.sell_amount) * u256_to_big_int(&prices[&order.sell_token]);
+    let buy_value = u256_to_big_int(&order.buy_amount) * u256_to_big_int(&prices[&order.buy_token]);
+    if sell_value >= buy_value {
+        return None;
+    }
+    if sell_value.is_zero() {
+        return Some(LimitViolation::Infinite);
+    }
+    Some(LimitViolation::Finite(BigRational::new(
+        buy_value, sell_value,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        liquidity::{tests::CapturingSettlementHandler, ConstantProductOrder},
+        solver::baseline_solver::AmmOrder,
+    };
+    use maplit::hashset;
+    use model::order::{Order, OrderCreation};
+    use num::rational::Ratio;
+    use std::sync::Arc;
+
+    fn order(
+        sell_token: H160,
+        buy_token: H160,
+        kind: OrderKind,
+        sell: u64,
+        buy: u64,
+    ) -> LimitOrder {
+        Order {
+            order_creation: OrderCreation {
+                sell_token,
+                buy_token,
+                sell_amount: sell.into(),
+                buy_amount: buy.into(),
+                kind,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .into()
+    }
+
+    fn amms(
+        tokens: TokenPair,
+    ) -> (
+        HashMap<TokenPair, Vec<Amm>>,
+        Arc<CapturingSettlementHandler<ConstantProductOrder>>,
+    ) {
+        let handler = CapturingSettlementHandler::arc();
+        let amm = Amm {
+            tokens,
+            order: AmmOrder::ConstantProduct(ConstantProductOrder {
+                tokens,
+                reserves: (10_000_000, 10_000_000),
+                fee: Ratio::new(3, 1000),
+                settlement_handling: handler.clone(),
+            }),
+        };
+        (maplit::hashmap! { tokens => vec![amm] }, handler)
+    }
+
+    #[test]
+    fn batches_orders_in_the_same_direction() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let pair = TokenPair::new(token_a, token_b).unwrap();
+        let (amms, amm_handler) = amms(pair);
+
+        let orders = vec![
+            order(token_a, token_b, OrderKind::Sell, 100_000, 90_000),
+            order(token_a, token_b, OrderKind::Sell, 50_000, 45_000),
+            order(token_a, token_b, OrderKind::Buy, 60_000, 50_000),
+        ];
+        let settlement = solve_pair(pair, orders, &amms, &hashset! {}).unwrap();
+        assert_eq!(settlement.trades().len(), 3);
+
+        // All orders share a single AMM execution.
+        let calls = amm_handler.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].input.0, token_a);
+        assert_eq!(
+            settlement.clearing_prices(),
+            &maplit::hashmap! {
+                token_a => calls[0].output.1,
+                token_b => settlement.clearing_price(token_b).unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn routes_net_amount_of_opposing_orders() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let pair = TokenPair::new(token_a, token_b).unwrap();
+        let (amms, amm_handler) = amms(pair);
+
+        let orders = vec![
+            order(token_a, token_b, OrderKind::Sell, 100_000, 90_000),
+            order(token_b, token_a, OrderKind::Sell, 60_000, 55_000),
+        ];
+        let settlement = solve_pair(pair, orders.clone(), &amms, &hashset! {}).unwrap();
+        assert_eq!(settlement.trades().len(), 2);
+
+        // Only the part that isn't matched between the orders is routed.
+        let calls = amm_handler.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].input.0, token_a);
+        assert!(calls[0].input.1 < 41_000.into());
+
+        // Both tokens are balanced with the amounts rounded like the contract does.
+        let price_a = settlement.clearing_price(token_a).unwrap();
+        let price_b = settlement.clearing_price(token_b).unwrap();
+        let ceil_div = |a: U256, b: U256| (a + b - 1) / b;
+        assert!(
+            ceil_div(orders[1].sell_amount * price_b, price_a) + calls[0].input.1
+                <= orders[0].sell_amount
+        );
+        assert!(
+            ceil_div(orders[0].sell_amount * price_a, price_b)
+                <= orders[1].sell_amount + calls[0].output.1
+        );
+    }
+
+    #[test]
+    fn covers_rounding_of_every_order() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let pair = TokenPair::new(token_a, token_b).unwrap();
+        let (amms, amm_handler) = amms(pair);
+
+        // Odd amounts so that the executed amounts of most orders are rounded.
+        let orders = vec![
+            order(token_a, token_b, OrderKind::Sell, 33_333, 30_000),
+            order(token_a, token_b, OrderKind::Sell, 22_223, 20_000),
+            order(token_a, token_b, OrderKind::Sell, 11_117, 10_000),
+            order(token_b, token_a, OrderKind::Sell, 7_001, 6_000),
+            order(token_b, token_a, OrderKind::Sell, 5_003, 4_000),
+            order(token_b, token_a, OrderKind::Buy, 9_000, 8_009),
+        ];
+        let settlement = solve_pair(pair, orders.clone(), &amms, &hashset! {}).unwrap();
+        assert_eq!(settlement.trades().len(), orders.len());
+        let calls = amm_handler.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].input.0, token_a);
+
+        // Compute the transfers like the contract does, rounding in favour of the users.
+        let price = |token| settlement.clearing_price(token).unwrap();
+        let ceil_div = |a: U256, b: U256| (a + b - 1) / b;
+        let (mut balance_a, mut balance_b) = (0i128, 0i128);
+        for order in &orders {
+            let (sell_price, buy_price) = (price(order.sell_token), price(order.buy_token));
+            let (sold, bought) = match order.kind {
+                OrderKind::Sell => (
+                    order.sell_amount,
+                    ceil_div(order.sell_amount * sell_price, buy_price),
+                ),
+                OrderKind::Buy => (order.buy_amount * buy_price / sell_price, order.buy_amount),
+            };
+            if order.sell_token == token_a {
+                balance_a += sold.as_u128() as i128;
+                balance_b -= bought.as_u128() as i128;
+            } else {
+                balance_b += sold.as_u128() as i128;
+                balance_a -= bought.as_u128() as i128;
+            }
+        }
+        balance_a -= calls[0].input.1.as_u128() as i128;
+        balance_b += calls[0].output.1.as_u128() as i128;
+        assert!(balance_a >= 0, "short {} of token a", -balance_a);
+        assert!(balance_b >= 0, "short {} of token b", -balance_b);
+    }
+
+    #[test]
+    fn removes_orders_with_violated_limit_price() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let pair = TokenPair::new(token_a, token_b).unwrap();
+        let (amms, _) = amms(pair);
+
+        let orders = vec![
+            order(token_a, token_b, OrderKind::Sell, 100_000, 90_000),
+            order(token_a, token_b, OrderKind::Sell, 100_000, 90_000),
+            order(token_a, token_b, OrderKind::Sell, 100_000, 110_000),
+        ];
+        let settlement = solve_pair(pair, orders, &amms, &hashset! {}).unwrap();
+        assert_eq!(settlement.trades().len(), 2);
+    }
+
+    #[test]
+    fn limit_violation_of_worthless_sell_token_is_infinite() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let order = order(token_a, token_b, OrderKind::Sell, 100, 1);
+
+        let prices = maplit::hashmap! { token_a => 0.into(), token_b => 1.into() };
+        let infinite = limit_violation(&order, &prices).unwrap();
+        assert_eq!(infinite, LimitViolation::Infinite);
+
+        let prices = maplit::hashmap! { token_a => 1.into(), token_b => 1_000.into() };
+        let finite = limit_violation(&order, &prices).unwrap();
+        assert_eq!(
+            finite,
+            LimitViolation::Finite(BigRational::new(10.into(), 1.into()))
+        );
+        assert!(infinite > finite);
+    }
+}
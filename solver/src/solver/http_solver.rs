@@ -3,6 +3,7 @@ mod settlement;
 
 use self::{model::*, settlement::SettlementContext};
 use crate::{
+    interactions::allowances::{AllowanceManaging, Approval},
     liquidity::{ConstantProductOrder, LimitOrder, Liquidity, WeightedProductOrder},
     settlement::Settlement,
     solver::Solver,
@@ -10,7 +11,7 @@ use crate::{
 use ::model::order::OrderKind;
 use anyhow::{ensure, Context, Result};
 use ethcontract::U256;
-use futures::join;
+use futures::{future, join};
 use lazy_static::lazy_static;
 use num::{BigInt, BigRational, ToPrimitive};
 use primitive_types::H160;
@@ -69,6 +70,7 @@ pub struct HttpSolver {
     network_id: String,
     chain_id: u64,
     fee_discount_factor: f64,
+    allowance_manager: Box<dyn AllowanceManaging>,
 }
 
 impl HttpSolver {
@@ -83,6 +85,7 @@ impl HttpSolver {
         network_id: String,
         chain_id: u64,
         fee_discount_factor: f64,
+        allowance_manager: Box<dyn AllowanceManaging>,
     ) -> Self {
         // Unwrap because we cannot handle client creation failing.
         let client = Client::builder().build().unwrap();
@@ -97,9 +100,28 @@ impl HttpSolver {
             network_id,
             chain_id,
             fee_discount_factor,
+            allowance_manager,
         }
     }
 
+    // Fetches the approvals needed by the custom interactions of the solution.
+    async fn get_approvals(
+        &self,
+        settled: &SettledBatchAuctionModel,
+    ) -> Result<HashMap<(H160, H160), Approval>> {
+        future::try_join_all(settlement::required_approvals(settled).into_iter().map(
+            |((token, spender), amount)| async move {
+                let approval = self
+                    .allowance_manager
+                    .get_approval(token, spender, amount)
+                    .await?;
+                Result::<_>::Ok(((token, spender), approval))
+            },
+        ))
+        .await
+        .map(|approvals| approvals.into_iter().collect())
+    }
+
     fn map_tokens_for_solver(&self, orders: &[Liquidity]) -> Vec<H160> {
         orders
             .iter()
@@ -448,7 +470,12 @@ impl Solver for HttpSolver {
         if !settled.has_execution_plan() {
             return Ok(Vec::new());
         }
-        settlement::convert_settlement(settled, context).map(|settlement| vec![settlement])
+        let approvals = self
+            .get_approvals(&settled)
+            .await
+            .context("failed to get approvals for custom interactions")?;
+        settlement::convert_settlement(settled, context, approvals)
+            .map(|settlement| vec![settlement])
     }
 
     fn name(&self) -> &'static str {
@@ -459,6 +486,7 @@ impl Solver for HttpSolver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::interactions::allowances::MockAllowanceManaging;
     use crate::liquidity::{tests::CapturingSettlementHandler, ConstantProductOrder, LimitOrder};
     use ::model::TokenPair;
     use maplit::hashmap;
@@ -511,6 +539,7 @@ mod tests {
             "mock_network_id".to_string(),
             0,
             1.,
+            Box::new(MockAllowanceManaging::new()),
         );
         let base = |x: u128| x * 10u128.pow(18);
         let orders = vec![
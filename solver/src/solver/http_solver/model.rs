@@ -1,3 +1,4 @@
+use crate::solver::solver_utils::{deserialize_prefixed_hex, serialize_prefixed_hex};
 use ethcontract::H160;
 use model::{
     ratio_as_decimal,
@@ -9,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use std::collections::HashMap;
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct BatchAuctionModel {
     pub tokens: HashMap<H160, TokenInfoModel>,
     pub orders: HashMap<usize, OrderModel>,
@@ -17,7 +18,7 @@ pub struct BatchAuctionModel {
     pub metadata: Option<MetadataModel>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct OrderModel {
     pub sell_token: H160,
     pub buy_token: H160,
@@ -87,7 +88,7 @@ pub struct WeightedProductPoolParameters {
     pub reserves: HashMap<H160, PoolTokenData>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TokenInfoModel {
     pub decimals: Option<u8>,
     pub external_price: Option<f64>,
@@ -101,20 +102,24 @@ pub struct CostModel {
     pub token: H160,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct FeeModel {
     #[serde(with = "u256_decimal")]
     pub amount: U256,
     pub token: H160,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct SettledBatchAuctionModel {
     pub orders: HashMap<usize, ExecutedOrderModel>,
     #[serde(default)]
     pub amms: HashMap<usize, UpdatedAmmModel>,
     pub ref_token: H160,
     pub prices: HashMap<H160, Price>,
+    /// Arbitrary contract calls the solver wants to execute as part of the settlement, for
+    /// example fills from private market makers.
+    #[serde(default)]
+    pub interaction_data: Vec<InteractionDataModel>,
 }
 
 impl SettledBatchAuctionModel {
@@ -128,15 +133,15 @@ impl SettledBatchAuctionModel {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Price(#[serde(with = "serde_with::rust::display_fromstr")] pub f64);
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MetadataModel {
     pub environment: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ExecutedOrderModel {
     #[serde(with = "u256_decimal")]
     pub exec_sell_amount: U256,
@@ -144,13 +149,13 @@ pub struct ExecutedOrderModel {
     pub exec_buy_amount: U256,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct UpdatedAmmModel {
     /// We ignore additional incoming amm fields we don't need.
     pub execution: Vec<ExecutedAmmModel>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ExecutedAmmModel {
     pub sell_token: H160,
     pub buy_token: H160,
@@ -176,12 +181,36 @@ impl UpdatedAmmModel {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ExecutionPlanCoordinatesModel {
     pub sequence: u32,
     pub position: u32,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InteractionDataModel {
+    pub target: H160,
+    #[serde(with = "u256_decimal")]
+    pub value: U256,
+    #[serde(
+        deserialize_with = "deserialize_prefixed_hex",
+        serialize_with = "serialize_prefixed_hex"
+    )]
+    pub call_data: Vec<u8>,
+    /// The approvals the settlement contract needs to have given for the interaction to succeed.
+    #[serde(default)]
+    pub allowances: Vec<ApprovalModel>,
+    pub exec_plan: ExecutionPlanCoordinatesModel,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApprovalModel {
+    pub token: H160,
+    pub spender: H160,
+    #[serde(with = "u256_decimal")]
+    pub amount: U256,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -415,4 +444,51 @@ mod tests {
         "#;
         assert!(serde_json::from_str::<SettledBatchAuctionModel>(empty_solution).is_ok());
     }
+
+    #[test]
+    fn decode_interaction_data() {
+        let solution = r#"
+            {
+                "orders": {},
+                "ref_token": "0xc778417e063141139fce010982780140aa0cd5ab",
+                "prices": {},
+                "interaction_data": [
+                    {
+                        "target": "0xdef1c0ded9bec7f1a1670819833240f027b25eff",
+                        "value": "0",
+                        "call_data": "0xa9059cbb",
+                        "allowances": [
+                            {
+                                "token": "0xc778417e063141139fce010982780140aa0cd5ab",
+                                "spender": "0xdef1c0ded9bec7f1a1670819833240f027b25eff",
+                                "amount": "1000"
+                            }
+                        ],
+                        "exec_plan": {
+                            "sequence": 0,
+                            "position": 2
+                        }
+                    }
+                ]
+            }
+        "#;
+        let settled = serde_json::from_str::<SettledBatchAuctionModel>(solution).unwrap();
+        let interaction = &settled.interaction_data[0];
+        assert_eq!(
+            interaction.target,
+            H160(hex_literal::hex!(
+                "def1c0ded9bec7f1a1670819833240f027b25eff"
+            ))
+        );
+        assert_eq!(interaction.value, U256::zero());
+        assert_eq!(interaction.call_data, vec![0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(interaction.allowances[0].amount, U256::from(1000));
+        assert_eq!(
+            interaction.exec_plan,
+            ExecutionPlanCoordinatesModel {
+                sequence: 0,
+                position: 2
+            }
+        );
+    }
 }
@@ -1,15 +1,18 @@
 use super::model::*;
 use crate::liquidity::WeightedProductOrder;
 use crate::{
+    encoding::EncodedInteraction,
+    interactions::allowances::Approval,
     liquidity::{AmmOrderExecution, ConstantProductOrder, LimitOrder},
-    settlement::Settlement,
+    settlement::{Interaction, Settlement},
 };
 use anyhow::{anyhow, ensure, Result};
+use ethcontract::Bytes;
 use itertools::Itertools;
 use model::order::OrderKind;
 use primitive_types::{H160, U256};
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     iter,
 };
 
@@ -22,11 +25,30 @@ pub struct SettlementContext {
     pub weighted_product_orders: HashMap<usize, WeightedProductOrder>,
 }
 
+// The approvals needed by the custom interactions of a solution, keyed by token and spender. The
+// amounts of interactions with the same token and spender are summed up.
+pub fn required_approvals(settled: &SettledBatchAuctionModel) -> HashMap<(H160, H160), U256> {
+    let mut approvals = HashMap::new();
+    for approval in settled
+        .interaction_data
+        .iter()
+        .flat_map(|interaction| &interaction.allowances)
+    {
+        let amount = approvals
+            .entry((approval.token, approval.spender))
+            .or_insert_with(U256::zero);
+        *amount = amount.saturating_add(approval.amount);
+    }
+    approvals
+}
+
+// `approvals` has to contain an approval for every entry of `required_approvals`.
 pub fn convert_settlement(
     settled: SettledBatchAuctionModel,
     context: SettlementContext,
+    approvals: HashMap<(H160, H160), Approval>,
 ) -> Result<Settlement> {
-    let intermediate = IntermediateSettlement::new(settled, context)?;
+    let intermediate = IntermediateSettlement::new(settled, context, approvals)?;
     intermediate.into_settlement()
 }
 
@@ -36,6 +58,7 @@ struct IntermediateSettlement {
     executed_limit_orders: Vec<ExecutedLimitOrder>,
     executed_constant_product_amms: Vec<ExecutedConstantProductAmms>,
     executed_weighted_product_amms: Vec<ExecutedWeightedProductAmms>,
+    executed_interactions: Vec<ExecutedInteraction>,
     prices: HashMap<H160, U256>,
 }
 
@@ -58,16 +81,61 @@ struct ExecutedConstantProductAmms {
     order: ConstantProductOrder,
     input: (H160, U256),
     output: (H160, U256),
+    exec_plan: Option<ExecutionPlanCoordinatesModel>,
 }
 
 struct ExecutedWeightedProductAmms {
     order: WeightedProductOrder,
     input: (H160, U256),
     output: (H160, U256),
+    exec_plan: Option<ExecutionPlanCoordinatesModel>,
+}
+
+struct ExecutedInteraction {
+    interaction: CustomInteraction,
+    // Approvals that have to be executed right before the interaction.
+    approvals: Vec<Approval>,
+    exec_plan: ExecutionPlanCoordinatesModel,
+}
+
+// An arbitrary contract call returned by the solver.
+#[derive(Debug)]
+struct CustomInteraction {
+    target: H160,
+    value: U256,
+    call_data: Bytes<Vec<u8>>,
+}
+
+impl Interaction for CustomInteraction {
+    fn encode(&self) -> Vec<EncodedInteraction> {
+        vec![(self.target, self.value, self.call_data.clone())]
+    }
+}
+
+// Everything that becomes part of the execution plan, so that it can be ordered by the
+// coordinates the solver assigned.
+enum Execution {
+    ConstantProductAmm(ExecutedConstantProductAmms),
+    WeightedProductAmm(ExecutedWeightedProductAmms),
+    Interaction(ExecutedInteraction),
+}
+
+impl Execution {
+    fn exec_plan(&self) -> Option<&ExecutionPlanCoordinatesModel> {
+        match self {
+            Execution::ConstantProductAmm(amm) => amm.exec_plan.as_ref(),
+            Execution::WeightedProductAmm(amm) => amm.exec_plan.as_ref(),
+            Execution::Interaction(interaction) => Some(&interaction.exec_plan),
+        }
+    }
 }
 
 impl IntermediateSettlement {
-    fn new(settled: SettledBatchAuctionModel, context: SettlementContext) -> Result<Self> {
+    fn new(
+        settled: SettledBatchAuctionModel,
+        context: SettlementContext,
+        approvals: HashMap<(H160, H160), Approval>,
+    ) -> Result<Self> {
         let executed_limit_orders =
             match_prepared_and_settled_orders(context.limit_orders, settled.orders)?;
         let executed_amms = match_prepared_and_settled_amms(
@@ -75,15 +143,41 @@ impl IntermediateSettlement {
             context.weighted_product_orders,
             settled.amms,
         )?;
+        let executed_interactions =
+            match_settled_interactions(settled.interaction_data, approvals)?;
         let prices = match_settled_prices(
             executed_limit_orders.as_slice(),
             (executed_amms.0.as_slice(), executed_amms.1.as_slice()),
             settled.prices,
         )?;
+        let mut positions = HashSet::new();
+        for exec_plan in executed_amms
+            .0
+            .iter()
+            .filter_map(|amm| amm.exec_plan.as_ref())
+            .chain(
+                executed_amms
+                    .1
+                    .iter()
+                    .filter_map(|amm| amm.exec_plan.as_ref()),
+            )
+            .chain(
+                executed_interactions
+                    .iter()
+                    .map(|interaction| &interaction.exec_plan),
+            )
+        {
+            ensure!(
+                positions.insert(exec_plan),
+                "duplicate execution plan coordinates {:?}",
+                exec_plan
+            );
+        }
         Ok(Self {
             executed_limit_orders,
             executed_constant_product_amms: executed_amms.0,
             executed_weighted_product_amms: executed_amms.1,
+            executed_interactions,
             prices,
         })
     }
@@ -93,23 +187,54 @@ impl IntermediateSettlement {
         for order in self.executed_limit_orders.iter() {
             settlement.with_liquidity(&order.order, order.executed_amount())?;
         }
-        for amm in self.executed_constant_product_amms.iter() {
-            settlement.with_liquidity(
-                &amm.order,
-                AmmOrderExecution {
-                    input: amm.input,
-                    output: amm.output,
-                },
-            )?;
-        }
-        for amm in self.executed_weighted_product_amms.iter() {
-            settlement.with_liquidity(
-                &amm.order,
-                AmmOrderExecution {
-                    input: amm.input,
-                    output: amm.output,
-                },
-            )?;
+        let executions = self
+            .executed_constant_product_amms
+            .into_iter()
+            .map(Execution::ConstantProductAmm)
+            .chain(
+                self.executed_weighted_product_amms
+                    .into_iter()
+                    .map(Execution::WeightedProductAmm),
+            )
+            .chain(
+                self.executed_interactions
+                    .into_iter()
+                    .map(Execution::Interaction),
+            )
+            .sorted_by(|a, b| a.exec_plan().cmp(&b.exec_plan()));
+        for execution in executions {
+            match execution {
+                Execution::ConstantProductAmm(amm) => settlement.with_liquidity(
+                    &amm.order,
+                    AmmOrderExecution {
+                        input: amm.input,
+                        output: amm.output,
+                    },
+                )?,
+                Execution::WeightedProductAmm(amm) => settlement.with_liquidity(
+                    &amm.order,
+                    AmmOrderExecution {
+                        input: amm.input,
+                        output: amm.output,
+                    },
+                )?,
+                Execution::Interaction(executed) => {
+                    // What a custom interaction trades is unknown so it could involve any token
+                    // of the settlement.
+                    let tokens = settlement
+                        .clearing_prices()
+                        .keys()
+                        .copied()
+                        .collect::<Vec<_>>();
+                    settlement.encoder.add_onchain_liquidity_tokens(tokens);
+                    for approval in executed.approvals {
+                        settlement.encoder.append_to_execution_plan(approval);
+                    }
+                    settlement
+                        .encoder
+                        .append_to_execution_plan(executed.interaction);
+                }
+            }
         }
         Ok(settlement)
     }
@@ -162,6 +287,7 @@ fn match_prepared_and_settled_amms(
             (settled.buy_token, settled.exec_buy_amount),
             (settled.sell_token, settled.exec_sell_amount),
         );
+        let exec_plan = settled.exec_plan;
         // Recall, prepared amm for weighted products are shifted by the constant product amms
         let shift = prepared_constant_product_orders.len();
         if index < shift && prepared_constant_product_orders.contains_key(&index) {
@@ -169,6 +295,7 @@ fn match_prepared_and_settled_amms(
                 order: prepared_constant_product_orders.remove(&index).unwrap(),
                 input,
                 output,
+                exec_plan,
             });
         } else if index >= shift && prepared_weighted_product_orders.contains_key(&(index - shift))
         {
@@ -178,6 +305,7 @@ fn match_prepared_and_settled_amms(
                     .unwrap(),
                 input,
                 output,
+                exec_plan,
             });
         } else {
             return Err(anyhow!("Invalid AMM {}", index));
@@ -186,6 +314,50 @@ fn match_prepared_and_settled_amms(
     Ok((constant_product_executions, weighted_product_executions))
 }
 
+fn match_settled_interactions(
+    interaction_data: Vec<InteractionDataModel>,
+    mut approvals: HashMap<(H160, H160), Approval>,
+) -> Result<Vec<ExecutedInteraction>> {
+    let mut seen_approvals = HashSet::new();
+    interaction_data
+        .into_iter()
+        .sorted_by(|a, b| a.exec_plan.cmp(&b.exec_plan))
+        .map(|interaction| {
+            ensure!(
+                !interaction.target.is_zero(),
+                "interaction with zero address target"
+            );
+            let mut interaction_approvals = Vec::new();
+            for approval in &interaction.allowances {
+                let key = (approval.token, approval.spender);
+                ensure!(
+                    !approval.amount.is_zero(),
+                    "zero approval of token {:?} for spender {:?}",
+                    approval.token,
+                    approval.spender
+                );
+                // Only the first interaction that needs an approval executes it.
+                if seen_approvals.insert(key) {
+                    interaction_approvals.push(
+                        approvals
+                            .remove(&key)
+                            .ok_or_else(|| anyhow!("missing approval for {:?}", key))?,
+                    );
+                }
+            }
+            Ok(ExecutedInteraction {
+                interaction: CustomInteraction {
+                    target: interaction.target,
+                    value: interaction.value,
+                    call_data: Bytes(interaction.call_data),
+                },
+                approvals: interaction_approvals,
+                exec_plan: interaction.exec_plan,
+            })
+        })
+        .collect()
+}
+
 fn match_settled_prices(
     executed_limit_orders: &[ExecutedLimitOrder],
     executed_amms: (
@@ -311,6 +483,7 @@ mod tests {
             amms: hashmap! { 0 => updated_uniswap, 1 => updated_balancer },
             ref_token: t0,
             prices: hashmap! { t0 => Price(10.0), t1 => Price(11.0) },
+            interaction_data: Vec::new(),
         };
 
         let prepared = SettlementContext {
@@ -319,7 +492,7 @@ mod tests {
             weighted_product_orders,
         };
 
-        let settlement = convert_settlement(settled, prepared).unwrap();
+        let settlement = convert_settlement(settled, prepared, HashMap::new()).unwrap();
         assert_eq!(
             settlement.clearing_prices(),
             &hashmap! { t0 => 10.into(), t1 => 11.into() }
@@ -342,6 +515,104 @@ mod tests {
         );
     }
 
+    fn interaction_data(
+        target: u64,
+        allowances: Vec<ApprovalModel>,
+        position: u32,
+    ) -> InteractionDataModel {
+        InteractionDataModel {
+            target: H160::from_low_u64_be(target),
+            value: 0.into(),
+            call_data: vec![target as u8],
+            allowances,
+            exec_plan: ExecutionPlanCoordinatesModel {
+                sequence: 0,
+                position,
+            },
+        }
+    }
+
+    #[test]
+    fn converts_custom_interactions_in_execution_plan_order() {
+        let token = H160::from_low_u64_be(1);
+        let spender = H160::from_low_u64_be(2);
+        let approval = |amount: u64| ApprovalModel {
+            token,
+            spender,
+            amount: amount.into(),
+        };
+        let settled = SettledBatchAuctionModel {
+            orders: HashMap::new(),
+            amms: HashMap::new(),
+            ref_token: token,
+            prices: HashMap::new(),
+            interaction_data: vec![
+                interaction_data(10, vec![approval(5)], 1),
+                interaction_data(11, vec![approval(3)], 0),
+            ],
+        };
+        assert_eq!(
+            required_approvals(&settled),
+            hashmap! { (token, spender) => 8.into() }
+        );
+
+        let context = SettlementContext {
+            limit_orders: HashMap::new(),
+            constant_product_orders: HashMap::new(),
+            weighted_product_orders: HashMap::new(),
+        };
+        let approvals = hashmap! { (token, spender) => Approval::Approve { token, spender } };
+        let settlement = convert_settlement(settled, context, approvals).unwrap();
+
+        let interactions = settlement.encoder.finish().interactions[1].clone();
+        let targets = interactions
+            .iter()
+            .map(|(target, _, _)| *target)
+            .collect::<Vec<_>>();
+        // The approval is only executed once, before the first interaction that needs it.
+        assert_eq!(
+            targets,
+            vec![token, H160::from_low_u64_be(11), H160::from_low_u64_be(10)]
+        );
+        assert_eq!(interactions[1].2 .0, vec![11]);
+    }
+
+    #[test]
+    fn rejects_invalid_custom_interactions() {
+        let convert = |interaction_data: Vec<InteractionDataModel>| {
+            let settled = SettledBatchAuctionModel {
+                orders: HashMap::new(),
+                amms: HashMap::new(),
+                ref_token: H160::zero(),
+                prices: HashMap::new(),
+                interaction_data,
+            };
+            let context = SettlementContext {
+                limit_orders: HashMap::new(),
+                constant_product_orders: HashMap::new(),
+                weighted_product_orders: HashMap::new(),
+            };
+            convert_settlement(settled, context, HashMap::new())
+        };
+
+        assert!(convert(vec![interaction_data(1, vec![], 0)]).is_ok());
+        // Zero address target.
+        assert!(convert(vec![interaction_data(0, vec![], 0)]).is_err());
+        // Duplicate position in the execution plan.
+        assert!(convert(vec![
+            interaction_data(1, vec![], 0),
+            interaction_data(2, vec![], 0)
+        ])
+        .is_err());
+        // Missing approval.
+        let approval = ApprovalModel {
+            token: H160::from_low_u64_be(3),
+            spender: H160::from_low_u64_be(4),
+            amount: 1.into(),
+        };
+        assert!(convert(vec![interaction_data(1, vec![approval], 0)]).is_err());
+    }
+
     #[test]
     fn match_prepared_and_settled_amms_() {
         let token_a = H160::from_slice(&hex!("a7d1c04faf998f9161fc9f800a99a809b84cfc9d"));
@@ -19,122 +19,90 @@
 
 pub mod api;
 
+use super::aggregator_solver::{
+    AggregatorError, Quote, SwapBuilding, SwapQuoting, SwapRequest, SwapTransaction,
+};
 use super::solver_utils::Slippage;
-use crate::interactions::allowances::{AllowanceManager, AllowanceManaging};
 use crate::solver::matcha_solver::api::MatchaApi;
 use anyhow::{ensure, Result};
-use contracts::GPv2Settlement;
 use ethcontract::Bytes;
-use maplit::hashmap;
-
-use super::single_order_solver::SingleOrderSolving;
 
 use self::api::{DefaultMatchaApi, SwapQuery, SwapResponse};
-use crate::{
-    encoding::EncodedInteraction,
-    liquidity::LimitOrder,
-    settlement::{Interaction, Settlement},
-};
+use crate::{encoding::EncodedInteraction, settlement::Interaction};
 use model::order::OrderKind;
-use shared::Web3;
 use std::fmt::{self, Display, Formatter};
 
 /// Constant maximum slippage of 5 BPS (0.05%) to use for on-chain liquidity.
 pub const STANDARD_MATCHA_SLIPPAGE_BPS: u16 = 5;
 
-/// A GPv2 solver that matches GP orders to direct Matcha swaps.
+/// An aggregator API that matches GP orders to direct Matcha swaps.
 pub struct MatchaSolver {
     client: Box<dyn MatchaApi + Send + Sync>,
-    allowance_fetcher: Box<dyn AllowanceManaging>,
 }
 
 /// Chain ID for Mainnet.
 const MAINNET_CHAIN_ID: u64 = 1;
 
 impl MatchaSolver {
-    pub fn new(web3: Web3, settlement_contract: GPv2Settlement, chain_id: u64) -> Result<Self> {
+    pub fn new(chain_id: u64) -> Result<Self> {
         ensure!(
             chain_id == MAINNET_CHAIN_ID,
             "Matcha solver only supported on Mainnet",
         );
-        let allowance_fetcher = AllowanceManager::new(web3, settlement_contract.address());
         Ok(Self {
-            allowance_fetcher: Box::new(allowance_fetcher),
             client: Box::new(DefaultMatchaApi::default()),
         })
     }
 }
 
 #[async_trait::async_trait]
-impl SingleOrderSolving for MatchaSolver {
-    async fn settle_order(&self, order: LimitOrder) -> Result<Option<Settlement>> {
-        let swap = match order.kind {
-            OrderKind::Sell => {
-                let query = SwapQuery {
-                    sell_token: order.sell_token,
-                    buy_token: order.buy_token,
-                    sell_amount: Some(order.sell_amount),
-                    buy_amount: None,
-                    slippage_percentage: Slippage::number_from_basis_points(
-                        STANDARD_MATCHA_SLIPPAGE_BPS,
-                    )
-                    .unwrap(),
-                    skip_validation: Some(true),
-                };
-
-                tracing::debug!("querying Matcha swap api with {:?}", query);
-                let swap = self.client.get_swap(query).await?;
-                tracing::debug!("proposed Matcha swap is {:?}", swap);
-
-                if swap.buy_amount < order.buy_amount {
-                    tracing::debug!("Order limit price not respected");
-                    return Ok(None);
-                }
-                swap
-            }
-            OrderKind::Buy => {
-                let query = SwapQuery {
-                    sell_token: order.sell_token,
-                    buy_token: order.buy_token,
-                    sell_amount: None,
-                    buy_amount: Some(order.buy_amount),
-                    slippage_percentage: Slippage::number_from_basis_points(
-                        STANDARD_MATCHA_SLIPPAGE_BPS,
-                    )
-                    .unwrap(),
-                    skip_validation: Some(true),
-                };
-
-                tracing::debug!("querying Matcha swap api with {:?}", query);
-                let swap = self.client.get_swap(query).await?;
-                tracing::debug!("proposed Matcha swap is {:?}", swap);
-
-                if swap.sell_amount > order.sell_amount {
-                    tracing::debug!("Order limit price not respected");
-                    return Ok(None);
-                }
-                swap
-            }
+impl SwapQuoting for MatchaSolver {
+    type Quote = SwapResponse;
+
+    fn name(&self) -> &'static str {
+        "Matcha"
+    }
+
+    async fn quote(&self, request: &SwapRequest) -> Result<Quote<SwapResponse>, AggregatorError> {
+        let (sell_amount, buy_amount) = match request.kind {
+            OrderKind::Sell => (Some(request.amount), None),
+            OrderKind::Buy => (None, Some(request.amount)),
+        };
+        let query = SwapQuery {
+            sell_token: request.sell_token,
+            buy_token: request.buy_token,
+            sell_amount,
+            buy_amount,
+            slippage_percentage: Slippage::number_from_basis_points(request.slippage_bps)?,
+            skip_validation: Some(true),
         };
-        let mut settlement = Settlement::new(hashmap! {
-            order.sell_token => swap.buy_amount,
-            order.buy_token => swap.sell_amount,
-        });
-        let spender = swap.allowance_target;
 
-        settlement.with_liquidity(&order, order.full_execution_amount())?;
+        tracing::debug!("querying Matcha swap api with {:?}", query);
+        let swap = self.client.get_swap(query).await?;
+        tracing::debug!("proposed Matcha swap is {:?}", swap);
 
-        settlement.encoder.append_to_execution_plan(
-            self.allowance_fetcher
-                .get_approval(order.sell_token, spender, swap.sell_amount)
-                .await?,
-        );
-        settlement.encoder.append_to_execution_plan(swap);
-        Ok(Some(settlement))
+        Ok(Quote {
+            sell_amount: swap.sell_amount,
+            buy_amount: swap.buy_amount,
+            data: swap,
+        })
     }
+}
 
-    fn name(&self) -> &'static str {
-        "Matcha"
+#[async_trait::async_trait]
+impl SwapBuilding for MatchaSolver {
+    type Interaction = SwapResponse;
+
+    async fn build_swap(
+        &self,
+        _: &SwapRequest,
+        quote: Quote<SwapResponse>,
+    ) -> Result<SwapTransaction<SwapResponse>, AggregatorError> {
+        // Matcha's quote already contains the swap transaction.
+        Ok(SwapTransaction {
+            spender: quote.data.allowance_target,
+            interaction: quote.data,
+        })
     }
 }
 
@@ -153,16 +121,33 @@ impl Display for MatchaSolver {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::interactions::allowances::{Approval, MockAllowanceManaging};
+    use crate::interactions::allowances::{AllowanceManager, Approval, MockAllowanceManaging};
     use crate::liquidity::tests::CapturingSettlementHandler;
     use crate::liquidity::LimitOrder;
+    use crate::metrics::NoopMetrics;
+    use crate::solver::aggregator_solver::AggregatorSolver;
     use crate::solver::matcha_solver::api::MockMatchaApi;
+    use crate::solver::single_order_solver::SingleOrderSolving;
     use contracts::{GPv2Settlement, WETH9};
     use ethcontract::{Web3, H160, U256};
+    use maplit::hashmap;
     use mockall::predicate::*;
     use mockall::Sequence;
     use model::order::{Order, OrderCreation, OrderKind};
     use shared::transport::{create_env_test_transport, create_test_transport};
+    use std::sync::Arc;
+
+    fn dummy_solver(
+        client: Box<MockMatchaApi>,
+        allowance_fetcher: Box<MockAllowanceManaging>,
+    ) -> AggregatorSolver<MatchaSolver> {
+        AggregatorSolver::new(
+            MatchaSolver { client },
+            allowance_fetcher,
+            STANDARD_MATCHA_SLIPPAGE_BPS,
+            Arc::new(NoopMetrics::default()),
+        )
+    }
 
     #[tokio::test]
     #[ignore]
@@ -174,7 +159,13 @@ mod tests {
         let weth = WETH9::deployed(&web3).await.unwrap();
         let gno = shared::addr!("6810e776880c02933d47db1b9fc05908e5386b96");
 
-        let solver = MatchaSolver::new(web3, settlement, chain_id).unwrap();
+        let allowance_fetcher = AllowanceManager::new(web3, settlement.address());
+        let solver = AggregatorSolver::new(
+            MatchaSolver::new(chain_id).unwrap(),
+            Box::new(allowance_fetcher),
+            STANDARD_MATCHA_SLIPPAGE_BPS,
+            Arc::new(NoopMetrics::default()),
+        );
         let settlement = solver
             .settle_order(
                 Order {
@@ -206,7 +197,13 @@ mod tests {
         let weth = WETH9::deployed(&web3).await.unwrap();
         let gno = shared::addr!("6810e776880c02933d47db1b9fc05908e5386b96");
 
-        let solver = MatchaSolver::new(web3, settlement, chain_id).unwrap();
+        let allowance_fetcher = AllowanceManager::new(web3, settlement.address());
+        let solver = AggregatorSolver::new(
+            MatchaSolver::new(chain_id).unwrap(),
+            Box::new(allowance_fetcher),
+            STANDARD_MATCHA_SLIPPAGE_BPS,
+            Arc::new(NoopMetrics::default()),
+        );
         let settlement = solver
             .settle_order(
                 Order {
@@ -260,10 +257,7 @@ mod tests {
                 })
             });
 
-        let solver = MatchaSolver {
-            client,
-            allowance_fetcher,
-        };
+        let solver = dummy_solver(client, allowance_fetcher);
 
         let buy_order_passing_limit = LimitOrder {
             sell_token,
@@ -344,9 +338,8 @@ mod tests {
             &std::env::var("NODE_URL_RINKEBY").unwrap(),
         ));
         let chain_id = web3.eth().chain_id().await.unwrap().as_u64();
-        let settlement = GPv2Settlement::deployed(&web3).await.unwrap();
 
-        assert!(MatchaSolver::new(web3, settlement, chain_id).is_err())
+        assert!(MatchaSolver::new(chain_id).is_err())
     }
 
     #[tokio::test]
@@ -389,10 +382,7 @@ mod tests {
             .returning(|_, _, _| Ok(Approval::AllowanceSufficient))
             .in_sequence(&mut seq);
 
-        let solver = MatchaSolver {
-            client,
-            allowance_fetcher,
-        };
+        let solver = dummy_solver(client, allowance_fetcher);
 
         let order = LimitOrder {
             sell_token,
@@ -434,10 +424,7 @@ mod tests {
             .expect_get_approval()
             .returning(|_, _, _| Ok(Approval::AllowanceSufficient));
 
-        let solver = MatchaSolver {
-            client,
-            allowance_fetcher,
-        };
+        let solver = dummy_solver(client, allowance_fetcher);
 
         let order = LimitOrder {
             sell_token,
@@ -1,4 +1,6 @@
+mod multi_hop_solver;
 mod multi_order_solver;
+mod ring_solver;
 
 use crate::{
     liquidity::{ConstantProductOrder, LimitOrder, Liquidity},
@@ -6,22 +8,35 @@ use crate::{
     solver::Solver,
 };
 use anyhow::Result;
+use ethcontract::H160;
 use model::TokenPair;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-pub struct NaiveSolver;
+#[derive(Default)]
+pub struct NaiveSolver {
+    base_tokens: HashSet<H160>,
+}
+
+impl NaiveSolver {
+    /// Creates a naive solver that routes orders between tokens without a direct AMM via the given
+    /// base tokens.
+    pub fn new(base_tokens: HashSet<H160>) -> Self {
+        Self { base_tokens }
+    }
+}
 
 #[async_trait::async_trait]
 impl Solver for NaiveSolver {
     async fn solve(&self, liquidity: Vec<Liquidity>, _gas_price: f64) -> Result<Vec<Settlement>> {
         let uniswaps = extract_deepest_amm_liquidity(&liquidity);
+        let amms = extract_amm_liquidity(&liquidity);
         let limit_orders = liquidity
             .into_iter()
             .filter_map(|liquidity| match liquidity {
                 Liquidity::Limit(order) => Some(order),
                 _ => None,
             });
-        Ok(settle(limit_orders, uniswaps).await)
+        Ok(settle(limit_orders, uniswaps, &amms, &self.base_tokens).await)
     }
 
     fn name(&self) -> &'static str {
@@ -32,28 +47,41 @@ impl Solver for NaiveSolver {
 async fn settle(
     orders: impl Iterator<Item = LimitOrder>,
     uniswaps: HashMap<TokenPair, ConstantProductOrder>,
+    amms: &HashMap<TokenPair, Vec<ConstantProductOrder>>,
+    base_tokens: &HashSet<H160>,
 ) -> Vec<Settlement> {
+    let orders: Vec<_> = orders.filter(usable_order).collect();
+
     // The multi order solver matches as many orders as possible together with one uniswap pool.
-    // Settlements between different token pairs are thus independent.
-    organize_orders_by_token_pair(orders)
+    // Settlements between different token pairs are thus independent. Pairs without a direct pool
+    // are routed over multiple pools instead.
+    let mut settlements: Vec<_> = organize_orders_by_token_pair(orders.iter().cloned())
         .into_iter()
-        .filter_map(|(pair, orders)| settle_pair(pair, orders, &uniswaps))
-        .collect()
+        .filter_map(|(pair, orders)| settle_pair(pair, orders, &uniswaps, amms, base_tokens))
+        .collect();
+
+    // Rings reuse orders of the pair settlements, so the driver has to pick between them.
+    settlements.extend(ring_solver::solve(orders.into_iter()));
+    settlements
 }
 
 fn settle_pair(
     pair: TokenPair,
     orders: Vec<LimitOrder>,
     uniswaps: &HashMap<TokenPair, ConstantProductOrder>,
+    amms: &HashMap<TokenPair, Vec<ConstantProductOrder>>,
+    base_tokens: &HashSet<H160>,
 ) -> Option<Settlement> {
-    let uniswap = match uniswaps.get(&pair) {
-        Some(uniswap) => uniswap,
+    match uniswaps.get(&pair) {
+        Some(uniswap) => multi_order_solver::solve(orders.into_iter(), uniswap),
         None => {
-            tracing::warn!("No AMM for: {:?}", pair);
-            return None;
+            let settlement = multi_hop_solver::solve(pair, orders.into_iter(), amms, base_tokens);
+            if settlement.is_none() {
+                tracing::debug!("No AMM path for: {:?}", pair);
+            }
+            settlement
         }
-    };
-    multi_order_solver::solve(orders.into_iter(), &uniswap)
+    }
 }
 
 fn organize_orders_by_token_pair(
@@ -73,6 +101,16 @@ fn usable_order(order: &LimitOrder) -> bool {
     !order.sell_amount.is_zero() && !order.buy_amount.is_zero()
 }
 
+fn extract_amm_liquidity(liquidity: &[Liquidity]) -> HashMap<TokenPair, Vec<ConstantProductOrder>> {
+    let mut result = HashMap::<_, Vec<_>>::new();
+    for liquidity in liquidity {
+        if let Liquidity::ConstantProduct(order) = liquidity {
+            result.entry(order.tokens).or_default().push(order.clone());
+        }
+    }
+    result
+}
+
 fn extract_deepest_amm_liquidity(
     liquidity: &[Liquidity],
 ) -> HashMap<TokenPair, ConstantProductOrder> {
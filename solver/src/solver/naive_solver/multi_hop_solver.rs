@@ -0,0 +1,339 @@
+use super::multi_order_solver::is_valid_solution;
+use crate::{
+    liquidity::{AmmOrderExecution, ConstantProductOrder, LimitOrder},
+    settlement::Settlement,
+    solver::baseline_solver::amm_to_pool,
+};
+use anyhow::Result;
+use model::{order::OrderKind, TokenPair};
+use num::{BigInt, BigRational, Integer as _, Signed as _};
+use primitive_types::U256;
+use shared::{
+    baseline_solver::{
+        estimate_buy_amount, estimate_spot_price, path_candidates, BaselineSolvable, Estimate,
+        DEFAULT_MAX_HOPS,
+    },
+    conversions::{big_int_to_u256, u256_to_big_int, u256_to_big_rational},
+};
+use std::collections::{HashMap, HashSet};
+use web3::types::Address;
+
+impl BaselineSolvable for ConstantProductOrder {
+    fn get_amount_out(&self, out_token: Address, input: (U256, Address)) -> Option<U256> {
+        amm_to_pool(self).get_amount_out(out_token, input)
+    }
+
+    fn get_amount_in(&self, in_token: Address, output: (U256, Address)) -> Option<U256> {
+        amm_to_pool(self).get_amount_in(in_token, output)
+    }
+
+    fn get_spot_price(&self, base_token: Address, quote_token: Address) -> Option<BigRational> {
+        amm_to_pool(self).get_spot_price(base_token, quote_token)
+    }
+
+    fn gas_cost(&self) -> usize {
+        amm_to_pool(self).gas_cost()
+    }
+}
+
+///
+/// Computes a settlement for sell orders of a single token pair that has no direct AMM. Orders on
+/// both sides are matched and the excess is routed through the best path over multiple pools that
+/// the baseline path finder can find via the base tokens.
+/// Buy orders are ignored since the amount they sell depends on the path's price.
+///
+pub fn solve(
+    pair: TokenPair,
+    orders: impl Iterator<Item = LimitOrder>,
+    amms: &HashMap<TokenPair, Vec<ConstantProductOrder>>,
+    base_tokens: &HashSet<Address>,
+) -> Option<Settlement> {
+    let mut orders: Vec<LimitOrder> = orders
+        .filter(|order| order.kind == OrderKind::Sell)
+        .collect();
+    let (token_a, token_b) = pair.get();
+    let (excess, shortage) = {
+        let price_a = best_spot_price(token_a, token_b, amms, base_tokens)?;
+        let volume_a = u256_to_big_rational(&sell_volume(&orders, token_a));
+        let volume_b = u256_to_big_rational(&sell_volume(&orders, token_b));
+        if volume_a * price_a > volume_b {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        }
+    };
+
+    while !orders.is_empty() {
+        if let Some(settlement) =
+            solve_orders(&orders, excess, shortage, amms, base_tokens).filter(is_valid_solution)
+        {
+            return Some(settlement);
+        }
+
+        // Remove order with worst limit price that is selling excess token (to make it less
+        // excessive) and try again.
+        let order_to_remove = orders
+            .iter()
+            .enumerate()
+            .filter(|o| o.1.sell_token == excess)
+            .max_by(|lhs, rhs| {
+                lhs.1
+                    .buy_amount
+                    .full_mul(rhs.1.sell_amount)
+                    .cmp(&lhs.1.sell_amount.full_mul(rhs.1.buy_amount))
+            });
+        match order_to_remove {
+            Some((index, _)) => orders.swap_remove(index),
+            None => break,
+        };
+    }
+
+    None
+}
+
+fn best_spot_price(
+    sell_token: Address,
+    buy_token: Address,
+    amms: &HashMap<TokenPair, Vec<ConstantProductOrder>>,
+    base_tokens: &HashSet<Address>,
+) -> Option<BigRational> {
+    path_candidates(sell_token, buy_token, base_tokens, DEFAULT_MAX_HOPS)
+        .iter()
+        .filter_map(|path| estimate_spot_price(path, amms))
+        .map(|estimate| estimate.value)
+        .max()
+}
+
+fn best_buy_estimate<'a>(
+    sell_amount: U256,
+    sell_token: Address,
+    buy_token: Address,
+    amms: &'a HashMap<TokenPair, Vec<ConstantProductOrder>>,
+    base_tokens: &HashSet<Address>,
+) -> Option<Estimate<'a, U256, ConstantProductOrder>> {
+    path_candidates(sell_token, buy_token, base_tokens, DEFAULT_MAX_HOPS)
+        .iter()
+        .filter_map(|path| estimate_buy_amount(sell_amount, path, amms))
+        .max_by_key(|estimate| estimate.value)
+}
+
+fn sell_volume(orders: &[LimitOrder], token: Address) -> U256 {
+    orders
+        .iter()
+        .filter(|order| order.sell_token == token)
+        .fold(U256::zero(), |volume, order| {
+            volume.saturating_add(order.sell_amount)
+        })
+}
+
+///
+/// Finds the largest amount `x` of the excess token that can be swapped along the best path for
+/// `y` of the shortage token such that the orders can be settled at the effective price of the
+/// swap (see `clearing_prices`). This is found with a binary search since the effective price of
+/// the path gets worse the more is swapped.
+///
+fn solve_orders(
+    orders: &[LimitOrder],
+    excess: Address,
+    shortage: Address,
+    amms: &HashMap<TokenPair, Vec<ConstantProductOrder>>,
+    base_tokens: &HashSet<Address>,
+) -> Option<Settlement> {
+    let is_balanced = |x: U256| -> Option<bool> {
+        let y = best_buy_estimate(x, excess, shortage, amms, base_tokens)?.value;
+        Some(clearing_prices(orders, excess, shortage, x, y).is_some())
+    };
+
+    let (mut low, mut high) = (U256::zero(), sell_volume(orders, excess));
+    if is_balanced(high)? {
+        low = high;
+    }
+    while high - low > U256::one() {
+        let middle = low + (high - low) / 2;
+        if is_balanced(middle).unwrap_or(false) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    if low.is_zero() {
+        return None;
+    }
+
+    let estimate = best_buy_estimate(low, excess, shortage, amms, base_tokens)?;
+    let prices = clearing_prices(orders, excess, shortage, low, estimate.value)?;
+    let settlement = Settlement::new(maplit::hashmap! {
+        excess => prices.0,
+        shortage => prices.1,
+    });
+    settle(settlement, orders, (low, excess), &estimate.path).ok()
+}
+
+///
+/// Returns the clearing prices of the excess and shortage token for swapping `x` for `y`, with
+/// `p_excess = y`, or `None` if the swap doesn't balance the tokens.
+/// The contract rounds the amount sell orders receive up, so every order can receive up to one
+/// atom more than at the exact price. With `n` orders selling the excess token the shortage token
+/// is balanced for `p_shortage >= S_excess * y / (S_shortage + y - n)`, where `S_t` is the total
+/// sell amount of token `t`. The excess token is balanced if `x` plus the rounded up amounts of
+/// the orders selling the shortage token don't exceed `S_excess`.
+///
+fn clearing_prices(
+    orders: &[LimitOrder],
+    excess: Address,
+    shortage: Address,
+    x: U256,
+    y: U256,
+) -> Option<(U256, U256)> {
+    if y.is_zero() {
+        return None;
+    }
+    let excess_orders = orders
+        .iter()
+        .filter(|order| order.sell_token == excess)
+        .count();
+    let (x, y) = (u256_to_big_int(&x), u256_to_big_int(&y));
+    let excess_volume = u256_to_big_int(&sell_volume(orders, excess));
+    let shortage_volume = u256_to_big_int(&sell_volume(orders, shortage));
+
+    let denominator = shortage_volume + &y - BigInt::from(excess_orders);
+    if !denominator.is_positive() {
+        return None;
+    }
+    let shortage_price = (&excess_volume * &y).div_ceil(&denominator);
+    let excess_bought = orders
+        .iter()
+        .filter(|order| order.sell_token == shortage)
+        .map(|order| (u256_to_big_int(&order.sell_amount) * &shortage_price).div_ceil(&y))
+        .sum::<BigInt>();
+    if x + excess_bought > excess_volume {
+        return None;
+    }
+    Some((
+        big_int_to_u256(&y).ok()?,
+        big_int_to_u256(&shortage_price).ok()?,
+    ))
+}
+
+fn settle(
+    mut settlement: Settlement,
+    orders: &[LimitOrder],
+    (mut sell_amount, mut sell_token): (U256, Address),
+    path: &[&ConstantProductOrder],
+) -> Result<Settlement> {
+    for order in orders {
+        settlement.with_liquidity(order, order.full_execution_amount())?;
+    }
+    for amm in path {
+        let buy_token = amm.tokens.other(&sell_token).expect("Inconsistent path");
+        let buy_amount = amm
+            .get_amount_out(buy_token, (sell_amount, sell_token))
+            .expect("Path was found, so amount must be calculable");
+        settlement.with_liquidity(
+            *amm,
+            AmmOrderExecution {
+                input: (sell_token, sell_amount),
+                output: (buy_token, buy_amount),
+            },
+        )?;
+        sell_amount = buy_amount;
+        sell_token = buy_token;
+    }
+    Ok(settlement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::liquidity::tests::CapturingSettlementHandler;
+    use maplit::{hashmap, hashset};
+    use model::order::{Order, OrderCreation};
+    use num::rational::Ratio;
+
+    fn to_wei(base: u128) -> U256 {
+        U256::from(base) * U256::from(10).pow(18.into())
+    }
+
+    fn sell_order(sell_token: Address, buy_token: Address, sell: u128, buy: u128) -> LimitOrder {
+        Order {
+            order_creation: OrderCreation {
+                sell_token,
+                buy_token,
+                sell_amount: to_wei(sell),
+                buy_amount: to_wei(buy),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn routes_excess_through_base_token() {
+        let token_a = Address::from_low_u64_be(1);
+        let token_b = Address::from_low_u64_be(2);
+        let base_token = Address::from_low_u64_be(3);
+
+        let orders = vec![
+            sell_order(token_a, token_b, 100, 90),
+            sell_order(token_b, token_a, 50, 45),
+        ];
+        let amm_handlers = [
+            CapturingSettlementHandler::arc(),
+            CapturingSettlementHandler::arc(),
+        ];
+        let pool = |tokens, handler| ConstantProductOrder {
+            tokens,
+            reserves: (to_wei(10_000).as_u128(), to_wei(10_000).as_u128()),
+            fee: Ratio::new(3, 1000),
+            settlement_handling: handler,
+        };
+        let pair_a = TokenPair::new(token_a, base_token).unwrap();
+        let pair_b = TokenPair::new(base_token, token_b).unwrap();
+        let amms = hashmap! {
+            pair_a => vec![pool(pair_a, amm_handlers[0].clone())],
+            pair_b => vec![pool(pair_b, amm_handlers[1].clone())],
+        };
+
+        let settlement = solve(
+            TokenPair::new(token_a, token_b).unwrap(),
+            orders.clone().into_iter(),
+            &amms,
+            &hashset! {base_token},
+        )
+        .unwrap();
+        assert_eq!(settlement.trades().len(), 2);
+
+        // The excess of token a is swapped for token b via the base token.
+        let first_hop = amm_handlers[0].calls()[0].clone();
+        let second_hop = amm_handlers[1].calls()[0].clone();
+        assert_eq!(first_hop.input.0, token_a);
+        assert_eq!(first_hop.output, (base_token, second_hop.input.1));
+        assert_eq!(second_hop.output.0, token_b);
+
+        // Both tokens are balanced after the contract pays out the orders.
+        let price_a = settlement.clearing_price(token_a).unwrap();
+        let price_b = settlement.clearing_price(token_b).unwrap();
+        // The contract rounds the amounts sell orders receive up.
+        let ceil_div = |a: U256, b: U256| (a + b - 1) / b;
+        let a_out = ceil_div(orders[1].sell_amount * price_b, price_a);
+        let b_out = ceil_div(orders[0].sell_amount * price_a, price_b);
+        assert!(a_out + first_hop.input.1 <= orders[0].sell_amount);
+        assert!(b_out <= orders[1].sell_amount + second_hop.output.1);
+    }
+
+    #[test]
+    fn returns_none_without_path() {
+        let token_a = Address::from_low_u64_be(1);
+        let token_b = Address::from_low_u64_be(2);
+        let orders = vec![sell_order(token_a, token_b, 100, 90)];
+        assert!(solve(
+            TokenPair::new(token_a, token_b).unwrap(),
+            orders.into_iter(),
+            &HashMap::new(),
+            &HashSet::new(),
+        )
+        .is_none());
+    }
+}
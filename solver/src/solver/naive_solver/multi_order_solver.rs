@@ -245,7 +245,7 @@ fn compute_uniswap_in(
 /// Returns true if for each trade the executed price is not smaller than the limit price
 /// Thus we ensure that `buy_token_price / sell_token_price >= limit_buy_amount / limit_sell_amount`
 ///
-fn is_valid_solution(solution: &Settlement) -> bool {
+pub(super) fn is_valid_solution(solution: &Settlement) -> bool {
     for trade in solution.trades().iter() {
         let order = trade.order.order_creation;
         let buy_token_price = solution
@@ -0,0 +1,306 @@
+use super::multi_order_solver::is_valid_solution;
+use crate::{liquidity::LimitOrder, settlement::Settlement};
+use anyhow::Result;
+use model::order::OrderKind;
+use num::Integer;
+use primitive_types::{U256, U512};
+use shared::conversions::{big_int_to_u256, u256_to_big_int};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use web3::types::Address;
+
+/// A ring of three distinct tokens `[a, b, c]` that is traded along `a -> b -> c -> a`.
+type Ring = [Address; 3];
+
+///
+/// Finds all rings of sell orders trading three tokens along a cycle (e.g. A->B, B->C, C->A) and
+/// matches each of them without using any AMM liquidity.
+/// Returns one settlement per ring, so settlements may share orders and are not independent.
+///
+pub fn solve(orders: impl Iterator<Item = LimitOrder>) -> Vec<Settlement> {
+    let edges = organize_sell_orders_by_direction(orders);
+    find_rings(&edges)
+        .into_iter()
+        .filter_map(|ring| {
+            let orders = ring
+                .iter()
+                .zip(ring.iter().cycle().skip(1))
+                .map(|(sell_token, buy_token)| edges[&(*sell_token, *buy_token)].clone())
+                .collect();
+            solve_ring(orders)
+        })
+        .collect()
+}
+
+fn organize_sell_orders_by_direction(
+    orders: impl Iterator<Item = LimitOrder>,
+) -> BTreeMap<(Address, Address), Vec<LimitOrder>> {
+    let mut result = BTreeMap::<_, Vec<_>>::new();
+    for order in orders.filter(|order| order.kind == OrderKind::Sell) {
+        result
+            .entry((order.sell_token, order.buy_token))
+            .or_default()
+            .push(order);
+    }
+    result
+}
+
+fn find_rings(edges: &BTreeMap<(Address, Address), Vec<LimitOrder>>) -> Vec<Ring> {
+    let mut successors = BTreeMap::<_, BTreeSet<_>>::new();
+    for (sell_token, buy_token) in edges.keys() {
+        successors
+            .entry(*sell_token)
+            .or_default()
+            .insert(*buy_token);
+    }
+
+    // Only rings starting at their smallest token are collected so that rotations of the same
+    // ring are not returned multiple times.
+    let mut rings = Vec::new();
+    for (a, successors_a) in &successors {
+        for b in successors_a.iter().filter(|b| *b > a) {
+            for c in successors
+                .get(b)
+                .into_iter()
+                .flatten()
+                .filter(|c| *c > a && *c != b)
+            {
+                if successors.get(c).map(|s| s.contains(a)).unwrap_or(false) {
+                    rings.push([*a, *b, *c]);
+                }
+            }
+        }
+    }
+    rings
+}
+
+///
+/// Settles the orders of a ring, where `orders[i]` are the orders for the i-th edge of the ring.
+/// Orders with the worst limit price are removed until all remaining orders can be settled.
+///
+fn solve_ring(mut orders: Vec<Vec<LimitOrder>>) -> Option<Settlement> {
+    while orders.iter().all(|edge| !edge.is_empty()) {
+        let prices = ring_clearing_prices(&orders)?;
+        let settlement = settle_orders(orders.iter().flatten(), &prices).ok()?;
+        let unbalanced_edges = unbalanced_edges(&orders, &prices);
+        if is_valid_solution(&settlement) && unbalanced_edges.is_empty() {
+            return Some(settlement);
+        }
+
+        // Remove the order with the worst limit price among those whose limit price isn't met and
+        // try again. If all limit prices are met, remove one from an edge whose orders receive more
+        // than the next edge sells because of rounding.
+        let violates_limit = |order: &LimitOrder| {
+            order.sell_amount.full_mul(prices[&order.sell_token])
+                < order.buy_amount.full_mul(prices[&order.buy_token])
+        };
+        let any_violates_limit = orders.iter().flatten().any(violates_limit);
+        let (edge, index) = orders
+            .iter()
+            .enumerate()
+            .flat_map(|(edge, orders)| {
+                orders
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, order)| (edge, index, order))
+            })
+            .filter(|(edge, _, order)| {
+                if any_violates_limit {
+                    violates_limit(order)
+                } else {
+                    unbalanced_edges.contains(edge)
+                }
+            })
+            .max_by(|(_, _, lhs), (_, _, rhs)| {
+                lhs.buy_amount
+                    .full_mul(rhs.sell_amount)
+                    .cmp(&lhs.sell_amount.full_mul(rhs.buy_amount))
+            })
+            .map(|(edge, index, _)| (edge, index))?;
+        orders[edge].swap_remove(index);
+    }
+
+    None
+}
+
+///
+/// Returns the edges whose orders receive more than the orders of the next edge sell. The prices
+/// balance the total amounts exactly but the contract rounds up the amount every sell order
+/// receives, so an edge with several orders can be short a few atoms. An edge with a single order
+/// is always balanced.
+///
+fn unbalanced_edges(orders: &[Vec<LimitOrder>], prices: &HashMap<Address, U256>) -> Vec<usize> {
+    let sold = |edge: &[LimitOrder]| {
+        edge.iter().fold(U512::zero(), |sold, order| {
+            sold + U512::from(order.sell_amount)
+        })
+    };
+    let bought = |edge: &[LimitOrder]| {
+        edge.iter().fold(U512::zero(), |bought, order| {
+            let sell_price = U512::from(prices[&order.sell_token]);
+            let buy_price = U512::from(prices[&order.buy_token]);
+            bought + (U512::from(order.sell_amount) * sell_price + buy_price - 1) / buy_price
+        })
+    };
+    (0..orders.len())
+        .filter(|&edge| bought(&orders[edge]) > sold(&orders[(edge + 1) % orders.len()]))
+        .collect()
+}
+
+///
+/// Computes uniform clearing prices for a ring such that the value sold along each edge is the
+/// same. This way every sold token is completely bought by the orders of the next edge and no
+/// external liquidity is needed: `p_a * S_a = p_b * S_b = p_c * S_c`, where `S_t` is the total sell
+/// amount of token `t`.
+///
+fn ring_clearing_prices(orders: &[Vec<LimitOrder>]) -> Option<HashMap<Address, U256>> {
+    let volumes = orders
+        .iter()
+        .map(|edge| {
+            let sell_token = edge.first()?.sell_token;
+            let volume = edge.iter().try_fold(U256::zero(), |volume, order| {
+                volume.checked_add(order.sell_amount)
+            })?;
+            // A price would be zero, which the contract can't divide by.
+            if volume.is_zero() {
+                return None;
+            }
+            Some((sell_token, u256_to_big_int(&volume)))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let prices = volumes
+        .iter()
+        .enumerate()
+        .map(|(i, _)| {
+            volumes
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (_, volume))| volume)
+                .product()
+        })
+        .collect::<Vec<num::BigInt>>();
+
+    // Reduce the prices so that multiplying them with order amounts is less likely to overflow.
+    let divisor = prices
+        .iter()
+        .fold(num::BigInt::from(0), |divisor, price| divisor.gcd(price));
+    if divisor == num::BigInt::from(0) {
+        return None;
+    }
+    volumes
+        .iter()
+        .zip(prices)
+        .map(|((token, _), price)| Some((*token, big_int_to_u256(&(price / &divisor)).ok()?)))
+        .collect()
+}
+
+fn settle_orders<'a>(
+    orders: impl Iterator<Item = &'a LimitOrder>,
+    prices: &HashMap<Address, U256>,
+) -> Result<Settlement> {
+    let mut settlement = Settlement::new(prices.clone());
+    for order in orders {
+        settlement.with_liquidity(order, order.full_execution_amount())?;
+    }
+    Ok(settlement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::order::{Order, OrderCreation};
+
+    fn sell_order(
+        sell_token: u64,
+        buy_token: u64,
+        sell_amount: u64,
+        buy_amount: u64,
+    ) -> LimitOrder {
+        Order {
+            order_creation: OrderCreation {
+                sell_token: Address::from_low_u64_be(sell_token),
+                buy_token: Address::from_low_u64_be(buy_token),
+                sell_amount: sell_amount.into(),
+                buy_amount: buy_amount.into(),
+                kind: OrderKind::Sell,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn settles_ring_of_three_orders() {
+        let orders = vec![
+            sell_order(1, 2, 100, 90),
+            sell_order(2, 3, 200, 95),
+            sell_order(3, 1, 100, 95),
+        ];
+        let settlements = solve(orders.into_iter());
+        assert_eq!(settlements.len(), 1);
+
+        let settlement = &settlements[0];
+        assert_eq!(settlement.trades().len(), 3);
+        let price = |token| {
+            settlement
+                .clearing_price(Address::from_low_u64_be(token))
+                .unwrap()
+        };
+        // Every order receives exactly what the next order in the ring sells.
+        assert_eq!(U256::from(100) * price(1), U256::from(200) * price(2));
+        assert_eq!(U256::from(200) * price(2), U256::from(100) * price(3));
+    }
+
+    #[test]
+    fn removes_orders_whose_limit_price_cannot_be_met() {
+        let orders = vec![
+            sell_order(1, 2, 100, 90),
+            sell_order(1, 2, 100, 150),
+            sell_order(2, 3, 100, 95),
+            sell_order(3, 1, 100, 95),
+        ];
+        let settlements = solve(orders.into_iter());
+        assert_eq!(settlements.len(), 1);
+
+        let settlement = &settlements[0];
+        assert_eq!(settlement.trades().len(), 3);
+        assert!(settlement
+            .trades()
+            .iter()
+            .all(|trade| trade.order.order_creation.buy_amount != 150.into()));
+    }
+
+    #[test]
+    fn removes_orders_of_edges_that_are_short_because_of_rounding() {
+        // At the ring's prices, each order of the first edge receives 3.5 of token 2, which the
+        // contract rounds up. This requires 8 of token 2 while only 7 are sold.
+        let orders = vec![
+            sell_order(1, 2, 1, 1),
+            sell_order(1, 2, 1, 1),
+            sell_order(2, 3, 7, 1),
+            sell_order(3, 1, 2, 1),
+        ];
+        let settlements = solve(orders.into_iter());
+        assert_eq!(settlements.len(), 1);
+
+        let settlement = &settlements[0];
+        assert_eq!(settlement.trades().len(), 3);
+        let price = |token| {
+            settlement
+                .clearing_price(Address::from_low_u64_be(token))
+                .unwrap()
+        };
+        assert_eq!(U256::from(1) * price(1), U256::from(7) * price(2));
+    }
+
+    #[test]
+    fn ignores_orders_not_forming_a_ring() {
+        let orders = vec![
+            sell_order(1, 2, 100, 90),
+            sell_order(2, 3, 100, 90),
+            sell_order(3, 4, 100, 90),
+        ];
+        assert!(solve(orders.into_iter()).is_empty());
+    }
+}
@@ -5,28 +5,25 @@
 
 pub mod api;
 use self::api::{Amount, OneInchClient, Swap, SwapQuery};
-use super::single_order_solver::SingleOrderSolving;
+use super::aggregator_solver::{
+    AggregatorError, Quote, SwapBuilding, SwapQuoting, SwapRequest, SwapTransaction,
+};
 use super::solver_utils::Slippage;
 use crate::{
-    encoding::EncodedInteraction,
-    interactions::allowances::{AllowanceManager, AllowanceManaging},
-    liquidity::{slippage::MAX_SLIPPAGE_BPS, LimitOrder},
-    settlement::{Interaction, Settlement},
+    encoding::EncodedInteraction, settlement::Interaction,
     solver::oneinch_solver::api::OneInchClientImpl,
 };
 use anyhow::{ensure, Result};
 use contracts::GPv2Settlement;
 use derivative::Derivative;
 use ethcontract::Bytes;
-use maplit::hashmap;
 use model::order::OrderKind;
-use shared::Web3;
 use std::{
     collections::HashSet,
     fmt::{self, Display, Formatter},
 };
 
-/// A GPv2 solver that matches GP **sell** orders to direct 1Inch swaps.
+/// An aggregator API that matches GP **sell** orders to direct 1Inch swaps.
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct OneInchSolver {
@@ -34,8 +31,6 @@ pub struct OneInchSolver {
     disabled_protocols: HashSet<String>,
     #[derivative(Debug = "ignore")]
     client: Box<dyn OneInchClient>,
-    #[derivative(Debug = "ignore")]
-    allowance_fetcher: Box<dyn AllowanceManaging>,
 }
 
 /// Chain ID for Mainnet.
@@ -44,7 +39,6 @@ const MAINNET_CHAIN_ID: u64 = 1;
 impl OneInchSolver {
     /// Creates a new 1Inch solver with a list of disabled protocols.
     pub fn with_disabled_protocols(
-        web3: Web3,
         settlement_contract: GPv2Settlement,
         chain_id: u64,
         disabled_protocols: impl IntoIterator<Item = String>,
@@ -54,12 +48,10 @@ impl OneInchSolver {
             "1Inch solver only supported on Mainnet",
         );
 
-        let settlement_address = settlement_contract.address();
         Ok(Self {
             settlement_contract,
             disabled_protocols: disabled_protocols.into_iter().collect(),
             client: Box::new(OneInchClientImpl::default()),
-            allowance_fetcher: Box::new(AllowanceManager::new(web3, settlement_address)),
         })
     }
 }
@@ -83,32 +75,24 @@ impl OneInchSolver {
         Ok(protocols)
     }
 
-    /// Settles a single sell order against a 1Inch swap using the spcified
-    /// protocols.
-    async fn settle_order_with_protocols(
+    /// Gets a 1Inch swap for a sell order using the specified protocols.
+    async fn get_swap_with_protocols(
         &self,
-        order: LimitOrder,
+        request: &SwapRequest,
         protocols: Option<Vec<String>>,
-    ) -> Result<Option<Settlement>> {
+    ) -> Result<Swap> {
         debug_assert_eq!(
-            order.kind,
+            request.kind,
             OrderKind::Sell,
-            "only sell orders should be passed to settle_order"
+            "only sell orders should be quoted by 1Inch"
         );
 
-        let spender = self.client.get_spender().await?;
-        // Fetching allowance before making the SwapQuery so that the Swap info is as recent as possible
-        let approval = self
-            .allowance_fetcher
-            .get_approval(order.sell_token, spender.address, order.sell_amount)
-            .await?;
-
         let query = SwapQuery {
-            from_token_address: order.sell_token,
-            to_token_address: order.buy_token,
-            amount: order.sell_amount,
+            from_token_address: request.sell_token,
+            to_token_address: request.buy_token,
+            amount: request.amount,
             from_address: self.settlement_contract.address(),
-            slippage: Slippage::percentage_from_basis_points(MAX_SLIPPAGE_BPS).unwrap(),
+            slippage: Slippage::percentage_from_basis_points(request.slippage_bps)?,
             protocols,
             // Disable balance/allowance checks, as the settlement contract
             // does not hold balances to traded tokens.
@@ -123,31 +107,10 @@ impl OneInchSolver {
         };
 
         tracing::debug!("querying 1Inch swap api with {:?}", query);
-        let swap = self.client.get_swap(query).await?;
-
-        if !satisfies_limit_price(&swap, &order) {
-            tracing::debug!("Order limit price not respected");
-            return Ok(None);
-        }
-
-        let mut settlement = Settlement::new(hashmap! {
-            order.sell_token => swap.to_token_amount,
-            order.buy_token => swap.from_token_amount,
-        });
-
-        settlement.with_liquidity(&order, order.sell_amount)?;
-
-        settlement.encoder.append_to_execution_plan(approval);
-        settlement.encoder.append_to_execution_plan(swap);
-
-        Ok(Some(settlement))
+        self.client.get_swap(query).await
     }
 }
 
-fn satisfies_limit_price(swap: &Swap, order: &LimitOrder) -> bool {
-    swap.to_token_amount >= order.buy_amount
-}
-
 impl Interaction for Swap {
     fn encode(&self) -> Vec<EncodedInteraction> {
         vec![(self.tx.to, self.tx.value, Bytes(self.tx.data.clone()))]
@@ -155,19 +118,46 @@ impl Interaction for Swap {
 }
 
 #[async_trait::async_trait]
-impl SingleOrderSolving for OneInchSolver {
-    async fn settle_order(&self, order: LimitOrder) -> Result<Option<Settlement>> {
-        if order.kind != OrderKind::Sell {
-            // 1Inch only supports sell orders
-            return Ok(None);
-        }
-        let protocols = self.supported_protocols().await?;
-        self.settle_order_with_protocols(order, protocols).await
-    }
+impl SwapQuoting for OneInchSolver {
+    type Quote = Swap;
 
     fn name(&self) -> &'static str {
         "1Inch"
     }
+
+    fn supports(&self, kind: OrderKind) -> bool {
+        // 1Inch only supports sell orders
+        kind == OrderKind::Sell
+    }
+
+    async fn quote(&self, request: &SwapRequest) -> Result<Quote<Swap>, AggregatorError> {
+        let protocols = self.supported_protocols().await?;
+        let swap = self.get_swap_with_protocols(request, protocols).await?;
+        Ok(Quote {
+            sell_amount: swap.from_token_amount,
+            buy_amount: swap.to_token_amount,
+            data: swap,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SwapBuilding for OneInchSolver {
+    type Interaction = Swap;
+
+    async fn build_swap(
+        &self,
+        _: &SwapRequest,
+        quote: Quote<Swap>,
+    ) -> Result<SwapTransaction<Swap>, AggregatorError> {
+        // The quote already is the swap transaction, so only the spender is
+        // missing.
+        let spender = self.client.get_spender().await?;
+        Ok(SwapTransaction {
+            spender: spender.address,
+            interaction: quote.data,
+        })
+    }
 }
 
 impl Display for OneInchSolver {
@@ -180,34 +170,50 @@ impl Display for OneInchSolver {
 mod tests {
 
     use super::{api::MockOneInchClient, *};
-    use crate::interactions::allowances::{Approval, MockAllowanceManaging};
-    use crate::liquidity::LimitOrder;
+    use crate::interactions::allowances::{AllowanceManager, Approval, MockAllowanceManaging};
+    use crate::liquidity::{slippage::MAX_SLIPPAGE_BPS, LimitOrder};
+    use crate::metrics::NoopMetrics;
+    use crate::solver::aggregator_solver::AggregatorSolver;
     use crate::solver::oneinch_solver::api::Protocols;
     use crate::solver::oneinch_solver::api::Spender;
+    use crate::solver::single_order_solver::SingleOrderSolving;
     use contracts::{GPv2Settlement, WETH9};
     use ethcontract::{Web3, H160, U256};
+    use maplit::hashmap;
     use maplit::hashset;
     use mockall::{predicate::*, Sequence};
     use model::order::{Order, OrderCreation, OrderKind};
-    use shared::{
-        dummy_contract,
-        transport::{create_env_test_transport, create_test_transport},
-    };
-    use std::iter;
+    use shared::{dummy_contract, transport::create_env_test_transport};
+    use std::{iter, sync::Arc};
 
-    fn dummy_solver(
-        client: MockOneInchClient,
-        allowance_fetcher: MockAllowanceManaging,
-    ) -> OneInchSolver {
+    fn dummy_api(client: MockOneInchClient) -> OneInchSolver {
         let settlement_contract = dummy_contract!(GPv2Settlement, H160::zero());
         OneInchSolver {
             settlement_contract,
             disabled_protocols: HashSet::new(),
             client: Box::new(client),
-            allowance_fetcher: Box::new(allowance_fetcher),
         }
     }
 
+    fn aggregator_solver(
+        api: OneInchSolver,
+        allowance_fetcher: MockAllowanceManaging,
+    ) -> AggregatorSolver<OneInchSolver> {
+        AggregatorSolver::new(
+            api,
+            Box::new(allowance_fetcher),
+            MAX_SLIPPAGE_BPS,
+            Arc::new(NoopMetrics::default()),
+        )
+    }
+
+    fn dummy_solver(
+        client: MockOneInchClient,
+        allowance_fetcher: MockAllowanceManaging,
+    ) -> AggregatorSolver<OneInchSolver> {
+        aggregator_solver(dummy_api(client), allowance_fetcher)
+    }
+
     #[tokio::test]
     async fn ignores_buy_orders() {
         assert!(
@@ -224,7 +230,7 @@ mod tests {
 
     #[tokio::test]
     async fn returns_none_when_no_protocols_are_disabled() {
-        let protocols = dummy_solver(MockOneInchClient::new(), MockAllowanceManaging::new())
+        let protocols = dummy_api(MockOneInchClient::new())
             .supported_protocols()
             .await
             .unwrap();
@@ -324,10 +330,16 @@ mod tests {
             })
         });
 
-        let solver = OneInchSolver {
-            disabled_protocols: hashset!["BadProtocol".to_string(), "VeryBadProtocol".to_string()],
-            ..dummy_solver(client, allowance_fetcher)
-        };
+        let solver = aggregator_solver(
+            OneInchSolver {
+                disabled_protocols: hashset![
+                    "BadProtocol".to_string(),
+                    "VeryBadProtocol".to_string()
+                ],
+                ..dummy_api(client)
+            },
+            allowance_fetcher,
+        );
 
         // Limit price violated. Actual assert is happening in `expect_get_swap()`
         assert!(solver
@@ -403,13 +415,11 @@ mod tests {
 
     #[test]
     fn returns_error_on_non_mainnet() {
-        let web3 = Web3::new(create_test_transport("http://never.used"));
         let chain_id = 42;
         let settlement = dummy_contract!(GPv2Settlement, H160::zero());
 
         assert!(
-            OneInchSolver::with_disabled_protocols(web3, settlement, chain_id, iter::empty())
-                .is_err()
+            OneInchSolver::with_disabled_protocols(settlement, chain_id, iter::empty()).is_err()
         )
     }
 
@@ -423,15 +433,16 @@ mod tests {
         let weth = WETH9::deployed(&web3).await.unwrap();
         let gno = shared::addr!("6810e776880c02933d47db1b9fc05908e5386b96");
 
-        let solver = OneInchSolver::with_disabled_protocols(
-            web3,
-            settlement,
-            chain_id,
-            vec!["PMM1".to_string()],
-        )
-        .unwrap();
+        let allowance_fetcher = AllowanceManager::new(web3, settlement.address());
+        let solver = AggregatorSolver::new(
+            OneInchSolver::with_disabled_protocols(settlement, chain_id, vec!["PMM1".to_string()])
+                .unwrap(),
+            Box::new(allowance_fetcher),
+            MAX_SLIPPAGE_BPS,
+            Arc::new(NoopMetrics::default()),
+        );
         let settlement = solver
-            .settle_order_with_protocols(
+            .settle_order(
                 Order {
                     order_creation: OrderCreation {
                         sell_token: weth.address(),
@@ -444,7 +455,6 @@ mod tests {
                     ..Default::default()
                 }
                 .into(),
-                None,
             )
             .await
             .unwrap()
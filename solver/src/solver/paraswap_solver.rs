@@ -4,208 +4,135 @@ use self::api::{
     DefaultParaswapApi, ParaswapApi, PriceQuery, PriceResponse, Side, TransactionBuilderQuery,
     TransactionBuilderResponse,
 };
-use super::single_order_solver::SingleOrderSolving;
-use crate::solver::paraswap_solver::api::ParaswapResponseError;
-use crate::{
-    encoding::EncodedInteraction,
-    interactions::allowances::{AllowanceManager, AllowanceManaging},
-    liquidity::LimitOrder,
-    settlement::{Interaction, Settlement},
+use super::aggregator_solver::{
+    AggregatorError, Quote, SwapBuilding, SwapQuoting, SwapRequest, SwapTransaction,
 };
-use anyhow::{anyhow, Error, Result};
-use contracts::GPv2Settlement;
+use crate::solver::paraswap_solver::api::ParaswapResponseError;
+use crate::{encoding::EncodedInteraction, settlement::Interaction};
+use anyhow::{anyhow, Result};
 use derivative::Derivative;
-use ethcontract::{Bytes, H160, U256};
-use maplit::hashmap;
+use ethcontract::{Bytes, H160};
+use model::order::OrderKind;
 use shared::token_info::TokenInfo;
-use shared::{conversions::U256Ext, token_info::TokenInfoFetching, Web3};
+use shared::token_info::TokenInfoFetching;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 const REFERRER: &str = "GPv2";
 const APPROVAL_RECEIVER: H160 = shared::addr!("b70bc06d2c9bf03b3373799606dc7d39346c06b3");
 
-/// A GPv2 solver that matches GP orders to direct ParaSwap swaps.
+/// An aggregator API that matches GP orders to direct ParaSwap swaps.
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct ParaswapSolver {
-    settlement_contract: GPv2Settlement,
     solver_address: H160,
     #[derivative(Debug = "ignore")]
     token_info: Arc<dyn TokenInfoFetching>,
     #[derivative(Debug = "ignore")]
-    allowance_fetcher: Box<dyn AllowanceManaging>,
-    #[derivative(Debug = "ignore")]
     client: Box<dyn ParaswapApi + Send + Sync>,
-    slippage_bps: usize,
 }
 
 impl ParaswapSolver {
-    pub fn new(
-        web3: Web3,
-        settlement_contract: GPv2Settlement,
-        solver_address: H160,
-        token_info: Arc<dyn TokenInfoFetching>,
-        slippage_bps: usize,
-    ) -> Self {
-        let allowance_fetcher = AllowanceManager::new(web3, settlement_contract.address());
+    pub fn new(solver_address: H160, token_info: Arc<dyn TokenInfoFetching>) -> Self {
         Self {
-            settlement_contract,
             solver_address,
             token_info,
-            allowance_fetcher: Box::new(allowance_fetcher),
             client: Box::new(DefaultParaswapApi::default()),
-            slippage_bps,
         }
     }
 }
 
-#[derive(Debug)]
-struct SettlementError {
-    inner: anyhow::Error,
-    retryable: bool,
-}
-
-impl From<anyhow::Error> for SettlementError {
-    fn from(err: Error) -> Self {
-        SettlementError {
-            inner: err,
-            retryable: false,
-        }
-    }
+/// A ParaSwap price along with the token decimals needed for building the
+/// transaction.
+#[derive(Clone, Debug)]
+pub struct ParaswapQuote {
+    price: PriceResponse,
+    from_decimals: usize,
+    to_decimals: usize,
 }
 
-impl From<ParaswapResponseError> for SettlementError {
+impl From<ParaswapResponseError> for AggregatorError {
     fn from(err: ParaswapResponseError) -> Self {
-        SettlementError {
-            inner: anyhow!("Paraswap Response Error {:?}", err),
-            retryable: matches!(
-                err,
-                ParaswapResponseError::PriceChange | ParaswapResponseError::BuildingTransaction(_)
-            ),
+        let retryable = matches!(
+            err,
+            ParaswapResponseError::PriceChange | ParaswapResponseError::BuildingTransaction(_)
+        );
+        let inner = anyhow!("Paraswap Response Error {:?}", err);
+        if retryable {
+            AggregatorError::Retryable(inner)
+        } else {
+            AggregatorError::Other(inner)
         }
     }
 }
 
 #[async_trait::async_trait]
-impl SingleOrderSolving for ParaswapSolver {
-    async fn settle_order(&self, order: LimitOrder) -> Result<Option<Settlement>> {
-        let max_retries = 2;
-        for _ in 0..max_retries {
-            match self.try_settle_order(order.clone()).await {
-                Ok(settlement) => return Ok(settlement),
-                Err(err) if err.retryable => {
-                    tracing::debug!("Retrying Paraswap settlement due to: {:?}", &err);
-                    continue;
-                }
-                Err(err) => return Err(err.inner),
-            }
-        }
-        // One last attempt, else throw converted error
-        self.try_settle_order(order).await.map_err(|err| err.inner)
-    }
+impl SwapQuoting for ParaswapSolver {
+    type Quote = ParaswapQuote;
 
     fn name(&self) -> &'static str {
         "ParaSwap"
     }
-}
 
-impl ParaswapSolver {
-    async fn try_settle_order(
-        &self,
-        order: LimitOrder,
-    ) -> Result<Option<Settlement>, SettlementError> {
+    async fn quote(&self, request: &SwapRequest) -> Result<Quote<ParaswapQuote>, AggregatorError> {
         let token_info = self
             .token_info
-            .get_token_infos(&[order.sell_token, order.buy_token])
+            .get_token_infos(&[request.sell_token, request.buy_token])
             .await;
-        let (price_response, amount) = self.get_price_for_order(&order, &token_info).await?;
-        if !satisfies_limit_price(&order, &price_response) {
-            tracing::debug!("Order limit price not respected");
-            return Ok(None);
-        }
-        let transaction_query =
-            self.transaction_query_from(&order, &price_response, &token_info)?;
-        let transaction = self.client.transaction(transaction_query).await?;
-        let mut settlement = Settlement::new(hashmap! {
-            order.sell_token => price_response.dest_amount,
-            order.buy_token => price_response.src_amount,
-        });
-        settlement.with_liquidity(&order, amount)?;
-
-        settlement.encoder.append_to_execution_plan(
-            self.allowance_fetcher
-                .get_approval(
-                    order.sell_token,
-                    APPROVAL_RECEIVER,
-                    price_response.src_amount,
-                )
-                .await?,
-        );
-        settlement.encoder.append_to_execution_plan(transaction);
-        Ok(Some(settlement))
-    }
-
-    async fn get_price_for_order(
-        &self,
-        order: &LimitOrder,
-        token_info: &HashMap<H160, TokenInfo>,
-    ) -> Result<(PriceResponse, U256)> {
-        let (amount, side) = match order.kind {
-            model::order::OrderKind::Buy => (order.buy_amount, Side::Buy),
-            model::order::OrderKind::Sell => (order.sell_amount, Side::Sell),
-        };
+        let from_decimals = decimals(&token_info, &request.sell_token)?;
+        let to_decimals = decimals(&token_info, &request.buy_token)?;
 
         let price_query = PriceQuery {
-            from: order.sell_token,
-            to: order.buy_token,
-            from_decimals: decimals(token_info, &order.sell_token)?,
-            to_decimals: decimals(token_info, &order.buy_token)?,
-            amount,
-            side,
+            from: request.sell_token,
+            to: request.buy_token,
+            from_decimals,
+            to_decimals,
+            amount: request.amount,
+            side: match request.kind {
+                OrderKind::Buy => Side::Buy,
+                OrderKind::Sell => Side::Sell,
+            },
         };
-        let price_response = self.client.price(price_query).await?;
-        Ok((price_response, amount))
+        let price = self.client.price(price_query).await?;
+        Ok(Quote {
+            sell_amount: price.src_amount,
+            buy_amount: price.dest_amount,
+            data: ParaswapQuote {
+                price,
+                from_decimals,
+                to_decimals,
+            },
+        })
     }
+}
+
+#[async_trait::async_trait]
+impl SwapBuilding for ParaswapSolver {
+    type Interaction = TransactionBuilderResponse;
 
-    fn transaction_query_from(
+    async fn build_swap(
         &self,
-        order: &LimitOrder,
-        price_response: &PriceResponse,
-        token_info: &HashMap<H160, TokenInfo>,
-    ) -> Result<TransactionBuilderQuery> {
-        let (src_amount, dest_amount) = match order.kind {
-            // Buy orders apply slippage to src amount, dest amount unchanged
-            model::order::OrderKind::Buy => (
-                price_response
-                    .src_amount
-                    .checked_mul((10000 + self.slippage_bps).into())
-                    .ok_or_else(|| anyhow!("Overflow during slippage computation"))?
-                    / 10000,
-                price_response.dest_amount,
-            ),
-            // Sell orders apply slippage to dest amount, src amount unchanged
-            model::order::OrderKind::Sell => (
-                price_response.src_amount,
-                price_response
-                    .dest_amount
-                    .checked_mul((10000 - self.slippage_bps).into())
-                    .ok_or_else(|| anyhow!("Overflow during slippage computation"))?
-                    / 10000,
-            ),
-        };
+        request: &SwapRequest,
+        quote: Quote<ParaswapQuote>,
+    ) -> Result<SwapTransaction<TransactionBuilderResponse>, AggregatorError> {
+        let (src_amount, dest_amount) =
+            request.amounts_with_slippage(quote.sell_amount, quote.buy_amount)?;
         let query = TransactionBuilderQuery {
-            src_token: order.sell_token,
-            dest_token: order.buy_token,
+            src_token: request.sell_token,
+            dest_token: request.buy_token,
             src_amount,
             dest_amount,
-            from_decimals: decimals(token_info, &order.sell_token)?,
-            to_decimals: decimals(token_info, &order.buy_token)?,
-            price_route: price_response.clone().price_route_raw,
+            from_decimals: quote.data.from_decimals,
+            to_decimals: quote.data.to_decimals,
+            price_route: quote.data.price.price_route_raw,
             user_address: self.solver_address,
             referrer: REFERRER.to_string(),
         };
-        Ok(query)
+        let transaction = self.client.transaction(query).await?;
+        Ok(SwapTransaction {
+            spender: APPROVAL_RECEIVER,
+            interaction: transaction,
+        })
     }
 }
 
@@ -222,68 +149,43 @@ impl Interaction for TransactionBuilderResponse {
     }
 }
 
-fn satisfies_limit_price(order: &LimitOrder, response: &PriceResponse) -> bool {
-    // We check if order.sell / order.buy >= response.sell / response.buy
-    order.sell_amount.to_big_rational() * response.dest_amount.to_big_rational()
-        >= response.src_amount.to_big_rational() * order.buy_amount.to_big_rational()
-}
-
 #[cfg(test)]
 mod tests {
     use super::{api::MockParaswapApi, *};
-    use crate::interactions::allowances::{Approval, MockAllowanceManaging};
-    use contracts::WETH9;
+    use crate::interactions::allowances::{AllowanceManager, Approval, MockAllowanceManaging};
+    use crate::liquidity::LimitOrder;
+    use crate::metrics::NoopMetrics;
+    use crate::solver::aggregator_solver::AggregatorSolver;
+    use crate::solver::single_order_solver::SingleOrderSolving;
+    use contracts::{GPv2Settlement, WETH9};
     use ethcontract::U256;
+    use maplit::hashmap;
     use mockall::predicate::*;
     use mockall::Sequence;
     use model::order::{Order, OrderCreation, OrderKind};
     use shared::{
-        dummy_contract,
         token_info::{MockTokenInfoFetching, TokenInfo, TokenInfoFetcher},
         transport::create_env_test_transport,
+        Web3,
     };
     use std::collections::HashMap;
 
-    #[test]
-    fn test_satisfies_limit_price() {
-        assert!(!satisfies_limit_price(
-            &LimitOrder {
-                sell_amount: 100.into(),
-                buy_amount: 95.into(),
-                ..Default::default()
-            },
-            &PriceResponse {
-                src_amount: 100.into(),
-                dest_amount: 90.into(),
-                ..Default::default()
-            }
-        ));
-
-        assert!(satisfies_limit_price(
-            &LimitOrder {
-                sell_amount: 100.into(),
-                buy_amount: 95.into(),
-                ..Default::default()
-            },
-            &PriceResponse {
-                src_amount: 100.into(),
-                dest_amount: 100.into(),
-                ..Default::default()
-            }
-        ));
-
-        assert!(satisfies_limit_price(
-            &LimitOrder {
-                sell_amount: 100.into(),
-                buy_amount: 95.into(),
-                ..Default::default()
+    fn dummy_solver(
+        client: Box<MockParaswapApi>,
+        allowance_fetcher: Box<MockAllowanceManaging>,
+        token_info: MockTokenInfoFetching,
+        slippage_bps: u16,
+    ) -> AggregatorSolver<ParaswapSolver> {
+        AggregatorSolver::new(
+            ParaswapSolver {
+                client,
+                solver_address: Default::default(),
+                token_info: Arc::new(token_info),
             },
-            &PriceResponse {
-                src_amount: 100.into(),
-                dest_amount: 95.into(),
-                ..Default::default()
-            }
-        ));
+            allowance_fetcher,
+            slippage_bps,
+            Arc::new(NoopMetrics::default()),
+        )
     }
 
     #[tokio::test]
@@ -296,14 +198,7 @@ mod tests {
             .expect_get_token_infos()
             .return_const(HashMap::new());
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 10,
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 10);
 
         let order = LimitOrder::default();
         let result = solver.settle_order(order).await;
@@ -343,14 +238,7 @@ mod tests {
             }
         });
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 10,
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 10);
 
         let order_passing_limit = LimitOrder {
             sell_token,
@@ -433,14 +321,7 @@ mod tests {
             }
         });
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 10,
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 10);
 
         let order = LimitOrder {
             sell_token,
@@ -508,14 +389,7 @@ mod tests {
             }
         });
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 1000, // 10%
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 1000); // 10%
 
         let sell_order = LimitOrder {
             sell_token,
@@ -555,7 +429,13 @@ mod tests {
         let weth = WETH9::deployed(&web3).await.unwrap();
         let gno = shared::addr!("6810e776880c02933d47db1b9fc05908e5386b96");
 
-        let solver = ParaswapSolver::new(web3, settlement, solver, token_info_fetcher, 0);
+        let allowance_fetcher = AllowanceManager::new(web3, settlement.address());
+        let solver = AggregatorSolver::new(
+            ParaswapSolver::new(solver, token_info_fetcher),
+            Box::new(allowance_fetcher),
+            0,
+            Arc::new(NoopMetrics::default()),
+        );
 
         let settlement = solver
             .settle_order(
@@ -624,14 +504,7 @@ mod tests {
             }
         });
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 1000, // 10%
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 1000); // 10%
 
         let order = LimitOrder {
             sell_token,
@@ -684,14 +557,7 @@ mod tests {
             }
         });
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 1000, // 10%
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 1000); // 10%
 
         let order = LimitOrder {
             sell_token,
@@ -731,14 +597,7 @@ mod tests {
             }
         });
 
-        let solver = ParaswapSolver {
-            client,
-            solver_address: Default::default(),
-            token_info: Arc::new(token_info),
-            allowance_fetcher,
-            settlement_contract: dummy_contract!(GPv2Settlement, H160::zero()),
-            slippage_bps: 1000, // 10%
-        };
+        let solver = dummy_solver(client, allowance_fetcher, token_info, 1000); // 10%
 
         let order = LimitOrder {
             sell_token,
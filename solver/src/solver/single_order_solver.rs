@@ -1,6 +1,15 @@
 use anyhow::Result;
+use ethcontract::H160;
 use futures::future;
-use rand::prelude::SliceRandom;
+use model::order::OrderKind;
+use num::{BigRational, Signed as _, Zero as _};
+use rand::{prelude::SliceRandom, Rng};
+use shared::{conversions::U256Ext, price_estimate::PriceEstimating};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::{
     liquidity::{LimitOrder, Liquidity},
@@ -14,6 +23,11 @@ pub trait SingleOrderSolving {
     /// Return a settlement for the given limit order (if possible)
     async fn settle_order(&self, order: LimitOrder) -> Result<Option<Settlement>>;
 
+    /// Return a settlement for the given limit orders, which all have the same sell token, buy
+    /// token and kind, executing them with a single swap at a uniform clearing price (if possible).
+    /// Orders whose limit price can't be met may be left out of the settlement.
+    async fn settle_orders(&self, orders: Vec<LimitOrder>) -> Result<Option<Settlement>>;
+
     fn name(&self) -> &'static str;
 }
 
@@ -23,12 +37,43 @@ pub trait SingleOrderSolving {
 /// requests may a non-trivial amount of time.
 const MAX_SETTLEMENTS: usize = 5;
 
+/// Configuration for combining orders into a single settlement.
+#[derive(Clone)]
+pub struct OrderBatching {
+    /// Estimator for the prices of the traded tokens in the native token that
+    /// are used to estimate the surplus of orders.
+    pub price_estimator: Arc<dyn PriceEstimating>,
+    pub native_token: H160,
+}
+
 pub struct SingleOrderSolver<I> {
     inner: I,
+    /// If set, orders with the same sell token, buy token and kind are settled together and the
+    /// ones with the highest estimated surplus are settled first. Otherwise orders are settled
+    /// individually in random order.
+    order_batching: Option<OrderBatching>,
+}
+
+impl<I: SingleOrderSolving> SingleOrderSolver<I> {
+    pub fn new(inner: I, order_batching: Option<OrderBatching>) -> Self {
+        Self {
+            inner,
+            order_batching,
+        }
+    }
+
+    async fn settle(&self, mut orders: Vec<LimitOrder>) -> Result<Option<Settlement>> {
+        if orders.len() == 1 {
+            self.inner.settle_order(orders.remove(0)).await
+        } else {
+            self.inner.settle_orders(orders).await
+        }
+    }
 }
+
 impl<I: SingleOrderSolving> From<I> for SingleOrderSolver<I> {
     fn from(inner: I) -> Self {
-        Self { inner }
+        Self::new(inner, None)
     }
 }
 
@@ -43,17 +88,23 @@ impl<I: SingleOrderSolving + Send + Sync> Solver for SingleOrderSolver<I> {
             })
             .collect::<Vec<_>>();
 
-        // Randomize which orders we take, this prevents this solver "getting
-        // stuck" on bad orders.
-        if orders.len() > MAX_SETTLEMENTS {
-            orders.shuffle(&mut rand::thread_rng());
-        }
+        let batches = match &self.order_batching {
+            Some(order_batching) => order_batching.prioritized_batches(orders).await,
+            None => {
+                // Randomize which orders we take, this prevents this solver "getting
+                // stuck" on bad orders.
+                if orders.len() > MAX_SETTLEMENTS {
+                    orders.shuffle(&mut rand::thread_rng());
+                }
+                orders.into_iter().map(|order| vec![order]).collect()
+            }
+        };
 
         let settlements = future::join_all(
-            orders
+            batches
                 .into_iter()
                 .take(MAX_SETTLEMENTS)
-                .map(|order| self.inner.settle_order(order)),
+                .map(|orders| self.settle(orders)),
         )
         .await;
 
@@ -77,3 +128,166 @@ impl<I: SingleOrderSolving + Send + Sync> Solver for SingleOrderSolver<I> {
         self.inner.name()
     }
 }
+
+impl OrderBatching {
+    /// Groups the orders by sell token, buy token and kind and sorts the groups by their total
+    /// estimated surplus, highest first. Orders within a group are sorted the same way.
+    async fn prioritized_batches(&self, orders: Vec<LimitOrder>) -> Vec<Vec<LimitOrder>> {
+        let tokens = orders
+            .iter()
+            .flat_map(|order| vec![order.sell_token, order.buy_token])
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let prices: HashMap<_, _> = self
+            .price_estimator
+            .estimate_prices(&tokens, self.native_token)
+            .await
+            .into_iter()
+            .zip(tokens)
+            .filter_map(|(result, token)| Some((token, result.ok()?)))
+            .collect();
+
+        let mut batches = HashMap::<_, Vec<_>>::new();
+        for order in orders {
+            let surplus = estimated_surplus(&order, &prices);
+            batches
+                .entry((order.sell_token, order.buy_token, order.kind))
+                .or_default()
+                .push((surplus, order));
+        }
+        prioritize(batches, &mut rand::thread_rng())
+    }
+}
+
+/// Returns the surplus of an order in the native token if it was executed at the estimated
+/// prices: `sell_amount * p_sell - buy_amount * p_buy`. This is the same for sell orders (which
+/// receive more than their buy amount) and buy orders (which pay less than their sell amount).
+fn estimated_surplus(
+    order: &LimitOrder,
+    prices: &HashMap<H160, BigRational>,
+) -> Option<BigRational> {
+    let sell_price = prices.get(&order.sell_token)?;
+    let buy_price = prices.get(&order.buy_token)?;
+    Some(
+        order.sell_amount.to_big_rational() * sell_price
+            - order.buy_amount.to_big_rational() * buy_price,
+    )
+}
+
+/// Orders with their estimated surplus grouped by sell token, buy token and kind.
+type Batches = HashMap<(H160, H160, OrderKind), Vec<(Option<BigRational>, LimitOrder)>>;
+
+/// Sorts batches and their orders by estimated surplus, highest first. Orders without an estimate
+/// come last and don't add to the surplus of their batch, neither do orders with a negative
+/// estimate. Ties are broken randomly so that the solver doesn't get stuck on the same bad orders,
+/// for example when no prices can be estimated.
+fn prioritize(batches: Batches, rng: &mut impl Rng) -> Vec<Vec<LimitOrder>> {
+    let mut batches = batches
+        .into_iter()
+        .map(|(_, mut orders)| {
+            orders.shuffle(rng);
+            orders.sort_by(|(lhs, _), (rhs, _)| rhs.cmp(lhs));
+            let surplus = orders
+                .iter()
+                .filter_map(|(surplus, _)| surplus.as_ref())
+                .filter(|surplus| surplus.is_positive())
+                .fold(BigRational::zero(), |total, surplus| total + surplus);
+            let orders = orders.into_iter().map(|(_, order)| order).collect();
+            (surplus, orders)
+        })
+        .collect::<Vec<(_, Vec<_>)>>();
+    batches.shuffle(rng);
+    batches.sort_by_key(|(surplus, _)| Reverse(surplus.clone()));
+    batches.into_iter().map(|(_, orders)| orders).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+
+    fn order(sell_token: u64, buy_token: u64, kind: OrderKind, sell: u64, buy: u64) -> LimitOrder {
+        LimitOrder {
+            id: format!("{}-{}-{}", sell_token, sell, buy),
+            sell_token: H160::from_low_u64_be(sell_token),
+            buy_token: H160::from_low_u64_be(buy_token),
+            sell_amount: sell.into(),
+            buy_amount: buy.into(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    fn ids(batches: &[Vec<LimitOrder>]) -> Vec<Vec<&str>> {
+        batches
+            .iter()
+            .map(|orders| orders.iter().map(|order| order.id.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn estimates_surplus_in_native_token() {
+        let prices = hashmap! {
+            H160::from_low_u64_be(1) => BigRational::from_integer(2.into()),
+            H160::from_low_u64_be(2) => BigRational::from_integer(3.into()),
+        };
+        assert_eq!(
+            estimated_surplus(&order(1, 2, OrderKind::Sell, 30, 10), &prices),
+            Some(BigRational::from_integer(30.into()))
+        );
+        assert_eq!(
+            estimated_surplus(&order(1, 2, OrderKind::Buy, 30, 30), &prices),
+            Some(BigRational::from_integer((-30).into()))
+        );
+        assert_eq!(
+            estimated_surplus(&order(1, 3, OrderKind::Sell, 30, 10), &prices),
+            None
+        );
+    }
+
+    #[test]
+    fn prioritizes_batches_by_estimated_surplus() {
+        let surplus = |value: i64| Some(BigRational::from_integer(value.into()));
+        let batches = hashmap! {
+            (H160::from_low_u64_be(1), H160::from_low_u64_be(2), OrderKind::Sell) => vec![
+                (None, order(1, 2, OrderKind::Sell, 1, 1)),
+                (surplus(-5), order(1, 2, OrderKind::Sell, 1, 2)),
+                (surplus(3), order(1, 2, OrderKind::Sell, 1, 3)),
+            ],
+            (H160::from_low_u64_be(1), H160::from_low_u64_be(2), OrderKind::Buy) => vec![
+                (surplus(1), order(1, 2, OrderKind::Buy, 2, 1)),
+                (surplus(4), order(1, 2, OrderKind::Buy, 2, 2)),
+            ],
+            (H160::from_low_u64_be(2), H160::from_low_u64_be(1), OrderKind::Sell) => vec![
+                (None, order(2, 1, OrderKind::Sell, 3, 1)),
+            ],
+        };
+        assert_eq!(
+            ids(&prioritize(batches, &mut rand::thread_rng())),
+            vec![
+                vec!["1-2-2", "1-2-1"],
+                vec!["1-1-3", "1-1-2", "1-1-1"],
+                vec!["2-3-1"],
+            ]
+        );
+    }
+
+    #[test]
+    fn breaks_ties_randomly() {
+        let batches = || {
+            hashmap! {
+                (H160::from_low_u64_be(1), H160::from_low_u64_be(2), OrderKind::Sell) => vec![
+                    (None, order(1, 2, OrderKind::Sell, 1, 1)),
+                ],
+                (H160::from_low_u64_be(2), H160::from_low_u64_be(1), OrderKind::Sell) => vec![
+                    (None, order(2, 1, OrderKind::Sell, 1, 1)),
+                ],
+            }
+        };
+        let first_batches = (0..100)
+            .map(|_| ids(&prioritize(batches(), &mut rand::thread_rng()))[0][0].to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(first_batches.len(), 2);
+    }
+}
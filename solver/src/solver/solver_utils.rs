@@ -1,7 +1,7 @@
 use anyhow::{ensure, Result};
 use serde::{
     de::{Deserializer, Error as _},
-    Deserialize,
+    Deserialize, Serializer,
 };
 use web3::types::Bytes;
 
@@ -63,6 +63,14 @@ where
     hex::decode(hex_str).map_err(D::Error::custom)
 }
 
+pub fn serialize_prefixed_hex<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+}
+
 pub fn debug_bytes(
     bytes: &Bytes,
     formatter: &mut std::fmt::Formatter,
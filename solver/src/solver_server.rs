@@ -0,0 +1,509 @@
+//! Serves the built-in solvers over the same JSON protocol that `HttpSolver` uses to talk to
+//! external solvers (`POST /solve` with a `BatchAuctionModel`, responding with a
+//! `SettledBatchAuctionModel`).
+//!
+//! Instances are converted to `Liquidity` whose settlement handlers do not encode real contract
+//! interactions but record the executions so that they can be read back from the `Settlement`.
+
+use crate::{
+    encoding::EncodedInteraction,
+    liquidity::{
+        AmmOrderExecution, ConstantProductOrder, LimitOrder, Liquidity, SettlementHandling,
+        WeightedProductOrder,
+    },
+    settlement::{Interaction, Settlement, SettlementEncoder},
+    solver::{
+        http_solver::model::{
+            AmmParameters, BatchAuctionModel, ExecutedAmmModel, ExecutedOrderModel,
+            ExecutionPlanCoordinatesModel, OrderModel, Price, SettledBatchAuctionModel,
+            UpdatedAmmModel,
+        },
+        Solver,
+    },
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use ethcontract::Bytes;
+use model::{
+    order::{Order, OrderCreation, OrderKind, OrderMetaData, OrderUid},
+    TokenPair,
+};
+use num::{BigRational, ToPrimitive};
+use primitive_types::{H160, U256};
+use shared::{
+    conversions::{big_rational_to_u256, U256Ext},
+    sources::balancer::{pool_fetching::PoolTokenState, swap::fixed_point::Bfp},
+};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use warp::{hyper::StatusCode, reply, Filter, Rejection, Reply};
+
+pub fn handle_solve(
+    solver: Arc<dyn Solver + Send + Sync>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("solve")
+        .and(warp::post())
+        // Query parameters like `instance_name` or `time_limit` are accepted but ignored.
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::body::json())
+        .and_then(move |_query, model: BatchAuctionModel| {
+            let solver = solver.clone();
+            async move {
+                let result = solve(solver.as_ref(), model).await;
+                Result::<_, Infallible>::Ok(match result {
+                    Ok(settled) => reply::with_status(reply::json(&settled), StatusCode::OK),
+                    Err(err) => {
+                        tracing::warn!("failed to solve instance: {:?}", err);
+                        reply::with_status(
+                            reply::json(&format!("{:?}", err)),
+                            StatusCode::BAD_REQUEST,
+                        )
+                    }
+                })
+            }
+        })
+}
+
+pub async fn solve(
+    solver: &(dyn Solver + Send + Sync),
+    model: BatchAuctionModel,
+) -> Result<SettledBatchAuctionModel> {
+    let liquidity = liquidity_from_model(&model)?;
+    // The built-in solvers don't take the gas price into account.
+    let settlements = solver.solve(liquidity, 0.).await?;
+    let external_prices = external_prices(&model);
+    let best = settlements
+        .into_iter()
+        .map(|settlement| (objective_value(&settlement, &external_prices), settlement))
+        .fold(
+            None,
+            |best: Option<(BigRational, Settlement)>, (value, settlement)| match best {
+                Some(best) if best.0 >= value => Some(best),
+                _ => Some((value, settlement)),
+            },
+        );
+    match best {
+        Some((_, settlement)) => settled_model_from_settlement(&model, settlement),
+        None => Ok(SettledBatchAuctionModel {
+            ref_token: model.tokens.keys().copied().min().unwrap_or_default(),
+            ..Default::default()
+        }),
+    }
+}
+
+fn external_prices(model: &BatchAuctionModel) -> HashMap<H160, BigRational> {
+    model
+        .tokens
+        .iter()
+        .filter_map(|(token, info)| Some((*token, BigRational::from_float(info.external_price?)?)))
+        .collect()
+}
+
+// Settlements trading tokens without external price can't be compared by surplus so they are
+// only picked if there is no other settlement.
+fn objective_value(
+    settlement: &Settlement,
+    external_prices: &HashMap<H160, BigRational>,
+) -> BigRational {
+    let has_all_prices = settlement.trades().iter().all(|trade| {
+        external_prices.contains_key(&trade.order.order_creation.sell_token)
+            && external_prices.contains_key(&trade.order.order_creation.buy_token)
+    });
+    if !has_all_prices {
+        return num::zero();
+    }
+    settlement.total_surplus(external_prices) + settlement.total_fees(external_prices)
+}
+
+fn liquidity_from_model(model: &BatchAuctionModel) -> Result<Vec<Liquidity>> {
+    let orders = model
+        .orders
+        .iter()
+        .map(|(index, order)| Ok(Liquidity::Limit(limit_order_from_model(*index, order)?)));
+    let amms = model.amms.iter().map(|(index, amm)| {
+        let index = *index;
+        Ok(match &amm.parameters {
+            AmmParameters::ConstantProduct(parameters) => {
+                let tokens = parameters.reserves.keys().copied().collect::<Vec<_>>();
+                ensure!(
+                    tokens.len() == 2,
+                    "amm {} does not have two reserves",
+                    index
+                );
+                let tokens = TokenPair::new(tokens[0], tokens[1])
+                    .ok_or_else(|| anyhow!("invalid token pair for amm {}", index))?;
+                let reserve = |token| -> Result<u128> {
+                    let reserve = parameters.reserves[&token];
+                    ensure!(reserve <= U256::from(u128::MAX), "reserve overflow");
+                    Ok(reserve.low_u128())
+                };
+                let fee = (
+                    amm.fee.numer().to_u32().context("fee numerator overflow")?,
+                    amm.fee
+                        .denom()
+                        .to_u32()
+                        .context("fee denominator overflow")?,
+                );
+                Liquidity::ConstantProduct(ConstantProductOrder {
+                    tokens,
+                    reserves: (reserve(tokens.get().0)?, reserve(tokens.get().1)?),
+                    fee: num::rational::Ratio::new(fee.0, fee.1),
+                    settlement_handling: Arc::new(AmmRecorder { index }),
+                })
+            }
+            AmmParameters::WeightedProduct(parameters) => {
+                let reserves = parameters
+                    .reserves
+                    .iter()
+                    .map(|(token, data)| {
+                        let decimals = model
+                            .tokens
+                            .get(token)
+                            .and_then(|info| info.decimals)
+                            .unwrap_or(18);
+                        let weight = big_rational_to_u256(
+                            &(&data.weight * BigRational::from_integer(10u64.pow(18).into())),
+                        )?;
+                        Ok((
+                            *token,
+                            PoolTokenState {
+                                balance: data.balance,
+                                weight: Bfp::from_wei(weight),
+                                scaling_exponent: 18u8
+                                    .checked_sub(decimals)
+                                    .context("token with more than 18 decimals")?,
+                            },
+                        ))
+                    })
+                    .collect::<Result<_>>()?;
+                Liquidity::WeightedProduct(WeightedProductOrder {
+                    reserves,
+                    fee: amm.fee.clone(),
+                    settlement_handling: Arc::new(AmmRecorder { index }),
+                })
+            }
+        })
+    });
+    orders.chain(amms).collect()
+}
+
+fn limit_order_from_model(index: usize, order: &OrderModel) -> Result<LimitOrder> {
+    let kind = if order.is_sell_order {
+        OrderKind::Sell
+    } else {
+        OrderKind::Buy
+    };
+    ensure!(
+        order.fee.token == order.sell_token,
+        "fee of order {} is not in the sell token",
+        index
+    );
+    let recorded_order = Order {
+        order_meta_data: OrderMetaData {
+            uid: order_uid(index),
+            ..Default::default()
+        },
+        order_creation: OrderCreation {
+            sell_token: order.sell_token,
+            buy_token: order.buy_token,
+            sell_amount: order.sell_amount,
+            buy_amount: order.buy_amount,
+            fee_amount: order.fee.amount,
+            kind,
+            partially_fillable: order.allow_partial_fill,
+            ..Default::default()
+        },
+    };
+    Ok(LimitOrder {
+        id: index.to_string(),
+        sell_token: order.sell_token,
+        buy_token: order.buy_token,
+        sell_amount: order.sell_amount,
+        buy_amount: order.buy_amount,
+        kind,
+        partially_fillable: order.allow_partial_fill,
+        fee_amount: order.fee.amount,
+        settlement_handling: Arc::new(LimitOrderRecorder {
+            order: recorded_order,
+        }),
+    })
+}
+
+// Identifies model orders in settlement trades through their uid.
+fn order_uid(index: usize) -> OrderUid {
+    let mut uid = [0u8; 56];
+    uid[..8].copy_from_slice(&(index as u64).to_be_bytes());
+    OrderUid(uid)
+}
+
+fn order_index(uid: &OrderUid) -> usize {
+    let mut index = [0u8; 8];
+    index.copy_from_slice(&uid.0[..8]);
+    u64::from_be_bytes(index) as usize
+}
+
+// Adds trades for an order whose uid identifies the order of the instance.
+struct LimitOrderRecorder {
+    order: Order,
+}
+
+impl SettlementHandling<LimitOrder> for LimitOrderRecorder {
+    fn encode(&self, executed_amount: U256, encoder: &mut SettlementEncoder) -> Result<()> {
+        encoder.add_trade(self.order.clone(), executed_amount)
+    }
+}
+
+// Records executions of the AMM with the given index of the instance.
+struct AmmRecorder {
+    index: usize,
+}
+
+impl SettlementHandling<ConstantProductOrder> for AmmRecorder {
+    fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        encoder.append_to_execution_plan(AmmExecutionRecord {
+            index: self.index,
+            execution,
+        });
+        Ok(())
+    }
+}
+
+impl SettlementHandling<WeightedProductOrder> for AmmRecorder {
+    fn encode(&self, execution: AmmOrderExecution, encoder: &mut SettlementEncoder) -> Result<()> {
+        encoder.append_to_execution_plan(AmmExecutionRecord {
+            index: self.index,
+            execution,
+        });
+        Ok(())
+    }
+}
+
+// An AMM execution encoded as an interaction so that it keeps its position in the execution plan.
+// The target is the AMM index and the calldata the input and output token and amount.
+#[derive(Debug)]
+struct AmmExecutionRecord {
+    index: usize,
+    execution: AmmOrderExecution,
+}
+
+impl Interaction for AmmExecutionRecord {
+    fn encode(&self) -> Vec<EncodedInteraction> {
+        let mut data = Vec::with_capacity(128);
+        for (token, amount) in &[self.execution.input, self.execution.output] {
+            data.extend_from_slice(&[0u8; 12]);
+            data.extend_from_slice(token.as_bytes());
+            let mut word = [0u8; 32];
+            amount.to_big_endian(&mut word);
+            data.extend_from_slice(&word);
+        }
+        vec![(
+            H160::from_low_u64_be(self.index as u64),
+            U256::zero(),
+            Bytes(data),
+        )]
+    }
+}
+
+fn decode_amm_execution(interaction: &EncodedInteraction) -> Result<(usize, AmmOrderExecution)> {
+    let (target, _, data) = interaction;
+    ensure!(data.0.len() == 128, "unexpected interaction in settlement");
+    let token = |offset: usize| H160::from_slice(&data.0[offset + 12..offset + 32]);
+    let amount = |offset: usize| U256::from_big_endian(&data.0[offset..offset + 32]);
+    Ok((
+        target.to_low_u64_be() as usize,
+        AmmOrderExecution {
+            input: (token(0), amount(32)),
+            output: (token(64), amount(96)),
+        },
+    ))
+}
+
+fn settled_model_from_settlement(
+    model: &BatchAuctionModel,
+    settlement: Settlement,
+) -> Result<SettledBatchAuctionModel> {
+    let prices = settlement.clearing_prices().clone();
+    let price = |token: &H160| {
+        prices
+            .get(token)
+            .copied()
+            .ok_or_else(|| anyhow!("missing clearing price for {:?}", token))
+    };
+
+    let mut orders = HashMap::new();
+    for trade in settlement.trades() {
+        let index = order_index(&trade.order.order_meta_data.uid);
+        let order = model
+            .orders
+            .get(&index)
+            .ok_or_else(|| anyhow!("settlement with unknown order {}", index))?;
+        let (sell_price, buy_price) = (
+            price(&order.sell_token)?.to_big_rational(),
+            price(&order.buy_token)?.to_big_rational(),
+        );
+        // Same rounding as the settlement contract.
+        let executed_amount = trade.executed_amount.to_big_rational();
+        let (exec_sell_amount, exec_buy_amount) = if order.is_sell_order {
+            let buy = &executed_amount * sell_price / buy_price;
+            (trade.executed_amount, big_rational_to_u256(&buy.ceil())?)
+        } else {
+            let sell = &executed_amount * buy_price / sell_price;
+            (big_rational_to_u256(&sell.floor())?, trade.executed_amount)
+        };
+        orders.insert(
+            index,
+            ExecutedOrderModel {
+                exec_sell_amount,
+                exec_buy_amount,
+            },
+        );
+    }
+
+    let mut amms = HashMap::<usize, UpdatedAmmModel>::new();
+    let interactions = settlement.encoder.finish().interactions;
+    for (position, interaction) in interactions[1].iter().enumerate() {
+        let (index, execution) = decode_amm_execution(interaction)?;
+        amms.entry(index)
+            .or_insert_with(|| UpdatedAmmModel {
+                execution: Vec::new(),
+            })
+            .execution
+            .push(ExecutedAmmModel {
+                sell_token: execution.output.0,
+                buy_token: execution.input.0,
+                exec_sell_amount: execution.output.1,
+                exec_buy_amount: execution.input.1,
+                exec_plan: Some(ExecutionPlanCoordinatesModel {
+                    sequence: 0,
+                    position: position as u32,
+                }),
+            });
+    }
+
+    let ref_token = prices
+        .keys()
+        .copied()
+        .min()
+        .ok_or_else(|| anyhow!("settlement without prices"))?;
+    Ok(SettledBatchAuctionModel {
+        orders,
+        amms,
+        ref_token,
+        prices: prices
+            .into_iter()
+            .map(|(token, price)| (token, Price(price.to_f64_lossy())))
+            .collect(),
+        interaction_data: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{
+        http_solver::model::{
+            AmmModel, ConstantProductPoolParameters, CostModel, FeeModel, TokenInfoModel,
+        },
+        NaiveSolver,
+    };
+    use maplit::hashmap;
+
+    #[test]
+    fn amm_execution_record_roundtrip() {
+        let execution = AmmOrderExecution {
+            input: (H160::from_low_u64_be(1), 2.into()),
+            output: (H160::from_low_u64_be(3), U256::MAX),
+        };
+        let record = AmmExecutionRecord {
+            index: 42,
+            execution: execution.clone(),
+        };
+        let encoded = record.encode();
+        assert_eq!(encoded.len(), 1);
+        let (index, decoded) = decode_amm_execution(&encoded[0]).unwrap();
+        assert_eq!(index, 42);
+        assert_eq!(decoded, execution);
+    }
+
+    #[test]
+    fn order_uid_roundtrip() {
+        assert_eq!(order_index(&order_uid(0)), 0);
+        assert_eq!(order_index(&order_uid(1337)), 1337);
+    }
+
+    #[tokio::test]
+    async fn solves_instance_with_naive_solver() {
+        let token_a = H160::from_low_u64_be(1);
+        let token_b = H160::from_low_u64_be(2);
+        let token_info = || TokenInfoModel {
+            decimals: Some(18),
+            external_price: Some(1.),
+            normalize_priority: Some(0),
+        };
+        let model = BatchAuctionModel {
+            tokens: hashmap! {
+                token_a => token_info(),
+                token_b => token_info(),
+            },
+            orders: hashmap! {
+                0 => OrderModel {
+                    sell_token: token_a,
+                    buy_token: token_b,
+                    sell_amount: 1_000_000.into(),
+                    buy_amount: 900_000.into(),
+                    allow_partial_fill: false,
+                    is_sell_order: true,
+                    fee: FeeModel {
+                        amount: 0.into(),
+                        token: token_a,
+                    },
+                    cost: CostModel::default(),
+                },
+            },
+            amms: hashmap! {
+                0 => AmmModel {
+                    parameters: AmmParameters::ConstantProduct(ConstantProductPoolParameters {
+                        reserves: hashmap! {
+                            token_a => U256::from(1_000_000_000_000u64),
+                            token_b => U256::from(1_000_000_000_000u64),
+                        },
+                    }),
+                    fee: BigRational::new(3.into(), 1000.into()),
+                    cost: CostModel::default(),
+                    mandatory: false,
+                },
+            },
+            metadata: None,
+        };
+
+        let settled = solve(&NaiveSolver::default(), model).await.unwrap();
+
+        let order = &settled.orders[&0];
+        assert_eq!(order.exec_sell_amount, 1_000_000.into());
+        assert!(order.exec_buy_amount >= 900_000.into());
+        let amm = &settled.amms[&0].execution[0];
+        assert_eq!(amm.buy_token, token_a);
+        assert_eq!(amm.sell_token, token_b);
+        assert_eq!(
+            amm.exec_plan,
+            Some(ExecutionPlanCoordinatesModel {
+                sequence: 0,
+                position: 0,
+            })
+        );
+        assert!(settled.prices.contains_key(&token_a));
+        assert!(settled.prices.contains_key(&token_b));
+        assert!(settled.has_execution_plan());
+
+        // The response can be consumed by `HttpSolver`.
+        let json = serde_json::to_string(&settled).unwrap();
+        assert!(serde_json::from_str::<SettledBatchAuctionModel>(&json).is_ok());
+    }
+
+    #[tokio::test]
+    async fn solving_instance_without_orders_returns_empty_solution() {
+        let settled = solve(&NaiveSolver::default(), BatchAuctionModel::default())
+            .await
+            .unwrap();
+        assert!(settled.orders.is_empty());
+        assert!(settled.amms.is_empty());
+    }
+}